@@ -0,0 +1,10 @@
+tool
+extends EditorPlugin
+
+var gizmo_plugin = preload("res://hex_terrain_gizmo_plugin.gdns").new()
+
+func _enter_tree():
+	add_spatial_gizmo_plugin(gizmo_plugin)
+
+func _exit_tree():
+	remove_spatial_gizmo_plugin(gizmo_plugin)
@@ -0,0 +1,149 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::thread::JoinHandle;
+use terrain::save;
+use terrain::save::Chunk;
+
+/// Extension of map files.
+pub const MAP_EXTENSION: &str = "hexmap";
+
+/// Writes snapshots of the terrain to a rotating set of files on a background thread, so a crash
+/// loses at most the work since the last snapshot.
+#[derive(Default)]
+pub struct Autosave {
+    elapsed: f64,
+    last_hash: Option<u64>,
+    writer: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl Autosave {
+    /// Advances the timer by `delta` seconds and returns whether a snapshot is due. An `interval`
+    /// of 0 disables snapshots. No snapshot is due while the last one is still written.
+    pub fn tick(&mut self, delta: f64, interval: f64) -> bool {
+        if interval <= 0.0 {
+            self.elapsed = 0.0;
+            return false;
+        }
+        self.elapsed += delta;
+        if self.elapsed < interval || self.is_writing() {
+            return false;
+        }
+        self.elapsed = 0.0;
+        true
+    }
+
+    /// Returns whether a snapshot is still written.
+    pub fn is_writing(&self) -> bool {
+        matches!(&self.writer, Some(writer) if !writer.is_finished())
+    }
+
+    /// Writes the chunks of a map to the oldest of `slots` files in the directory, on a
+    /// background thread. Nothing is written if the chunks did not change since the last snapshot
+    /// or the last snapshot is still written. Returns whether a snapshot is written.
+    pub fn save(
+        &mut self,
+        directory: PathBuf,
+        slots: usize,
+        chunks: Vec<Chunk>,
+        compression_level: u32,
+    ) -> bool {
+        let mut hasher = DefaultHasher::new();
+        chunks.hash(&mut hasher);
+        let hash = hasher.finish();
+        if self.last_hash == Some(hash) || self.is_writing() {
+            return false;
+        }
+        self.last_hash = Some(hash);
+
+        let path = slot_path(&directory, oldest_slot(&directory, slots));
+        self.writer = Some(thread::spawn(move || {
+            fs::create_dir_all(&directory)?;
+            // A crash while writing leaves the previous snapshot in the slot intact.
+            let temporary = path.with_extension("tmp");
+            fs::write(&temporary, save::write_chunks(&chunks, compression_level))?;
+            fs::rename(&temporary, &path)
+        }));
+        true
+    }
+
+    /// Waits until the last snapshot is written and returns whether that worked.
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self.writer.take() {
+            None => Ok(()),
+            Some(writer) => writer
+                .join()
+                .unwrap_or_else(|_| Err(io::Error::other("autosave failed"))),
+        }
+    }
+}
+
+/// Returns the path of the snapshot in a slot.
+pub fn slot_path(directory: &Path, slot: usize) -> PathBuf {
+    directory.join(format!("autosave_{}.{}", slot, MAP_EXTENSION))
+}
+
+/// Returns the newest of `slots` snapshots in the directory, None if there is none.
+pub fn latest(directory: &Path, slots: usize) -> Option<PathBuf> {
+    (0..slots.max(1))
+        .map(|slot| slot_path(directory, slot))
+        .filter_map(|path| Some((fs::metadata(&path).ok()?.modified().ok()?, path)))
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
+/// Returns the first slot without a snapshot, or the slot with the oldest one.
+fn oldest_slot(directory: &Path, slots: usize) -> usize {
+    (0..slots.max(1))
+        .min_by_key(|slot| {
+            fs::metadata(slot_path(directory, *slot))
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunks(value: u8) -> Vec<Chunk> {
+        vec![Chunk {
+            id: *b"TEST",
+            data: vec![value; 10],
+        }]
+    }
+
+    #[test]
+    fn tick_is_due_after_interval() {
+        let mut autosave = Autosave::default();
+
+        assert!(!autosave.tick(1.0, 2.0));
+        assert!(autosave.tick(1.0, 2.0));
+        assert!(!autosave.tick(1.0, 2.0));
+        assert!(!autosave.tick(5.0, 0.0));
+    }
+
+    #[test]
+    fn save_writes_changed_snapshots_to_slots() {
+        let directory = std::env::temp_dir().join("hex_terrain_autosave_test");
+        let _ = fs::remove_dir_all(&directory);
+        let mut autosave = Autosave::default();
+
+        assert!(autosave.save(directory.clone(), 2, chunks(1), 6));
+        autosave.finish().unwrap();
+        assert!(!autosave.save(directory.clone(), 2, chunks(1), 6));
+        assert!(autosave.save(directory.clone(), 2, chunks(2), 6));
+        autosave.finish().unwrap();
+
+        let latest = latest(&directory, 2).unwrap();
+        let (_, saved) = save::read_chunks(&fs::read(latest).unwrap()).unwrap();
+        assert_eq!(chunks(2), saved);
+        assert!(slot_path(&directory, 0).exists());
+        assert!(slot_path(&directory, 1).exists());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
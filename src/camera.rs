@@ -0,0 +1,179 @@
+use crate::hex_terrain::HexTerrain;
+use gdnative::api::{Camera, GlobalConstants, InputEventMouseButton, InputEventMouseMotion};
+use gdnative::prelude::*;
+use std::f32::consts::FRAC_PI_2;
+
+/// A camera that orbits around a point on the terrain. Dragging with the middle mouse button
+/// orbits, dragging with the right mouse button pans and the mouse wheel zooms. Moving the mouse to
+/// the edge of the viewport scrolls if `edge_scrolling` is enabled.
+#[derive(NativeClass)]
+#[inherit(Camera)]
+pub struct HexTerrainCamera {
+    #[property]
+    orbit_speed: f32,
+    #[property]
+    pan_speed: f32,
+    #[property]
+    zoom_speed: f32,
+    #[property]
+    min_distance: f32,
+    #[property]
+    max_distance: f32,
+    #[property]
+    min_pitch: f32,
+    #[property]
+    max_pitch: f32,
+    #[property]
+    edge_scrolling: bool,
+    #[property]
+    edge_scroll_margin: f32,
+    #[property]
+    edge_scroll_speed: f32,
+    target: Vector3,
+    yaw: f32,
+    pitch: f32,
+    distance: f32,
+}
+
+#[methods]
+impl HexTerrainCamera {
+    pub fn new(_owner: TRef<'_, Camera>) -> Self {
+        Self {
+            orbit_speed: 0.005,
+            pan_speed: 0.002,
+            zoom_speed: 0.1,
+            min_distance: 2.0,
+            max_distance: 50.0,
+            min_pitch: 0.2,
+            max_pitch: 1.5,
+            edge_scrolling: false,
+            edge_scroll_margin: 10.0,
+            edge_scroll_speed: 1.0,
+            target: Vector3::zero(),
+            yaw: 0.0,
+            pitch: FRAC_PI_2 / 2.0,
+            distance: 14.0,
+        }
+    }
+
+    #[export]
+    pub fn _ready(&mut self, owner: TRef<'_, Camera>) {
+        self.update_transform(owner);
+    }
+
+    #[export]
+    pub fn _process(&mut self, owner: TRef<'_, Camera>, delta: f64) {
+        if !self.edge_scrolling {
+            return;
+        }
+        let viewport = match owner.get_viewport() {
+            None => return,
+            Some(viewport) => unsafe { viewport.assume_safe() },
+        };
+
+        let mouse = viewport.get_mouse_position();
+        let size = viewport.get_visible_rect().size;
+        let mut direction = Vector2::zero();
+        if mouse.x < self.edge_scroll_margin {
+            direction.x -= 1.0;
+        } else if mouse.x > size.width - self.edge_scroll_margin {
+            direction.x += 1.0;
+        }
+        if mouse.y < self.edge_scroll_margin {
+            direction.y -= 1.0;
+        } else if mouse.y > size.height - self.edge_scroll_margin {
+            direction.y += 1.0;
+        }
+
+        if direction != Vector2::zero() {
+            // Scroll faster when zoomed out, so the speed on screen stays about the same.
+            let speed = self.edge_scroll_speed * self.distance * delta as f32;
+            self.pan(owner, direction * speed);
+        }
+    }
+
+    #[export]
+    pub fn _unhandled_input(&mut self, owner: TRef<'_, Camera>, event: Variant) {
+        if let Some(event) = event.clone().try_to_object::<InputEventMouseMotion>() {
+            let event = unsafe { event.assume_safe() };
+            let relative = event.relative();
+            let button_mask = event.button_mask();
+            if button_mask & GlobalConstants::BUTTON_MASK_MIDDLE != 0 {
+                self.yaw -= relative.x * self.orbit_speed;
+                self.pitch += relative.y * self.orbit_speed;
+                self.update_transform(owner);
+            } else if button_mask & GlobalConstants::BUTTON_MASK_RIGHT != 0 {
+                self.pan(owner, -relative * self.pan_speed * self.distance);
+            }
+        } else if let Some(event) = event.try_to_object::<InputEventMouseButton>() {
+            let event = unsafe { event.assume_safe() };
+            if !event.is_pressed() {
+                return;
+            }
+            match event.button_index() {
+                GlobalConstants::BUTTON_WHEEL_UP => {
+                    self.distance *= 1.0 - self.zoom_speed;
+                    self.update_transform(owner);
+                }
+                GlobalConstants::BUTTON_WHEEL_DOWN => {
+                    self.distance *= 1.0 + self.zoom_speed;
+                    self.update_transform(owner);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Moves the camera so it looks at the given global position.
+    #[export]
+    pub fn focus_position(&mut self, owner: TRef<'_, Camera>, position: Vector3) {
+        self.target = position;
+        self.update_transform(owner);
+    }
+
+    /// Moves the camera so it looks at the center of the given cell of the terrain.
+    #[export]
+    pub fn focus_cell(
+        &mut self,
+        owner: TRef<'_, Camera>,
+        terrain: Instance<HexTerrain, Shared>,
+        x: i64,
+        y: i64,
+    ) {
+        let terrain = unsafe { terrain.assume_safe() };
+        let position = terrain
+            .map(|terrain, terrain_owner| terrain.get_cell_position(terrain_owner, x, y))
+            .ok();
+        if let Some(position) = position {
+            self.focus_position(owner, position);
+        }
+    }
+
+    /// Moves the target on the horizontal plane. `offset.x` moves to the right of the view and
+    /// `offset.y` towards the camera.
+    fn pan(&mut self, owner: TRef<'_, Camera>, offset: Vector2) {
+        let (sin, cos) = self.yaw.sin_cos();
+        self.target +=
+            Vector3::new(cos, 0.0, -sin) * offset.x + Vector3::new(sin, 0.0, cos) * offset.y;
+        self.update_transform(owner);
+    }
+
+    fn update_transform(&mut self, owner: TRef<'_, Camera>) {
+        self.pitch = self
+            .pitch
+            .clamp(self.min_pitch, self.max_pitch.max(self.min_pitch));
+        self.distance = self
+            .distance
+            .clamp(self.min_distance, self.max_distance.max(self.min_distance));
+
+        let (yaw_sin, yaw_cos) = self.yaw.sin_cos();
+        let (pitch_sin, pitch_cos) = self.pitch.sin_cos();
+        let offset = Vector3::new(yaw_sin * pitch_cos, pitch_sin, yaw_cos * pitch_cos);
+
+        owner.look_at_from_position(
+            self.target + offset * self.distance,
+            self.target,
+            Vector3::new(0.0, 1.0, 0.0),
+        );
+    }
+}
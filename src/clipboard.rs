@@ -0,0 +1,23 @@
+use crate::region::Region;
+use gdnative::prelude::*;
+
+/// A copied region of the terrain, relative to the first copied cell.
+#[derive(NativeClass)]
+#[inherit(Reference)]
+pub struct HexTerrainClipboard {
+    pub region: Region,
+}
+
+#[methods]
+impl HexTerrainClipboard {
+    pub fn new(_owner: TRef<'_, Reference>) -> Self {
+        Self {
+            region: Region::default(),
+        }
+    }
+
+    #[export]
+    pub fn is_empty(&self, _owner: TRef<'_, Reference>) -> bool {
+        self.region.heights.is_empty()
+    }
+}
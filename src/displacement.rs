@@ -0,0 +1,103 @@
+use crate::hex::Vector2Di32;
+
+/// Shader of the flat terrain mesh while `gpu_displacement` is set. It raises every vertex by the
+/// height of its key in the `heights` texture, which it finds through the second UV that spans the
+/// map. Vertices with a transparent color, like those of decks, are already at their height.
+/// Normals are taken from the faces, as the mesh only knows its flat normals.
+pub const SHADER: &str = "shader_type spatial;
+
+uniform sampler2D heights;
+uniform vec2 key_range = vec2(1.0);
+uniform float node_height = 1.0;
+uniform vec4 albedo : hint_color = vec4(1.0);
+
+void vertex() {
+    if (COLOR.a > 0.5) {
+        ivec2 texel = ivec2(round(UV2 * max(key_range, vec2(1.0))));
+        VERTEX.y += texelFetch(heights, texel, 0).r * node_height;
+    }
+}
+
+void fragment() {
+    NORMAL = normalize(cross(dFdx(VERTEX), dFdy(VERTEX)));
+    ALBEDO = albedo.rgb;
+}
+";
+
+/// The heights of the vertices as a single channel float image with one texel per key unit,
+/// starting at the smallest key. Texels between the vertices are 0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeightTexture {
+    pub width: i64,
+    pub height: i64,
+    /// Key of the first texel.
+    origin: Vector2Di32,
+    texels: Vec<f32>,
+}
+
+impl HeightTexture {
+    /// Creates a texture for the keys from `min` to `max`, both included.
+    pub fn new(min: Vector2Di32, max: Vector2Di32) -> HeightTexture {
+        let width = i64::from((max.x - min.x).max(0) + 1);
+        let height = i64::from((max.y - min.y).max(0) + 1);
+        HeightTexture {
+            width,
+            height,
+            origin: min,
+            texels: vec![0.0; (width * height) as usize],
+        }
+    }
+
+    /// Returns whether the texture was created for the keys from `min` to `max`.
+    pub fn covers(&self, min: Vector2Di32, max: Vector2Di32) -> bool {
+        self.origin == min
+            && self.width == i64::from((max.x - min.x).max(0) + 1)
+            && self.height == i64::from((max.y - min.y).max(0) + 1)
+    }
+
+    /// Sets the height of the texel of the key. Returns whether the texel changed.
+    pub fn set(&mut self, key: Vector2Di32, height: i32) -> bool {
+        let (x, y) = (
+            i64::from(key.x - self.origin.x),
+            i64::from(key.y - self.origin.y),
+        );
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return false;
+        }
+        let texel = &mut self.texels[(y * self.width + x) as usize];
+        if *texel == height as f32 {
+            return false;
+        }
+        *texel = height as f32;
+        true
+    }
+
+    /// Returns the texels as the bytes of an image of the `FORMAT_RF` format.
+    pub fn bytes(&self) -> Vec<u8> {
+        self.texels
+            .iter()
+            .flat_map(|texel| texel.to_le_bytes().to_vec())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_writes_texel_of_key() {
+        let mut texture = HeightTexture::new(Vector2Di32::new(-1, 2), Vector2Di32::new(1, 3));
+
+        assert_eq!((3, 2), (texture.width, texture.height));
+        assert!(texture.set(Vector2Di32::new(0, 3), 5));
+        assert!(!texture.set(Vector2Di32::new(0, 3), 5));
+        assert!(!texture.set(Vector2Di32::new(2, 3), 1));
+
+        let bytes = texture.bytes();
+        assert_eq!(24, bytes.len());
+        assert_eq!(5.0f32.to_le_bytes(), bytes[16..20]);
+        assert!(texture.covers(Vector2Di32::new(-1, 2), Vector2Di32::new(1, 3)));
+        assert!(!texture.covers(Vector2Di32::new(-1, 2), Vector2Di32::new(1, 4)));
+    }
+}
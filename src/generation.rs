@@ -0,0 +1,479 @@
+use crate::rebuild::Rebuild;
+use crate::world::World;
+use gdnative::prelude::*;
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+
+/// A step of terrain generation, e.g. noise, erosion or biomes. Passes are run by a
+/// `GenerationPipeline`, which gives every pass its own seed. They only see the `World`, so a
+/// pipeline can run on a background thread.
+pub trait GeneratorPass: Send {
+    /// The name the pass is configured with.
+    fn name(&self) -> &'static str;
+
+    fn run(&self, world: &mut World, seed: i64);
+}
+
+/// Configuration of a generator pass, so pipelines can be put together in the inspector and saved
+/// as resources. `generator` is the name of the pass and `parameters` maps the names of its
+/// parameters to their values. Missing parameters are 0.
+#[derive(NativeClass)]
+#[inherit(Resource)]
+pub struct HexGeneratorPass {
+    #[property]
+    pub generator: GodotString,
+    #[property]
+    pub parameters: Dictionary,
+}
+
+#[methods]
+impl HexGeneratorPass {
+    pub fn new(_owner: TRef<'_, Resource>) -> Self {
+        Self {
+            generator: GodotString::new(),
+            parameters: Dictionary::new_shared(),
+        }
+    }
+}
+
+/// Passes that run one after another.
+#[derive(Default)]
+pub struct GenerationPipeline {
+    passes: Vec<Box<dyn GeneratorPass>>,
+}
+
+impl GenerationPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a pipeline from a configuration. Every entry is either a `HexGeneratorPass` or an
+    /// array of the name of a pass followed by its parameters in the order of `create_pass`.
+    /// Unknown passes are skipped.
+    pub fn from_config(config: &VariantArray) -> Self {
+        let mut pipeline = Self::new();
+        for entry in config.iter() {
+            let pass = if let Some(step) = entry.try_to_array() {
+                let name = step.get(0).to_godot_string().to_string();
+                create_pass(&name, &Parameters::Positional(step))
+            } else {
+                entry
+                    .try_to_object::<Resource>()
+                    .and_then(Instance::<HexGeneratorPass, Shared>::try_from_base)
+                    .and_then(|pass| {
+                        unsafe { pass.assume_safe() }
+                            .map(|pass, _| {
+                                let parameters = Parameters::Named(pass.parameters.new_ref());
+                                create_pass(&pass.generator.to_string(), &parameters)
+                            })
+                            .ok()
+                            .flatten()
+                    })
+            };
+            if let Some(pass) = pass {
+                pipeline.add(pass);
+            }
+        }
+        pipeline
+    }
+
+    pub fn add(&mut self, pass: Box<dyn GeneratorPass>) {
+        self.passes.push(pass);
+    }
+
+    pub fn len(&self) -> usize {
+        self.passes.len()
+    }
+
+    /// Runs all passes. Pass `index` gets `seed + index` as seed, so the same seed always produces
+    /// the same terrain.
+    pub fn run(&self, world: &mut World, seed: i64) {
+        for index in 0..self.passes.len() {
+            self.run_pass(index, world, seed);
+        }
+    }
+
+    /// Runs a single pass with the seed it gets in `run`. Returns its name.
+    pub fn run_pass(&self, index: usize, world: &mut World, seed: i64) -> &'static str {
+        let pass = &self.passes[index];
+        pass.run(world, seed.wrapping_add(index as i64));
+        pass.name()
+    }
+}
+
+/// A pipeline that runs on a copy of the world on a background thread, see
+/// `HexTerrain::regenerate_async`.
+pub struct GenerationJob {
+    rebuild: Rebuild<World>,
+    progress: Receiver<(&'static str, i64)>,
+}
+
+impl GenerationJob {
+    /// Resets `world` and runs the pipeline on it on a background thread. `version` identifies
+    /// the terrain the world was copied from, see `Rebuild`.
+    pub fn start(pipeline: GenerationPipeline, world: World, version: u64, seed: i64) -> Self {
+        let (sender, progress) = mpsc::channel();
+        let mut rebuild = Rebuild::default();
+        rebuild.start("regenerate", version, world, move |world| {
+            world.reset();
+            for index in 0..pipeline.len() {
+                let name = pipeline.run_pass(index, world, seed);
+                // Nobody listens any more if the generation was restarted.
+                let _ = sender.send((name, ((index + 1) * 100 / pipeline.len()) as i64));
+            }
+        });
+        Self { rebuild, progress }
+    }
+
+    /// Returns the passes that have run since the last call, each with its name and the share of
+    /// passes that have run in percent.
+    pub fn progress(&self) -> Vec<(&'static str, i64)> {
+        self.progress.try_iter().collect()
+    }
+
+    /// Returns whether all passes have run, so `finish` returns the world.
+    pub fn is_done(&self) -> bool {
+        self.rebuild.is_done()
+    }
+
+    /// Returns the generated world once all passes have run. It is None if the generation
+    /// failed or the terrain is no longer the one with `version`.
+    pub fn finish(&mut self, version: u64) -> Option<Option<World>> {
+        self.rebuild.finish(version).map(|(_, world)| world)
+    }
+}
+
+/// Parameters of a pass, either by position from an array that starts with the name of the pass
+/// or by name from a dictionary.
+enum Parameters {
+    Positional(VariantArray),
+    Named(Dictionary),
+}
+
+impl Parameters {
+    fn get(&self, index: i32, name: &str) -> Variant {
+        match self {
+            Parameters::Positional(step) if index + 1 < step.len() => step.get(index + 1),
+            Parameters::Named(parameters) if parameters.contains(name) => parameters.get(name),
+            _ => Variant::new(),
+        }
+    }
+
+    fn f64(&self, index: i32, name: &str) -> f64 {
+        self.get(index, name).to_f64()
+    }
+
+    fn i64(&self, index: i32, name: &str) -> i64 {
+        self.get(index, name).to_i64()
+    }
+}
+
+/// Creates the named pass: "noise" (frequency, amplitude), "midpoint" (size, amplitude,
+/// roughness), "island" (land_fraction, falloff), "ridges" (count, height, width), "erosion"
+/// (talus_angle, iterations), "weathering" (intensity, iterations), "rain_shadow"
+/// (wind_direction, rainfall, uplift), "biomes", "lakes" (fill, birth, survival, iterations),
+/// "rivers" (threshold), "wfc", "maze" (wall_height), "provinces" (count), "resources"
+/// (resource, probabilities, min_spacing) or "symmetry". Returns None for unknown names.
+fn create_pass(name: &str, parameters: &Parameters) -> Option<Box<dyn GeneratorPass>> {
+    let pass: Box<dyn GeneratorPass> = match name {
+        "noise" => Box::new(NoisePass {
+            frequency: parameters.f64(0, "frequency"),
+            amplitude: parameters.f64(1, "amplitude"),
+        }),
+        "midpoint" => Box::new(MidpointPass {
+            size: parameters.i64(0, "size"),
+            amplitude: parameters.f64(1, "amplitude"),
+            roughness: parameters.f64(2, "roughness"),
+        }),
+        "island" => Box::new(IslandPass {
+            land_fraction: parameters.f64(0, "land_fraction"),
+            falloff: parameters.f64(1, "falloff"),
+        }),
+        "ridges" => Box::new(RidgesPass {
+            count: parameters.i64(0, "count"),
+            height: parameters.i64(1, "height"),
+            width: parameters.f64(2, "width"),
+        }),
+        "erosion" => Box::new(ErosionPass {
+            talus_angle: parameters.f64(0, "talus_angle"),
+            iterations: parameters.i64(1, "iterations"),
+        }),
+        "weathering" => Box::new(WeatheringPass {
+            intensity: parameters.f64(0, "intensity"),
+            iterations: parameters.i64(1, "iterations"),
+        }),
+        "rain_shadow" => Box::new(RainShadowPass {
+            wind_direction: parameters.f64(0, "wind_direction"),
+            rainfall: parameters.f64(1, "rainfall"),
+            uplift: parameters.f64(2, "uplift"),
+        }),
+        "biomes" => Box::new(BiomesPass),
+        "lakes" => Box::new(LakesPass {
+            fill: parameters.f64(0, "fill"),
+            birth: parameters.i64(1, "birth"),
+            survival: parameters.i64(2, "survival"),
+            iterations: parameters.i64(3, "iterations"),
+        }),
+        "rivers" => Box::new(RiversPass {
+            threshold: parameters.f64(0, "threshold"),
+        }),
+        "wfc" => Box::new(WfcPass),
+        "maze" => Box::new(MazePass {
+            wall_height: parameters.i64(0, "wall_height"),
+        }),
+        "provinces" => Box::new(ProvincesPass {
+            count: parameters.i64(0, "count"),
+        }),
+        "resources" => Box::new(ScatterPass {
+            resource: parameters.get(0, "resource").to_godot_string().to_string(),
+            probabilities: parameters
+                .get(1, "probabilities")
+                .to_dictionary()
+                .iter()
+                .map(|(terrain_type, probability)| {
+                    (terrain_type.to_i64() as i32, probability.to_f64() as f32)
+                })
+                .collect(),
+            min_spacing: parameters.i64(2, "min_spacing"),
+        }),
+        "symmetry" => Box::new(SymmetryPass),
+        _ => return None,
+    };
+    Some(pass)
+}
+
+/// See `HexTerrain::generate_noise`.
+pub struct NoisePass {
+    pub frequency: f64,
+    pub amplitude: f64,
+}
+
+impl GeneratorPass for NoisePass {
+    fn name(&self) -> &'static str {
+        "noise"
+    }
+
+    fn run(&self, world: &mut World, seed: i64) {
+        world.generate_noise(seed, self.frequency, self.amplitude);
+    }
+}
+
+/// See `HexTerrain::generate_midpoint`.
+pub struct MidpointPass {
+    pub size: i64,
+    pub amplitude: f64,
+    pub roughness: f64,
+}
+
+impl GeneratorPass for MidpointPass {
+    fn name(&self) -> &'static str {
+        "midpoint"
+    }
+
+    fn run(&self, world: &mut World, seed: i64) {
+        world.generate_midpoint(seed, self.size, self.amplitude, self.roughness);
+    }
+}
+
+/// See `HexTerrain::apply_island_mask`.
+pub struct IslandPass {
+    pub land_fraction: f64,
+    pub falloff: f64,
+}
+
+impl GeneratorPass for IslandPass {
+    fn name(&self) -> &'static str {
+        "island"
+    }
+
+    fn run(&self, world: &mut World, seed: i64) {
+        world.apply_island_mask(seed, self.land_fraction, self.falloff);
+    }
+}
+
+/// See `HexTerrain::generate_ridges`.
+pub struct RidgesPass {
+    pub count: i64,
+    pub height: i64,
+    pub width: f64,
+}
+
+impl GeneratorPass for RidgesPass {
+    fn name(&self) -> &'static str {
+        "ridges"
+    }
+
+    fn run(&self, world: &mut World, seed: i64) {
+        world.generate_ridges(seed, self.count, self.height, self.width);
+    }
+}
+
+/// See `HexTerrain::thermal_erosion`.
+pub struct ErosionPass {
+    pub talus_angle: f64,
+    pub iterations: i64,
+}
+
+impl GeneratorPass for ErosionPass {
+    fn name(&self) -> &'static str {
+        "erosion"
+    }
+
+    fn run(&self, world: &mut World, _seed: i64) {
+        world.erode(self.talus_angle, self.iterations);
+    }
+}
+
+/// See `HexTerrain::weather`.
+pub struct WeatheringPass {
+    pub intensity: f64,
+    pub iterations: i64,
+}
+
+impl GeneratorPass for WeatheringPass {
+    fn name(&self) -> &'static str {
+        "weathering"
+    }
+
+    fn run(&self, world: &mut World, seed: i64) {
+        world.weather(seed, self.intensity, self.iterations);
+    }
+}
+
+/// See `HexTerrain::simulate_rain_shadow`.
+pub struct RainShadowPass {
+    pub wind_direction: f64,
+    pub rainfall: f64,
+    pub uplift: f64,
+}
+
+impl GeneratorPass for RainShadowPass {
+    fn name(&self) -> &'static str {
+        "rain_shadow"
+    }
+
+    fn run(&self, world: &mut World, _seed: i64) {
+        world.simulate_rain_shadow(self.wind_direction, self.rainfall, self.uplift);
+    }
+}
+
+/// See `HexTerrain::generate_biomes`.
+pub struct BiomesPass;
+
+impl GeneratorPass for BiomesPass {
+    fn name(&self) -> &'static str {
+        "biomes"
+    }
+
+    fn run(&self, world: &mut World, seed: i64) {
+        world.generate_biomes(seed);
+    }
+}
+
+/// See `HexTerrain::generate_lakes`.
+pub struct LakesPass {
+    pub fill: f64,
+    pub birth: i64,
+    pub survival: i64,
+    pub iterations: i64,
+}
+
+impl GeneratorPass for LakesPass {
+    fn name(&self) -> &'static str {
+        "lakes"
+    }
+
+    fn run(&self, world: &mut World, seed: i64) {
+        world.generate_lakes(seed, self.fill, self.birth, self.survival, self.iterations);
+    }
+}
+
+/// See `HexTerrain::generate_rivers`.
+pub struct RiversPass {
+    pub threshold: f64,
+}
+
+impl GeneratorPass for RiversPass {
+    fn name(&self) -> &'static str {
+        "rivers"
+    }
+
+    fn run(&self, world: &mut World, seed: i64) {
+        world.generate_rivers(seed, self.threshold);
+    }
+}
+
+/// See `HexTerrain::generate_wfc`.
+pub struct WfcPass;
+
+impl GeneratorPass for WfcPass {
+    fn name(&self) -> &'static str {
+        "wfc"
+    }
+
+    fn run(&self, world: &mut World, seed: i64) {
+        world.generate_wfc(seed);
+    }
+}
+
+/// See `HexTerrain::generate_maze`.
+pub struct MazePass {
+    pub wall_height: i64,
+}
+
+impl GeneratorPass for MazePass {
+    fn name(&self) -> &'static str {
+        "maze"
+    }
+
+    fn run(&self, world: &mut World, seed: i64) {
+        world.generate_maze(seed, self.wall_height);
+    }
+}
+
+/// See `HexTerrain::generate_provinces`.
+pub struct ProvincesPass {
+    pub count: i64,
+}
+
+impl GeneratorPass for ProvincesPass {
+    fn name(&self) -> &'static str {
+        "provinces"
+    }
+
+    fn run(&self, world: &mut World, seed: i64) {
+        world.generate_provinces(seed, self.count);
+    }
+}
+
+/// Scatters a resource over the cells, see `HexTerrain::place_resources`.
+pub struct ScatterPass {
+    pub resource: String,
+    /// Chance of a cell to get the resource by terrain type.
+    pub probabilities: HashMap<i32, f32>,
+    pub min_spacing: i64,
+}
+
+impl GeneratorPass for ScatterPass {
+    fn name(&self) -> &'static str {
+        "resources"
+    }
+
+    fn run(&self, world: &mut World, seed: i64) {
+        world.place_resources(&self.resource, &self.probabilities, self.min_spacing, seed);
+    }
+}
+
+/// See `HexTerrain::symmetrize`.
+pub struct SymmetryPass;
+
+impl GeneratorPass for SymmetryPass {
+    fn name(&self) -> &'static str {
+        "symmetry"
+    }
+
+    fn run(&self, world: &mut World, _seed: i64) {
+        world.symmetrize();
+    }
+}
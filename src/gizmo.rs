@@ -0,0 +1,188 @@
+use crate::hex_terrain::HexTerrain;
+use gdnative::api::{Camera, EditorSpatialGizmo, EditorSpatialGizmoPlugin};
+use gdnative::prelude::*;
+
+const HANDLE_MATERIAL: &str = "handles";
+
+/// Draws a handle at every vertex of a `HexTerrain` in the editor. Dragging a handle up or down
+/// changes the height of its vertex.
+#[derive(NativeClass)]
+#[inherit(EditorSpatialGizmoPlugin)]
+pub struct HexTerrainGizmoPlugin {
+    material_created: bool,
+}
+
+#[methods]
+impl HexTerrainGizmoPlugin {
+    pub fn new(_owner: TRef<'_, EditorSpatialGizmoPlugin>) -> Self {
+        Self {
+            material_created: false,
+        }
+    }
+
+    #[export]
+    pub fn get_name(&self, _owner: TRef<'_, EditorSpatialGizmoPlugin>) -> GodotString {
+        GodotString::from("HexTerrain")
+    }
+
+    #[export]
+    pub fn has_gizmo(
+        &self,
+        _owner: TRef<'_, EditorSpatialGizmoPlugin>,
+        spatial: Ref<Spatial>,
+    ) -> bool {
+        Instance::<HexTerrain, Shared>::try_from_base(spatial).is_some()
+    }
+
+    #[export]
+    pub fn redraw(
+        &mut self,
+        owner: TRef<'_, EditorSpatialGizmoPlugin>,
+        gizmo: Ref<EditorSpatialGizmo>,
+    ) {
+        if !self.material_created {
+            owner.create_handle_material(HANDLE_MATERIAL, false);
+            self.material_created = true;
+        }
+
+        let gizmo = unsafe { gizmo.assume_safe() };
+        gizmo.clear();
+        let positions = match Self::terrain(gizmo) {
+            None => return,
+            Some(terrain) => unsafe { terrain.assume_safe() }
+                .map(|terrain, owner| terrain.get_vertex_positions(owner))
+                .unwrap_or_else(|_| Vector3Array::new()),
+        };
+
+        if let Some(material) = owner.get_material(HANDLE_MATERIAL, gizmo) {
+            gizmo.add_handles(positions, material, false, false);
+        }
+    }
+
+    #[export]
+    pub fn get_handle_name(
+        &self,
+        _owner: TRef<'_, EditorSpatialGizmoPlugin>,
+        gizmo: Ref<EditorSpatialGizmo>,
+        index: i64,
+    ) -> GodotString {
+        let gizmo = unsafe { gizmo.assume_safe() };
+        match Self::vertex_key(gizmo, index) {
+            None => GodotString::new(),
+            Some(key) => GodotString::from(format!("Vertex {}, {}", key.x, key.y)),
+        }
+    }
+
+    /// Returns the height of the vertex, used to restore it if dragging is cancelled.
+    #[export]
+    pub fn get_handle_value(
+        &self,
+        _owner: TRef<'_, EditorSpatialGizmoPlugin>,
+        gizmo: Ref<EditorSpatialGizmo>,
+        index: i64,
+    ) -> Variant {
+        let gizmo = unsafe { gizmo.assume_safe() };
+        let (terrain, key) = match (Self::terrain(gizmo), Self::vertex_key(gizmo, index)) {
+            (Some(terrain), Some(key)) => (terrain, key),
+            _ => return Variant::new(),
+        };
+        unsafe { terrain.assume_safe() }
+            .map(|terrain, owner| terrain.get_vertex_height(owner, key.x as i64, key.y as i64))
+            .map_or_else(|_| Variant::new(), |height| height.to_variant())
+    }
+
+    /// Moves the vertex to the height on its vertical line that is closest to the mouse ray.
+    #[export]
+    pub fn set_handle(
+        &self,
+        _owner: TRef<'_, EditorSpatialGizmoPlugin>,
+        gizmo: Ref<EditorSpatialGizmo>,
+        index: i64,
+        camera: Ref<Camera>,
+        point: Vector2,
+    ) {
+        let gizmo = unsafe { gizmo.assume_safe() };
+        let camera = unsafe { camera.assume_safe() };
+        let (terrain, key) = match (Self::terrain(gizmo), Self::vertex_key(gizmo, index)) {
+            (Some(terrain), Some(key)) => (terrain, key),
+            _ => return,
+        };
+        let terrain = unsafe { terrain.assume_safe() };
+
+        let spatial = terrain.base();
+        let origin = spatial.to_local(camera.project_ray_origin(point));
+        let direction = spatial
+            .to_local(camera.project_ray_origin(point) + camera.project_ray_normal(point))
+            - origin;
+        let node_height = spatial.get("node_height").to_f64() as f32;
+        if node_height <= 0.0 {
+            return;
+        }
+
+        let vertex = match terrain
+            .map(|terrain, owner| terrain.get_vertex_position(owner, index))
+            .ok()
+            .and_then(|position| position.try_to_vector3())
+        {
+            Some(vertex) => vertex,
+            None => return,
+        };
+
+        // Closest point between the ray and the vertical line through the vertex.
+        let vertex = Vector3::new(vertex.x, 0.0, vertex.z);
+        let horizontal = Vector3::new(direction.x, 0.0, direction.z);
+        let horizontal_length = horizontal.square_length();
+        let y = if horizontal_length < f32::EPSILON {
+            return;
+        } else {
+            let t = (vertex - origin).dot(horizontal) / horizontal_length;
+            origin.y + direction.y * t
+        };
+
+        let height = (y / node_height).round() as i64;
+        terrain
+            .map_mut(|terrain, owner| {
+                terrain.set_vertex_height(owner, key.x as i64, key.y as i64, height)
+            })
+            .ok();
+        spatial.update_gizmo();
+    }
+
+    #[export]
+    pub fn commit_handle(
+        &self,
+        _owner: TRef<'_, EditorSpatialGizmoPlugin>,
+        gizmo: Ref<EditorSpatialGizmo>,
+        index: i64,
+        restore: Variant,
+        cancel: bool,
+    ) {
+        if !cancel {
+            return;
+        }
+        let gizmo = unsafe { gizmo.assume_safe() };
+        if let (Some(terrain), Some(key)) = (Self::terrain(gizmo), Self::vertex_key(gizmo, index)) {
+            let terrain = unsafe { terrain.assume_safe() };
+            terrain
+                .map_mut(|terrain, owner| {
+                    terrain.set_vertex_height(owner, key.x as i64, key.y as i64, restore.to_i64())
+                })
+                .ok();
+            terrain.base().update_gizmo();
+        }
+    }
+
+    fn terrain(gizmo: TRef<'_, EditorSpatialGizmo>) -> Option<Instance<HexTerrain, Shared>> {
+        gizmo
+            .get_spatial_node()
+            .and_then(Instance::<HexTerrain, Shared>::try_from_base)
+    }
+
+    fn vertex_key(gizmo: TRef<'_, EditorSpatialGizmo>, index: i64) -> Option<Vector2> {
+        let terrain = Self::terrain(gizmo)?;
+        unsafe { terrain.assume_safe() }
+            .map(|terrain, owner| terrain.get_vertex_key(owner, index))
+            .ok()?
+            .try_to_vector2()
+    }
+}
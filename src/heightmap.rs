@@ -0,0 +1,113 @@
+use crate::hex;
+use crate::hex::Vector2Di32;
+use std::collections::HashMap;
+
+/// An RGBA image of vertex heights, one pixel per key unit. Heights are scaled from black at the
+/// lowest to white at the highest vertex, pixels outside of the cells are transparent.
+pub struct Heightmap {
+    pub width: i64,
+    pub height: i64,
+    pub pixels: Vec<u8>,
+    /// Key at the top left pixel.
+    pub origin: Vector2Di32,
+}
+
+impl Heightmap {
+    /// Renders the vertex heights. Pixels between vertices get the height of the center of the
+    /// cell they are in.
+    pub fn render(heights: &[(Vector2Di32, i32)]) -> Heightmap {
+        if heights.is_empty() {
+            return Heightmap {
+                width: 0,
+                height: 0,
+                pixels: Vec::new(),
+                origin: Vector2Di32::zero(),
+            };
+        }
+
+        let min_x = heights.iter().map(|(key, _)| key.x).min().unwrap();
+        let max_x = heights.iter().map(|(key, _)| key.x).max().unwrap();
+        let min_y = heights.iter().map(|(key, _)| key.y).min().unwrap();
+        let max_y = heights.iter().map(|(key, _)| key.y).max().unwrap();
+        let lowest = heights.iter().map(|(_, height)| *height).min().unwrap();
+        let highest = heights.iter().map(|(_, height)| *height).max().unwrap();
+        let range = (highest - lowest).max(1) as f32;
+
+        let height_map: HashMap<Vector2Di32, i32> = heights.iter().copied().collect();
+        let width = max_x - min_x + 1;
+        let height = max_y - min_y + 1;
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let key = Vector2Di32::new(x, y);
+                let value = height_map
+                    .get(&key)
+                    .or_else(|| height_map.get(&hex::nearest_cell(x as f32, y as f32)));
+                match value {
+                    None => pixels.extend_from_slice(&[0, 0, 0, 0]),
+                    Some(value) => {
+                        let gray = ((*value - lowest) as f32 / range * 255.0).round() as u8;
+                        pixels.extend_from_slice(&[gray, gray, gray, 255]);
+                    }
+                }
+            }
+        }
+
+        Heightmap {
+            width: width as i64,
+            height: height as i64,
+            pixels,
+            origin: Vector2Di32::new(min_x, min_y),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(heightmap: &Heightmap, x: i64, y: i64) -> &[u8] {
+        let index = ((y * heightmap.width + x) * 4) as usize;
+        &heightmap.pixels[index..index + 4]
+    }
+
+    #[test]
+    fn render_scales_heights_between_black_and_white() {
+        let cell = hex::Hexagon::new(Vector2Di32::zero());
+        let mut heights: Vec<(Vector2Di32, i32)> =
+            cell.keys().iter().map(|key| (*key, 1)).collect();
+        heights[0].1 = 3;
+
+        let heightmap = Heightmap::render(&heights);
+
+        assert_eq!(5, heightmap.width);
+        assert_eq!(5, heightmap.height);
+        assert_eq!(Vector2Di32::new(-2, -2), heightmap.origin);
+        assert_eq!(&[255, 255, 255, 255], pixel(&heightmap, 2, 2));
+        assert_eq!(&[0, 0, 0, 255], pixel(&heightmap, 0, 2));
+        assert_eq!(&[255, 255, 255, 255], pixel(&heightmap, 2, 1));
+    }
+
+    #[test]
+    fn render_leaves_pixels_outside_of_cells_transparent() {
+        let heights = [
+            (hex::LEFT, 0),
+            (hex::RIGHT + hex::RIGHT + hex::TOP_RIGHT, 0),
+        ];
+
+        let heightmap = Heightmap::render(&heights);
+
+        assert_eq!(
+            0,
+            pixel(&heightmap, heightmap.width - 1, heightmap.height - 1)[3]
+        );
+    }
+
+    #[test]
+    fn render_returns_empty_image_without_heights() {
+        let heightmap = Heightmap::render(&[]);
+
+        assert_eq!(0, heightmap.width);
+        assert!(heightmap.pixels.is_empty());
+    }
+}
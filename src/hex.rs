@@ -0,0 +1,732 @@
+use euclid::{UnknownUnit, Vector2D};
+use std::collections::HashMap;
+use std::ops::Index;
+
+pub type Vector2Di32 = Vector2D<i32, UnknownUnit>;
+
+pub const LEFT: Vector2Di32 = Vector2Di32::new(-2, 0);
+pub const TOP_LEFT: Vector2Di32 = Vector2Di32::new(-1, -2);
+pub const TOP_RIGHT: Vector2Di32 = Vector2Di32::new(1, -2);
+pub const RIGHT: Vector2Di32 = Vector2Di32::new(2, 0);
+pub const BOTTOM_RIGHT: Vector2Di32 = Vector2Di32::new(1, 2);
+pub const BOTTOM_LEFT: Vector2Di32 = Vector2Di32::new(-1, 2);
+
+/// Offsets of the corners of a hexagon from its center, clockwise from the left one.
+pub const CORNERS: [Vector2Di32; 6] = [LEFT, TOP_LEFT, TOP_RIGHT, RIGHT, BOTTOM_RIGHT, BOTTOM_LEFT];
+
+/// Distance between the centers of two neighbouring cells in key units.
+pub const CELL_DISTANCE: f32 = 4.0;
+
+pub struct Hexagon {
+    pub center: Vector2Di32,
+    pub left: Vector2Di32,
+    pub top_left: Vector2Di32,
+    pub top_right: Vector2Di32,
+    pub right: Vector2Di32,
+    pub bottom_right: Vector2Di32,
+    pub bottom_left: Vector2Di32,
+}
+
+impl Hexagon {
+    pub fn new(center: Vector2Di32) -> Hexagon {
+        let left = center + LEFT;
+        let top_left = center + TOP_LEFT;
+        let top_right = center + TOP_RIGHT;
+        let right = center + RIGHT;
+        let bottom_right = center + BOTTOM_RIGHT;
+        let bottom_left = center + BOTTOM_LEFT;
+
+        Hexagon {
+            center,
+            left,
+            top_left,
+            top_right,
+            right,
+            bottom_right,
+            bottom_left,
+        }
+    }
+
+    /// Returns the keys of the center and the six corners of the hexagon.
+    pub fn keys(&self) -> [Vector2Di32; 7] {
+        [
+            self.center,
+            self.left,
+            self.top_left,
+            self.top_right,
+            self.right,
+            self.bottom_right,
+            self.bottom_left,
+        ]
+    }
+}
+
+// Cell centers form the lattice `q * (3, -2) + r * (0, 4)`. The (q, r) pair is used as axial
+// coordinate of a cell, with the neighbours of a cell being at (±1, 0), (0, ±1) and ±(1, 1).
+
+/// Converts the center of a cell to its axial coordinates.
+pub fn cell_to_axial(cell: Vector2Di32) -> Vector2Di32 {
+    let q = cell.x.div_euclid(3);
+    let r = (cell.y + 2 * q).div_euclid(4);
+    Vector2Di32::new(q, r)
+}
+
+/// Converts axial coordinates to the center of the cell.
+pub fn axial_to_cell(axial: Vector2Di32) -> Vector2Di32 {
+    Vector2Di32::new(3 * axial.x, 4 * axial.y - 2 * axial.x)
+}
+
+/// Converts offset coordinates, as used by tile map editors, to axial coordinates. Every other
+/// column is shifted down by half a cell: the odd ones if `odd_shifted`, otherwise the even ones.
+pub fn offset_to_axial(column: i32, row: i32, odd_shifted: bool) -> Vector2Di32 {
+    let shift = if odd_shifted {
+        (column + (column & 1)) / 2
+    } else {
+        (column - (column & 1)) / 2
+    };
+    Vector2Di32::new(column, row + shift)
+}
+
+/// Converts axial coordinates to the column and row of offset coordinates, see `offset_to_axial`.
+pub fn axial_to_offset(axial: Vector2Di32, odd_shifted: bool) -> (i32, i32) {
+    let column = axial.x;
+    let shift = offset_to_axial(column, 0, odd_shifted).y;
+    (column, axial.y - shift)
+}
+
+/// Returns the number of steps between two axial coordinates.
+pub fn axial_distance(first: Vector2Di32, second: Vector2Di32) -> i32 {
+    let dq = second.x - first.x;
+    let dr = second.y - first.y;
+    dq.abs().max(dr.abs()).max((dq - dr).abs())
+}
+
+/// Returns all axial coordinates that are at most `range` steps away from `center`.
+pub fn axial_range(center: Vector2Di32, range: u32) -> Vec<Vector2Di32> {
+    let range = range as i32;
+    let mut result = Vec::new();
+    for dq in -range..=range {
+        for dr in (dq - range).max(-range)..=(dq + range).min(range) {
+            result.push(center + Vector2Di32::new(dq, dr));
+        }
+    }
+    result
+}
+
+/// Returns the centers of all cells that are at most `range` cells away from `cell`.
+pub fn cells_in_range(cell: Vector2Di32, range: u32) -> Vec<Vector2Di32> {
+    axial_range(cell_to_axial(cell), range)
+        .into_iter()
+        .map(axial_to_cell)
+        .collect()
+}
+
+/// Converts a vertex key to coordinates on the triangular lattice that the centers and corners of
+/// all cells form together. The lattice is spanned by `RIGHT` and `BOTTOM_RIGHT`, so the
+/// neighbours of a vertex are at (±1, 0), (0, ±1) and ±(1, -1).
+pub fn key_to_lattice(key: Vector2Di32) -> Vector2Di32 {
+    let b = key.y.div_euclid(2);
+    Vector2Di32::new((key.x - b).div_euclid(2), b)
+}
+
+/// Returns the centers of the six cells around `cell`, starting with the one above it and going
+/// clockwise.
+pub fn neighbouring_cells(cell: Vector2Di32) -> [Vector2Di32; 6] {
+    [
+        cell + Vector2Di32::new(0, -4),
+        cell + Vector2Di32::new(3, -2),
+        cell + Vector2Di32::new(3, 2),
+        cell + Vector2Di32::new(0, 4),
+        cell + Vector2Di32::new(-3, 2),
+        cell + Vector2Di32::new(-3, -2),
+    ]
+}
+
+/// Returns the centers of all cells that are at most `radius` cells away from `center`, starting
+/// with `center` and then ring by ring, every ring starting with the cell above `center` and
+/// going clockwise.
+pub fn cell_spiral(center: Vector2Di32, radius: u32) -> Vec<Vector2Di32> {
+    let mut cells = vec![center];
+    for ring in 1..=radius as i32 {
+        let offsets = neighbouring_cells(Vector2Di32::zero());
+        let mut cell = center + offsets[0] * ring;
+        for side in 0..6 {
+            for _ in 0..ring {
+                cells.push(cell);
+                cell += offsets[(side + 2) % 6];
+            }
+        }
+    }
+    cells
+}
+
+/// Returns the centers of the cells on a winding line from `start`. The line heads in
+/// `direction`, an index into `neighbouring_cells`, and takes one step per entry of `turns`, after
+/// turning clockwise by that many 60° steps.
+pub fn winding_line(start: Vector2Di32, direction: i32, turns: &[i32]) -> Vec<Vector2Di32> {
+    let mut cells = vec![start];
+    let mut cell = start;
+    let mut direction = direction;
+    for turn in turns {
+        direction += turn;
+        cell = neighbouring_cells(cell)[direction.rem_euclid(6) as usize];
+        cells.push(cell);
+    }
+    cells
+}
+
+/// Returns the centers of the cells on the straight line between two cells, including both.
+pub fn cells_on_line(from: Vector2Di32, to: Vector2Di32) -> Vec<Vector2Di32> {
+    let steps = axial_distance(cell_to_axial(from), cell_to_axial(to));
+    if steps == 0 {
+        return vec![from];
+    }
+
+    // A small nudge keeps points that are exactly between two cells from alternating sides.
+    let nudge = 0.001;
+    (0..=steps)
+        .map(|step| {
+            let t = step as f32 / steps as f32;
+            nearest_cell(
+                from.x as f32 + (to.x - from.x) as f32 * t + nudge,
+                from.y as f32 + (to.y - from.y) as f32 * t + nudge,
+            )
+        })
+        .collect()
+}
+
+/// Rotates a key around the origin by 60° steps. One step moves the left corner of a hexagon to
+/// its top left corner. The origin has to be a cell center for the result to be a valid key.
+pub fn rotate_key(key: Vector2Di32, steps: i32) -> Vector2Di32 {
+    let mut key = key;
+    for _ in 0..steps.rem_euclid(6) {
+        key = Vector2Di32::new((2 * key.x - 3 * key.y) / 4, key.x + key.y / 2);
+    }
+    key
+}
+
+/// Mirrors a key across an axis through the origin. Axis 0 passes through the middle of the top and
+/// bottom edges of a hexagon, every further axis is turned by 30°.
+pub fn mirror_key(key: Vector2Di32, axis: i32) -> Vector2Di32 {
+    rotate_key(Vector2Di32::new(-key.x, key.y), axis)
+}
+
+/// Symmetry of edits around a cell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    None,
+    /// Mirrored across the axis, as used by `mirror_key`.
+    Mirror(i32),
+    /// Repeated the given number of times around the center. Only 2, 3 and 6 fit the grid, other
+    /// values are rounded down to the next of them.
+    Rotation(i32),
+}
+
+/// Returns the images of the key under every transformation of the symmetry around the center,
+/// starting with the key itself. Keys on an axis or at the center appear more than once, so the
+/// images of different keys correspond by index. The center has to be a cell center.
+pub fn symmetric_images(
+    key: Vector2Di32,
+    center: Vector2Di32,
+    symmetry: Symmetry,
+) -> Vec<Vector2Di32> {
+    let offset = key - center;
+    let mut images = vec![key];
+    match symmetry {
+        Symmetry::None => {}
+        Symmetry::Mirror(axis) => images.push(center + mirror_key(offset, axis)),
+        Symmetry::Rotation(folds) => {
+            let folds = match folds {
+                folds if folds >= 6 => 6,
+                folds if folds >= 3 => 3,
+                folds if folds >= 2 => 2,
+                _ => 1,
+            };
+            for fold in 1..folds {
+                images.push(center + rotate_key(offset, fold * 6 / folds));
+            }
+        }
+    }
+    images
+}
+
+/// Returns the key and all keys it is mapped to by the symmetry around the center, without
+/// duplicates. The center has to be a cell center.
+pub fn symmetric_keys(
+    key: Vector2Di32,
+    center: Vector2Di32,
+    symmetry: Symmetry,
+) -> Vec<Vector2Di32> {
+    let mut keys = Vec::new();
+    for image in symmetric_images(key, center, symmetry) {
+        if !keys.contains(&image) {
+            keys.push(image);
+        }
+    }
+    keys
+}
+
+/// Returns the center of the cell closest to the given position in key space.
+pub fn nearest_cell(x: f32, y: f32) -> Vector2Di32 {
+    // Round in cube coordinates, where the third axis is derived from the other two.
+    let q = x / 3.0;
+    let r = (y + 2.0 * q) / 4.0 - q;
+    let s = -q - r;
+
+    let mut rounded_q = q.round();
+    let mut rounded_r = r.round();
+    let rounded_s = s.round();
+
+    let q_diff = (rounded_q - q).abs();
+    let r_diff = (rounded_r - r).abs();
+    let s_diff = (rounded_s - s).abs();
+
+    if q_diff > r_diff && q_diff > s_diff {
+        rounded_q = -rounded_r - rounded_s;
+    } else if r_diff > s_diff {
+        rounded_r = -rounded_q - rounded_s;
+    }
+
+    let q = rounded_q as i32;
+    axial_to_cell(Vector2Di32::new(q, rounded_r as i32 + q))
+}
+
+/// Returns the chunk the cell belongs to. Chunks are `chunk_size` cells wide along both axial axes.
+pub fn chunk_of_cell(cell: Vector2Di32, chunk_size: i32) -> Vector2Di32 {
+    let axial = cell_to_axial(cell);
+    Vector2Di32::new(
+        axial.x.div_euclid(chunk_size),
+        axial.y.div_euclid(chunk_size),
+    )
+}
+
+/// Returns the centers of all cells in the chunk.
+pub fn cells_of_chunk(chunk: Vector2Di32, chunk_size: i32) -> Vec<Vector2Di32> {
+    let mut result = Vec::new();
+    for q in 0..chunk_size {
+        for r in 0..chunk_size {
+            result.push(axial_to_cell(Vector2Di32::new(
+                chunk.x * chunk_size + q,
+                chunk.y * chunk_size + r,
+            )));
+        }
+    }
+    result
+}
+
+/// Width and height of the shards of a `ChunkMap` in key units.
+pub const SHARD_SIZE: i32 = 32;
+
+/// A map from keys to values that is split into square shards of `SHARD_SIZE` key units, so the
+/// values of neighbouring keys are stored together and whole shards can be dropped at once.
+#[derive(Clone, Debug)]
+pub struct ChunkMap<V> {
+    shards: HashMap<Vector2Di32, HashMap<Vector2Di32, V>>,
+    len: usize,
+}
+
+impl<V> Default for ChunkMap<V> {
+    fn default() -> ChunkMap<V> {
+        ChunkMap {
+            shards: HashMap::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<V> ChunkMap<V> {
+    /// Returns the shard the key belongs to.
+    pub fn shard_of(key: Vector2Di32) -> Vector2Di32 {
+        Vector2Di32::new(key.x.div_euclid(SHARD_SIZE), key.y.div_euclid(SHARD_SIZE))
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, key: &Vector2Di32) -> Option<&V> {
+        self.shards.get(&Self::shard_of(*key))?.get(key)
+    }
+
+    pub fn contains_key(&self, key: &Vector2Di32) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Sets the value of a key. Returns the previous value.
+    pub fn insert(&mut self, key: Vector2Di32, value: V) -> Option<V> {
+        let previous = self
+            .shards
+            .entry(Self::shard_of(key))
+            .or_default()
+            .insert(key, value);
+        if previous.is_none() {
+            self.len += 1;
+        }
+        previous
+    }
+
+    pub fn remove(&mut self, key: &Vector2Di32) -> Option<V> {
+        let shard = Self::shard_of(*key);
+        let values = self.shards.get_mut(&shard)?;
+        let value = values.remove(key)?;
+        if values.is_empty() {
+            self.shards.remove(&shard);
+        }
+        self.len -= 1;
+        Some(value)
+    }
+
+    /// Removes all values of a shard. Returns them.
+    pub fn remove_shard(&mut self, shard: Vector2Di32) -> HashMap<Vector2Di32, V> {
+        let values = self.shards.remove(&shard).unwrap_or_default();
+        self.len -= values.len();
+        values
+    }
+
+    /// Returns the shards that have values.
+    pub fn shards(&self) -> impl Iterator<Item = &Vector2Di32> + Clone {
+        self.shards.keys()
+    }
+
+    /// Returns the keys shard by shard.
+    pub fn keys(&self) -> impl Iterator<Item = &Vector2Di32> + Clone {
+        self.shards.values().flat_map(|values| values.keys())
+    }
+
+    /// Returns the keys and values shard by shard.
+    pub fn iter(&self) -> impl Iterator<Item = (&Vector2Di32, &V)> + Clone {
+        self.shards.values().flat_map(|values| values.iter())
+    }
+}
+
+impl<V> Extend<(Vector2Di32, V)> for ChunkMap<V> {
+    fn extend<I: IntoIterator<Item = (Vector2Di32, V)>>(&mut self, values: I) {
+        for (key, value) in values {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<V> Index<&Vector2Di32> for ChunkMap<V> {
+    type Output = V;
+
+    fn index(&self, key: &Vector2Di32) -> &V {
+        self.get(key).expect("key not in map")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn axial_conversion_round_trips() {
+        for axial in axial_range(Vector2Di32::zero(), 4) {
+            assert_eq!(axial, cell_to_axial(axial_to_cell(axial)));
+        }
+    }
+
+    #[test]
+    fn axial_to_offset_reverses_offset_to_axial() {
+        for odd_shifted in &[false, true] {
+            for column in -3..3 {
+                for row in -3..3 {
+                    let axial = offset_to_axial(column, row, *odd_shifted);
+                    assert_eq!((column, row), axial_to_offset(axial, *odd_shifted));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn offset_to_axial_keeps_neighbouring_tiles_next_to_each_other() {
+        for odd_shifted in &[false, true] {
+            for column in -3..3 {
+                for row in -3..3 {
+                    let axial = offset_to_axial(column, row, *odd_shifted);
+                    let below = offset_to_axial(column, row + 1, *odd_shifted);
+                    let right = offset_to_axial(column + 1, row, *odd_shifted);
+                    assert_eq!(1, axial_distance(axial, below));
+                    assert_eq!(1, axial_distance(axial, right));
+                    // Shifted columns are lower than their neighbours.
+                    let shifted = (column & 1 == 1) == *odd_shifted;
+                    let down = axial_to_cell(right).y > axial_to_cell(axial).y;
+                    assert_eq!(shifted, !down);
+                }
+            }
+        }
+    }
+
+    fn neighbours() -> [Vector2Di32; 6] {
+        let hexagon = Hexagon::new(Vector2Di32::zero());
+        [
+            hexagon.left + TOP_LEFT,
+            hexagon.top_left + TOP_RIGHT,
+            hexagon.top_right + RIGHT,
+            hexagon.right + BOTTOM_RIGHT,
+            hexagon.bottom_right + BOTTOM_LEFT,
+            hexagon.bottom_left + LEFT,
+        ]
+    }
+
+    #[test]
+    fn neighbouring_cells_are_one_step_away() {
+        let center = cell_to_axial(Vector2Di32::zero());
+        for neighbour in neighbours().iter() {
+            assert_eq!(1, axial_distance(center, cell_to_axial(*neighbour)));
+        }
+    }
+
+    #[test]
+    fn axial_range_contains_hexagonal_number_of_cells() {
+        assert_eq!(1, axial_range(Vector2Di32::zero(), 0).len());
+        assert_eq!(7, axial_range(Vector2Di32::zero(), 1).len());
+        assert_eq!(19, axial_range(Vector2Di32::zero(), 2).len());
+    }
+
+    #[test]
+    fn neighbouring_cells_returns_all_neighbours() {
+        let cells = neighbouring_cells(Vector2Di32::zero());
+
+        for neighbour in neighbours().iter() {
+            assert!(cells.contains(neighbour));
+        }
+    }
+
+    #[test]
+    fn key_to_lattice_maps_corners_to_lattice_neighbours() {
+        let center = Vector2Di32::new(3, -2);
+        let lattice = key_to_lattice(center);
+        assert_eq!(Vector2Di32::new(2, -1), lattice);
+
+        for corner in [LEFT, TOP_LEFT, TOP_RIGHT, RIGHT, BOTTOM_RIGHT, BOTTOM_LEFT].iter() {
+            let offset = key_to_lattice(center + *corner) - lattice;
+            assert!([(1, 0), (-1, 0), (0, 1), (0, -1), (1, -1), (-1, 1)]
+                .contains(&(offset.x, offset.y)));
+        }
+    }
+
+    #[test]
+    fn winding_line_follows_turns() {
+        let start = Vector2Di32::zero();
+
+        let cells = winding_line(start, 3, &[0, 0, 1, -7]);
+
+        assert_eq!(
+            vec![
+                start,
+                Vector2Di32::new(0, 4),
+                Vector2Di32::new(0, 8),
+                Vector2Di32::new(-3, 10),
+                Vector2Di32::new(-3, 14),
+            ],
+            cells
+        );
+    }
+
+    #[test]
+    fn cells_in_range_returns_neighbouring_cells() {
+        let cells = cells_in_range(Vector2Di32::zero(), 1);
+
+        assert_eq!(7, cells.len());
+        assert!(cells.contains(&Vector2Di32::zero()));
+        for neighbour in neighbours().iter() {
+            assert!(cells.contains(neighbour));
+        }
+    }
+
+    #[test]
+    fn cells_on_line_returns_connected_cells() {
+        let from = Vector2Di32::new(-3, -2);
+        let to = Vector2Di32::new(9, 6);
+
+        let cells = cells_on_line(from, to);
+
+        assert_eq!(5, cells.len());
+        assert_eq!(from, cells[0]);
+        assert_eq!(to, cells[4]);
+        for index in 1..cells.len() {
+            assert_eq!(
+                1,
+                axial_distance(cell_to_axial(cells[index - 1]), cell_to_axial(cells[index]))
+            );
+        }
+    }
+
+    #[test]
+    fn rotate_key_moves_corners_to_next_corner() {
+        let corners = [LEFT, TOP_LEFT, TOP_RIGHT, RIGHT, BOTTOM_RIGHT, BOTTOM_LEFT];
+        for index in 0..corners.len() {
+            assert_eq!(
+                corners[(index + 1) % corners.len()],
+                rotate_key(corners[index], 1)
+            );
+            assert_eq!(
+                corners[(index + 5) % corners.len()],
+                rotate_key(corners[index], -1)
+            );
+        }
+    }
+
+    #[test]
+    fn rotate_key_moves_neighbouring_cells_to_next_neighbour() {
+        let neighbours = neighbours();
+        for index in 0..neighbours.len() {
+            assert_eq!(
+                neighbours[(index + 1) % neighbours.len()],
+                rotate_key(neighbours[index], 1)
+            );
+        }
+    }
+
+    #[test]
+    fn rotate_key_by_six_steps_returns_key() {
+        let key = Vector2Di32::new(7, -6);
+        assert_eq!(key, rotate_key(key, 6));
+        assert_eq!(key, rotate_key(key, 0));
+    }
+
+    #[test]
+    fn mirror_key_swaps_left_and_right_on_first_axis() {
+        assert_eq!(RIGHT, mirror_key(LEFT, 0));
+        assert_eq!(TOP_RIGHT, mirror_key(TOP_LEFT, 0));
+        assert_eq!(BOTTOM_LEFT, mirror_key(BOTTOM_RIGHT, 0));
+    }
+
+    #[test]
+    fn mirror_key_twice_returns_key() {
+        for axis in 0..6 {
+            for key in Hexagon::new(Vector2Di32::new(3, -2)).keys().iter() {
+                assert_eq!(*key, mirror_key(mirror_key(*key, axis), axis));
+            }
+        }
+    }
+
+    #[test]
+    fn symmetric_keys_mirrors_key_around_center() {
+        let center = Vector2Di32::new(3, -2);
+
+        let keys = symmetric_keys(center + LEFT, center, Symmetry::Mirror(0));
+
+        assert_eq!(vec![center + LEFT, center + RIGHT], keys);
+    }
+
+    #[test]
+    fn symmetric_keys_rotates_key_around_center() {
+        let center = Vector2Di32::new(3, -2);
+
+        let keys = symmetric_keys(center + LEFT, center, Symmetry::Rotation(3));
+
+        assert_eq!(
+            vec![center + LEFT, center + TOP_RIGHT, center + BOTTOM_RIGHT],
+            keys
+        );
+        assert_eq!(
+            6,
+            symmetric_keys(LEFT, Vector2Di32::zero(), Symmetry::Rotation(6)).len()
+        );
+    }
+
+    #[test]
+    fn symmetric_keys_does_not_duplicate_center() {
+        let center = Vector2Di32::new(3, -2);
+
+        assert_eq!(
+            vec![center],
+            symmetric_keys(center, center, Symmetry::Rotation(6))
+        );
+        assert_eq!(
+            vec![LEFT],
+            symmetric_keys(LEFT, Vector2Di32::zero(), Symmetry::None)
+        );
+    }
+
+    #[test]
+    fn symmetric_images_correspond_by_transformation() {
+        let center = Vector2Di32::new(3, -2);
+
+        let images = symmetric_images(center, center, Symmetry::Rotation(3));
+        let left = symmetric_images(center + LEFT, center, Symmetry::Rotation(3));
+
+        assert_eq!(vec![center; 3], images);
+        assert_eq!(
+            vec![center + LEFT, center + TOP_RIGHT, center + BOTTOM_RIGHT],
+            left
+        );
+    }
+
+    #[test]
+    fn nearest_cell_returns_cell_of_its_corners() {
+        for offset in neighbours().iter() {
+            let hexagon = Hexagon::new(*offset);
+            for key in hexagon.keys().iter() {
+                let key = *key - *offset;
+                let position = *offset + key / 2;
+                assert_eq!(*offset, nearest_cell(position.x as f32, position.y as f32));
+            }
+        }
+    }
+
+    #[test]
+    fn cell_spiral_walks_rings_from_center() {
+        let center = Vector2Di32::new(3, -2);
+        let cells = cell_spiral(center, 3);
+
+        assert_eq!(&[center], &cells[..1]);
+        assert_eq!(&neighbouring_cells(center)[..], &cells[1..7]);
+        let mut sorted = cells.clone();
+        sorted.sort_unstable_by_key(|cell| (cell.x, cell.y));
+        let mut in_range = cells_in_range(center, 3);
+        in_range.sort_unstable_by_key(|cell| (cell.x, cell.y));
+        assert_eq!(in_range, sorted);
+        for (index, cell) in cells.iter().enumerate().skip(1) {
+            let distance = axial_distance(cell_to_axial(center), cell_to_axial(*cell));
+            assert!(
+                distance >= axial_distance(cell_to_axial(center), cell_to_axial(cells[index - 1]))
+            );
+        }
+    }
+
+    #[test]
+    fn chunk_of_cell_matches_cells_of_chunk() {
+        let chunk = Vector2Di32::new(-1, 2);
+        for cell in cells_of_chunk(chunk, 4) {
+            assert_eq!(chunk, chunk_of_cell(cell, 4));
+        }
+    }
+
+    #[test]
+    fn corners_match_hexagon_keys() {
+        let center = Vector2Di32::new(3, -2);
+        let keys = Hexagon::new(center).keys();
+        for (index, corner) in CORNERS.iter().enumerate() {
+            assert_eq!(center + *corner, keys[index + 1]);
+        }
+    }
+
+    #[test]
+    fn chunk_map_stores_values_by_shard() {
+        let mut map = ChunkMap::default();
+        let near = Vector2Di32::new(1, 2);
+        let far = Vector2Di32::new(-SHARD_SIZE, 2);
+
+        assert_eq!(None, map.insert(near, 1));
+        assert_eq!(Some(1), map.insert(near, 2));
+        map.extend(vec![(far, 3), (near + RIGHT, 4)]);
+
+        assert_eq!(3, map.len());
+        assert_eq!(2, map.shards().count());
+        assert_eq!(4, map[&(near + RIGHT)]);
+        assert_eq!(Some(&3), map.get(&far));
+        assert!(!map.contains_key(&Vector2Di32::new(0, 0)));
+
+        assert_eq!(2, map.remove_shard(ChunkMap::<i32>::shard_of(near)).len());
+        assert_eq!(Some(3), map.remove(&far));
+        assert!(map.is_empty());
+        assert_eq!(0, map.shards().count());
+    }
+}
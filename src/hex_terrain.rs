@@ -1,569 +1,5890 @@
-use euclid::{UnknownUnit, Vector2D};
+use crate::autosave;
+use crate::autosave::Autosave;
+use crate::clipboard::HexTerrainClipboard;
+use crate::displacement;
+use crate::displacement::HeightTexture;
+use crate::generation::{GenerationJob, GenerationPipeline};
+use crate::heightmap::Heightmap;
+use crate::hex;
+use crate::hex::{ChunkMap, Hexagon, Vector2Di32, CELL_DISTANCE, CORNERS};
+use crate::map_format::HexMap;
+use crate::map_render;
+use crate::map_render::Canvas;
+use crate::mesh;
+use crate::mesh::{Bounds, Corner, MeshArrays, VertexColumns};
+use crate::minimap;
+use crate::minimap::Minimap;
+use crate::preset::HexGenPreset;
+use crate::rebuild::Rebuild;
+use crate::region::Region;
+use crate::stamp::HexStamp;
+use crate::telemetry::Telemetry;
+use crate::world;
+use crate::world::{Settings, World, EROSION_RATE};
+use gdnative::api::File;
 use gdnative::api::GlobalConstants;
 use gdnative::api::Node as GodotNode;
+use gdnative::api::JSON;
 use gdnative::api::{
-    ArrayMesh, CollisionShape, Mesh, MeshInstance, SphereShape, StaticBody, SurfaceTool,
+    ArrayMesh, Camera, CanvasLayer, CollisionShape, ConcavePolygonShape, GridMap, Image,
+    ImageTexture, InputEventMagnifyGesture, InputEventMouseButton, InputEventMouseMotion,
+    InputEventScreenDrag, InputEventScreenTouch, InputMap, Label, Material, Mesh, MeshInstance,
+    ProjectSettings, Shader, ShaderMaterial, SpatialMaterial, StaticBody,
 };
+use gdnative::nativescript::init::property::{EnumHint, IntHint};
 use gdnative::prelude::*;
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
-use std::sync::mpsc;
-use std::sync::mpsc::{Receiver, Sender};
-use std::thread;
-use std::time::Duration;
+use std::mem;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use terrain::climate::Biome;
+use terrain::csv;
+#[cfg(feature = "dem")]
+use terrain::dem::Dem;
+use terrain::gltf;
+use terrain::gltf::{Surface, Vertex};
+use terrain::graph;
+use terrain::hexmaps;
+use terrain::history::{Edit, History};
+use terrain::json;
+use terrain::json::Json;
+use terrain::lockstep;
+use terrain::lockstep::Command;
+use terrain::migration::Migrations;
+use terrain::noise::Fractal;
+use terrain::path;
+use terrain::provinces;
+use terrain::random::Random;
+use terrain::regions;
+use terrain::replication;
+use terrain::replication::Conflict;
+use terrain::ron;
+use terrain::save;
+use terrain::save::Chunk;
+use terrain::scatter;
+use terrain::streaming::ChunkStore;
 use terrain::terrain::Terrain;
+use terrain::tiled;
+use terrain::tools;
+use terrain::tools::BlendMode;
+use terrain::wfc::Module;
 
-type Vector2Di32 = Vector2D<i32, UnknownUnit>;
-type HexagonData = (Hexagon, HashMap<Vector2Di32, Vector2>, Vec<TerrainNode>);
-type NodeData = (Vector2Di32, u32);
-
-const LEFT: Vector2Di32 = Vector2Di32::new(-2, 0);
-const TOP_LEFT: Vector2Di32 = Vector2Di32::new(-1, -2);
-const TOP_RIGHT: Vector2Di32 = Vector2Di32::new(1, -2);
-const RIGHT: Vector2Di32 = Vector2Di32::new(2, 0);
-const BOTTOM_RIGHT: Vector2Di32 = Vector2Di32::new(1, 2);
-const BOTTOM_LEFT: Vector2Di32 = Vector2Di32::new(-1, 2);
-
-struct Hexagon {
-    center: Vector2Di32,
-    left: Vector2Di32,
-    top_left: Vector2Di32,
-    top_right: Vector2Di32,
-    right: Vector2Di32,
-    bottom_right: Vector2Di32,
-    bottom_left: Vector2Di32,
+/// UV of the center of a hexagon.
+const CENTER_UV: (f32, f32) = (0.5, 0.5);
+
+/// UVs of the corners of a hexagon, in the order of `hex::CORNERS`.
+const CORNER_UVS: [(f32, f32); 6] = [
+    (0.0, 0.5),
+    (0.25, 0.0),
+    (0.75, 0.0),
+    (1.0, 0.5),
+    (0.75, 1.0),
+    (0.25, 1.0),
+];
+
+/// Steepest slope of a ramp in steps per key unit. The longest connection between two vertices is
+/// √5 key units long, so no connection along a ramp rises by more than one step.
+const MAX_RAMP_SLOPE: f32 = 0.447;
+
+/// Metadata entry that holds the resource of a cell.
+const RESOURCE_KEY: &str = "resource";
+
+/// Metadata entry that holds the moisture of a cell from `simulate_rain_shadow`.
+const MOISTURE_KEY: &str = "moisture";
+
+/// Number of candidates `sample_blue_noise` tries around every picked cell.
+const BLUE_NOISE_ATTEMPTS: u32 = 30;
+
+/// Compression level of autosave snapshots, which are written in the background.
+const AUTOSAVE_COMPRESSION_LEVEL: u32 = 6;
+
+/// Compression level of the files of chunks streamed to `chunk_directory`.
+const CHUNK_COMPRESSION_LEVEL: u32 = 6;
+
+/// Version of the sidecar layout `export_bundle` writes.
+const BUNDLE_VERSION: f64 = 1.0;
+
+/// Device of the mouse events Godot emulates from touches. They are ignored, as the touches are
+/// handled directly.
+const TOUCH_MOUSE_DEVICE: i64 = -1;
+
+/// Factor by which the fingers have to move apart or together to change `brush_radius` by one.
+const PINCH_STEP: f32 = 1.5;
+
+/// Cells along both axial axes of the chunks of the grid. Every chunk has one grid mesh.
+const GRID_CHUNK_SIZE: i32 = 8;
+
+/// What is raised or lowered when clicking on the terrain with `direct_editing` enabled.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum EditMode {
+    /// The vertex closest to the cursor.
+    Vertex = 0,
+    /// All vertices of the hexagon under the cursor.
+    Hex = 1,
+}
+
+impl EditMode {
+    fn from_i64(value: i64) -> EditMode {
+        match value {
+            1 => EditMode::Hex,
+            _ => EditMode::Vertex,
+        }
+    }
 }
 
-impl Hexagon {
-    pub fn new(center: Vector2Di32) -> Hexagon {
-        let left = center + LEFT;
-        let top_left = center + TOP_LEFT;
-        let top_right = center + TOP_RIGHT;
-        let right = center + RIGHT;
-        let bottom_right = center + BOTTOM_RIGHT;
-        let bottom_left = center + BOTTOM_LEFT;
-
-        Hexagon {
-            center,
-            left,
-            top_left,
-            top_right,
-            right,
-            bottom_right,
-            bottom_left,
+/// How edits are repeated to keep the terrain symmetric around `symmetry_center`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SymmetryMode {
+    None = 0,
+    /// Mirrored across `symmetry_axis`.
+    Mirror = 1,
+    /// Repeated `symmetry_folds` times around the center.
+    Rotation = 2,
+}
+
+impl SymmetryMode {
+    fn from_i64(value: i64) -> SymmetryMode {
+        match value {
+            1 => SymmetryMode::Mirror,
+            2 => SymmetryMode::Rotation,
+            _ => SymmetryMode::None,
         }
     }
 }
 
-#[derive(Clone)]
+/// Editing input from the mouse, a touch screen or the keyboard, so all of them share the same
+/// editing code.
+enum PointerInput {
+    /// A mouse button was pressed or a finger touched the screen.
+    Pressed {
+        button: i64,
+        modifiers: i64,
+        position: Vector2,
+    },
+    Released {
+        button: i64,
+    },
+    Moved {
+        position: Vector2,
+    },
+    /// The raise or lower action was pressed while the cursor was at the position.
+    Action {
+        raise: bool,
+        position: Vector2,
+    },
+    /// The distance between two fingers changed by the factor.
+    Pinched(f32),
+}
+
+#[derive(Clone, Copy)]
 struct TerrainNode {
     key: Vector2Di32,
-    connections: Vec<Vector2Di32>,
     uv: Vector2,
 }
 
 impl TerrainNode {
-    pub fn new(key: Vector2Di32, uv: Vector2) -> TerrainNode {
+    pub fn new(key: Vector2Di32, uv: (f32, f32)) -> TerrainNode {
         TerrainNode {
             key,
-            connections: Vec::new(),
-            uv,
+            uv: Vector2::new(uv.0, uv.1),
+        }
+    }
+}
+
+/// The data of many hexagons, allocated once for all of them so creating a large field does not
+/// allocate per hexagon.
+struct HexagonBuffers {
+    /// Three nodes per triangle, six triangles per hexagon.
+    nodes: Vec<TerrainNode>,
+    hexagons: HashMap<Vector2Di32, Hexagon>,
+    vertices: ChunkMap<Vector2>,
+}
+
+impl HexagonBuffers {
+    /// Reserves room for the given number of hexagons.
+    fn with_capacity(hexagons: usize) -> HexagonBuffers {
+        HexagonBuffers {
+            nodes: Vec::with_capacity(hexagons * 18),
+            hexagons: HashMap::with_capacity(hexagons),
+            vertices: ChunkMap::default(),
         }
     }
 }
 
+/// The height texture and material of `gpu_displacement`.
+struct Displacement {
+    heights: HeightTexture,
+    texture: Ref<ImageTexture>,
+    material: Ref<ShaderMaterial>,
+    /// Material of the terrain mesh before the displacement material replaced it.
+    previous_material: Option<Ref<Material>>,
+    /// Holes and decks the flat mesh was built with, see `mesh_shape`.
+    shape: Vec<(Vector2Di32, bool, Option<i32>)>,
+}
+
 #[derive(NativeClass)]
 #[inherit(Spatial)]
+#[register_with(Self::register)]
 pub struct HexTerrain {
     nodes: Vec<TerrainNode>,
     hexagon_map: HashMap<Vector2Di32, Hexagon>,
-    vertex_map: HashMap<Vector2Di32, Vector2>,
+    /// Position of every vertex, sharded so large maps stay quick to look up and iterate.
+    vertex_map: ChunkMap<Vector2>,
+    /// Keys of `vertex_map` sorted by row and column. Created on first use and cleared whenever
+    /// the vertices are recreated.
+    sorted_vertex_keys: RefCell<Option<Vec<Vector2Di32>>>,
+    /// Horizontal position and height of every entry of `nodes`, read in order by
+    /// `update_vertices`. Only the heights of changed nodes are updated; it is rebuilt whenever
+    /// `nodes` changes.
+    columns: VertexColumns,
+    /// Indices in `columns` of every node.
+    column_slots: HashMap<Vector2Di32, Vec<usize>>,
     terrain: Terrain<Vector2Di32>,
     #[property]
     hex_radius: f32,
     #[property]
     field_radius: u32,
+    /// Creates the cells of the field as they are needed instead of all at start: a few per frame
+    /// within `lazy_radius` of the tracked nodes, and cells that are edited or requested.
+    #[property]
+    lazy_field: bool,
+    #[property]
+    lazy_radius: u32,
+    #[property]
+    lazy_cells_per_frame: u32,
     #[property]
     node_height: f32,
+    #[property]
+    min_height: i64,
+    #[property]
+    max_height: i64,
+    height_limits: (i64, i64),
+    history: History<Vector2Di32>,
+    #[property]
+    history_size: i64,
+    #[property]
+    infinite: bool,
+    #[property]
+    chunk_size: u32,
+    #[property]
+    chunk_load_radius: u32,
+    #[property]
+    chunk_unload_radius: u32,
+    tracked_nodes: Vec<Ref<Spatial>>,
+    loaded_chunks: HashSet<Vector2Di32>,
+    chunk_regions: HashMap<Vector2Di32, Region>,
+    #[property]
+    chunk_directory: GodotString,
+    chunk_store: Option<ChunkStore>,
+    #[property]
+    debug_overlay: bool,
+    #[property]
+    debug_overlay_vertices: bool,
+    debug_overlay_state: (bool, bool),
+    debug_labels: Vec<(Vector3, Ref<Label>)>,
+    #[property]
+    perf_overlay: bool,
+    perf_label: Option<Ref<Label>>,
+    telemetry: Telemetry,
+    /// Splits the terrain mesh into one mesh per chunk of `chunk_size` cells, so chunks outside of
+    /// the view are culled on their own.
+    #[property]
+    chunked_mesh: bool,
+    chunked_mesh_state: bool,
+    /// Hides the chunks of a chunked mesh that are further from the camera, 0 for no limit.
+    #[property]
+    draw_distance: f32,
+    /// Hides the chunks of a chunked mesh that are outside of the view of the camera.
+    #[property]
+    frustum_culling: bool,
+    /// Mesh and local bounds of every chunk of a chunked mesh.
+    chunk_meshes: HashMap<Vector2Di32, (Ref<MeshInstance>, Bounds)>,
+    /// Keeps the terrain mesh flat and raises its vertices in a shader by the heights in a texture,
+    /// so height edits only write texels instead of building the mesh again. Replaces the material
+    /// of the terrain mesh while set.
+    #[property]
+    gpu_displacement: bool,
+    gpu_displacement_state: bool,
+    displacement: Option<Displacement>,
+    /// Format flags of the terrain and grid meshes, a combination of the `Mesh.ARRAY_COMPRESS_*`
+    /// flags, e.g. 0 to keep full precision for shaders that read the vertices back.
+    #[property]
+    mesh_compression: i64,
+    mesh_compression_state: i64,
+    #[property]
+    grid_visible: bool,
+    #[property]
+    grid_color: Color,
+    #[property]
+    grid_thickness: f32,
+    #[property]
+    grid_height_offset: f32,
+    grid_state: (bool, Color, f32, f32),
+    grid_material: Ref<SpatialMaterial>,
+    #[property]
+    direct_editing: bool,
+    #[property]
+    indicator_scene: GodotString,
+    /// Scene and instance of `indicator_scene`.
+    indicator: Option<(GodotString, Ref<Spatial>)>,
+    /// Body with the triangles of the terrain that is clicked to edit it, and its shape.
+    picking_body: Option<(Ref<StaticBody>, Ref<ConcavePolygonShape>)>,
+    /// Grid mesh of every chunk of `GRID_CHUNK_SIZE` cells.
+    grid_meshes: HashMap<Vector2Di32, Ref<MeshInstance>>,
+    #[property]
+    raise_button: i64,
+    #[property]
+    raise_modifiers: i64,
+    #[property]
+    lower_button: i64,
+    #[property]
+    lower_modifiers: i64,
+    edit_mode: EditMode,
+    symmetry_mode: SymmetryMode,
+    #[property]
+    symmetry_axis: i64,
+    #[property]
+    symmetry_folds: i64,
+    #[property]
+    symmetry_center: Vector2,
+    #[property]
+    drag_painting: bool,
+    #[property]
+    paint_interval: f64,
+    painting: Option<(bool, i64)>,
+    painted_keys: Vec<Vector2Di32>,
+    time_since_paint: f64,
+    vertices_dirty: bool,
+    generation: Option<GenerationJob>,
+    #[property]
+    brush_radius: i64,
+    #[property]
+    touch_lowers: bool,
+    touches: HashMap<i64, Vector2>,
+    pinch_scale: f32,
+    #[property]
+    noise_octaves: i64,
+    #[property]
+    noise_lacunarity: f64,
+    #[property]
+    noise_gain: f64,
+    #[property]
+    noise_warp: f64,
+    #[property]
+    island_distortion: f64,
+    #[property]
+    island_depth: i64,
+    #[property]
+    river_feature: i64,
+    #[property]
+    road_feature: i64,
+    #[property]
+    road_slope_cost: f64,
+    #[property]
+    road_water_cost: f64,
+    tools: Vec<(GodotString, Variant)>,
+    #[property]
+    active_tool: GodotString,
+    tool_cell: Option<Vector2Di32>,
+    cell_metadata: HashMap<Vector2Di32, Dictionary>,
+    provinces: HashMap<Vector2Di32, i32>,
+    #[property]
+    grow_action: GodotString,
+    #[property]
+    shrink_action: GodotString,
+    #[property]
+    raise_action: GodotString,
+    #[property]
+    lower_action: GodotString,
+    #[property]
+    elevation_level_names: StringArray,
+    #[property]
+    elevation_level_heights: Int32Array,
+    #[property]
+    snap_to_elevation_levels: bool,
+    #[property]
+    biome_types: Int32Array,
+    #[property]
+    biome_temperatures: Float32Array,
+    #[property]
+    biome_moistures: Float32Array,
+    #[property]
+    biome_height_cooling: f64,
+    #[property]
+    biome_variation: f64,
+    #[property]
+    wfc_module_types: Int32Array,
+    #[property]
+    wfc_module_heights: Int32Array,
+    #[property]
+    wfc_module_weights: Float32Array,
+    #[property]
+    wfc_rules: Int32Array,
+    #[property]
+    generation_steps: VariantArray,
+    #[property]
+    autosave_interval: f64,
+    #[property]
+    autosave_slots: i64,
+    #[property]
+    autosave_directory: GodotString,
+    autosave: Autosave,
+    #[property]
+    background_rebuilds: bool,
+    rebuild: Rebuild<Terrain<Vector2Di32>>,
+    /// Added to the edits of the terrain for `terrain_version`.
+    version_offset: u64,
+    terrain_data: Option<Ref<Resource>>,
+    #[property]
+    replicate_edits: bool,
+    outgoing_deltas: Vec<Vec<u8>>,
+    #[property]
+    minimap_type_colors: ColorArray,
+    minimap: Option<(Minimap, Ref<ImageTexture>)>,
+    minimap_heights: (i32, i32),
 }
 
 #[methods]
 impl HexTerrain {
     pub fn new(_owner: TRef<'_, Spatial>) -> Self {
+        let preset = HexGenPreset::default();
         Self {
             nodes: Vec::new(),
             hexagon_map: HashMap::new(),
-            vertex_map: HashMap::new(),
+            vertex_map: ChunkMap::default(),
+            sorted_vertex_keys: RefCell::default(),
+            columns: VertexColumns::default(),
+            column_slots: HashMap::new(),
             terrain: Terrain::new(1),
             hex_radius: 0.5,
             field_radius: 0,
+            lazy_field: false,
+            lazy_radius: 8,
+            lazy_cells_per_frame: 64,
             node_height: 0.5,
+            min_height: -100,
+            max_height: 100,
+            height_limits: (-100, 100),
+            history: History::new(100),
+            history_size: 100,
+            infinite: false,
+            chunk_size: 8,
+            chunk_load_radius: 1,
+            chunk_unload_radius: 2,
+            tracked_nodes: Vec::new(),
+            loaded_chunks: HashSet::new(),
+            chunk_regions: HashMap::new(),
+            chunk_directory: GodotString::new(),
+            chunk_store: None,
+            debug_overlay: false,
+            debug_overlay_vertices: false,
+            debug_overlay_state: (false, false),
+            debug_labels: Vec::new(),
+            perf_overlay: false,
+            chunked_mesh: false,
+            chunked_mesh_state: false,
+            draw_distance: 0.0,
+            frustum_culling: false,
+            chunk_meshes: HashMap::new(),
+            gpu_displacement: false,
+            gpu_displacement_state: false,
+            displacement: None,
+            mesh_compression: Mesh::ARRAY_COMPRESS_DEFAULT,
+            mesh_compression_state: Mesh::ARRAY_COMPRESS_DEFAULT,
+            perf_label: None,
+            telemetry: Telemetry::default(),
+            grid_visible: true,
+            grid_color: Color::rgb(1.0, 1.0, 1.0),
+            grid_thickness: 0.0,
+            grid_height_offset: 0.01,
+            grid_state: (true, Color::rgb(1.0, 1.0, 1.0), 0.0, 0.01),
+            grid_material: Self::create_grid_material(),
+            direct_editing: false,
+            indicator_scene: GodotString::from("res://Indicator.tscn"),
+            indicator: None,
+            picking_body: None,
+            grid_meshes: HashMap::new(),
+            raise_button: GlobalConstants::BUTTON_LEFT,
+            raise_modifiers: 0,
+            lower_button: GlobalConstants::BUTTON_LEFT,
+            lower_modifiers: GlobalConstants::KEY_MASK_SHIFT,
+            edit_mode: EditMode::Vertex,
+            symmetry_mode: SymmetryMode::None,
+            symmetry_axis: 0,
+            symmetry_folds: 2,
+            symmetry_center: Vector2::zero(),
+            drag_painting: true,
+            paint_interval: 0.05,
+            painting: None,
+            painted_keys: Vec::new(),
+            time_since_paint: 0.0,
+            vertices_dirty: false,
+            generation: None,
+            brush_radius: 0,
+            touch_lowers: false,
+            touches: HashMap::new(),
+            pinch_scale: 1.0,
+            noise_octaves: preset.noise_octaves,
+            noise_lacunarity: preset.noise_lacunarity,
+            noise_gain: preset.noise_gain,
+            noise_warp: preset.noise_warp,
+            island_distortion: preset.island_distortion,
+            island_depth: preset.island_depth,
+            river_feature: 1,
+            road_feature: 2,
+            road_slope_cost: 2.0,
+            road_water_cost: 10.0,
+            tools: Vec::new(),
+            active_tool: GodotString::new(),
+            tool_cell: None,
+            cell_metadata: HashMap::new(),
+            provinces: HashMap::new(),
+            grow_action: GodotString::from("hexterrain_grow"),
+            shrink_action: GodotString::from("hexterrain_shrink"),
+            raise_action: GodotString::from("hexterrain_raise"),
+            lower_action: GodotString::from("hexterrain_lower"),
+            elevation_level_names: StringArray::from_vec(vec![
+                GodotString::from("water"),
+                GodotString::from("plain"),
+                GodotString::from("hill"),
+                GodotString::from("mountain"),
+            ]),
+            elevation_level_heights: Int32Array::from_vec(vec![-1, 0, 2, 4]),
+            snap_to_elevation_levels: false,
+            biome_types: preset.biome_types,
+            biome_temperatures: preset.biome_temperatures,
+            biome_moistures: preset.biome_moistures,
+            biome_height_cooling: preset.biome_height_cooling,
+            biome_variation: preset.biome_variation,
+            wfc_module_types: preset.wfc_module_types,
+            wfc_module_heights: preset.wfc_module_heights,
+            wfc_module_weights: preset.wfc_module_weights,
+            generation_steps: preset.generation_steps,
+            wfc_rules: preset.wfc_rules,
+            autosave_interval: 0.0,
+            autosave_slots: 3,
+            autosave_directory: GodotString::from("user://autosave"),
+            autosave: Autosave::default(),
+            background_rebuilds: false,
+            rebuild: Rebuild::default(),
+            version_offset: 0,
+            terrain_data: None,
+            replicate_edits: false,
+            outgoing_deltas: Vec::new(),
+            minimap_type_colors: ColorArray::new(),
+            minimap: None,
+            minimap_heights: (0, 0),
         }
     }
 
+    fn register(builder: &ClassBuilder<Self>) {
+        builder.add_signal(Signal {
+            name: "generation_progress",
+            args: &[
+                SignalArgument {
+                    name: "percent",
+                    default: Variant::from_i64(0),
+                    export_info: ExportInfo::new(VariantType::I64),
+                    usage: PropertyUsage::DEFAULT,
+                },
+                SignalArgument {
+                    name: "pass_name",
+                    default: Variant::from_str(""),
+                    export_info: ExportInfo::new(VariantType::GodotString),
+                    usage: PropertyUsage::DEFAULT,
+                },
+            ],
+        });
+        builder.add_signal(Signal {
+            name: "generation_finished",
+            args: &[],
+        });
+        builder.add_signal(Signal {
+            name: "rebuild_finished",
+            args: &[SignalArgument {
+                name: "operation",
+                default: Variant::from_str(""),
+                export_info: ExportInfo::new(VariantType::GodotString),
+                usage: PropertyUsage::DEFAULT,
+            }],
+        });
+        builder.add_signal(Signal {
+            name: "edit_replicated",
+            args: &[SignalArgument {
+                name: "delta",
+                default: ByteArray::new().to_variant(),
+                export_info: ExportInfo::new(VariantType::ByteArray),
+                usage: PropertyUsage::DEFAULT,
+            }],
+        });
+        builder
+            .add_property::<i64>("edit_mode")
+            .with_default(EditMode::Vertex as i64)
+            .with_hint(IntHint::Enum(EnumHint::new(vec![
+                "Vertex".to_owned(),
+                "Hex".to_owned(),
+            ])))
+            .with_getter(|this: &Self, _| this.edit_mode as i64)
+            .with_setter(|this: &mut Self, _, value: i64| {
+                this.edit_mode = EditMode::from_i64(value)
+            })
+            .done();
+        builder
+            .add_property::<i64>("symmetry_mode")
+            .with_default(SymmetryMode::None as i64)
+            .with_hint(IntHint::Enum(EnumHint::new(vec![
+                "None".to_owned(),
+                "Mirror".to_owned(),
+                "Rotation".to_owned(),
+            ])))
+            .with_getter(|this: &Self, _| this.symmetry_mode as i64)
+            .with_setter(|this: &mut Self, _, value: i64| {
+                this.symmetry_mode = SymmetryMode::from_i64(value)
+            })
+            .done();
+        builder
+            .add_property::<Option<Ref<Resource>>>("terrain_data")
+            .with_default(None)
+            .with_getter(|this: &Self, _| this.terrain_data.clone())
+            .with_setter(|this: &mut Self, _, value: Option<Ref<Resource>>| {
+                this.terrain_data = value;
+                this.load_terrain_data();
+            })
+            .done();
+    }
+
+    fn create_grid_material() -> Ref<SpatialMaterial> {
+        let material = SpatialMaterial::new();
+        material.set_flag(SpatialMaterial::FLAG_UNSHADED, true);
+        material.set_cull_mode(SpatialMaterial::CULL_DISABLED);
+        material.into_shared()
+    }
+
     #[export]
-    pub fn _input(&mut self, owner: TRef<'_, Spatial>, event: Variant) {
-        if let Some(event) = event.try_to_object::<InputEventKey>() {
+    pub fn _input(&mut self, _owner: TRef<'_, Spatial>, event: Variant) {
+        if self.infinite {
+            return;
+        }
+        if let Some(event) = event.try_to_object::<InputEvent>() {
             let event = unsafe { event.assume_safe() };
-            if event.is_pressed() {
-                let scancode = event.scancode();
-                if scancode == GlobalConstants::KEY_PLUS || scancode == GlobalConstants::KEY_KP_ADD
-                {
-                    self.field_radius += 1;
-                    self.terrain = Terrain::new(1);
-                    self.create_hex_nodes();
+            if event.is_action_pressed(self.grow_action.clone(), false) {
+                self.field_radius += 1;
+            } else if event.is_action_pressed(self.shrink_action.clone(), false)
+                && self.field_radius > 0
+            {
+                self.field_radius -= 1;
+            } else {
+                return;
+            }
+
+            self.resize_field();
+            self.vertices_dirty = true;
+        }
+    }
+
+    /// Raises or lowers the vertex or hexagon under the mouse cursor, depending on `edit_mode`, if
+    /// `direct_editing` is enabled. In hex mode, all cells within `brush_radius` are edited. If
+    /// `active_tool` names a registered tool, the input is passed to the tool instead. Clicking
+    /// uses the configured buttons, the raise and lower actions edit at the current position of
+    /// the mouse cursor. If `drag_painting` is enabled, dragging with a pressed button keeps
+    /// editing the vertices or hexagons under the cursor, at most once every `paint_interval`
+    /// seconds.
+    ///
+    /// Touching the screen acts like the raise button, or the lower button if `touch_lowers` is
+    /// enabled. Pinching with two fingers or on a trackpad changes `brush_radius`.
+    #[export]
+    pub fn _unhandled_input(&mut self, owner: TRef<'_, Spatial>, event: Variant) {
+        if !self.direct_editing {
+            return;
+        }
+        let event = match event.try_to_object::<InputEvent>() {
+            None => return,
+            Some(event) => unsafe { event.assume_safe() },
+        };
+        let input = match self.pointer_input(owner, event) {
+            None => return,
+            Some(input) => input,
+        };
+        if self.handle_tool_input(owner, &input) {
+            return;
+        }
+
+        let mut pressed_button = None;
+        let mut dragging = false;
+        let (raise, position) = match input {
+            PointerInput::Pressed {
+                button,
+                modifiers,
+                position,
+            } => {
+                let raise = button == self.raise_button && modifiers == self.raise_modifiers;
+                let lower = button == self.lower_button && modifiers == self.lower_modifiers;
+                if !raise && !lower {
+                    return;
                 }
-                if (scancode == GlobalConstants::KEY_MINUS
-                    || scancode == GlobalConstants::KEY_KP_SUBTRACT)
-                    && self.field_radius > 0
+                pressed_button = Some(button);
+                (raise, position)
+            }
+            PointerInput::Released { button } => {
+                if matches!(self.painting, Some((_, painting_button)) if painting_button == button)
                 {
-                    self.field_radius -= 1;
-                    self.terrain = Terrain::new(1);
-                    self.create_hex_nodes();
+                    self.painting = None;
+                }
+                return;
+            }
+            PointerInput::Moved { position } => {
+                let raise = match self.painting {
+                    None => return,
+                    Some((raise, _)) => raise,
+                };
+                if !self.drag_painting || self.time_since_paint < self.paint_interval {
+                    return;
                 }
 
-                self.update_vertices(owner);
+                dragging = true;
+                (raise, position)
+            }
+            PointerInput::Action { raise, position } => (raise, position),
+            PointerInput::Pinched(factor) => {
+                self.pinch_brush(factor);
+                return;
+            }
+        };
+
+        let keys = match self.edit_mode {
+            EditMode::Vertex => self.pick_vertex(owner, position).map(|key| vec![key]),
+            EditMode::Hex => self.pick_cell(owner, position).map(|cell| {
+                self.keys_of_cells(&hex::cells_in_range(cell, self.brush_radius.max(0) as u32))
+            }),
+        };
+        if let Some(keys) = keys {
+            // While dragging, every vertex or hexagon is only edited once when the cursor enters
+            // it.
+            if dragging && keys == self.painted_keys {
+                return;
+            }
+
+            self.begin_edit();
+            if raise {
+                self.raise_keys(&keys);
+                self.end_edit("raise");
+            } else {
+                self.lower_keys(&keys);
+                self.end_edit("lower");
+            }
+            self.painted_keys = keys;
+            self.time_since_paint = 0.0;
+            self.vertices_dirty = true;
+            if let Some(button) = pressed_button {
+                self.painting = Some((raise, button));
+            }
+
+            if let Some(tree) = owner.get_tree() {
+                unsafe { tree.assume_safe() }.set_input_as_handled();
             }
         }
     }
 
+    /// Registers an object as tool. While it is the `active_tool`, clicking calls its
+    /// `on_cell_clicked(terrain, cell, button)` method, dragging calls `on_drag(terrain, cell)`
+    /// whenever another cell is entered and releasing the button calls `on_commit(terrain)`. The
+    /// methods are called deferred, so they can use the editing methods of the terrain.
     #[export]
-    pub fn node_increase(&mut self, owner: TRef<'_, Spatial>, x: i64, y: i64) {
-        let clicked_node = Vector2Di32::new(x as i32, y as i32);
-        self.terrain.increase_height(clicked_node);
-        self.update_vertices(owner);
+    pub fn register_tool(&mut self, _owner: TRef<'_, Spatial>, name: GodotString, tool: Variant) {
+        self.tools.retain(|(tool_name, _)| *tool_name != name);
+        self.tools.push((name, tool));
     }
 
     #[export]
-    pub fn node_decrease(&mut self, owner: TRef<'_, Spatial>, x: i64, y: i64) {
-        let clicked_node = Vector2Di32::new(x as i32, y as i32);
-        self.terrain.decrease_height(clicked_node);
-        self.update_vertices(owner);
+    pub fn unregister_tool(&mut self, _owner: TRef<'_, Spatial>, name: GodotString) {
+        self.tools.retain(|(tool_name, _)| *tool_name != name);
     }
 
     #[export]
-    pub fn _ready(&mut self, owner: TRef<'_, Spatial>) {
-        self.create_hex_nodes();
-        self.update_vertices(owner);
+    pub fn get_tool_names(&self, _owner: TRef<'_, Spatial>) -> StringArray {
+        StringArray::from_vec(self.tools.iter().map(|(name, _)| name.clone()).collect())
     }
 
-    fn update_vertices(&mut self, owner: TRef<'_, Spatial>) {
-        let surface_tool_hex = SurfaceTool::new();
-        let surface_tool_grid = SurfaceTool::new();
-
-        surface_tool_hex.begin(Mesh::PRIMITIVE_TRIANGLES);
+    /// Returns the centers of all cells whose center lies within the rectangle spanned by the two
+    /// world positions on the horizontal plane.
+    #[export]
+    pub fn select_in_world_rect(
+        &self,
+        owner: TRef<'_, Spatial>,
+        a: Vector3,
+        b: Vector3,
+    ) -> Vector2Array {
+        let a = owner.to_local(a);
+        let b = owner.to_local(b);
+        let (min_x, max_x) = (a.x.min(b.x), a.x.max(b.x));
+        let (min_z, max_z) = (a.z.min(b.z), a.z.max(b.z));
 
-        let mut processed_indicators = HashSet::<Vector2Di32>::new();
+        let cells = self
+            .hexagon_map
+            .keys()
+            .filter(|center| {
+                let position = self.vertex_map[center];
+                (min_x..=max_x).contains(&position.x) && (min_z..=max_z).contains(&position.y)
+            })
+            .copied()
+            .collect();
+        Self::cells_to_array(cells)
+    }
 
-        let resource_loader = ResourceLoader::godot_singleton();
-        let indicator_node = resource_loader
-            .load("res://Indicator.tscn", "PackedScene", false)
-            .unwrap()
-            .cast::<PackedScene>()
-            .unwrap();
-        let indicator_mesh: TRef<'_, PackedScene> = unsafe { indicator_node.assume_safe() };
+    /// Returns the centers of all cells that are at most `hex_range` cells away from `center`.
+    #[export]
+    pub fn select_in_range(
+        &self,
+        _owner: TRef<'_, Spatial>,
+        center: Vector2,
+        hex_range: i64,
+    ) -> Vector2Array {
+        let center = Vector2Di32::new(center.x.round() as i32, center.y.round() as i32);
+        let cells = hex::cells_in_range(center, hex_range.max(0) as u32)
+            .into_iter()
+            .filter(|cell| self.hexagon_map.contains_key(cell))
+            .collect();
+        Self::cells_to_array(cells)
+    }
 
-        let indicator_mesh = unsafe { indicator_mesh.instance(0).unwrap().assume_safe() };
-        let indicator_mesh: TRef<'_, StaticBody> = indicator_mesh.cast::<StaticBody>().unwrap();
-        let collision = indicator_mesh.get_node("Collision").unwrap();
-        let collision = unsafe { collision.assume_safe() };
-        let collision: TRef<'_, CollisionShape> = collision.cast::<CollisionShape>().unwrap();
+    /// Raises all vertices of the given cells by one step, or to the next elevation level if
+    /// `snap_to_elevation_levels` is set.
+    #[export]
+    pub fn raise_cells(&mut self, _owner: TRef<'_, Spatial>, cells: Vector2Array) {
+        let cells = Self::cells_from_array(&cells);
+        self.generate_cells(&cells);
+        self.begin_edit();
+        let keys = self.keys_of_cells(&cells);
+        self.raise_keys(&keys);
+        self.end_edit("raise");
+        self.vertices_dirty = true;
+    }
 
-        let shape = SphereShape::new();
-        shape.set_radius(self.hex_radius.into());
-        shape.set_margin(5.0);
+    /// Lowers all vertices of the given cells by one step, or to the previous elevation level if
+    /// `snap_to_elevation_levels` is set.
+    #[export]
+    pub fn lower_cells(&mut self, _owner: TRef<'_, Spatial>, cells: Vector2Array) {
+        let cells = Self::cells_from_array(&cells);
+        self.generate_cells(&cells);
+        self.begin_edit();
+        let keys = self.keys_of_cells(&cells);
+        self.lower_keys(&keys);
+        self.end_edit("lower");
+        self.vertices_dirty = true;
+    }
 
-        collision.set_shape(shape);
+    /// Copies the heights, terrain types and edge features of the given cells. The first cell is
+    /// used as anchor when pasting.
+    #[export]
+    pub fn copy_region(
+        &self,
+        _owner: TRef<'_, Spatial>,
+        cells: Vector2Array,
+    ) -> Instance<HexTerrainClipboard, Unique> {
+        let cells = Self::cells_from_array(&cells);
+        let anchor = cells.first().copied().unwrap_or_else(Vector2Di32::zero);
+        let region = self.capture_region(&cells, anchor);
 
-        let nodes_node = unsafe { owner.get_node("Nodes").unwrap().assume_safe() };
+        Instance::emplace(HexTerrainClipboard { region })
+    }
 
-        for child in nodes_node.get_children().iter() {
-            let child = child.try_to_object::<GodotNode>().unwrap();
-            nodes_node.remove_child(child);
-            unsafe { child.assume_safe().queue_free() };
-        }
+    /// Pastes a copied region with the anchor cell at the target cell, rotated by 60° steps.
+    #[export]
+    pub fn paste_region(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        clipboard: Instance<HexTerrainClipboard, Shared>,
+        target_x: i64,
+        target_y: i64,
+        rotation_steps: i64,
+    ) {
+        self.begin_edit();
+        let target = Vector2Di32::new(target_x as i32, target_y as i32);
+        let clipboard = unsafe { clipboard.assume_safe() };
+        let region = clipboard
+            .map(|clipboard, _| {
+                clipboard
+                    .region
+                    .transformed(|offset| target + hex::rotate_key(offset, rotation_steps as i32))
+            })
+            .unwrap_or_default();
 
-        for node_data in self.nodes.clone() {
-            for connection in node_data.connections {
-                self.terrain.add_connected_nodes(node_data.key, connection);
-            }
+        self.apply_region(&region);
+        self.end_edit("paste");
+        self.vertices_dirty = true;
+    }
 
-            let height: i32 = match self.terrain.get_height_of_node(node_data.key) {
-                None => panic!(),
-                Some(height) => height,
-            };
+    /// Captures the heights, terrain types and edge features of the given cells as a stamp. The
+    /// first cell is used as anchor, heights are stored relative to its center.
+    #[export]
+    pub fn capture_stamp(
+        &self,
+        _owner: TRef<'_, Spatial>,
+        cells: Vector2Array,
+    ) -> Instance<HexStamp, Unique> {
+        let cells = Self::cells_from_array(&cells);
+        let anchor = cells.first().copied().unwrap_or_else(Vector2Di32::zero);
+        let anchor_height = self.terrain.get_height_of_node(anchor).unwrap_or(0);
 
-            let vector_data = self.vertex_map[&node_data.key];
+        let mut region = self.capture_region(&cells, anchor);
+        for (_, height) in &mut region.heights {
+            *height -= anchor_height;
+        }
 
-            let vertex = Vector3::new(
-                vector_data.x,
-                height as f32 * self.node_height,
-                vector_data.y,
-            );
+        Instance::emplace(HexStamp::from_region(&region))
+    }
 
-            let uv = node_data.uv;
-            surface_tool_hex.add_uv(uv);
-            surface_tool_hex.add_vertex(vertex);
+    /// Applies a stamp with its anchor at the target cell, rotated by 60° steps. The blend mode
+    /// decides how the stamp heights are combined with the terrain: 0 replaces them relative to
+    /// the height of the target cell, 1 adds them, 2 keeps the higher and 3 the lower height.
+    #[export]
+    pub fn apply_stamp(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        stamp: Instance<HexStamp, Shared>,
+        x: i64,
+        y: i64,
+        rotation_steps: i64,
+        blend_mode: i64,
+    ) {
+        let target = Vector2Di32::new(x as i32, y as i32);
+        let stamp = unsafe { stamp.assume_safe() };
+        let region = match stamp.map(|stamp, _| {
+            stamp
+                .to_region()
+                .transformed(|offset| target + hex::rotate_key(offset, rotation_steps as i32))
+        }) {
+            Err(_) => return,
+            Ok(region) => region,
+        };
+        let blend_mode = match blend_mode {
+            1 => BlendMode::Add,
+            2 => BlendMode::Max,
+            3 => BlendMode::Min,
+            _ => BlendMode::Replace,
+        };
 
-            if !processed_indicators.contains(&node_data.key) {
-                let new_indicator = unsafe {
-                    indicator_mesh
-                        .duplicate(Node::DUPLICATE_USE_INSTANCING)
-                        .unwrap()
-                        .assume_safe()
-                };
-                let new_indicator: TRef<'_, StaticBody> =
-                    new_indicator.cast::<StaticBody>().unwrap();
-                new_indicator.set_translation(vertex);
-
-                let signal_data = VariantArray::new();
-                signal_data.push(node_data.key.x);
-                signal_data.push(node_data.key.y);
-
-                new_indicator
-                    .connect(
-                        "increase",
-                        owner,
-                        "node_increase",
-                        signal_data.duplicate().into_shared(),
-                        0,
-                    )
-                    .unwrap();
-                new_indicator
-                    .connect(
-                        "decrease",
-                        owner,
-                        "node_decrease",
-                        signal_data.duplicate().into_shared(),
-                        0,
-                    )
-                    .unwrap();
+        self.begin_edit();
+        let base = self.terrain.get_height_of_node(target).unwrap_or(0);
+        let nodes: Vec<(Vector2Di32, i32, i32)> = region
+            .heights
+            .iter()
+            .filter_map(|(key, stamp_height)| {
+                self.terrain
+                    .get_height_of_node(*key)
+                    .map(|height| (*key, height, *stamp_height))
+            })
+            .collect();
+        let heights = tools::blend_heights(&nodes, base, blend_mode);
+        self.set_heights(&heights);
+        for (cell, terrain_type) in &region.terrain_types {
+            self.terrain.set_terrain_type(*cell, *terrain_type);
+        }
+        for (first, second, feature) in &region.edge_features {
+            self.terrain.set_edge_feature(*first, *second, *feature);
+        }
+        self.end_edit("stamp");
+        self.vertices_dirty = true;
+    }
 
-                nodes_node.add_child(new_indicator, false);
+    /// Rotates the given cells in place by 60° steps around the first cell.
+    #[export]
+    pub fn rotate_region(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        cells: Vector2Array,
+        rotation_steps: i64,
+    ) {
+        self.begin_edit();
+        let cells = Self::cells_from_array(&cells);
+        self.transform_region(&cells, |offset| {
+            hex::rotate_key(offset, rotation_steps as i32)
+        });
+        self.end_edit("rotate");
+        self.vertices_dirty = true;
+    }
 
-                processed_indicators.insert(node_data.key);
-            }
-        }
+    /// Mirrors the given cells in place across an axis through the first cell. Axis 0 runs
+    /// through the top and bottom edges of the cell, every further axis is turned by 30°.
+    #[export]
+    pub fn mirror_region(&mut self, _owner: TRef<'_, Spatial>, cells: Vector2Array, axis: i64) {
+        self.begin_edit();
+        let cells = Self::cells_from_array(&cells);
+        self.transform_region(&cells, |offset| hex::mirror_key(offset, axis as i32));
+        self.end_edit("mirror");
+        self.vertices_dirty = true;
+    }
 
-        let mut tmp_mesh = ArrayMesh::new();
-        surface_tool_hex.generate_normals(false);
-        tmp_mesh = match surface_tool_hex.commit(tmp_mesh, Mesh::ARRAY_COMPRESS_DEFAULT) {
-            None => return,
-            Some(mesh) => unsafe { mesh.assume_unique() },
+    /// Creates a walkable slope along the line between two cells, `width` cells to either side.
+    /// If the height difference is too big for the distance, the ramp is as steep as allowed.
+    #[export]
+    pub fn create_ramp(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        from_x: i64,
+        from_y: i64,
+        to_x: i64,
+        to_y: i64,
+        width: i64,
+    ) {
+        self.begin_edit();
+        let from = Vector2Di32::new(from_x as i32, from_y as i32);
+        let to = Vector2Di32::new(to_x as i32, to_y as i32);
+        let (from_height, to_height) = match (
+            self.terrain.get_height_of_node(from),
+            self.terrain.get_height_of_node(to),
+        ) {
+            (Some(from_height), Some(to_height)) => (from_height, to_height),
+            _ => return,
         };
 
-        let mesh_instance = owner
-            .get_node("HexMesh")
-            .and_then(|node| unsafe { node.assume_safe_if_sane() })
-            .and_then(|node| node.cast::<MeshInstance>());
-        match mesh_instance {
-            None => {}
-            Some(mesh_instance) => {
-                mesh_instance.set_mesh(tmp_mesh);
+        let mut cells = Vec::new();
+        for cell in hex::cells_on_line(from, to) {
+            for cell in hex::cells_in_range(cell, width.max(0) as u32) {
+                if !cells.contains(&cell) {
+                    cells.push(cell);
+                }
             }
         }
 
-        let grid_node = owner
-            .get_node("Grid")
-            .and_then(|node| unsafe { node.assume_safe_if_sane() });
-        let grid_node: TRef<'_, GodotNode> = match grid_node {
-            None => panic!(),
-            Some(grid_node) => grid_node,
-        };
+        let direction = Vector2::new((to.x - from.x) as f32, (to.y - from.y) as f32);
+        let length = direction.length();
+        let nodes: Vec<(Vector2Di32, f32)> = self
+            .keys_of_cells(&cells)
+            .into_iter()
+            .map(|key| {
+                let offset = Vector2::new((key.x - from.x) as f32, (key.y - from.y) as f32);
+                let distance = if length > 0.0 {
+                    offset.dot(direction) / length
+                } else {
+                    0.0
+                };
+                (key, distance)
+            })
+            .collect();
 
-        for child in grid_node.get_children().iter() {
-            let child: Variant = child;
-            let child = child.try_to_object::<GodotNode>().unwrap();
-            let child = unsafe { child.assume_safe() };
-            grid_node.remove_child(child);
-            child.queue_free();
-        }
-        let line_height = 0.01;
+        let heights = tools::ramp_heights(&nodes, length, from_height, to_height, MAX_RAMP_SLOPE);
+        self.set_heights(&heights);
+        self.end_edit("ramp");
+        self.vertices_dirty = true;
+    }
 
-        for hexagon in self.hexagon_map.values() {
-            let mut grid_mesh = ArrayMesh::new();
-            surface_tool_grid.begin(Mesh::PRIMITIVE_LINE_LOOP);
+    /// Snaps the heights of the given cells to multiples of `step`, or of all vertices if no
+    /// cells are given. Vertices between terraces are raised where needed, so connected vertices
+    /// stay within one step. Vertices of locked cells are not changed.
+    #[export]
+    pub fn terrace(&mut self, _owner: TRef<'_, Spatial>, step: i64, cells: Vector2Array) {
+        self.begin_edit();
+        let heights: Vec<(Vector2Di32, i32)> = if cells.len() == 0 {
+            self.terrain.heights().collect()
+        } else {
+            self.heights_of_keys(&self.keys_of_cells(&Self::cells_from_array(&cells)))
+        };
 
-            let key = hexagon.left;
-            let vertex = self.vertex_map[&key];
-            let vertex_height =
-                self.terrain.get_height_of_node(key).unwrap() as f32 * self.node_height;
-            let vertex = Vector3::new(vertex.x, vertex_height + line_height, vertex.y);
-            surface_tool_grid.add_vertex(vertex);
+        // Snapping neighbours to different steps would leave them more than one step apart, so
+        // the terraces are set like generated heights and the slopes between them are filled.
+        let heights = self.symmetric_heights(&tools::terrace_heights(&heights, step as i32));
+        self.terrain.set_generated_heights(&heights);
+        self.end_edit("terrace");
+        self.vertices_dirty = true;
+    }
 
-            let key = hexagon.top_left;
-            let vertex = self.vertex_map[&key];
-            let vertex_height =
-                self.terrain.get_height_of_node(key).unwrap() as f32 * self.node_height;
-            let vertex = Vector3::new(vertex.x, vertex_height + line_height, vertex.y);
-            surface_tool_grid.add_vertex(vertex);
+    /// Adds seeded random height jitter to all vertices within `radius` cells, fading out towards
+    /// the edge of the brush.
+    #[export]
+    pub fn noise_brush(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        x: i64,
+        y: i64,
+        radius: i64,
+        amplitude: f64,
+        seed: i64,
+    ) {
+        self.begin_edit();
+        let nodes: Vec<(Vector2Di32, i32, f32, f32)> = self
+            .brush_strengths(Vector2Di32::new(x as i32, y as i32), radius)
+            .into_iter()
+            .filter_map(|(key, strength)| {
+                let random = Random::for_position(seed as u64, key.x, key.y).next_f32();
+                self.terrain
+                    .get_height_of_node(key)
+                    .map(|height| (key, height, strength, random * 2.0 - 1.0))
+            })
+            .collect();
 
-            let key = hexagon.top_right;
-            let vertex = self.vertex_map[&key];
-            let vertex_height =
-                self.terrain.get_height_of_node(key).unwrap() as f32 * self.node_height;
-            let vertex = Vector3::new(vertex.x, vertex_height + line_height, vertex.y);
-            surface_tool_grid.add_vertex(vertex);
+        let heights = tools::jitter_heights(&nodes, amplitude as f32);
+        self.set_heights(&heights);
+        self.end_edit("noise");
+        self.vertices_dirty = true;
+    }
 
-            let key = hexagon.right;
-            let vertex = self.vertex_map[&key];
-            let vertex_height =
-                self.terrain.get_height_of_node(key).unwrap() as f32 * self.node_height;
-            let vertex = Vector3::new(vertex.x, vertex_height + line_height, vertex.y);
-            surface_tool_grid.add_vertex(vertex);
+    /// Moves all vertices within `radius` cells towards the average height of their neighbours.
+    /// A `strength` of 1 flattens the center of the brush completely.
+    #[export]
+    pub fn smooth_brush(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        x: i64,
+        y: i64,
+        radius: i64,
+        strength: f64,
+    ) {
+        self.begin_edit();
+        let nodes: Vec<(Vector2Di32, i32, f32, f32)> = self
+            .brush_strengths(Vector2Di32::new(x as i32, y as i32), radius)
+            .into_iter()
+            .filter_map(|(key, brush_strength)| {
+                let height = self.terrain.get_height_of_node(key)?;
+                let average = self.terrain.get_average_connected_height(key)?;
+                Some((key, height, brush_strength, average))
+            })
+            .collect();
 
-            let key = hexagon.bottom_right;
-            let vertex = self.vertex_map[&key];
-            let vertex_height =
-                self.terrain.get_height_of_node(key).unwrap() as f32 * self.node_height;
-            let vertex = Vector3::new(vertex.x, vertex_height + line_height, vertex.y);
-            surface_tool_grid.add_vertex(vertex);
+        let heights = tools::smooth_heights(&nodes, strength as f32);
+        self.set_heights(&heights);
+        self.end_edit("smooth");
+        self.vertices_dirty = true;
+    }
 
-            let key = hexagon.bottom_left;
-            let vertex = self.vertex_map[&key];
-            let vertex_height =
-                self.terrain.get_height_of_node(key).unwrap() as f32 * self.node_height;
-            let vertex = Vector3::new(vertex.x, vertex_height + line_height, vertex.y);
-            surface_tool_grid.add_vertex(vertex);
+    /// Replaces the heights of the whole field with coherent noise between `-amplitude` and
+    /// `amplitude`. The `frequency` is the number of noise features per cell, so lower values
+    /// produce wider hills. `noise_octaves` layers of noise are added up, each with
+    /// `noise_lacunarity` times the frequency and `noise_gain` times the amplitude of the previous
+    /// one. `noise_warp` bends the noise by moving the sampled positions.
+    #[export]
+    pub fn generate_noise(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        seed: i64,
+        frequency: f64,
+        amplitude: f64,
+    ) {
+        self.generate(Some("generate_noise"), |world| {
+            world.generate_noise(seed, frequency, amplitude)
+        });
+    }
 
-            grid_mesh = match surface_tool_grid.commit(grid_mesh, Mesh::ARRAY_COMPRESS_DEFAULT) {
-                None => {
-                    godot_error!("Could not commit grid mesh");
-                    return;
-                }
-                Some(mesh) => unsafe { mesh.assume_unique() },
-            };
-            let mesh_instance = MeshInstance::new();
+    /// Replaces the heights of the whole field with midpoint displacement, which gives blocky
+    /// relief at a low cost. Vertices about `size` cells apart get random heights between
+    /// `-amplitude` and `amplitude`; the vertices in between are interpolated and displaced by
+    /// `roughness` times less at each halving of the distance.
+    #[export]
+    pub fn generate_midpoint(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        seed: i64,
+        size: i64,
+        amplitude: f64,
+        roughness: f64,
+    ) {
+        self.generate(Some("midpoint"), |world| {
+            world.generate_midpoint(seed, size, amplitude, roughness)
+        });
+    }
 
-            mesh_instance.set_mesh(grid_mesh);
+    /// Lowers the terrain towards the edge of the field below sea level (0), so the land forms an
+    /// island or continent that covers about `land_fraction` of the field. Outside of the shore
+    /// the heights fall down to `-island_depth` at the edge, shaped by `falloff` (see
+    /// `terrain::noise::island_heights`). With an `island_distortion` above 0 the shore is
+    /// distorted by seeded noise, otherwise it is round.
+    #[export]
+    pub fn apply_island_mask(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        seed: i64,
+        land_fraction: f64,
+        falloff: f64,
+    ) {
+        self.generate(Some("island_mask"), |world| {
+            world.apply_island_mask(seed, land_fraction, falloff)
+        });
+    }
 
-            grid_node.add_child(mesh_instance, false);
-        }
+    /// Carves lakes with a cellular automaton. A random `fill` share of the cells starts as water;
+    /// in each of the `iterations`, a land cell turns to water if at least `birth` of its
+    /// neighbours are water, and a water cell turns to land if fewer than `survival` are. The
+    /// vertices of the resulting water cells are lowered to the height of the "water" elevation
+    /// level, or -1 if there is none. Returns the water cells.
+    ///
+    /// Cave systems would need several terrain layers, which do not exist yet.
+    #[export]
+    pub fn generate_lakes(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        seed: i64,
+        fill: f64,
+        birth: i64,
+        survival: i64,
+        iterations: i64,
+    ) -> Vector2Array {
+        let lakes = self.generate(Some("lakes"), |world| {
+            world.generate_lakes(seed, fill, birth, survival, iterations)
+        });
+        Self::cells_to_array(lakes)
     }
 
-    fn create_hex_nodes(&mut self) {
-        let (vertex_data_sender, vertex_data_receiver): (
-            Sender<HexagonData>,
-            Receiver<HexagonData>,
-        ) = mpsc::channel();
-        let (node_sender, node_receiver): (Sender<NodeData>, Receiver<NodeData>) = mpsc::channel();
-        let mut nodes_data = Vec::<TerrainNode>::new();
-        let mut hexagons = HashMap::<Vector2Di32, Hexagon>::new();
-        let mut vertices_data = HashMap::<Vector2Di32, Vector2>::new();
+    /// Raises `count` mountain chains along seeded random walks over the cells. The vertices on
+    /// a ridge line are raised by `height`, which fades out over `width` cells on both sides.
+    /// Ridges are about as long as the field is wide.
+    #[export]
+    pub fn generate_ridges(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        seed: i64,
+        count: i64,
+        height: i64,
+        width: f64,
+    ) {
+        self.generate(Some("ridges"), |world| {
+            world.generate_ridges(seed, count, height, width)
+        });
+    }
 
-        let mut threads = Vec::new();
+    /// Marks rivers as edge features with the value `river_feature`, replacing the rivers of an
+    /// earlier call. Every vertex gets between 0 and 1 rainfall from seeded noise, which flows
+    /// downhill along the connections to the vertices at or below the "water" elevation level
+    /// (-1 if there is none). Connections that carry at least `threshold` rainfall become rivers,
+    /// so lower values produce more and longer tributaries. Without water there are no rivers.
+    /// Returns the number of connections that were marked.
+    #[export]
+    pub fn generate_rivers(&mut self, _owner: TRef<'_, Spatial>, seed: i64, threshold: f64) -> i64 {
+        self.generate(None, |world| world.generate_rivers(seed, threshold))
+    }
 
-        let radius = self.field_radius;
-        let hex_radius = self.hex_radius;
-        let mut processed_nodes = HashSet::new();
-        let mut finished_threads = 0;
+    /// Connects the cells with roads, marked as edge features with the value `road_feature`. Every
+    /// cell is connected to the closest of the cells before it along the cheapest path. A step to
+    /// a neighbouring cell costs 1, plus `road_slope_cost` per step of height difference, plus
+    /// `road_water_cost` if the cell is at or below the "water" elevation level. Roads do not
+    /// cross holes. If `flatten` is set, the vertices along the roads are smoothed a little.
+    /// Returns the number of connections that were marked.
+    #[export]
+    pub fn generate_roads(
+        &mut self,
+        owner: TRef<'_, Spatial>,
+        cells: Vector2Array,
+        flatten: bool,
+    ) -> i64 {
+        let water_level =
+            self.get_elevation_level_height(owner, GodotString::from("water"), -1) as i32;
+        let cells: Vec<Vector2Di32> = Self::cells_from_array(&cells)
+            .into_iter()
+            .filter(|cell| self.hexagon_map.contains_key(cell))
+            .collect();
 
-        processed_nodes.insert(Vector2Di32::zero());
+        let mut roads = Vec::new();
+        for (index, cell) in cells.iter().enumerate().skip(1) {
+            let closest = cells[..index].iter().min_by_key(|other| {
+                hex::axial_distance(hex::cell_to_axial(**other), hex::cell_to_axial(*cell))
+            });
+            let path = closest.and_then(|closest| {
+                path::cheapest_path(*closest, *cell, |from| {
+                    let from_height = self.terrain.get_height_of_node(from).unwrap_or(0);
+                    hex::neighbouring_cells(from)
+                        .iter()
+                        .filter(|to| {
+                            self.hexagon_map.contains_key(*to) && !self.terrain.is_hole(**to)
+                        })
+                        .map(|to| {
+                            let height = self.terrain.get_height_of_node(*to).unwrap_or(0);
+                            let mut cost = 1.0
+                                + self.road_slope_cost as f32 * (height - from_height).abs() as f32;
+                            if height <= water_level {
+                                cost += self.road_water_cost as f32;
+                            }
+                            (*to, cost)
+                        })
+                        .collect()
+                })
+            });
+            if let Some(path) = path {
+                roads.push(self.road_vertices(&path));
+            }
+        }
 
-        {
-            let vertex_data_sender = vertex_data_sender.clone();
-            let node_sender = node_sender.clone();
-            threads.push(thread::spawn(move || {
-                Self::create_hex_vertices(
-                    Vector2Di32::zero(),
-                    radius,
-                    hex_radius,
-                    vertex_data_sender,
-                    node_sender,
-                );
-            }));
-        }
-
-        while processed_nodes.len() != finished_threads {
-            let mut received = true;
-            while received {
-                match node_receiver.try_recv() {
-                    Ok(node) => {
-                        if !processed_nodes.contains(&node.0) {
-                            processed_nodes.insert(node.0);
-                            let vertex_data_sender = vertex_data_sender.clone();
-                            let node_sender = node_sender.clone();
-                            threads.push(thread::spawn(move || {
-                                Self::create_hex_vertices(
-                                    node.0,
-                                    node.1,
-                                    hex_radius,
-                                    vertex_data_sender,
-                                    node_sender,
-                                );
-                            }));
+        self.begin_edit();
+        let feature = self.road_feature as i32;
+        let mut count = 0;
+        for road in &roads {
+            for connection in road.windows(2) {
+                if self
+                    .terrain
+                    .set_edge_feature(connection[0], connection[1], feature)
+                {
+                    count += 1;
+                }
+            }
+        }
+
+        if flatten {
+            for road in &roads {
+                let heights: Vec<(Vector2Di32, i32)> = road
+                    .windows(3)
+                    .filter_map(|vertices| {
+                        let heights = self.heights_of_keys(vertices);
+                        if heights.len() < 3 {
+                            return None;
+                        }
+                        let sum: i32 = heights.iter().map(|(_, height)| height).sum();
+                        Some((vertices[1], (sum as f32 / 3.0).round() as i32))
+                    })
+                    .collect();
+                self.terrain.set_heights(&heights);
+            }
+            self.vertices_dirty = true;
+        }
+        self.end_edit("roads");
+        count
+    }
+
+    /// Places a resource, e.g. ore, forest or fish, in randomly chosen cells by storing its name
+    /// under "resource" in their metadata. `probabilities` maps terrain types to the chance that a
+    /// cell of that type gets the resource; other types get none. Cells are at least
+    /// `min_spacing` cells apart from each other and from cells that already have a resource.
+    /// Returns the cells that got the resource.
+    #[export]
+    pub fn place_resources(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        resource: GodotString,
+        probabilities: Dictionary,
+        min_spacing: i64,
+        seed: i64,
+    ) -> Vector2Array {
+        let probabilities: HashMap<i32, f32> = probabilities
+            .iter()
+            .map(|(terrain_type, probability)| {
+                (terrain_type.to_i64() as i32, probability.to_f64() as f32)
+            })
+            .collect();
+        let placed = self.generate(None, |world| {
+            world.place_resources(&resource.to_string(), &probabilities, min_spacing, seed)
+        });
+        Self::cells_to_array(placed)
+    }
+
+    /// Picks cells that are at least `min_spacing` cells apart, spread evenly without clumps, e.g.
+    /// for props, resources or spawn points. Only cells in `mask` are picked, all cells if it is
+    /// empty. Every masked cell ends up closer than `min_spacing` to a picked one.
+    #[export]
+    pub fn sample_blue_noise(
+        &self,
+        _owner: TRef<'_, Spatial>,
+        mask: Vector2Array,
+        min_spacing: i64,
+        seed: i64,
+    ) -> Vector2Array {
+        let mut cells: Vec<Vector2Di32> = if mask.len() == 0 {
+            self.hexagon_map.keys().copied().collect()
+        } else {
+            Self::cells_from_array(&mask)
+                .into_iter()
+                .filter(|cell| self.hexagon_map.contains_key(cell))
+                .collect()
+        };
+        cells.sort_unstable_by_key(|cell| (cell.y, cell.x));
+        cells.dedup();
+        let min_spacing = min_spacing.max(1) as u32;
+        let distance = |first: Vector2Di32, second: Vector2Di32| {
+            hex::axial_distance(hex::cell_to_axial(first), hex::cell_to_axial(second)) as f32
+        };
+
+        let picked = scatter::poisson_disk(
+            &cells,
+            min_spacing as f32,
+            distance,
+            |cell| {
+                hex::cells_in_range(cell, 2 * min_spacing)
+                    .into_iter()
+                    .filter(|other| distance(cell, *other) >= min_spacing as f32)
+                    .collect()
+            },
+            BLUE_NOISE_ATTEMPTS,
+            seed as u64,
+        );
+        Self::cells_to_array(picked)
+    }
+
+    /// Turns the field into a maze. Cells with even axial coordinates are rooms, every other cell
+    /// lies between two rooms and is either a passage or a wall. A randomized depth-first search
+    /// opens the passages, so every room can be reached on exactly one way. Rooms and passages
+    /// are set to height 0, walls are raised by `wall_height` as far as the slopes allow.
+    #[export]
+    pub fn generate_maze(&mut self, _owner: TRef<'_, Spatial>, seed: i64, wall_height: i64) {
+        self.generate(Some("maze"), |world| world.generate_maze(seed, wall_height));
+    }
+
+    /// Fills the field with wave function collapse. The modules are defined by `wfc_module_types`,
+    /// `wfc_module_heights` and `wfc_module_weights`; `wfc_rules` lists which modules may be
+    /// neighbours as triples of module, direction and neighbouring module. Directions count
+    /// clockwise from the top, 0 to 5, and -1 stands for all directions. Every rule also allows
+    /// the reverse, so it only needs to be listed once. Every cell gets the terrain type and
+    /// height of its module, vertices between cells get the average height. If the rules
+    /// contradict themselves, the following seeds are tried. Returns false if none succeeded.
+    #[export]
+    pub fn generate_wfc(&mut self, _owner: TRef<'_, Spatial>, seed: i64) -> bool {
+        self.generate(Some("wfc"), |world| world.generate_wfc(seed))
+    }
+
+    /// Partitions the cells into `count` contiguous provinces that grow from randomly chosen
+    /// cells, like a Voronoi diagram over the cells. Holes get no province.
+    #[export]
+    pub fn generate_provinces(&mut self, _owner: TRef<'_, Spatial>, seed: i64, count: i64) {
+        self.generate(None, |world| world.generate_provinces(seed, count));
+    }
+
+    /// Returns the province of a cell, -1 if it has none.
+    #[export]
+    pub fn get_cell_province(&self, _owner: TRef<'_, Spatial>, x: i64, y: i64) -> i64 {
+        let cell = Vector2Di32::new(x as i32, y as i32);
+        self.provinces
+            .get(&cell)
+            .map_or(-1, |province| *province as i64)
+    }
+
+    /// Returns the cells of a province, sorted by row and column.
+    #[export]
+    pub fn get_province_cells(&self, _owner: TRef<'_, Spatial>, province: i64) -> Vector2Array {
+        let mut cells: Vec<Vector2Di32> = self
+            .provinces
+            .iter()
+            .filter(|(_, cell_province)| **cell_province as i64 == province)
+            .map(|(cell, _)| *cell)
+            .collect();
+        cells.sort_unstable_by_key(|cell| (cell.y, cell.x));
+        Self::cells_to_array(cells)
+    }
+
+    /// Returns the provinces that share a border with a province, in ascending order.
+    #[export]
+    pub fn get_province_neighbors(&self, _owner: TRef<'_, Spatial>, province: i64) -> Int32Array {
+        let province = province as i32;
+        let neighbors = provinces::adjacency(&self.provinces, |cell| {
+            hex::neighbouring_cells(cell).to_vec()
+        })
+        .into_iter()
+        .filter_map(|(first, second)| {
+            if first == province {
+                Some(second)
+            } else if second == province {
+                Some(first)
+            } else {
+                None
+            }
+        })
+        .collect();
+        Int32Array::from_vec(neighbors)
+    }
+
+    /// Blows moist air across the field from the direction `wind_direction`, in degrees clockwise
+    /// from the positive X axis towards the positive Z axis of the terrain node, so at 0 the air
+    /// comes from +X and moves towards -X. The moisture of every cell is stored in its metadata
+    /// under "moisture", where `generate_biomes` picks it up. The air is saturated over the sea
+    /// (the "water" elevation level, or -1 if there is none) and loses `rainfall` of its moisture
+    /// per cell over land, plus `uplift` per step it rises. So windward slopes get wet and the
+    /// land behind mountains turns into desert.
+    #[export]
+    pub fn simulate_rain_shadow(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        wind_direction: f64,
+        rainfall: f64,
+        uplift: f64,
+    ) {
+        self.generate(None, |world| {
+            world.simulate_rain_shadow(wind_direction, rainfall, uplift)
+        });
+    }
+
+    /// Assigns a terrain type to every cell from its climate. The temperature falls from the middle
+    /// row of the field towards the top and bottom edges and by `biome_height_cooling` per step
+    /// above 0, the moisture comes from `simulate_rain_shadow` or else from noise, and both are
+    /// varied by noise of strength `biome_variation`. Every cell gets the type of the entry in the
+    /// biome table (`biome_types`, `biome_temperatures`, `biome_moistures`) whose temperature and
+    /// moisture are closest to its own.
+    #[export]
+    pub fn generate_biomes(&mut self, _owner: TRef<'_, Spatial>, seed: i64) {
+        self.generate(None, |world| world.generate_biomes(seed));
+    }
+
+    /// Replaces the terrain with a generated one. The heights, terrain types and edge features of
+    /// all cells that are not locked and the moisture, resources and provinces of all cells are
+    /// reset, then the `generation_steps` run in order. Each step is either a `HexGeneratorPass`
+    /// or an array of the name of a pass followed by its parameters (see `GenerationPipeline`).
+    /// Every step gets its own seed derived from `seed`, so the same seed and steps always produce
+    /// the same terrain. Unknown steps are skipped.
+    #[export]
+    pub fn regenerate(&mut self, _owner: TRef<'_, Spatial>, seed: i64) {
+        self.generation = None;
+        let pipeline = GenerationPipeline::from_config(&self.generation_steps);
+        self.generate(Some("regenerate"), |world| {
+            world.reset();
+            pipeline.run(world, seed);
+        });
+    }
+
+    /// Like `regenerate`, but runs the steps on a copy of the terrain on a background thread, so
+    /// the game stays responsive while a large terrain is generated. After every step
+    /// `generation_progress` is emitted with the share of steps that are done in percent and the
+    /// name of the step, at the end `generation_finished`. The generated terrain and metadata
+    /// replace the current ones in the frame the last step is done. If the terrain was edited in
+    /// the meantime, the generated one is dropped. Calling it again restarts the generation.
+    #[export]
+    pub fn regenerate_async(&mut self, _owner: TRef<'_, Spatial>, seed: i64) {
+        let pipeline = GenerationPipeline::from_config(&self.generation_steps);
+        self.begin_edit();
+        let world = self.world(self.terrain.clone());
+        let version = self.terrain_version();
+        self.generation = Some(GenerationJob::start(pipeline, world, version, seed));
+    }
+
+    /// Makes the whole terrain symmetric with the symmetry settings, e.g. for fair maps for
+    /// several players. Of all vertices the symmetry maps onto each other, the first by row and
+    /// column is copied to the others with its height, terrain type and metadata, so the wedge at
+    /// the top of the field is repeated. Edge features such as rivers and roads are repeated from
+    /// that wedge as well. As "symmetry" step it belongs after the steps whose results should be
+    /// repeated, e.g. after rivers and resources. Locked vertices keep their height.
+    #[export]
+    pub fn symmetrize(&mut self, _owner: TRef<'_, Spatial>) {
+        let sources = self.generate(Some("symmetrize"), |world| {
+            world.symmetrize();
+            world.symmetry_sources()
+        });
+        for (key, source) in sources {
+            if source == key {
+                continue;
+            }
+            match self.cell_metadata.get(&source) {
+                None => self.cell_metadata.remove(&key),
+                Some(metadata) => self
+                    .cell_metadata
+                    .insert(key, metadata.duplicate().into_shared()),
+            };
+        }
+    }
+
+    /// Replaces the generator settings with those of a preset. The "water" elevation level is
+    /// moved to the sea level of the preset, or added if there is none.
+    #[export]
+    pub fn apply_preset(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        preset: Instance<HexGenPreset, Shared>,
+    ) {
+        let preset = unsafe { preset.assume_safe() };
+        let sea_level = preset.map(|preset, _| {
+            self.generation_steps = preset.generation_steps.duplicate().into_shared();
+            self.noise_octaves = preset.noise_octaves;
+            self.noise_lacunarity = preset.noise_lacunarity;
+            self.noise_gain = preset.noise_gain;
+            self.noise_warp = preset.noise_warp;
+            self.island_distortion = preset.island_distortion;
+            self.island_depth = preset.island_depth;
+            self.biome_types = preset.biome_types.clone();
+            self.biome_temperatures = preset.biome_temperatures.clone();
+            self.biome_moistures = preset.biome_moistures.clone();
+            self.biome_height_cooling = preset.biome_height_cooling;
+            self.biome_variation = preset.biome_variation;
+            self.wfc_module_types = preset.wfc_module_types.clone();
+            self.wfc_module_heights = preset.wfc_module_heights.clone();
+            self.wfc_module_weights = preset.wfc_module_weights.clone();
+            self.wfc_rules = preset.wfc_rules.clone();
+            preset.sea_level as i32
+        });
+        let sea_level = match sea_level {
+            Err(_) => return,
+            Ok(sea_level) => sea_level,
+        };
+
+        let water = GodotString::from("water");
+        let index = self
+            .elevation_level_names
+            .read()
+            .iter()
+            .position(|name| *name == water);
+        match index {
+            Some(index) if (index as i32) < self.elevation_level_heights.len() => {
+                self.elevation_level_heights.set(index as i32, sea_level)
+            }
+            _ => {
+                self.elevation_level_names.push(water);
+                self.elevation_level_heights.push(sea_level);
+            }
+        }
+    }
+
+    /// Returns the current generator settings as a preset, e.g. to save them as a resource.
+    #[export]
+    pub fn capture_preset(&self, owner: TRef<'_, Spatial>) -> Instance<HexGenPreset, Unique> {
+        Instance::emplace(HexGenPreset {
+            generation_steps: self.generation_steps.duplicate().into_shared(),
+            noise_octaves: self.noise_octaves,
+            noise_lacunarity: self.noise_lacunarity,
+            noise_gain: self.noise_gain,
+            noise_warp: self.noise_warp,
+            island_distortion: self.island_distortion,
+            island_depth: self.island_depth,
+            sea_level: self.get_elevation_level_height(owner, GodotString::from("water"), -1),
+            biome_types: self.biome_types.clone(),
+            biome_temperatures: self.biome_temperatures.clone(),
+            biome_moistures: self.biome_moistures.clone(),
+            biome_height_cooling: self.biome_height_cooling,
+            biome_variation: self.biome_variation,
+            wfc_module_types: self.wfc_module_types.clone(),
+            wfc_module_heights: self.wfc_module_heights.clone(),
+            wfc_module_weights: self.wfc_module_weights.clone(),
+            wfc_rules: self.wfc_rules.clone(),
+        })
+    }
+
+    /// Returns whether `regenerate_async` is still running.
+    #[export]
+    pub fn is_generating(&self, _owner: TRef<'_, Spatial>) -> bool {
+        self.generation.is_some()
+    }
+
+    /// Returns whether an edit is still computed in the background, see `background_rebuilds`.
+    #[export]
+    pub fn is_rebuilding(&self, _owner: TRef<'_, Spatial>) -> bool {
+        self.rebuild.is_running()
+    }
+
+    /// Lets material slide down wherever the terrain is steeper than `talus_angle` degrees,
+    /// which removes spikes, e.g. after `generate_noise`. The angle is measured along the shortest
+    /// connections between vertices. Vertices of locked cells are not changed. Runs in the
+    /// background if `background_rebuilds` is set.
+    #[export]
+    pub fn thermal_erosion(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        talus_angle: f64,
+        iterations: i64,
+    ) {
+        let talus = match world::talus(talus_angle as f32, self.hex_radius, self.node_height) {
+            None => return,
+            Some(talus) => talus,
+        };
+        let iterations = iterations.max(0) as u32;
+
+        self.rebuild_terrain("erosion", move |terrain| {
+            terrain.erode(talus, EROSION_RATE, iterations)
+        });
+    }
+
+    /// Makes the terrain look older by lowering peaks, filling pits and roughening flat areas by
+    /// one step at a time. `intensity` is the chance of every vertex to change per iteration,
+    /// between 0 and 1. Helps stamped or edited areas to blend in with generated ones. Vertices
+    /// of locked cells are not changed. Runs in the background if `background_rebuilds` is set.
+    #[export]
+    pub fn weather(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        seed: i64,
+        intensity: f64,
+        iterations: i64,
+    ) {
+        let (intensity, iterations) = (intensity as f32, iterations.max(0) as u32);
+        self.rebuild_terrain("weathering", move |terrain| {
+            terrain.weather(intensity, iterations, seed as u64)
+        });
+    }
+
+    /// Returns the height of the named elevation level, or `default` if there is no such level.
+    #[export]
+    pub fn get_elevation_level_height(
+        &self,
+        _owner: TRef<'_, Spatial>,
+        name: GodotString,
+        default: i64,
+    ) -> i64 {
+        self.elevation_levels()
+            .into_iter()
+            .find(|(level_name, _)| *level_name == name)
+            .map_or(default, |(_, height)| height as i64)
+    }
+
+    /// Returns the name of the elevation level closest to the height of the vertex.
+    #[export]
+    pub fn get_elevation_level_name(
+        &self,
+        _owner: TRef<'_, Spatial>,
+        x: i64,
+        y: i64,
+    ) -> GodotString {
+        let height = self
+            .terrain
+            .get_height_of_node(Vector2Di32::new(x as i32, y as i32))
+            .unwrap_or(0);
+        let levels = self.elevation_levels();
+        let heights: Vec<i32> = levels.iter().map(|(_, height)| *height).collect();
+
+        tools::nearest_level(&heights, height)
+            .and_then(|level| levels.into_iter().find(|(_, height)| *height == level))
+            .map_or_else(GodotString::new, |(name, _)| name)
+    }
+
+    /// Sets all vertices of the given cells to the named elevation level. Returns whether the level
+    /// exists.
+    #[export]
+    pub fn set_elevation_level(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        cells: Vector2Array,
+        name: GodotString,
+    ) -> bool {
+        self.begin_edit();
+        let level = match self
+            .elevation_levels()
+            .into_iter()
+            .find(|(level_name, _)| *level_name == name)
+        {
+            None => return false,
+            Some((_, level)) => level,
+        };
+
+        let heights: Vec<(Vector2Di32, i32)> = self
+            .keys_of_cells(&Self::cells_from_array(&cells))
+            .into_iter()
+            .map(|key| (key, level))
+            .collect();
+        self.set_heights(&heights);
+        self.end_edit("elevation_level");
+        self.vertices_dirty = true;
+        true
+    }
+
+    /// Snaps the heights of the given cells to the closest elevation level, or of all vertices if
+    /// no cells are given.
+    #[export]
+    pub fn snap_cells_to_elevation_levels(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        cells: Vector2Array,
+    ) {
+        self.begin_edit();
+        let heights: Vec<(Vector2Di32, i32)> = if cells.len() == 0 {
+            self.terrain.heights().collect()
+        } else {
+            self.heights_of_keys(&self.keys_of_cells(&Self::cells_from_array(&cells)))
+        };
+
+        let levels = self.elevation_level_heights.read().to_vec();
+        let heights: Vec<(Vector2Di32, i32)> = heights
+            .into_iter()
+            .filter_map(|(key, height)| {
+                tools::nearest_level(&levels, height).map(|level| (key, level))
+            })
+            .collect();
+        self.set_heights(&heights);
+        self.end_edit("snap_to_elevation_levels");
+        self.vertices_dirty = true;
+    }
+
+    /// Returns a top-down texture of `size` by `size` pixels with a color per cell: blue for
+    /// water at or below the "water" elevation level, otherwise the color of the terrain type in
+    /// `minimap_type_colors`, white if there is none. Lower cells are darker. The texture is
+    /// updated after edits, painting only the cells that changed, until the next call.
+    #[export]
+    pub fn generate_minimap(&mut self, _owner: TRef<'_, Spatial>, size: i64) -> Ref<ImageTexture> {
+        let cells: Vec<Vector2Di32> = self.hexagon_map.keys().copied().collect();
+        let heights = cells
+            .iter()
+            .filter_map(|cell| self.terrain.get_height_of_node(*cell));
+        self.minimap_heights = (
+            heights.clone().min().unwrap_or(0),
+            heights.max().unwrap_or(0),
+        );
+
+        let mut minimap = Minimap::new(size.max(1), &cells);
+        minimap.update(self.minimap_colors().into_iter());
+        let texture = ImageTexture::new();
+        texture.create_from_image(Self::minimap_image(&minimap), 0);
+        let texture = texture.into_shared();
+        self.minimap = Some((minimap, texture.clone()));
+        texture
+    }
+
+    /// Renders the field seen from above into an image of `width` by `height` pixels, e.g. for
+    /// printed maps or loading screens. It is drawn without a viewport, so it also works on
+    /// headless servers. Triangles have the color of their terrain type in `type_colors`, white if
+    /// there is none, and are darker on slopes facing away from the light. Decks are drawn above
+    /// the ground, then the grid if `grid_visible`, then edge features as lines with the color in
+    /// `feature_colors` at the index of the feature, if there is one. The field fills the image
+    /// keeping its aspect ratio, pixels outside of it are transparent.
+    #[export]
+    pub fn render_map(
+        &self,
+        _owner: TRef<'_, Spatial>,
+        width: i64,
+        height: i64,
+        type_colors: ColorArray,
+        feature_colors: ColorArray,
+    ) -> Ref<Image, Unique> {
+        let type_colors = type_colors.read();
+        let feature_colors = feature_colors.read();
+        let mut canvas = Canvas::new(width.max(1), height.max(1), &self.vertex_keys());
+        let point = |key: Vector2Di32| (key.x as f32, key.y as f32);
+
+        let mut draw_triangle = |triangle: &[TerrainNode], height: &dyn Fn(Vector2Di32) -> i32| {
+            let terrain_type = self.terrain.get_terrain_type(triangle[0].key).unwrap_or(0);
+            let color = type_colors
+                .get(terrain_type.max(0) as usize)
+                .map_or([255; 4], |color| Self::color_bytes(*color));
+            let corner = |node: &TerrainNode| {
+                [
+                    node.key.x as f32 * self.hex_radius,
+                    height(node.key) as f32 * self.node_height,
+                    node.key.y as f32 * self.hex_radius,
+                ]
+            };
+            canvas.fill_triangle(
+                [
+                    point(triangle[0].key),
+                    point(triangle[1].key),
+                    point(triangle[2].key),
+                ],
+                map_render::shade(
+                    color,
+                    [
+                        corner(&triangle[0]),
+                        corner(&triangle[1]),
+                        corner(&triangle[2]),
+                    ],
+                ),
+            );
+        };
+        for triangle in self.nodes.chunks(3) {
+            if !self.terrain.is_hole(triangle[0].key) {
+                draw_triangle(triangle, &|key| {
+                    self.terrain.get_height_of_node(key).unwrap_or(0)
+                });
+            }
+        }
+        for triangle in self.nodes.chunks(3) {
+            if let Some(deck_height) = self.terrain.get_deck_height(triangle[0].key) {
+                draw_triangle(triangle, &|_| deck_height);
+            }
+        }
+
+        if self.grid_visible {
+            let color = Self::color_bytes(self.grid_color);
+            let thickness = self.grid_thickness / self.hex_radius;
+            for hexagon in self.hexagon_map.values() {
+                if self.terrain.is_hole(hexagon.center) {
+                    continue;
+                }
+                let corners = [
+                    hexagon.left,
+                    hexagon.top_left,
+                    hexagon.top_right,
+                    hexagon.right,
+                    hexagon.bottom_right,
+                    hexagon.bottom_left,
+                ];
+                for index in 0..corners.len() {
+                    canvas.draw_line(
+                        point(corners[index]),
+                        point(corners[(index + 1) % corners.len()]),
+                        thickness,
+                        color,
+                    );
+                }
+            }
+        }
+
+        for (first, second, feature) in self.terrain.edge_features() {
+            if let Some(color) = feature_colors.get(feature.max(0) as usize) {
+                canvas.draw_line(point(first), point(second), 1.0, Self::color_bytes(*color));
+            }
+        }
+
+        let image = Image::new();
+        image.create_from_data(
+            canvas.width,
+            canvas.height,
+            false,
+            Image::FORMAT_RGBA8,
+            ByteArray::from_slice(&canvas.pixels),
+        );
+        image
+    }
+
+    /// Returns a grayscale image of the heights of the given cells, or of all cells if no cells
+    /// are given. Every pixel is one key unit, pixels outside of the cells are transparent.
+    #[export]
+    pub fn export_heightmap(
+        &self,
+        _owner: TRef<'_, Spatial>,
+        cells: Vector2Array,
+    ) -> Ref<Image, Unique> {
+        let heights: Vec<(Vector2Di32, i32)> = if cells.len() == 0 {
+            self.terrain.heights().collect()
+        } else {
+            self.heights_of_keys(&self.keys_of_cells(&Self::cells_from_array(&cells)))
+        };
+        Self::heightmap_image(&Heightmap::render(&heights))
+    }
+
+    /// Replaces the heights of the whole field with a digital elevation model, e.g. of a real
+    /// location. The file is either an SRTM HGT tile (with a `width` of 0) or a raw grid of signed
+    /// 16 bit samples of the given size, in big or little endian byte order. The model is
+    /// stretched over the field, and every `meters_per_step` meters above `base_elevation` make
+    /// one height step. Samples without data count as `base_elevation`. Returns whether the file
+    /// could be read. Needs the "dem" feature.
+    #[export]
+    #[allow(clippy::too_many_arguments)]
+    pub fn import_dem(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        path: GodotString,
+        width: i64,
+        height: i64,
+        big_endian: bool,
+        meters_per_step: f64,
+        base_elevation: f64,
+    ) -> bool {
+        let heights = match self.dem_heights(
+            path,
+            width,
+            height,
+            big_endian,
+            meters_per_step,
+            base_elevation,
+        ) {
+            None => return false,
+            Some(heights) => heights,
+        };
+
+        self.begin_edit();
+        self.terrain.set_generated_heights(&heights);
+        self.end_edit("import_dem");
+        self.vertices_dirty = true;
+        true
+    }
+
+    /// Imports a hexagonal map of the Tiled map editor, as TMX or JSON file. The local tile IDs of
+    /// `type_layer` become the terrain types of the cells and those of `elevation_layer` their
+    /// heights in steps; an empty name skips the layer. The top left tile becomes the cell at the
+    /// origin. The cells of the field are flat-topped, so maps with pointy-topped tiles are
+    /// mirrored along their diagonal. Tiles outside of the field are ignored. Returns false if the
+    /// file cannot be read or a layer does not exist.
+    #[export]
+    pub fn import_tiled(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        path: GodotString,
+        type_layer: GodotString,
+        elevation_layer: GodotString,
+    ) -> bool {
+        let file = File::new();
+        if file.open(path, File::READ).is_err() {
+            return false;
+        }
+        let text = file.get_as_text().to_string();
+        file.close();
+        let map = if text.trim_start().starts_with('<') {
+            tiled::parse_tmx(&text)
+        } else {
+            tiled::parse_json(&text)
+        };
+        let map = match map {
+            None => return false,
+            Some(map) => map,
+        };
+        let cell = |column: u32, row: u32| {
+            let axial = if map.stagger_columns {
+                hex::offset_to_axial(column as i32, row as i32, map.stagger_odd)
+            } else {
+                hex::offset_to_axial(row as i32, column as i32, map.stagger_odd)
+            };
+            hex::axial_to_cell(axial)
+        };
+        let layer = |name: &GodotString| {
+            if name.is_empty() {
+                Some(Vec::new())
+            } else {
+                map.tiles(&name.to_string())
+            }
+        };
+        let (types, elevations) = match (layer(&type_layer), layer(&elevation_layer)) {
+            (Some(types), Some(elevations)) => (types, elevations),
+            _ => return false,
+        };
+
+        let mut sums: HashMap<Vector2Di32, (i32, i32)> = HashMap::new();
+        for (column, row, elevation) in elevations {
+            if let Some(hexagon) = self.hexagon_map.get(&cell(column, row)) {
+                for key in hexagon.keys().iter() {
+                    let sum = sums.entry(*key).or_insert((0, 0));
+                    sum.0 += elevation as i32;
+                    sum.1 += 1;
+                }
+            }
+        }
+        let mut heights: Vec<(Vector2Di32, i32)> = sums
+            .into_iter()
+            .map(|(key, (sum, count))| (key, (sum as f32 / count as f32).round() as i32))
+            .collect();
+        heights.sort_unstable_by_key(|(key, _)| (key.y, key.x));
+
+        self.begin_edit();
+        for (column, row, terrain_type) in types {
+            let cell = cell(column, row);
+            if self.hexagon_map.contains_key(&cell) {
+                self.terrain.set_terrain_type(cell, terrain_type as i32);
+            }
+        }
+        self.terrain.set_generated_heights(&heights);
+        self.end_edit("import_tiled");
+        self.vertices_dirty = true;
+        true
+    }
+
+    /// Imports the terrain types of a map of a third-party hex map editor: a Hex Kit map (JSON)
+    /// or a Hexographer CSV export. `mapping` maps palette entries, like terrain names or tile
+    /// images, to terrain types; an image can also be mapped by its file name without folders
+    /// and extension. The top left tile becomes the cell at the origin, maps with pointy-topped
+    /// tiles are mirrored along their diagonal like in `import_tiled`. Tiles without a mapping
+    /// and tiles outside of the field are ignored. Returns the number of cells that were set, or
+    /// -1 if the file cannot be read.
+    #[export]
+    pub fn import_hex_map(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        path: GodotString,
+        mapping: Dictionary,
+    ) -> i64 {
+        let file = File::new();
+        if file.open(path, File::READ).is_err() {
+            return -1;
+        }
+        let text = file.get_as_text().to_string();
+        file.close();
+        let map = if text.trim_start().starts_with('{') {
+            hexmaps::parse_hex_kit(&text)
+        } else {
+            hexmaps::parse_hexographer_csv(&text)
+        };
+        let map = match map {
+            None => return -1,
+            Some(map) => map,
+        };
+        let mapping = |entry: &str| {
+            let entry = GodotString::from(entry);
+            if mapping.contains(entry.clone()) {
+                mapping
+                    .get(entry)
+                    .try_to_i64()
+                    .map(|terrain_type| terrain_type as i32)
+            } else {
+                None
+            }
+        };
+
+        self.begin_edit();
+        let mut count = 0;
+        for (column, row, entry) in &map.tiles {
+            let axial = if map.stagger_columns {
+                hex::offset_to_axial(*column as i32, *row as i32, true)
+            } else {
+                hex::offset_to_axial(*row as i32, *column as i32, true)
+            };
+            let cell = hex::axial_to_cell(axial);
+            if !self.hexagon_map.contains_key(&cell) {
+                continue;
+            }
+            if let Some(terrain_type) = hexmaps::palette_type(entry, mapping) {
+                self.terrain.set_terrain_type(cell, terrain_type);
+                count += 1;
+            }
+        }
+        self.end_edit("import_hex_map");
+        self.vertices_dirty = true;
+        count
+    }
+
+    /// Writes the cells to a GridMap, e.g. to compare the field with a GridMap prototype. The
+    /// GridMap is cleared, then every cell that is not a hole gets an item at its offset
+    /// coordinates, the column as x and the row as z with odd columns shifted down by half a cell.
+    /// Its height in steps divided by `band_height`, rounded down, is the layer y. The item is the
+    /// one in `type_items` at the index of the terrain type of the cell, or the terrain type
+    /// itself if there is none. Returns the number of cells written.
+    #[export]
+    pub fn export_grid_map(
+        &self,
+        _owner: TRef<'_, Spatial>,
+        grid_map: Ref<GridMap>,
+        type_items: Int32Array,
+        band_height: i64,
+    ) -> i64 {
+        let grid_map = unsafe { grid_map.assume_safe() };
+        let type_items = type_items.read();
+        let band_height = band_height.max(1) as i32;
+        grid_map.clear();
+        let mut count = 0;
+        for cell in self.hexagon_map.keys() {
+            if self.terrain.is_hole(*cell) {
+                continue;
+            }
+            let (column, row) = hex::axial_to_offset(hex::cell_to_axial(*cell), true);
+            let height = self.terrain.get_height_of_node(*cell).unwrap_or(0);
+            let terrain_type = self.terrain.get_terrain_type(*cell).unwrap_or(0);
+            let item = type_items
+                .get(terrain_type.max(0) as usize)
+                .copied()
+                .unwrap_or(terrain_type);
+            grid_map.set_cell_item(
+                column as i64,
+                height.div_euclid(band_height) as i64,
+                row as i64,
+                item as i64,
+                0,
+            );
+            count += 1;
+        }
+        count
+    }
+
+    /// Sets the heights and terrain types of the cells from a GridMap laid out like
+    /// `export_grid_map` writes, e.g. to start from a GridMap prototype. The highest item of
+    /// every column and row wins: its layer times `band_height` becomes the height of the cell
+    /// in steps and the index of the item in `type_items` its terrain type, or the item itself if
+    /// it is not in there. Cells outside of the field are ignored, cells without items keep their
+    /// data.
+    #[export]
+    pub fn import_grid_map(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        grid_map: Ref<GridMap>,
+        type_items: Int32Array,
+        band_height: i64,
+    ) {
+        let grid_map = unsafe { grid_map.assume_safe() };
+        let type_items = type_items.read();
+        let mut top: HashMap<Vector2Di32, (i32, i32)> = HashMap::new();
+        for used in grid_map.get_used_cells().iter() {
+            let used = match used.try_to_vector3() {
+                None => continue,
+                Some(used) => used,
+            };
+            let (x, y, z) = (used.x as i32, used.y as i32, used.z as i32);
+            let item = grid_map.get_cell_item(x as i64, y as i64, z as i64) as i32;
+            let cell = hex::axial_to_cell(hex::offset_to_axial(x, z, true));
+            if self.hexagon_map.contains_key(&cell)
+                && !matches!(top.get(&cell), Some((layer, _)) if *layer >= y)
+            {
+                top.insert(cell, (y, item));
+            }
+        }
+
+        let mut sums: HashMap<Vector2Di32, (i32, i32)> = HashMap::new();
+        for (cell, (layer, _)) in &top {
+            for key in self.hexagon_map[cell].keys().iter() {
+                let sum = sums.entry(*key).or_insert((0, 0));
+                sum.0 += layer * band_height.max(1) as i32;
+                sum.1 += 1;
+            }
+        }
+        let mut heights: Vec<(Vector2Di32, i32)> = sums
+            .into_iter()
+            .map(|(key, (sum, count))| (key, (sum as f32 / count as f32).round() as i32))
+            .collect();
+        heights.sort_unstable_by_key(|(key, _)| (key.y, key.x));
+
+        self.begin_edit();
+        for (cell, (_, item)) in top {
+            let terrain_type = type_items
+                .iter()
+                .position(|type_item| *type_item == item)
+                .map_or(item, |index| index as i32);
+            self.terrain.set_terrain_type(cell, terrain_type);
+        }
+        self.terrain.set_generated_heights(&heights);
+        self.end_edit("import_grid_map");
+        self.vertices_dirty = true;
+    }
+
+    /// Returns the heights, terrain types and cell metadata of the field as JSON text, e.g. for
+    /// external tools, web viewers or version control. Metadata values that JSON cannot hold,
+    /// like vectors, are stored as text.
+    #[export]
+    pub fn to_json(&self, _owner: TRef<'_, Spatial>) -> GodotString {
+        let metadata: Vec<(Vector2Di32, Json)> = self
+            .cell_metadata
+            .iter()
+            .filter_map(|(cell, metadata)| {
+                Some((*cell, json::parse(&Self::metadata_to_json(metadata))?))
+            })
+            .collect();
+        GodotString::from(json::to_json(&self.terrain, &metadata, |key| {
+            (key.x, key.y)
+        }))
+    }
+
+    /// Sets the heights, terrain types and cell metadata of the field from JSON text written by
+    /// `to_json`. Vertices and cells that are not in the text keep their data, entries outside
+    /// of the field are ignored. Returns whether the text could be read.
+    #[export]
+    pub fn from_json(&mut self, _owner: TRef<'_, Spatial>, text: GodotString) -> bool {
+        let save = match json::from_json(&text.to_string(), Vector2Di32::new) {
+            None => return false,
+            Some(save) => save,
+        };
+
+        self.begin_edit();
+        self.apply_saved_terrain(&save.terrain);
+        for key in save.terrain.heights().map(|(key, _)| key) {
+            self.cell_metadata.remove(&key);
+        }
+        for (cell, data) in save.metadata {
+            if !self.hexagon_map.contains_key(&cell) {
+                continue;
+            }
+            if let Some(metadata) = Self::metadata_from_json(&data.to_string()) {
+                if !metadata.is_empty() {
+                    self.cell_metadata.insert(cell, metadata);
+                }
+            }
+        }
+        self.end_edit("from_json");
+        self.vertices_dirty = true;
+        true
+    }
+
+    /// Saves the field with its terrain types, holes, bridges, edge features and cell metadata to
+    /// a binary map file. With a `compression_level` from 1 (fastest) to 9 (smallest) the file is
+    /// compressed with gzip, 0 stores it uncompressed. Returns the Godot error code, 0 on success.
+    #[export]
+    pub fn save_map(
+        &self,
+        _owner: TRef<'_, Spatial>,
+        path: GodotString,
+        compression_level: i64,
+    ) -> i64 {
+        let bytes = self.map_bytes(compression_level.clamp(0, 9) as u32);
+        let file = File::new();
+        if let Err(error) = file.open(path, File::WRITE) {
+            return error as i64;
+        }
+        file.store_buffer(ByteArray::from_vec(bytes));
+        file.close();
+        0
+    }
+
+    /// Exports the field for other engines or server code: a heightmap image at `path` + ".png",
+    /// see `export_heightmap`, and a JSON sidecar at `path` + ".json". The sidecar holds the
+    /// `layout` to read the image: the key at its top left pixel (`origin`), the heights that
+    /// black and white stand for, `hex_radius` and `node_height`, with which a vertex is at
+    /// (key x * hex_radius, height * node_height, key y * hex_radius). It also lists the `cells`
+    /// with their center key, height, terrain type and metadata, and the `rivers` as pairs of
+    /// vertex keys. Returns the Godot error code, 0 on success.
+    #[export]
+    pub fn export_bundle(&self, _owner: TRef<'_, Spatial>, path: GodotString) -> i64 {
+        let path = path.to_string();
+        let heights: Vec<(Vector2Di32, i32)> = self.terrain.heights().collect();
+        let heightmap = Heightmap::render(&heights);
+        if let Err(error) = Self::heightmap_image(&heightmap).save_png(format!("{}.png", path)) {
+            return error as i64;
+        }
+
+        let number = |value: i32| Json::Number(value as f64);
+        let key = |key: Vector2Di32| Json::Array(vec![number(key.x), number(key.y)]);
+        let mut cells: Vec<Vector2Di32> = self.hexagon_map.keys().copied().collect();
+        cells.sort_unstable_by_key(|cell| (cell.y, cell.x));
+        let cells = cells
+            .into_iter()
+            .map(|cell| {
+                let mut entry = vec![
+                    ("key".to_owned(), key(cell)),
+                    (
+                        "height".to_owned(),
+                        number(self.terrain.get_height_of_node(cell).unwrap_or(0)),
+                    ),
+                    (
+                        "type".to_owned(),
+                        number(self.terrain.get_terrain_type(cell).unwrap_or(0)),
+                    ),
+                ];
+                let metadata = self
+                    .cell_metadata
+                    .get(&cell)
+                    .and_then(|metadata| json::parse(&Self::metadata_to_json(metadata)));
+                if let Some(metadata) = metadata {
+                    entry.push(("metadata".to_owned(), metadata));
+                }
+                Json::Object(entry)
+            })
+            .collect();
+        let mut rivers: Vec<(Vector2Di32, Vector2Di32)> = self
+            .terrain
+            .edge_features()
+            .filter(|(_, _, feature)| *feature == self.river_feature as i32)
+            .map(|(first, second, _)| (first, second))
+            .collect();
+        rivers.sort_unstable_by_key(|(first, second)| (first.y, first.x, second.y, second.x));
+        let rivers = rivers
+            .into_iter()
+            .map(|(first, second)| Json::Array(vec![key(first), key(second)]))
+            .collect();
+
+        let heights = heights.iter().map(|(_, height)| *height);
+        let layout = vec![
+            ("origin".to_owned(), key(heightmap.origin)),
+            (
+                "black_height".to_owned(),
+                number(heights.clone().min().unwrap_or(0)),
+            ),
+            (
+                "white_height".to_owned(),
+                number(heights.max().unwrap_or(0)),
+            ),
+            (
+                "hex_radius".to_owned(),
+                Json::Number(self.hex_radius as f64),
+            ),
+            (
+                "node_height".to_owned(),
+                Json::Number(self.node_height as f64),
+            ),
+        ];
+        let image_name = format!("{}.png", path.rsplit('/').next().unwrap_or(&path));
+        let sidecar = Json::Object(vec![
+            ("version".to_owned(), Json::Number(BUNDLE_VERSION)),
+            ("heightmap".to_owned(), Json::String(image_name)),
+            ("layout".to_owned(), Json::Object(layout)),
+            ("cells".to_owned(), Json::Array(cells)),
+            ("rivers".to_owned(), Json::Array(rivers)),
+        ]);
+
+        let file = File::new();
+        if let Err(error) = file.open(format!("{}.json", path), File::WRITE) {
+            return error as i64;
+        }
+        file.store_string(sidecar.to_string());
+        file.close();
+        0
+    }
+
+    /// Exports the mesh of the field, including decks, as glTF 2.0 file with embedded buffer, so it
+    /// renders the same in other tools. Every terrain type gets its own surface and material,
+    /// named "terrain_type_" and the type, with the color from `type_colors` at the index of the
+    /// type, white if there is none. The vertex colors hold the same color for splatting shaders,
+    /// the second UV spans the whole field. Returns the Godot error code, 0 on success.
+    #[export]
+    pub fn export_gltf(
+        &self,
+        _owner: TRef<'_, Spatial>,
+        path: GodotString,
+        type_colors: ColorArray,
+    ) -> i64 {
+        let type_colors = type_colors.read();
+        let keys = self.vertex_keys();
+        let min_x = keys.iter().map(|key| key.x).min().unwrap_or(0) as f32;
+        let max_x = keys.iter().map(|key| key.x).max().unwrap_or(0) as f32;
+        let min_y = keys.iter().map(|key| key.y).min().unwrap_or(0) as f32;
+        let max_y = keys.iter().map(|key| key.y).max().unwrap_or(0) as f32;
+
+        let mut surfaces: Vec<(i32, Surface)> = Vec::new();
+        let mut add_triangle = |triangle: &[TerrainNode], height: &dyn Fn(Vector2Di32) -> i32| {
+            let terrain_type = self.terrain.get_terrain_type(triangle[0].key).unwrap_or(0);
+            let color = type_colors
+                .get(terrain_type.max(0) as usize)
+                .map_or([1.0; 4], |color| [color.r, color.g, color.b, color.a]);
+            let vertex = |node: &TerrainNode| {
+                let position = self.vertex_map[&node.key];
+                Vertex {
+                    position: [
+                        position.x,
+                        height(node.key) as f32 * self.node_height,
+                        position.y,
+                    ],
+                    uv: [node.uv.x, node.uv.y],
+                    uv2: [
+                        (node.key.x as f32 - min_x) / (max_x - min_x).max(1.0),
+                        (node.key.y as f32 - min_y) / (max_y - min_y).max(1.0),
+                    ],
+                    color,
+                }
+            };
+            // Godot treats clockwise triangles as front faces, glTF counter-clockwise ones.
+            let triangle = [
+                vertex(&triangle[0]),
+                vertex(&triangle[2]),
+                vertex(&triangle[1]),
+            ];
+            match surfaces
+                .iter_mut()
+                .find(|(other, _)| *other == terrain_type)
+            {
+                Some((_, surface)) => surface.triangles.push(triangle),
+                None => surfaces.push((
+                    terrain_type,
+                    Surface {
+                        material: format!("terrain_type_{}", terrain_type),
+                        base_color: color,
+                        triangles: vec![triangle],
+                    },
+                )),
+            }
+        };
+        for triangle in self.nodes.chunks(3) {
+            if !self.terrain.is_hole(triangle[0].key) {
+                add_triangle(triangle, &|key| {
+                    self.terrain.get_height_of_node(key).unwrap_or(0)
+                });
+            }
+            if let Some(deck_height) = self.terrain.get_deck_height(triangle[0].key) {
+                add_triangle(triangle, &|_| deck_height);
+            }
+        }
+        surfaces.sort_unstable_by_key(|(terrain_type, _)| *terrain_type);
+        let surfaces: Vec<Surface> = surfaces.into_iter().map(|(_, surface)| surface).collect();
+
+        let file = File::new();
+        if let Err(error) = file.open(path, File::WRITE) {
+            return error as i64;
+        }
+        file.store_string(gltf::to_gltf(&surfaces));
+        file.close();
+        0
+    }
+
+    /// Loads a map file written by `save_map`, compressed or not. Vertices and cells that are not
+    /// in the file keep their data, entries outside of the field are ignored. Returns whether the
+    /// file could be read.
+    #[export]
+    pub fn load_map(&mut self, _owner: TRef<'_, Spatial>, path: GodotString) -> bool {
+        let file = File::new();
+        if file.open(path, File::READ).is_err() {
+            return false;
+        }
+        let bytes = file.get_buffer(file.get_len()).read().to_vec();
+        file.close();
+        self.apply_map_bytes(&bytes, "load_map")
+    }
+
+    /// Writes a snapshot of the field to the oldest of the `autosave_slots` map files in
+    /// `autosave_directory` now, on a background thread. Snapshots are also written every
+    /// `autosave_interval` seconds if it is above 0. Returns false if the field did not change
+    /// since the last snapshot or that is still written.
+    #[export]
+    pub fn autosave_now(&mut self, _owner: TRef<'_, Spatial>) -> bool {
+        let directory = self.autosave_path();
+        self.autosave.save(
+            directory,
+            self.autosave_slots.max(1) as usize,
+            self.map_chunks(),
+            AUTOSAVE_COMPRESSION_LEVEL,
+        )
+    }
+
+    /// Writes the loaded chunks of an infinite terrain that changed since they were last written
+    /// to `chunk_directory`, one file per chunk plus an index. Chunks are also written when they
+    /// are unloaded, and chunks that were written are read from there when they are loaded again,
+    /// so only the loaded chunks are kept in memory. Returns the number of chunks written, or -1
+    /// if `chunk_directory` is empty or writing failed.
+    #[export]
+    pub fn save_streamed_chunks(&mut self, _owner: TRef<'_, Spatial>) -> i64 {
+        self.store_chunks();
+        self.write_streamed_chunks()
+            .map_or(-1, |written| written as i64)
+    }
+
+    /// Loads the newest autosave snapshot, e.g. on startup after a crash. Returns whether there
+    /// was one that could be read.
+    #[export]
+    pub fn recover_latest(&mut self, _owner: TRef<'_, Spatial>) -> bool {
+        let path = autosave::latest(&self.autosave_path(), self.autosave_slots.max(1) as usize);
+        match path.and_then(|path| std::fs::read(path).ok()) {
+            None => false,
+            Some(bytes) => self.apply_map_bytes(&bytes, "recover_autosave"),
+        }
+    }
+
+    /// Returns the field as a `HexMap` resource, e.g. to save it with `ResourceSaver` as a
+    /// `.hexmap` file for the `terrain_data` property. See `save_map` for `compression_level`.
+    #[export]
+    pub fn capture_terrain_data(
+        &self,
+        _owner: TRef<'_, Spatial>,
+        compression_level: i64,
+    ) -> Instance<HexMap, Unique> {
+        let data = self.map_bytes(compression_level.clamp(0, 9) as u32);
+        Instance::emplace(HexMap {
+            data: ByteArray::from_vec(data),
+        })
+    }
+
+    /// Returns the height of every vertex as CSV text with `x`, `y` and `height` columns, and a
+    /// `type` column with the terrain types if `include_types`, e.g. for spreadsheets or to diff
+    /// generated terrains in CI.
+    #[export]
+    pub fn to_csv(&self, _owner: TRef<'_, Spatial>, include_types: bool) -> GodotString {
+        GodotString::from(csv::to_csv(
+            &self.terrain,
+            |key| (key.x, key.y),
+            include_types,
+        ))
+    }
+
+    /// Returns the vertices and their connections as a Graphviz DOT graph, or as GraphML if
+    /// `graphml`, with the height and terrain type of every vertex, e.g. to inspect adjacency and
+    /// height propagation in Graphviz or Gephi.
+    #[export]
+    pub fn to_graph(&self, _owner: TRef<'_, Spatial>, graphml: bool) -> GodotString {
+        let position = |key: Vector2Di32| (key.x, key.y);
+        GodotString::from(if graphml {
+            graph::to_graphml(&self.terrain, position)
+        } else {
+            graph::to_dot(&self.terrain, position)
+        })
+    }
+
+    /// Returns the heights and terrain types of the field as RON text, which is easier to edit by
+    /// hand than JSON, e.g. for mods.
+    #[export]
+    pub fn to_ron(&self, _owner: TRef<'_, Spatial>) -> GodotString {
+        GodotString::from(ron::to_ron(&self.terrain, |key| (key.x, key.y)))
+    }
+
+    /// Sets the heights and terrain types of the field from RON text like `to_ron` writes.
+    /// Vertices that are not in the text keep their data, entries outside of the field are
+    /// ignored. Returns whether the text could be read.
+    #[export]
+    pub fn from_ron(&mut self, _owner: TRef<'_, Spatial>, text: GodotString) -> bool {
+        let saved = match ron::from_ron(&text.to_string(), Vector2Di32::new) {
+            None => return false,
+            Some(saved) => saved,
+        };
+
+        self.begin_edit();
+        self.apply_saved_terrain(&saved);
+        self.end_edit("from_ron");
+        self.vertices_dirty = true;
+        true
+    }
+
+    /// Returns the keys of all vertices, sorted by row and column.
+    #[export]
+    pub fn get_vertex_keys(&self, _owner: TRef<'_, Spatial>) -> Vector2Array {
+        Self::cells_to_array(self.vertex_keys().iter().copied())
+    }
+
+    /// Returns the key of the vertex at the index of `get_vertex_keys`, null if there is none.
+    #[export]
+    pub fn get_vertex_key(&self, _owner: TRef<'_, Spatial>, index: i64) -> Variant {
+        match self.vertex_keys().get(index as usize) {
+            Some(key) => Self::cell_to_variant(*key),
+            _ => Variant::new(),
+        }
+    }
+
+    /// Returns the position of the vertex at the index of `get_vertex_keys` relative to the
+    /// terrain, null if there is none.
+    #[export]
+    pub fn get_vertex_position(&self, _owner: TRef<'_, Spatial>, index: i64) -> Variant {
+        match self.vertex_keys().get(index as usize) {
+            Some(key) => self.vertex_position(*key).to_variant(),
+            _ => Variant::new(),
+        }
+    }
+
+    /// Returns the positions of all vertices relative to the terrain, in the order of
+    /// `get_vertex_keys`.
+    #[export]
+    pub fn get_vertex_positions(&self, _owner: TRef<'_, Spatial>) -> Vector3Array {
+        Vector3Array::from_vec(
+            self.vertex_keys()
+                .iter()
+                .copied()
+                .map(|key| self.vertex_position(key))
+                .collect(),
+        )
+    }
+
+    /// Returns the keys of all vertices packed into one integer each, in the order of
+    /// `get_vertex_keys`. The x coordinate is in the upper 16 bits and the y coordinate in the
+    /// lower 16 bits, both signed, so they can be used as dictionary keys or in shaders.
+    #[export]
+    pub fn get_packed_vertex_keys(&self, _owner: TRef<'_, Spatial>) -> Int32Array {
+        Int32Array::from_vec(
+            self.vertex_keys()
+                .iter()
+                .copied()
+                .map(|key| key.x << 16 | (key.y & 0xFFFF))
+                .collect(),
+        )
+    }
+
+    /// Returns the height of every vertex in steps, in the order of `get_vertex_keys`.
+    #[export]
+    pub fn get_vertex_heights(&self, _owner: TRef<'_, Spatial>) -> Int32Array {
+        Int32Array::from_vec(
+            self.vertex_keys()
+                .iter()
+                .copied()
+                .map(|key| self.terrain.get_height_of_node(key).unwrap_or(0))
+                .collect(),
+        )
+    }
+
+    /// Returns the height of every vertex relative to the terrain, in the order of
+    /// `get_vertex_keys`, e.g. to upload them to a shader.
+    #[export]
+    pub fn get_vertex_world_heights(&self, _owner: TRef<'_, Spatial>) -> Float32Array {
+        Float32Array::from_vec(
+            self.vertex_keys()
+                .iter()
+                .copied()
+                .map(|key| {
+                    self.terrain.get_height_of_node(key).unwrap_or(0) as f32 * self.node_height
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns the connections between vertices as pairs of indices into
+    /// `get_vertex_keys`, the lower index first, every connection once.
+    #[export]
+    pub fn get_vertex_adjacency(&self, _owner: TRef<'_, Spatial>) -> Int32Array {
+        let indices: HashMap<Vector2Di32, i32> = self
+            .vertex_keys()
+            .iter()
+            .copied()
+            .enumerate()
+            .map(|(index, key)| (key, index as i32))
+            .collect();
+        let mut pairs: Vec<(i32, i32)> = self
+            .terrain
+            .connections()
+            .into_iter()
+            .filter_map(|(first, second)| {
+                let (first, second) = (*indices.get(&first)?, *indices.get(&second)?);
+                Some((first.min(second), first.max(second)))
+            })
+            .collect();
+        pairs.sort_unstable();
+        pairs.dedup();
+        Int32Array::from_vec(
+            pairs
+                .into_iter()
+                .flat_map(|(first, second)| vec![first, second])
+                .collect(),
+        )
+    }
+
+    /// Sets the height of a vertex. Connected vertices follow like when raising or lowering.
+    #[export]
+    pub fn set_vertex_height(&mut self, _owner: TRef<'_, Spatial>, x: i64, y: i64, height: i64) {
+        let key = Vector2Di32::new(x as i32, y as i32);
+        if self.terrain.get_height_of_node(key) == Some(height as i32) {
+            return;
+        }
+
+        self.begin_edit();
+        self.set_heights(&[(key, height as i32)]);
+        self.end_edit("vertex_height");
+        self.vertices_dirty = true;
+    }
+
+    #[export]
+    pub fn get_vertex_height(&self, _owner: TRef<'_, Spatial>, x: i64, y: i64) -> i64 {
+        let key = Vector2Di32::new(x as i32, y as i32);
+        self.terrain.get_height_of_node(key).unwrap_or(0) as i64
+    }
+
+    /// Returns the global position of the center of a cell.
+    #[export]
+    pub fn get_cell_position(&self, owner: TRef<'_, Spatial>, x: i64, y: i64) -> Vector3 {
+        let cell = Vector2Di32::new(x as i32, y as i32);
+        let height = self.terrain.get_height_of_node(cell).unwrap_or(0);
+        owner.to_global(Vector3::new(
+            cell.x as f32 * self.hex_radius,
+            height as f32 * self.node_height,
+            cell.y as f32 * self.hex_radius,
+        ))
+    }
+
+    #[export]
+    pub fn get_cell_type(&self, _owner: TRef<'_, Spatial>, x: i64, y: i64) -> i64 {
+        let cell = Vector2Di32::new(x as i32, y as i32);
+        self.terrain.get_terrain_type(cell).unwrap_or(0) as i64
+    }
+
+    #[export]
+    pub fn set_cell_type(&mut self, _owner: TRef<'_, Spatial>, x: i64, y: i64, terrain_type: i64) {
+        let cell = Vector2Di32::new(x as i32, y as i32);
+        if self.hexagon_map.contains_key(&cell) {
+            self.begin_edit();
+            for cell in self.symmetric_keys(&[cell]) {
+                self.terrain.set_terrain_type(cell, terrain_type as i32);
+            }
+            self.end_edit("cell_type");
+            self.update_minimap();
+        }
+    }
+
+    /// Returns the metadata of a cell, an empty dictionary if none was set.
+    #[export]
+    pub fn get_cell_metadata(&self, _owner: TRef<'_, Spatial>, x: i64, y: i64) -> Dictionary {
+        let cell = Vector2Di32::new(x as i32, y as i32);
+        self.cell_metadata
+            .get(&cell)
+            .cloned()
+            .unwrap_or_else(|| Dictionary::new().into_shared())
+    }
+
+    /// Stores arbitrary data with a cell, e.g. for game logic. An empty dictionary removes it.
+    #[export]
+    pub fn set_cell_metadata(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        x: i64,
+        y: i64,
+        metadata: Dictionary,
+    ) {
+        let cell = Vector2Di32::new(x as i32, y as i32);
+        if !self.hexagon_map.contains_key(&cell) {
+            return;
+        }
+        if metadata.is_empty() {
+            self.cell_metadata.remove(&cell);
+        } else {
+            self.cell_metadata.insert(cell, metadata);
+        }
+    }
+
+    /// Checks whether the field is playable. Land cells are cells above the "water" elevation level
+    /// (-1 if there is none) that are not holes, and neighbouring land cells are connected if
+    /// their centers differ by at most one step. The main region is the connected land of the
+    /// first start position, or the largest one without start positions. Returns a report with
+    /// the number of `land_regions`, the cells of the `disconnected_regions` other than the main
+    /// region, the `unreachable_starts` outside of it, the `flat_fraction` of land cells whose
+    /// vertices all have the same height, and whether the map is `valid`: all start positions
+    /// can be reached and at least `min_flat_fraction` of the land is flat. Disconnected islands
+    /// without start positions do not make a map invalid. With `auto_fix`, a ramp is carved
+    /// from every unreachable start position to the main region along the cheapest path first,
+    /// and the report describes the fixed map and the number of `carved_connections`.
+    #[export]
+    pub fn validate_map(
+        &mut self,
+        owner: TRef<'_, Spatial>,
+        start_positions: Vector2Array,
+        min_flat_fraction: f64,
+        auto_fix: bool,
+    ) -> Dictionary {
+        let sea_level =
+            self.get_elevation_level_height(owner, GodotString::from("water"), -1) as i32;
+        let starts = Self::cells_from_array(&start_positions);
+
+        let mut carved = 0;
+        if auto_fix {
+            self.begin_edit();
+            for start in &starts {
+                let regions = self.land_regions(sea_level, &starts);
+                let main = match regions.first() {
+                    None => break,
+                    Some(main) => main,
+                };
+                if main.contains(start) || !self.hexagon_map.contains_key(start) {
+                    continue;
+                }
+                let axial = hex::cell_to_axial(*start);
+                let goal = *main
+                    .iter()
+                    .min_by_key(|cell| hex::axial_distance(axial, hex::cell_to_axial(**cell)))
+                    .unwrap();
+                if let Some(path) = self.carving_path(*start, goal, sea_level) {
+                    self.carve_ramp(&path, sea_level);
+                    carved += 1;
+                }
+            }
+            self.end_edit("validate_map");
+            if carved > 0 {
+                self.vertices_dirty = true;
+            }
+        }
+
+        let regions = self.land_regions(sea_level, &starts);
+        let land: Vec<Vector2Di32> = regions.iter().flatten().copied().collect();
+        let flat = land
+            .iter()
+            .filter(|cell| {
+                let heights = self.heights_of_keys(&self.keys_of_cells(&[**cell]));
+                heights.iter().all(|(_, height)| *height == heights[0].1)
+            })
+            .count();
+        let flat_fraction = flat as f64 / land.len().max(1) as f64;
+        let unreachable: Vec<Vector2Di32> = starts
+            .iter()
+            .copied()
+            .filter(|start| !matches!(regions.first(), Some(main) if main.contains(start)))
+            .collect();
+        let disconnected = VariantArray::new();
+        for region in regions.iter().skip(1) {
+            disconnected.push(Self::cells_to_array(region.clone()));
+        }
+
+        let report = Dictionary::new();
+        report.insert("land_regions", regions.len() as i64);
+        report.insert("disconnected_regions", disconnected.into_shared());
+        report.insert(
+            "valid",
+            unreachable.is_empty() && flat_fraction >= min_flat_fraction,
+        );
+        report.insert("unreachable_starts", Self::cells_to_array(unreachable));
+        report.insert("flat_fraction", flat_fraction);
+        report.insert("carved_connections", carved as i64);
+        report.into_shared()
+    }
+
+    /// Returns everything known about a cell as a dictionary with its `height`, global
+    /// `position`, terrain `type`, the centers of its existing `neighbors`, the `slopes` to them
+    /// as height difference over distance in world units, and its `metadata`. Returns an empty
+    /// dictionary if the cell does not exist.
+    #[export]
+    pub fn get_cell_info(&self, owner: TRef<'_, Spatial>, x: i64, y: i64) -> Dictionary {
+        let info = Dictionary::new();
+        let cell = Vector2Di32::new(x as i32, y as i32);
+        let height = match self.terrain.get_height_of_node(cell) {
+            Some(height) if self.hexagon_map.contains_key(&cell) => height,
+            _ => return info.into_shared(),
+        };
+
+        let mut neighbors = Vec::new();
+        let mut slopes = Vec::new();
+        for neighbor in hex::neighbouring_cells(cell).iter() {
+            if !self.hexagon_map.contains_key(neighbor) {
+                continue;
+            }
+            let neighbor_height = self.terrain.get_height_of_node(*neighbor).unwrap_or(0);
+            let distance = (*neighbor - cell).to_f32().length() * self.hex_radius;
+            let rise = (neighbor_height - height) as f32 * self.node_height;
+            neighbors.push(*neighbor);
+            slopes.push(rise / distance);
+        }
+
+        info.insert("height", height as i64);
+        info.insert("position", self.get_cell_position(owner, x, y));
+        info.insert("type", self.get_cell_type(owner, x, y));
+        info.insert("neighbors", Self::cells_to_array(neighbors));
+        info.insert("slopes", Float32Array::from_vec(slopes));
+        info.insert("metadata", self.get_cell_metadata(owner, x, y));
+        info.into_shared()
+    }
+
+    /// Returns whether all vertices of the cell are locked.
+    #[export]
+    pub fn is_cell_locked(&self, _owner: TRef<'_, Spatial>, x: i64, y: i64) -> bool {
+        let keys = self.keys_of_cells(&[Vector2Di32::new(x as i32, y as i32)]);
+        !keys.is_empty() && keys.iter().all(|key| self.terrain.is_locked(*key))
+    }
+
+    /// Locks or unlocks all vertices of the cells. Generators like `thermal_erosion` do not change
+    /// locked vertices.
+    #[export]
+    pub fn set_cells_locked(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        cells: Vector2Array,
+        locked: bool,
+    ) {
+        for key in self.keys_of_cells(&Self::cells_from_array(&cells)) {
+            self.terrain.set_locked(key, locked);
+        }
+    }
+
+    #[export]
+    pub fn is_cell_hole(&self, _owner: TRef<'_, Spatial>, x: i64, y: i64) -> bool {
+        self.terrain.is_hole(Vector2Di32::new(x as i32, y as i32))
+    }
+
+    /// Marks a cell as hole, which is left out of the mesh and the grid, or fills it again.
+    #[export]
+    pub fn set_cell_hole(&mut self, _owner: TRef<'_, Spatial>, x: i64, y: i64, hole: bool) {
+        let cell = Vector2Di32::new(x as i32, y as i32);
+        if self.hexagon_map.contains_key(&cell) && self.terrain.is_hole(cell) != hole {
+            self.begin_edit();
+            for cell in self.symmetric_keys(&[cell]) {
+                self.terrain.set_hole(cell, hole);
+            }
+            self.end_edit("hole");
+            self.vertices_dirty = true;
+        }
+    }
+
+    #[export]
+    pub fn is_cell_bridge(&self, _owner: TRef<'_, Spatial>, x: i64, y: i64) -> bool {
+        let cell = Vector2Di32::new(x as i32, y as i32);
+        self.terrain.get_deck_height(cell).is_some()
+    }
+
+    /// Returns the height of the bridge deck above the cell, 0 if the cell is no bridge.
+    #[export]
+    pub fn get_cell_bridge_height(&self, _owner: TRef<'_, Spatial>, x: i64, y: i64) -> i64 {
+        let cell = Vector2Di32::new(x as i32, y as i32);
+        self.terrain.get_deck_height(cell).unwrap_or(0) as i64
+    }
+
+    /// Adds a flat bridge deck above the cell as a second surface. The deck has to be above all
+    /// vertices of the cell. Returns whether the bridge was added.
+    #[export]
+    pub fn set_cell_bridge(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        x: i64,
+        y: i64,
+        deck_height: i64,
+    ) -> bool {
+        let cell = Vector2Di32::new(x as i32, y as i32);
+        let keys = self.keys_of_cells(&[cell]);
+        let highest = self
+            .heights_of_keys(&keys)
+            .into_iter()
+            .map(|(_, height)| height)
+            .max();
+        match highest {
+            Some(highest) if (deck_height as i32) > highest => {
+                self.begin_edit();
+                self.terrain.set_deck_height(cell, Some(deck_height as i32));
+                self.end_edit("bridge");
+                self.vertices_dirty = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    #[export]
+    pub fn remove_cell_bridge(&mut self, _owner: TRef<'_, Spatial>, x: i64, y: i64) {
+        let cell = Vector2Di32::new(x as i32, y as i32);
+        if self.terrain.get_deck_height(cell).is_some() {
+            self.begin_edit();
+            self.terrain.set_deck_height(cell, None);
+            self.end_edit("remove_bridge");
+            self.vertices_dirty = true;
+        }
+    }
+
+    /// Returns the feature of the connection between two vertices, 0 if there is none.
+    #[export]
+    pub fn get_edge_feature(
+        &self,
+        _owner: TRef<'_, Spatial>,
+        x1: i64,
+        y1: i64,
+        x2: i64,
+        y2: i64,
+    ) -> i64 {
+        let first = Vector2Di32::new(x1 as i32, y1 as i32);
+        let second = Vector2Di32::new(x2 as i32, y2 as i32);
+        self.terrain.get_edge_feature(first, second) as i64
+    }
+
+    /// Sets the feature of the connection between two vertices, 0 removes it. Returns whether the
+    /// vertices are connected.
+    #[export]
+    pub fn set_edge_feature(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        x1: i64,
+        y1: i64,
+        x2: i64,
+        y2: i64,
+        feature: i64,
+    ) -> bool {
+        let first = Vector2Di32::new(x1 as i32, y1 as i32);
+        let second = Vector2Di32::new(x2 as i32, y2 as i32);
+        self.begin_edit();
+        let connected = self.terrain.set_edge_feature(first, second, feature as i32);
+        self.end_edit("edge_feature");
+        connected
+    }
+
+    /// Reverts the most recent edit. Returns whether there was an edit to undo.
+    #[export]
+    pub fn undo(&mut self, _owner: TRef<'_, Spatial>) -> bool {
+        let edit = match self.history.undo() {
+            None => return false,
+            Some(edit) => edit.reversed("undo"),
+        };
+        self.queue_delta(&edit);
+        edit.apply(&mut self.terrain);
+        self.vertices_dirty = true;
+        true
+    }
+
+    /// Applies the most recently undone edit again. Returns whether there was an edit to redo.
+    #[export]
+    pub fn redo(&mut self, _owner: TRef<'_, Spatial>) -> bool {
+        let edit = match self.history.redo() {
+            None => return false,
+            Some(edit) => Edit {
+                operation: "redo".to_owned(),
+                ..edit.clone()
+            },
+        };
+        self.queue_delta(&edit);
+        edit.apply(&mut self.terrain);
+        self.vertices_dirty = true;
+        true
+    }
+
+    /// Applies a delta from the `edit_replicated` signal of another peer, e.g. received through an
+    /// rpc. Vertices, cells and connections that were changed here since the remote edit started
+    /// conflict: they keep their value, unless `remote_wins`. The new values are set without
+    /// moving connected vertices, as the remote edit already contains every vertex it moved. The
+    /// edit is added to the history but not replicated again. Returns the number of conflicts, or
+    /// -1 if the delta is damaged.
+    #[export]
+    pub fn apply_remote_delta(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        delta: ByteArray,
+        remote_wins: bool,
+    ) -> i64 {
+        let edit = match replication::decode_delta(&delta.read(), Vector2Di32::new) {
+            None => return -1,
+            Some(edit) => edit,
+        };
+        let rule = if remote_wins {
+            Conflict::RemoteWins
+        } else {
+            Conflict::LocalWins
+        };
+        let (resolved, conflicts) = replication::resolve(&edit, &self.terrain, rule);
+
+        let replicate_edits = self.replicate_edits;
+        self.replicate_edits = false;
+        self.begin_edit();
+        resolved.apply(&mut self.terrain);
+        self.end_edit(&edit.operation);
+        self.replicate_edits = replicate_edits;
+        self.vertices_dirty = true;
+        conflicts.len() as i64
+    }
+
+    /// Encodes lockstep commands to send to the other peers of a lockstep game. Every command is
+    /// a dictionary with an `op`, one of "set_heights", "raise", "lower" or "set_terrain_types",
+    /// the vertices as `cells` and for "set_heights" and "set_terrain_types" the `values`.
+    /// Commands with an unknown `op` are left out.
+    #[export]
+    pub fn encode_lockstep_commands(
+        &self,
+        _owner: TRef<'_, Spatial>,
+        commands: VariantArray,
+    ) -> ByteArray {
+        let commands: Vec<Command<Vector2Di32>> = commands
+            .iter()
+            .filter_map(|command| {
+                let command = command.try_to_dictionary()?;
+                let cells = Self::cells_from_array(&command.get("cells").to_vector2_array());
+                let values = command.get("values").to_int32_array().read().to_vec();
+                let pairs = || cells.iter().copied().zip(values.iter().copied()).collect();
+                match command.get("op").to_string().as_str() {
+                    "set_heights" => Some(Command::SetHeights(pairs())),
+                    "raise" => Some(Command::IncreaseHeights(cells.clone())),
+                    "lower" => Some(Command::DecreaseHeights(cells.clone())),
+                    "set_terrain_types" => Some(Command::SetTerrainTypes(pairs())),
+                    _ => None,
+                }
+            })
+            .collect();
+        ByteArray::from_vec(lockstep::encode_commands(&commands, |key| (key.x, key.y)))
+    }
+
+    /// Applies lockstep commands from `encode_lockstep_commands` as one edit. Every peer that
+    /// applies the same commands in the same order gets the same terrain, which
+    /// `get_terrain_checksum` confirms. Returns false if the commands are damaged.
+    #[export]
+    pub fn apply_lockstep_commands(
+        &mut self,
+        _owner: TRef<'_, Spatial>,
+        commands: ByteArray,
+    ) -> bool {
+        let commands = match lockstep::decode_commands(&commands.read(), Vector2Di32::new) {
+            None => return false,
+            Some(commands) => commands,
+        };
+        self.begin_edit();
+        for command in &commands {
+            command.apply(&mut self.terrain);
+        }
+        self.end_edit("lockstep");
+        self.vertices_dirty = true;
+        true
+    }
+
+    /// Returns a checksum of the terrain, which peers of a lockstep game can compare to detect
+    /// desyncs.
+    #[export]
+    pub fn get_terrain_checksum(&self, _owner: TRef<'_, Spatial>) -> i64 {
+        self.terrain.checksum(|key| (key.x, key.y)) as i64
+    }
+
+    /// Returns up to `count` of the most recent edits, newest first. Every edit is a dictionary
+    /// with its `index`, the `op` that was used, the vertices whose height changed as `vertices`,
+    /// their `old_heights` and `new_heights`, the number of vertices whose terrain type, hole mark
+    /// or bridge changed as `layers`, the number of changed edge features as `features` and
+    /// whether it was `undone`.
+    #[export]
+    pub fn get_edit_history(&self, _owner: TRef<'_, Spatial>, count: i64) -> VariantArray {
+        let history = VariantArray::new();
+        let edits = self.history.edits();
+        for (index, edit) in edits.iter().enumerate().rev().take(count.max(0) as usize) {
+            let entry = Dictionary::new();
+            entry.insert("index", index as i64);
+            entry.insert("op", edit.operation.as_str());
+            entry.insert(
+                "vertices",
+                Self::cells_to_array(edit.changes.iter().map(|(key, _, _)| *key).collect()),
+            );
+            entry.insert(
+                "old_heights",
+                Int32Array::from_vec(edit.changes.iter().map(|(_, old, _)| *old).collect()),
+            );
+            entry.insert(
+                "new_heights",
+                Int32Array::from_vec(edit.changes.iter().map(|(_, _, new)| *new).collect()),
+            );
+            entry.insert("layers", edit.layers.len() as i64);
+            entry.insert("features", edit.features.len() as i64);
+            entry.insert("undone", index >= self.history.position());
+            history.push(entry.into_shared());
+        }
+        history.into_shared()
+    }
+
+    /// Undoes or redoes edits until the edit with the given index is the most recent applied one.
+    /// An index of -1 undoes all edits.
+    #[export]
+    pub fn jump_to_edit(&mut self, _owner: TRef<'_, Spatial>, index: i64) {
+        let position = (index + 1).max(0) as usize;
+        while self.history.position() > position {
+            if let Some(edit) = self.history.undo().map(|edit| edit.reversed("undo")) {
+                self.queue_delta(&edit);
+                edit.apply(&mut self.terrain);
+            }
+        }
+        while self.history.position() < position.min(self.history.edits().len()) {
+            if let Some(edit) = self.history.redo().map(|edit| Edit {
+                operation: "redo".to_owned(),
+                ..edit.clone()
+            }) {
+                self.queue_delta(&edit);
+                edit.apply(&mut self.terrain);
+            }
+        }
+        self.vertices_dirty = true;
+    }
+
+    /// Rebuilds the mesh, the grid and the picking body now if the terrain changed since they
+    /// were last built. Edits only mark the terrain as changed and it is rebuilt once at the end of
+    /// the frame, so a burst of edits costs a single rebuild. Returns whether it was rebuilt.
+    #[export]
+    pub fn commit_edits(&mut self, owner: TRef<'_, Spatial>) -> bool {
+        if !self.vertices_dirty {
+            return false;
+        }
+        self.vertices_dirty = false;
+        self.update_vertices(owner);
+        true
+    }
+
+    #[export]
+    pub fn node_increase(&mut self, _owner: TRef<'_, Spatial>, x: i64, y: i64) {
+        self.begin_edit();
+        let clicked_node = Vector2Di32::new(x as i32, y as i32);
+        self.raise_keys(&[clicked_node]);
+        self.end_edit("raise");
+        self.vertices_dirty = true;
+    }
+
+    #[export]
+    pub fn node_decrease(&mut self, _owner: TRef<'_, Spatial>, x: i64, y: i64) {
+        self.begin_edit();
+        let clicked_node = Vector2Di32::new(x as i32, y as i32);
+        self.lower_keys(&[clicked_node]);
+        self.end_edit("lower");
+        self.vertices_dirty = true;
+    }
+
+    #[export]
+    pub fn _ready(&mut self, owner: TRef<'_, Spatial>) {
+        self.add_default_actions();
+        self.reset_terrain();
+        if self.infinite {
+            self.update_chunks(owner);
+        } else if self.lazy_field {
+            let radius = self.lazy_radius.min(self.field_radius);
+            self.generate_cells(&hex::cell_spiral(Vector2Di32::zero(), radius));
+        } else {
+            self.create_hex_nodes();
+        }
+        self.load_terrain_data();
+        self.update_vertices(owner);
+    }
+
+    #[export]
+    pub fn _process(&mut self, owner: TRef<'_, Spatial>, delta: f64) {
+        if self.infinite {
+            self.update_chunks(owner);
+        }
+
+        if (self.min_height, self.max_height) != self.height_limits {
+            self.height_limits = (self.min_height, self.max_height);
+            self.terrain
+                .set_height_limits(self.min_height as i32, self.max_height as i32);
+            self.vertices_dirty = true;
+        }
+
+        self.step_generation(owner);
+        self.step_lazy_field(owner);
+        self.step_autosave(delta);
+        self.step_rebuild(owner);
+        for delta in self.outgoing_deltas.drain(..) {
+            owner.emit_signal(
+                "edit_replicated",
+                &[ByteArray::from_vec(delta).to_variant()],
+            );
+        }
+
+        self.time_since_paint += delta;
+        self.commit_edits(owner);
+        if (self.debug_overlay, self.debug_overlay_vertices) != self.debug_overlay_state {
+            self.update_debug_overlay(owner);
+        }
+        if self.debug_overlay {
+            self.place_debug_labels(owner);
+        }
+        self.update_perf_overlay(owner);
+        self.update_chunk_visibility(owner);
+
+        if self.gpu_displacement != self.gpu_displacement_state {
+            self.gpu_displacement_state = self.gpu_displacement;
+            self.vertices_dirty = true;
+        }
+        if self.chunked_mesh != self.chunked_mesh_state {
+            self.chunked_mesh_state = self.chunked_mesh;
+            self.vertices_dirty = true;
+        }
+        if self.mesh_compression != self.mesh_compression_state {
+            self.mesh_compression_state = self.mesh_compression;
+            self.vertices_dirty = true;
+            self.update_grid(owner);
+        }
+
+        let grid_state = (
+            self.grid_visible,
+            self.grid_color,
+            self.grid_thickness,
+            self.grid_height_offset,
+        );
+        if grid_state != self.grid_state {
+            if (grid_state.2, grid_state.3) != (self.grid_state.2, self.grid_state.3) {
+                self.update_grid(owner);
+            } else {
+                self.apply_grid_appearance(owner);
+            }
+        }
+    }
+
+    /// Replaces the terrain with an empty one that is limited to `min_height..=max_height`.
+    fn reset_terrain(&mut self) {
+        let mut terrain = Terrain::new(1);
+        terrain.set_height_limits(self.min_height as i32, self.max_height as i32);
+        self.height_limits = (self.min_height, self.max_height);
+        self.replace_terrain(terrain);
+    }
+
+    /// Replaces the terrain, which counts as an edit for `terrain_version`.
+    fn replace_terrain(&mut self, terrain: Terrain<Vector2Di32>) {
+        self.version_offset = self
+            .terrain_version()
+            .wrapping_add(1)
+            .wrapping_sub(terrain.edits());
+        self.terrain = terrain;
+    }
+
+    /// Returns a number that grows with every edit of the terrain, also when the terrain is
+    /// replaced, so background edits can tell whether the terrain changed since they started.
+    fn terrain_version(&self) -> u64 {
+        self.version_offset.wrapping_add(self.terrain.edits())
+    }
+
+    /// Adds the input actions that are not defined by the project, bound to their default keys.
+    fn add_default_actions(&self) {
+        let input_map = InputMap::godot_singleton();
+        let actions = [
+            (
+                &self.grow_action,
+                [GlobalConstants::KEY_PLUS, GlobalConstants::KEY_KP_ADD],
+            ),
+            (
+                &self.shrink_action,
+                [GlobalConstants::KEY_MINUS, GlobalConstants::KEY_KP_SUBTRACT],
+            ),
+            (
+                &self.raise_action,
+                [GlobalConstants::KEY_PAGEUP, GlobalConstants::KEY_KP_9],
+            ),
+            (
+                &self.lower_action,
+                [GlobalConstants::KEY_PAGEDOWN, GlobalConstants::KEY_KP_3],
+            ),
+        ];
+
+        for (action, scancodes) in actions.iter() {
+            let action = (*action).clone();
+            if action.is_empty() || input_map.has_action(action.clone()) {
+                continue;
+            }
+            input_map.add_action(action.clone(), 0.5);
+            for scancode in scancodes.iter() {
+                let event = InputEventKey::new();
+                event.set_scancode(*scancode);
+                input_map.action_add_event(action.clone(), event);
+            }
+        }
+    }
+
+    /// Adds a node around which chunks are loaded when the terrain is infinite, or cells are
+    /// created when `lazy_field` is set.
+    #[export]
+    pub fn track_node(&mut self, _owner: TRef<'_, Spatial>, node: Ref<Spatial>) {
+        if !self.tracked_nodes.contains(&node) {
+            self.tracked_nodes.push(node);
+        }
+    }
+
+    #[export]
+    pub fn untrack_node(&mut self, _owner: TRef<'_, Spatial>, node: Ref<Spatial>) {
+        self.tracked_nodes.retain(|tracked| *tracked != node);
+    }
+
+    /// Paints the cells of the minimap that changed since it was last painted, if there is one.
+    fn update_minimap(&mut self) {
+        if self.minimap.is_none() {
+            return;
+        }
+        let colors = self.minimap_colors();
+        if let Some((minimap, texture)) = &mut self.minimap {
+            if minimap.update(colors.into_iter()) {
+                unsafe { texture.assume_safe() }.set_data(Self::minimap_image(minimap));
+            }
+        }
+    }
+
+    /// Returns the minimap color of every cell that is not a hole.
+    fn minimap_colors(&self) -> Vec<(Vector2Di32, [u8; 4])> {
+        let type_colors = self.minimap_type_colors.read();
+        let water_level = self
+            .elevation_levels()
+            .into_iter()
+            .find(|(name, _)| *name == GodotString::from("water"))
+            .map(|(_, height)| height);
+        self.hexagon_map
+            .keys()
+            .filter(|cell| !self.terrain.is_hole(**cell))
+            .map(|cell| {
+                let terrain_type = self.terrain.get_terrain_type(*cell).unwrap_or(0);
+                let type_color = type_colors
+                    .get(terrain_type.max(0) as usize)
+                    .map_or([255; 4], |color| Self::color_bytes(*color));
+                let height = self.terrain.get_height_of_node(*cell).unwrap_or(0);
+                (
+                    *cell,
+                    minimap::cell_color(type_color, height, water_level, self.minimap_heights),
+                )
+            })
+            .collect()
+    }
+
+    fn color_bytes(color: Color) -> [u8; 4] {
+        let channel = |value: f32| (value * 255.0).round() as u8;
+        [
+            channel(color.r),
+            channel(color.g),
+            channel(color.b),
+            channel(color.a),
+        ]
+    }
+
+    fn minimap_image(minimap: &Minimap) -> Ref<Image, Unique> {
+        let image = Image::new();
+        image.create_from_data(
+            minimap.size,
+            minimap.size,
+            false,
+            Image::FORMAT_RGBA8,
+            ByteArray::from_slice(&minimap.pixels),
+        );
+        image
+    }
+
+    /// Converts a rendered heightmap to an image.
+    pub fn heightmap_image(heightmap: &Heightmap) -> Ref<Image, Unique> {
+        let image = Image::new();
+        if heightmap.width > 0 {
+            image.create_from_data(
+                heightmap.width,
+                heightmap.height,
+                false,
+                Image::FORMAT_RGBA8,
+                ByteArray::from_slice(&heightmap.pixels),
+            );
+        }
+        image
+    }
+
+    /// Reads a digital elevation model and samples it at every vertex, see `import_dem`.
+    #[cfg(feature = "dem")]
+    fn dem_heights(
+        &self,
+        path: GodotString,
+        width: i64,
+        height: i64,
+        big_endian: bool,
+        meters_per_step: f64,
+        base_elevation: f64,
+    ) -> Option<Vec<(Vector2Di32, i32)>> {
+        let file = File::new();
+        file.open(path, File::READ).ok()?;
+        let bytes = file.get_buffer(file.get_len()).read().to_vec();
+        file.close();
+        let dem = if width <= 0 {
+            Dem::from_hgt(&bytes)?
+        } else {
+            Dem::from_raw(&bytes, width as usize, height.max(0) as usize, big_endian)?
+        };
+
+        let keys = self.vertex_keys();
+        let min_x = keys.iter().map(|key| key.x).min()? as f32;
+        let max_x = keys.iter().map(|key| key.x).max()? as f32;
+        let min_y = keys.iter().map(|key| key.y).min()? as f32;
+        let max_y = keys.iter().map(|key| key.y).max()? as f32;
+        let meters_per_step = meters_per_step.max(f64::EPSILON) as f32;
+        Some(
+            keys.into_iter()
+                .map(|key| {
+                    let x = (key.x as f32 - min_x) / (max_x - min_x).max(1.0);
+                    let y = (key.y as f32 - min_y) / (max_y - min_y).max(1.0);
+                    let elevation = dem.sample(x, y).unwrap_or(base_elevation as f32);
+                    let steps = (elevation - base_elevation as f32) / meters_per_step;
+                    (key, steps.round() as i32)
+                })
+                .collect(),
+        )
+    }
+
+    #[cfg(not(feature = "dem"))]
+    fn dem_heights(
+        &self,
+        _path: GodotString,
+        _width: i64,
+        _height: i64,
+        _big_endian: bool,
+        _meters_per_step: f64,
+        _base_elevation: f64,
+    ) -> Option<Vec<(Vector2Di32, i32)>> {
+        godot_error!("HexTerrain was built without the \"dem\" feature");
+        None
+    }
+
+    fn vertex_keys(&self) -> std::cell::Ref<'_, [Vector2Di32]> {
+        if self.sorted_vertex_keys.borrow().is_none() {
+            let mut keys: Vec<Vector2Di32> = self.vertex_map.keys().copied().collect();
+            keys.sort_unstable_by_key(|key| (key.y, key.x));
+            *self.sorted_vertex_keys.borrow_mut() = Some(keys);
+        }
+        std::cell::Ref::map(self.sorted_vertex_keys.borrow(), |keys| {
+            keys.as_deref().unwrap_or(&[])
+        })
+    }
+
+    fn cells_to_array(cells: impl IntoIterator<Item = Vector2Di32>) -> Vector2Array {
+        Vector2Array::from_vec(
+            cells
+                .into_iter()
+                .map(|cell| Vector2::new(cell.x as f32, cell.y as f32))
+                .collect(),
+        )
+    }
+
+    fn cells_from_array(cells: &Vector2Array) -> Vec<Vector2Di32> {
+        cells
+            .read()
+            .iter()
+            .map(|cell| Vector2Di32::new(cell.x.round() as i32, cell.y.round() as i32))
+            .collect()
+    }
+
+    /// Returns the keys of the centers and corners of the given existing cells, without duplicates.
+    fn keys_of_cells(&self, cells: &[Vector2Di32]) -> Vec<Vector2Di32> {
+        let mut keys = Vec::new();
+        let mut processed_keys = HashSet::new();
+        for cell in cells {
+            if let Some(hexagon) = self.hexagon_map.get(cell) {
+                for key in hexagon.keys().iter() {
+                    if processed_keys.insert(*key) {
+                        keys.push(*key);
+                    }
+                }
+            }
+        }
+        keys
+    }
+
+    /// Translates mouse, touch and keyboard events to the input used for editing. A second finger
+    /// ends painting with the first one and starts pinching.
+    fn pointer_input(
+        &mut self,
+        owner: TRef<'_, Spatial>,
+        event: TRef<'_, InputEvent>,
+    ) -> Option<PointerInput> {
+        if event.device() == TOUCH_MOUSE_DEVICE {
+            return None;
+        }
+
+        if let Some(event) = event.cast::<InputEventMouseButton>() {
+            let button = event.button_index();
+            if !event.is_pressed() {
+                return Some(PointerInput::Released { button });
+            }
+
+            let mut modifiers = 0;
+            if event.shift() {
+                modifiers |= GlobalConstants::KEY_MASK_SHIFT;
+            }
+            if event.control() {
+                modifiers |= GlobalConstants::KEY_MASK_CTRL;
+            }
+            if event.alt() {
+                modifiers |= GlobalConstants::KEY_MASK_ALT;
+            }
+            if event.metakey() {
+                modifiers |= GlobalConstants::KEY_MASK_META;
+            }
+
+            Some(PointerInput::Pressed {
+                button,
+                modifiers,
+                position: event.position(),
+            })
+        } else if let Some(event) = event.cast::<InputEventMouseMotion>() {
+            Some(PointerInput::Moved {
+                position: event.position(),
+            })
+        } else if let Some(event) = event.cast::<InputEventScreenTouch>() {
+            let (button, modifiers) = if self.touch_lowers {
+                (self.lower_button, self.lower_modifiers)
+            } else {
+                (self.raise_button, self.raise_modifiers)
+            };
+            if !event.is_pressed() {
+                self.touches.remove(&event.index());
+                return if self.touches.is_empty() {
+                    Some(PointerInput::Released { button })
+                } else {
+                    None
+                };
+            }
+
+            self.touches.insert(event.index(), event.position());
+            match self.touches.len() {
+                1 => Some(PointerInput::Pressed {
+                    button,
+                    modifiers,
+                    position: event.position(),
+                }),
+                2 => {
+                    self.pinch_scale = 1.0;
+                    Some(PointerInput::Released { button })
+                }
+                _ => None,
+            }
+        } else if let Some(event) = event.cast::<InputEventScreenDrag>() {
+            let previous = self.touches.insert(event.index(), event.position())?;
+            if self.touches.len() == 1 {
+                return Some(PointerInput::Moved {
+                    position: event.position(),
+                });
+            }
+
+            let other = self
+                .touches
+                .iter()
+                .find(|(index, _)| **index != event.index())
+                .map(|(_, position)| *position)?;
+            let distance_before = (previous - other).length();
+            if distance_before < f32::EPSILON {
+                return None;
+            }
+            Some(PointerInput::Pinched(
+                (event.position() - other).length() / distance_before,
+            ))
+        } else if let Some(event) = event.cast::<InputEventMagnifyGesture>() {
+            Some(PointerInput::Pinched(event.factor() as f32))
+        } else {
+            let raise = event.is_action_pressed(self.raise_action.clone(), false);
+            let lower = event.is_action_pressed(self.lower_action.clone(), false);
+            if !raise && !lower {
+                return None;
+            }
+            let viewport = owner.get_viewport()?;
+            Some(PointerInput::Action {
+                raise,
+                position: unsafe { viewport.assume_safe() }.get_mouse_position(),
+            })
+        }
+    }
+
+    /// Changes `brush_radius` by one whenever the fingers moved apart or together by
+    /// `PINCH_STEP`.
+    fn pinch_brush(&mut self, factor: f32) {
+        self.pinch_scale *= factor;
+        if self.pinch_scale >= PINCH_STEP {
+            self.brush_radius += 1;
+            self.pinch_scale = 1.0;
+        } else if self.pinch_scale <= 1.0 / PINCH_STEP {
+            self.brush_radius = (self.brush_radius - 1).max(0);
+            self.pinch_scale = 1.0;
+        }
+    }
+
+    /// Passes pointer input to the active tool. Returns whether there is an active tool.
+    fn handle_tool_input(&mut self, owner: TRef<'_, Spatial>, input: &PointerInput) -> bool {
+        let tool = self
+            .tools
+            .iter()
+            .find(|(name, _)| *name == self.active_tool)
+            .and_then(|(_, tool)| tool.try_to_object::<Object>());
+        let tool = match tool.and_then(|tool| unsafe { tool.assume_safe_if_sane() }) {
+            None => return false,
+            Some(tool) => tool,
+        };
+
+        match input {
+            PointerInput::Pressed {
+                button, position, ..
+            } => {
+                if let Some(cell) = self.pick_cell(owner, *position) {
+                    self.tool_cell = Some(cell);
+                    Self::call_tool(
+                        tool,
+                        "on_cell_clicked",
+                        &[
+                            owner.to_variant(),
+                            Self::cell_to_variant(cell),
+                            button.to_variant(),
+                        ],
+                    );
+                    if let Some(tree) = owner.get_tree() {
+                        unsafe { tree.assume_safe() }.set_input_as_handled();
+                    }
+                }
+            }
+            PointerInput::Released { .. } => {
+                if self.tool_cell.take().is_some() {
+                    Self::call_tool(tool, "on_commit", &[owner.to_variant()]);
+                }
+            }
+            PointerInput::Moved { position } => {
+                if let Some(previous_cell) = self.tool_cell {
+                    if let Some(cell) = self.pick_cell(owner, *position) {
+                        if cell != previous_cell {
+                            self.tool_cell = Some(cell);
+                            Self::call_tool(
+                                tool,
+                                "on_drag",
+                                &[owner.to_variant(), Self::cell_to_variant(cell)],
+                            );
                         }
-                        thread::sleep(Duration::from_millis(10));
                     }
-                    Err(_) => {
-                        received = false;
+                }
+            }
+            PointerInput::Action { .. } | PointerInput::Pinched(_) => {}
+        }
+        true
+    }
+
+    fn call_tool(tool: TRef<'_, Object>, method: &str, arguments: &[Variant]) {
+        if tool.has_method(method) {
+            unsafe { tool.call_deferred(method, arguments) };
+        }
+    }
+
+    fn cell_to_variant(cell: Vector2Di32) -> Variant {
+        Vector2::new(cell.x as f32, cell.y as f32).to_variant()
+    }
+
+    /// Starts an edit. Changes of the terrain that were not recorded as edit, e.g. by undo, are
+    /// forgotten, so `end_edit` only records the changes after this.
+    fn begin_edit(&mut self) {
+        self.terrain.take_changes();
+    }
+
+    /// Adds the changes since `begin_edit` to the edit history.
+    fn end_edit(&mut self, operation: &str) {
+        self.history.set_capacity(self.history_size.max(0) as usize);
+        let edit = Edit::new(operation, self.terrain.take_changes());
+        self.version_offset = self.version_offset.wrapping_add(1);
+        self.queue_delta(&edit);
+        self.history.push(edit);
+    }
+
+    /// Queues an edit to be sent with the `edit_replicated` signal on the next frame, if
+    /// `replicate_edits` is enabled.
+    fn queue_delta(&mut self, edit: &Edit<Vector2Di32>) {
+        if self.replicate_edits && !edit.is_empty() {
+            self.outgoing_deltas
+                .push(replication::encode_delta(edit, |key| (key.x, key.y)));
+        }
+    }
+
+    /// Copies the heights and terrain types of the vertices of a loaded terrain, without
+    /// propagating them. Vertices outside of the field are ignored.
+    fn apply_saved_terrain(&mut self, saved: &Terrain<Vector2Di32>) {
+        for (key, height) in saved.heights() {
+            if !self.vertex_map.contains_key(&key) {
+                continue;
+            }
+            self.terrain.set_height(key, height);
+            self.terrain
+                .set_terrain_type(key, saved.get_terrain_type(key).unwrap_or(0));
+        }
+    }
+
+    /// Returns the field as a binary map, see `save_map`.
+    fn map_bytes(&self, compression_level: u32) -> Vec<u8> {
+        save::write_chunks(&self.map_chunks(), compression_level)
+    }
+
+    /// Returns the chunks of a binary map of the field. Metadata is stored as JSON text.
+    fn map_chunks(&self) -> Vec<Chunk> {
+        let metadata: Vec<(Vector2Di32, Vec<u8>)> = self
+            .cell_metadata
+            .iter()
+            .map(|(cell, metadata)| (*cell, Self::metadata_to_json(metadata).into_bytes()))
+            .collect();
+        save::terrain_chunks(&self.terrain, &metadata, |key| (key.x, key.y))
+    }
+
+    /// Returns `autosave_directory` as a path of the file system.
+    fn autosave_path(&self) -> PathBuf {
+        let path =
+            ProjectSettings::godot_singleton().globalize_path(self.autosave_directory.clone());
+        PathBuf::from(path.to_string())
+    }
+
+    /// Replaces the field with `terrain_data`. Called when a map is assigned and once the field
+    /// is created, as the property is set before the node is ready.
+    fn load_terrain_data(&mut self) {
+        let resource = match &self.terrain_data {
+            Some(resource) if !self.vertex_map.is_empty() => resource.clone(),
+            _ => return,
+        };
+
+        let data = Instance::<HexMap, Shared>::try_from_base(resource).and_then(|map| {
+            unsafe { map.assume_safe() }
+                .map(|map, _| map.data.read().to_vec())
+                .ok()
+        });
+        let loaded = match data {
+            None => false,
+            Some(data) => self.apply_map_bytes(&data, "terrain_data"),
+        };
+        if !loaded {
+            godot_error!("terrain_data is no valid map");
+        }
+    }
+
+    /// Applies an edit that changes large parts of the terrain. With `background_rebuilds`, the
+    /// edit is applied to a copy on a background thread, while the mesh and all queries keep using
+    /// the current terrain until `step_rebuild` swaps the copy in. Only one edit is computed at a
+    /// time.
+    fn rebuild_terrain<F>(&mut self, operation: &str, edit: F)
+    where
+        F: FnOnce(&mut Terrain<Vector2Di32>) + Send + 'static,
+    {
+        if self.background_rebuilds {
+            // The copy only records the changes of the edit, which `step_rebuild` adds to the
+            // history.
+            self.begin_edit();
+            let version = self.terrain_version();
+            if !self
+                .rebuild
+                .start(operation, version, self.terrain.clone(), edit)
+            {
+                godot_error!("Cannot start {} before the last rebuild is done", operation);
+            }
+            return;
+        }
+        self.begin_edit();
+        edit(&mut self.terrain);
+        self.end_edit(operation);
+        self.vertices_dirty = true;
+    }
+
+    /// Swaps in the terrain of a finished background edit. The edit is dropped if the terrain was
+    /// changed in the meantime, as swapping it in would undo those changes.
+    fn step_rebuild(&mut self, owner: TRef<'_, Spatial>) {
+        if !self.rebuild.is_done() {
+            return;
+        }
+        let (operation, terrain) = match self.rebuild.finish(self.terrain_version()) {
+            Some(rebuild) => rebuild,
+            None => return,
+        };
+        match terrain {
+            Some(terrain) => {
+                self.begin_edit();
+                self.replace_terrain(terrain);
+                self.end_edit(&operation);
+                self.vertices_dirty = true;
+            }
+            None => godot_error!(
+                "Dropped {} as the terrain changed while it was computed",
+                operation
+            ),
+        }
+        owner.emit_signal("rebuild_finished", &[Variant::from_str(&operation)]);
+    }
+
+    /// Reports failed snapshots and writes a new one when `autosave_interval` has passed.
+    fn step_autosave(&mut self, delta: f64) {
+        if !self.autosave.is_writing() {
+            if let Err(error) = self.autosave.finish() {
+                godot_error!("Autosave failed: {}", error);
+            }
+        }
+        if self.autosave.tick(delta, self.autosave_interval) {
+            let directory = self.autosave_path();
+            self.autosave.save(
+                directory,
+                self.autosave_slots.max(1) as usize,
+                self.map_chunks(),
+                AUTOSAVE_COMPRESSION_LEVEL,
+            );
+        }
+    }
+
+    /// Sets the field from a binary map as one edit. Damaged parts of the map are skipped with a
+    /// warning. Returns whether the map could be read.
+    fn apply_map_bytes(&mut self, bytes: &[u8], operation: &str) -> bool {
+        let (saved, report) =
+            match save::read_repaired(bytes, Vector2Di32::new, &Migrations::default()) {
+                None => return false,
+                Some(saved) => saved,
+            };
+        if !report.is_clean() {
+            godot_warn!("Repaired damaged map: {:?}", report);
+        }
+
+        self.begin_edit();
+        self.apply_saved_terrain(&saved.terrain);
+        let features: Vec<(Vector2Di32, Vector2Di32)> = self
+            .terrain
+            .edge_features()
+            .map(|(first, second, _)| (first, second))
+            .collect();
+        for (first, second) in features {
+            self.terrain.set_edge_feature(first, second, 0);
+        }
+        for (first, second, feature) in saved.terrain.edge_features() {
+            self.terrain.set_edge_feature(first, second, feature);
+        }
+        for (key, _) in saved.terrain.heights() {
+            if !self.vertex_map.contains_key(&key) {
+                continue;
+            }
+            self.terrain.set_hole(key, saved.terrain.is_hole(key));
+            self.terrain.set_locked(key, saved.terrain.is_locked(key));
+            self.terrain
+                .set_deck_height(key, saved.terrain.get_deck_height(key));
+            self.cell_metadata.remove(&key);
+        }
+        for (cell, data) in saved.metadata {
+            if !self.hexagon_map.contains_key(&cell) {
+                continue;
+            }
+            let metadata = String::from_utf8(data)
+                .ok()
+                .and_then(|text| Self::metadata_from_json(&text));
+            if let Some(metadata) = metadata {
+                if !metadata.is_empty() {
+                    self.cell_metadata.insert(cell, metadata);
+                }
+            }
+        }
+        self.end_edit(operation);
+        self.vertices_dirty = true;
+        true
+    }
+
+    fn metadata_to_json(metadata: &Dictionary) -> String {
+        JSON::godot_singleton()
+            .print(metadata.to_variant(), "", true)
+            .to_string()
+    }
+
+    fn metadata_from_json(text: &str) -> Option<Dictionary> {
+        let result = JSON::godot_singleton().parse(text)?;
+        unsafe { result.assume_safe() }.result().try_to_dictionary()
+    }
+
+    fn symmetry(&self) -> hex::Symmetry {
+        match self.symmetry_mode {
+            SymmetryMode::None => hex::Symmetry::None,
+            SymmetryMode::Mirror => hex::Symmetry::Mirror(self.symmetry_axis as i32),
+            SymmetryMode::Rotation => hex::Symmetry::Rotation(self.symmetry_folds as i32),
+        }
+    }
+
+    /// Returns the existing keys together with all keys they are mapped to by the symmetry
+    /// settings, without duplicates.
+    fn symmetric_keys(&self, keys: &[Vector2Di32]) -> Vec<Vector2Di32> {
+        let symmetry = self.symmetry();
+        let center = hex::nearest_cell(self.symmetry_center.x, self.symmetry_center.y);
+
+        let mut result = Vec::new();
+        let mut processed_keys = HashSet::new();
+        for key in keys {
+            for key in hex::symmetric_keys(*key, center, symmetry) {
+                if self.vertex_map.contains_key(&key) && processed_keys.insert(key) {
+                    result.push(key);
+                }
+            }
+        }
+        result
+    }
+
+    /// Sets the heights of the vertices and of the vertices they are mapped to by the symmetry
+    /// settings.
+    fn set_heights(&mut self, heights: &[(Vector2Di32, i32)]) {
+        let heights = self.symmetric_heights(heights);
+        self.terrain.set_heights(&heights);
+    }
+
+    /// Returns the heights for the vertices and for the vertices they are mapped to by the
+    /// symmetry settings.
+    fn symmetric_heights(&self, heights: &[(Vector2Di32, i32)]) -> Vec<(Vector2Di32, i32)> {
+        let symmetry = self.symmetry();
+        let center = hex::nearest_cell(self.symmetry_center.x, self.symmetry_center.y);
+
+        let mut symmetric_heights = Vec::new();
+        for (key, height) in heights {
+            for key in hex::symmetric_keys(*key, center, symmetry) {
+                symmetric_heights.push((key, *height));
+            }
+        }
+        symmetric_heights
+    }
+
+    /// Returns the connected regions of land cells for `validate_map`, with the main region first.
+    fn land_regions(&self, sea_level: i32, starts: &[Vector2Di32]) -> Vec<Vec<Vector2Di32>> {
+        let height = |cell: Vector2Di32| self.terrain.get_height_of_node(cell).unwrap_or(0);
+        let mut land: Vec<Vector2Di32> = self
+            .hexagon_map
+            .keys()
+            .copied()
+            .filter(|cell| height(*cell) > sea_level && !self.terrain.is_hole(*cell))
+            .collect();
+        land.sort_unstable_by_key(|cell| (cell.y, cell.x));
+
+        let mut regions = regions::connected_regions(&land, |cell| {
+            hex::neighbouring_cells(cell)
+                .iter()
+                .copied()
+                .filter(|neighbour| (height(*neighbour) - height(cell)).abs() <= 1)
+                .collect()
+        });
+        if let Some(first) = starts.first() {
+            if let Some(index) = regions.iter().position(|region| region.contains(first)) {
+                let main = regions.remove(index);
+                regions.insert(0, main);
+            }
+        }
+        regions
+    }
+
+    /// Returns the cheapest path between two cells for carving a connection. Steps over water
+    /// and steep steps are expensive, holes can not be crossed.
+    fn carving_path(
+        &self,
+        start: Vector2Di32,
+        goal: Vector2Di32,
+        sea_level: i32,
+    ) -> Option<Vec<Vector2Di32>> {
+        path::cheapest_path(start, goal, |from| {
+            let from_height = self.terrain.get_height_of_node(from).unwrap_or(0);
+            hex::neighbouring_cells(from)
+                .iter()
+                .filter(|to| self.hexagon_map.contains_key(*to) && !self.terrain.is_hole(**to))
+                .map(|to| {
+                    let height = self.terrain.get_height_of_node(*to).unwrap_or(0);
+                    let mut cost =
+                        1.0 + self.road_slope_cost as f32 * (height - from_height).abs() as f32;
+                    if height <= sea_level {
+                        cost += self.road_water_cost as f32;
                     }
+                    (*to, cost)
+                })
+                .collect()
+        })
+    }
+
+    /// Sets the cells along the path to a ramp between the heights of its ends, at least one step
+    /// above sea level, so neighbouring cells on the path differ by at most one step where the
+    /// path is long enough.
+    fn carve_ramp(&mut self, path: &[Vector2Di32], sea_level: i32) {
+        let height = |cell: &Vector2Di32| {
+            (self.terrain.get_height_of_node(*cell).unwrap_or(0)).max(sea_level + 1) as f32
+        };
+        let (first, last) = match (path.first(), path.last()) {
+            (Some(first), Some(last)) => (height(first), height(last)),
+            _ => return,
+        };
+        let steps = (path.len() - 1).max(1) as f32;
+        let mut heights = Vec::new();
+        for (index, cell) in path.iter().enumerate() {
+            let ramp = (first + (last - first) * index as f32 / steps).round() as i32;
+            for key in self.keys_of_cells(&[*cell]) {
+                heights.push((key, ramp));
+            }
+        }
+        self.terrain.set_heights(&heights);
+    }
+
+    /// Returns the vertices a road along the cells passes, from center to center through the
+    /// flatter of the two corners that neighbouring cells share.
+    fn road_vertices(&self, cells: &[Vector2Di32]) -> Vec<Vector2Di32> {
+        let mut vertices = Vec::new();
+        for pair in cells.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            vertices.push(from);
+            let to_keys = self.keys_of_cells(&[to]);
+            let corner = self
+                .keys_of_cells(&[from])
+                .into_iter()
+                .filter(|key| *key != from && to_keys.contains(key))
+                .min_by_key(|key| {
+                    let height = self.terrain.get_height_of_node(*key).unwrap_or(0);
+                    let from_height = self.terrain.get_height_of_node(from).unwrap_or(0);
+                    let to_height = self.terrain.get_height_of_node(to).unwrap_or(0);
+                    (height - from_height).abs() + (height - to_height).abs()
+                });
+            if let Some(corner) = corner {
+                vertices.push(corner);
+            }
+        }
+        vertices.extend(cells.last());
+        vertices
+    }
+
+    fn get_metadata_value(&self, cell: Vector2Di32, key: &str) -> Option<Variant> {
+        let metadata = self.cell_metadata.get(&cell)?;
+        if metadata.contains(key) {
+            Some(metadata.get(key))
+        } else {
+            None
+        }
+    }
+
+    /// Sets a metadata entry of a cell, or erases it if there is no value.
+    fn set_metadata_value(&mut self, cell: Vector2Di32, key: &str, value: Option<Variant>) {
+        let metadata = match self.cell_metadata.get(&cell) {
+            None => Dictionary::new(),
+            Some(metadata) => metadata.duplicate(),
+        };
+        match value {
+            Some(value) => metadata.insert(key, value),
+            None => metadata.erase(key),
+        }
+        self.cell_metadata.insert(cell, metadata.into_shared());
+    }
+
+    /// Returns the entries of the biome table. Incomplete entries are ignored.
+    fn biomes(&self) -> Vec<Biome> {
+        let temperatures = self.biome_temperatures.read();
+        let moistures = self.biome_moistures.read();
+        self.biome_types
+            .read()
+            .iter()
+            .zip(temperatures.iter())
+            .zip(moistures.iter())
+            .map(|((terrain_type, temperature), moisture)| Biome {
+                terrain_type: *terrain_type,
+                temperature: *temperature,
+                moisture: *moisture,
+            })
+            .collect()
+    }
+
+    /// Reports the progress of `regenerate_async` and takes over the generated terrain once all
+    /// steps are done. The signals are deferred, so handlers can call back into the terrain.
+    fn step_generation(&mut self, owner: TRef<'_, Spatial>) {
+        let version = self.terrain_version();
+        let job = match &mut self.generation {
+            None => return,
+            Some(job) => job,
+        };
+        let done = job.is_done();
+        for (name, percent) in job.progress() {
+            owner.call_deferred(
+                "emit_signal",
+                &[
+                    "generation_progress".to_variant(),
+                    percent.to_variant(),
+                    name.to_variant(),
+                ],
+            );
+        }
+        if !done {
+            return;
+        }
+        let world = job.finish(version).flatten();
+        self.generation = None;
+
+        let start = Instant::now();
+        match world {
+            Some(world) => {
+                self.begin_edit();
+                self.commit_world(&world);
+                self.replace_terrain(world.terrain);
+                self.end_edit("regenerate");
+                self.vertices_dirty = true;
+            }
+            None => godot_error!("Dropped the generated terrain as the terrain changed meanwhile"),
+        }
+        self.telemetry.record("generation", start.elapsed());
+        owner.call_deferred("emit_signal", &["generation_finished".to_variant()]);
+    }
+
+    /// Returns the settings the generators read.
+    fn generator_settings(&self) -> Settings {
+        let sea_level = self
+            .elevation_levels()
+            .into_iter()
+            .find(|(name, _)| *name == GodotString::from("water"))
+            .map_or(-1, |(_, height)| height);
+        Settings {
+            hex_radius: self.hex_radius,
+            node_height: self.node_height,
+            noise: Fractal {
+                octaves: self.noise_octaves.max(1) as u32,
+                lacunarity: self.noise_lacunarity as f32,
+                gain: self.noise_gain as f32,
+                warp: self.noise_warp as f32,
+            },
+            island_distortion: self.island_distortion as f32,
+            island_depth: self.island_depth as i32,
+            sea_level,
+            river_feature: self.river_feature as i32,
+            wfc_modules: self.wfc_modules(),
+            wfc_module_types: self.wfc_module_types.read().to_vec(),
+            wfc_module_heights: self.wfc_module_heights.read().to_vec(),
+            biomes: self.biomes(),
+            biome_variation: self.biome_variation as f32,
+            biome_height_cooling: self.biome_height_cooling as f32,
+            symmetry: self.symmetry(),
+            symmetry_center: hex::nearest_cell(self.symmetry_center.x, self.symmetry_center.y),
+        }
+    }
+
+    /// Returns the field with the given terrain as the generators see it, with the moisture and
+    /// resources from the metadata of the cells.
+    fn world(&self, terrain: Terrain<Vector2Di32>) -> World {
+        let mut world = World::new(
+            terrain,
+            self.hexagon_map.keys().copied(),
+            self.vertex_map.keys().copied(),
+            self.generator_settings(),
+        );
+        for (cell, metadata) in &self.cell_metadata {
+            if metadata.contains(MOISTURE_KEY) {
+                let moisture = metadata.get(MOISTURE_KEY).to_f64() as f32;
+                world.moisture.insert(*cell, moisture);
+            }
+            if metadata.contains(RESOURCE_KEY) {
+                let resource = metadata.get(RESOURCE_KEY).to_godot_string().to_string();
+                world.resources.insert(*cell, resource);
+            }
+        }
+        world.provinces = self.provinces.clone();
+        world
+    }
+
+    /// Takes over the moisture, resources and provinces of a world. The terrain is left to the
+    /// caller, as it either replaces the current one or is put back.
+    fn commit_world(&mut self, world: &World) {
+        let cells: HashSet<Vector2Di32> = self
+            .cell_metadata
+            .keys()
+            .chain(world.moisture.keys())
+            .chain(world.resources.keys())
+            .copied()
+            .collect();
+        for cell in cells {
+            let moisture = world.moisture.get(&cell).copied();
+            let current = self.get_metadata_value(cell, MOISTURE_KEY);
+            if current.map(|moisture| moisture.to_f64() as f32) != moisture {
+                self.set_metadata_value(
+                    cell,
+                    MOISTURE_KEY,
+                    moisture.map(|moisture| moisture.to_variant()),
+                );
+            }
+            let resource = world.resources.get(&cell);
+            let current = self.get_metadata_value(cell, RESOURCE_KEY);
+            if current
+                .map(|resource| resource.to_godot_string().to_string())
+                .as_ref()
+                != resource
+            {
+                let resource =
+                    resource.map(|resource| GodotString::from(resource.as_str()).to_variant());
+                self.set_metadata_value(cell, RESOURCE_KEY, resource);
+            }
+        }
+        self.provinces = world.provinces.clone();
+    }
+
+    /// Runs generators on the whole field and takes over their results. `operation` names the
+    /// edit in the history, None for generators that are not undone.
+    fn generate<R>(
+        &mut self,
+        operation: Option<&str>,
+        generator: impl FnOnce(&mut World) -> R,
+    ) -> R {
+        if operation.is_some() {
+            self.begin_edit();
+        }
+        let terrain = mem::replace(&mut self.terrain, Terrain::new(1));
+        let mut world = self.world(terrain);
+        let edits = world.terrain.edits();
+        let result = generator(&mut world);
+        self.commit_world(&world);
+        if world.terrain.edits() != edits {
+            self.vertices_dirty = true;
+        }
+        // The terrain was only lent to the world, so it is put back as it is.
+        self.terrain = world.terrain;
+        if let Some(operation) = operation {
+            self.end_edit(operation);
+        }
+        result
+    }
+
+    /// Returns the modules for wave function collapse, with the rules of `wfc_rules` in both
+    /// directions. Only modules with a type, height and weight are used, rules with other modules
+    /// or unknown directions are ignored.
+    fn wfc_modules(&self) -> Vec<Module> {
+        let count = self
+            .wfc_module_types
+            .len()
+            .min(self.wfc_module_heights.len())
+            .min(self.wfc_module_weights.len()) as usize;
+        let mut modules: Vec<Module> = self.wfc_module_weights.read()[..count]
+            .iter()
+            .map(|weight| Module {
+                weight: *weight,
+                allowed: vec![Vec::new(); 6],
+            })
+            .collect();
+        for rule in self.wfc_rules.read().chunks_exact(3) {
+            let (module, direction, other) = (rule[0] as usize, rule[1], rule[2] as usize);
+            if module >= count || other >= count || !(-1..6).contains(&direction) {
+                continue;
+            }
+            let directions = if direction < 0 {
+                0..6
+            } else {
+                direction..direction + 1
+            };
+            for direction in directions {
+                let direction = direction as usize;
+                modules[module].allowed[direction].push(other);
+                modules[other].allowed[(direction + 3) % 6].push(module);
+            }
+        }
+        modules
+    }
+
+    /// Returns the named elevation levels. Names without a height are ignored.
+    fn elevation_levels(&self) -> Vec<(GodotString, i32)> {
+        self.elevation_level_names
+            .read()
+            .iter()
+            .cloned()
+            .zip(self.elevation_level_heights.read().iter().copied())
+            .collect()
+    }
+
+    /// Raises the vertices by one step, or to the next elevation level if
+    /// `snap_to_elevation_levels` is set.
+    fn raise_keys(&mut self, keys: &[Vector2Di32]) {
+        if self.snap_to_elevation_levels {
+            let levels = self.elevation_level_heights.read().to_vec();
+            let heights: Vec<(Vector2Di32, i32)> = self
+                .heights_of_keys(keys)
+                .into_iter()
+                .filter_map(|(key, height)| {
+                    tools::level_above(&levels, height).map(|level| (key, level))
+                })
+                .collect();
+            self.set_heights(&heights);
+        } else {
+            self.terrain.increase_heights(&self.symmetric_keys(keys));
+        }
+    }
+
+    /// Lowers the vertices by one step, or to the previous elevation level if
+    /// `snap_to_elevation_levels` is set.
+    fn lower_keys(&mut self, keys: &[Vector2Di32]) {
+        if self.snap_to_elevation_levels {
+            let levels = self.elevation_level_heights.read().to_vec();
+            let heights: Vec<(Vector2Di32, i32)> = self
+                .heights_of_keys(keys)
+                .into_iter()
+                .filter_map(|(key, height)| {
+                    tools::level_below(&levels, height).map(|level| (key, level))
+                })
+                .collect();
+            self.set_heights(&heights);
+        } else {
+            self.terrain.decrease_heights(&self.symmetric_keys(keys));
+        }
+    }
+
+    /// Returns the vertices of all cells within `radius` cells of the center, with the strength of
+    /// a brush at their position.
+    fn brush_strengths(&self, center: Vector2Di32, radius: i64) -> Vec<(Vector2Di32, f32)> {
+        let radius = radius.max(0) as u32;
+        let cells = hex::cells_in_range(center, radius);
+        let brush_radius = (radius + 1) as f32 * CELL_DISTANCE;
+
+        self.keys_of_cells(&cells)
+            .into_iter()
+            .map(|key| {
+                let offset = key - center;
+                let distance = ((offset.x * offset.x + offset.y * offset.y) as f32).sqrt();
+                (key, tools::falloff(distance, brush_radius))
+            })
+            .collect()
+    }
+
+    fn heights_of_keys(&self, keys: &[Vector2Di32]) -> Vec<(Vector2Di32, i32)> {
+        keys.iter()
+            .filter_map(|key| {
+                self.terrain
+                    .get_height_of_node(*key)
+                    .map(|height| (*key, height))
+            })
+            .collect()
+    }
+
+    /// Captures heights, terrain types, holes, bridges and edge features of the existing cells,
+    /// relative to the anchor.
+    fn capture_region(&self, cells: &[Vector2Di32], anchor: Vector2Di32) -> Region {
+        let keys = self.keys_of_cells(cells);
+        let key_set: HashSet<Vector2Di32> = keys.iter().copied().collect();
+
+        Region {
+            heights: self
+                .heights_of_keys(&keys)
+                .into_iter()
+                .map(|(key, height)| (key - anchor, height))
+                .collect(),
+            terrain_types: cells
+                .iter()
+                .filter(|cell| self.hexagon_map.contains_key(cell))
+                .filter_map(|cell| {
+                    self.terrain
+                        .get_terrain_type(*cell)
+                        .map(|terrain_type| (*cell - anchor, terrain_type))
+                })
+                .collect(),
+            holes: cells
+                .iter()
+                .filter(|cell| self.hexagon_map.contains_key(cell))
+                .map(|cell| (*cell - anchor, self.terrain.is_hole(*cell)))
+                .collect(),
+            deck_heights: cells
+                .iter()
+                .filter(|cell| self.hexagon_map.contains_key(cell))
+                .map(|cell| (*cell - anchor, self.terrain.get_deck_height(*cell)))
+                .collect(),
+            edge_features: self
+                .terrain
+                .edge_features()
+                .filter(|(first, second, _)| key_set.contains(first) && key_set.contains(second))
+                .map(|(first, second, feature)| (first - anchor, second - anchor, feature))
+                .collect(),
+        }
+    }
+
+    /// Applies a region at absolute positions. Surrounding vertices are raised or lowered as if
+    /// the heights were edited step by step.
+    fn apply_region(&mut self, region: &Region) {
+        self.terrain.set_heights(&region.heights);
+        for (cell, terrain_type) in &region.terrain_types {
+            self.terrain.set_terrain_type(*cell, *terrain_type);
+        }
+        for (cell, hole) in &region.holes {
+            self.terrain.set_hole(*cell, *hole);
+        }
+        for (cell, deck_height) in &region.deck_heights {
+            self.terrain.set_deck_height(*cell, *deck_height);
+        }
+        for (first, second, feature) in &region.edge_features {
+            self.terrain.set_edge_feature(*first, *second, *feature);
+        }
+    }
+
+    /// Replaces the cells by a transformed copy of them. The transformation is applied to the
+    /// offsets of the cells to the first cell. Cells the copy does not cover are reset to the
+    /// state of newly created cells, so the region is moved instead of duplicated.
+    fn transform_region(
+        &mut self,
+        cells: &[Vector2Di32],
+        transform: impl Fn(Vector2Di32) -> Vector2Di32,
+    ) {
+        let pivot = match cells.first() {
+            None => return,
+            Some(pivot) => *pivot,
+        };
+        let region = self.capture_region(cells, pivot);
+
+        for (first, second, _) in &region.edge_features {
+            self.terrain
+                .set_edge_feature(*first + pivot, *second + pivot, 0);
+        }
+        let targets: HashSet<Vector2Di32> = cells
+            .iter()
+            .map(|cell| pivot + transform(*cell - pivot))
+            .collect();
+        let uncovered: Vec<Vector2Di32> = cells
+            .iter()
+            .copied()
+            .filter(|cell| !targets.contains(cell) && self.hexagon_map.contains_key(cell))
+            .collect();
+        self.apply_region(&Region {
+            heights: self
+                .keys_of_cells(&uncovered)
+                .into_iter()
+                .map(|key| (key, 0))
+                .collect(),
+            terrain_types: uncovered.iter().map(|cell| (*cell, 0)).collect(),
+            holes: uncovered.iter().map(|cell| (*cell, false)).collect(),
+            deck_heights: uncovered.iter().map(|cell| (*cell, None)).collect(),
+            edge_features: Vec::new(),
+        });
+        self.apply_region(&region.transformed(|offset| pivot + transform(offset)));
+    }
+
+    /// Loads the chunks around the tracked nodes and unloads the ones that are too far away.
+    fn update_chunks(&mut self, owner: TRef<'_, Spatial>) {
+        let chunk_size = self.chunk_size.max(1) as i32;
+        let unload_radius = self.chunk_unload_radius.max(self.chunk_load_radius) as i32;
+
+        self.tracked_nodes
+            .retain(|node| unsafe { node.assume_safe_if_sane() }.is_some());
+
+        let mut tracked_chunks = Vec::new();
+        for node in &self.tracked_nodes {
+            let node = unsafe { node.assume_safe() };
+            let position = owner.to_local(node.global_transform().origin);
+            let cell =
+                hex::nearest_cell(position.x / self.hex_radius, position.z / self.hex_radius);
+            tracked_chunks.push(hex::chunk_of_cell(cell, chunk_size));
+        }
+
+        let mut wanted_chunks = HashSet::new();
+        for chunk in &tracked_chunks {
+            wanted_chunks.extend(hex::axial_range(*chunk, self.chunk_load_radius));
+        }
+        for chunk in &self.loaded_chunks {
+            if tracked_chunks
+                .iter()
+                .any(|tracked| hex::axial_distance(*tracked, *chunk) <= unload_radius)
+            {
+                wanted_chunks.insert(*chunk);
+            }
+        }
+
+        if wanted_chunks == self.loaded_chunks {
+            return;
+        }
+
+        self.store_chunks();
+        let previous_chunks = std::mem::replace(&mut self.loaded_chunks, wanted_chunks);
+        if self.write_streamed_chunks().is_some() {
+            let loaded_chunks = &self.loaded_chunks;
+            self.chunk_regions
+                .retain(|chunk, _| loaded_chunks.contains(chunk));
+        }
+        self.load_chunks(&previous_chunks);
+        self.vertices_dirty = true;
+    }
+
+    /// Stores the data of all loaded chunks, so it can be restored when a chunk is loaded again.
+    fn store_chunks(&mut self) {
+        let chunk_size = self.chunk_size.max(1) as i32;
+        for chunk in &self.loaded_chunks {
+            let cells = hex::cells_of_chunk(*chunk, chunk_size);
+            let region = self.capture_region(&cells, Vector2Di32::zero());
+            self.chunk_regions.insert(*chunk, region);
+        }
+    }
+
+    /// Recreates the terrain from the loaded chunks.
+    fn load_chunks(&mut self, previous_chunks: &HashSet<Vector2Di32>) {
+        let chunk_size = self.chunk_size.max(1) as i32;
+        let mut buffers = HexagonBuffers::with_capacity(
+            self.loaded_chunks.len() * (chunk_size * chunk_size) as usize,
+        );
+
+        for chunk in &self.loaded_chunks {
+            for cell in hex::cells_of_chunk(*chunk, chunk_size) {
+                Self::add_hexagon_data(cell, self.hex_radius, &mut buffers);
+            }
+        }
+
+        self.reset_terrain();
+        self.connect_nodes(&buffers.nodes);
+
+        // Corners shared with a chunk that stayed loaded have to keep their current height, so
+        // the data of chunks that were loaded before is restored last.
+        let (new_chunks, kept_chunks): (Vec<_>, Vec<_>) = self
+            .loaded_chunks
+            .iter()
+            .copied()
+            .partition(|chunk| !previous_chunks.contains(chunk));
+        for chunk in new_chunks.into_iter().chain(kept_chunks) {
+            let region = match self.chunk_regions.get(&chunk) {
+                Some(region) => Some(region.clone()),
+                None => self.read_streamed_chunk(chunk),
+            };
+            if let Some(region) = region {
+                self.restore_region(&region);
+            }
+        }
+
+        self.nodes = buffers.nodes;
+        self.hexagon_map = buffers.hexagons;
+        self.vertex_map = buffers.vertices;
+        self.sorted_vertex_keys = RefCell::default();
+        self.columns = VertexColumns::default();
+    }
+
+    /// Returns the store of the chunks in `chunk_directory`, None if it is empty or the store
+    /// cannot be opened.
+    fn chunk_store(&mut self) -> Option<&mut ChunkStore> {
+        if self.chunk_directory.is_empty() {
+            self.chunk_store = None;
+            return None;
+        }
+        let directory = PathBuf::from(
+            ProjectSettings::godot_singleton()
+                .globalize_path(self.chunk_directory.clone())
+                .to_string(),
+        );
+        if self.chunk_store.as_ref().map(ChunkStore::directory) != Some(directory.as_path()) {
+            match ChunkStore::open(directory, CHUNK_COMPRESSION_LEVEL) {
+                Ok(store) => self.chunk_store = Some(store),
+                Err(error) => {
+                    godot_error!("Could not open chunk_directory: {}", error);
+                    self.chunk_store = None;
+                }
+            }
+        }
+        self.chunk_store.as_mut()
+    }
+
+    /// Writes the stored chunks that changed since they were last written to `chunk_directory`.
+    /// Returns the number of chunks written, None if there is no `chunk_directory` or writing
+    /// failed.
+    fn write_streamed_chunks(&mut self) -> Option<usize> {
+        let map_chunks: Vec<((i32, i32), Vec<Chunk>)> = self
+            .chunk_regions
+            .iter()
+            .map(|(chunk, region)| ((chunk.x, chunk.y), region.to_chunks()))
+            .collect();
+        match self.chunk_store()?.store(map_chunks) {
+            Ok(written) => Some(written),
+            Err(error) => {
+                godot_error!("Could not write chunks: {}", error);
+                None
+            }
+        }
+    }
+
+    /// Reads a chunk from `chunk_directory`. Returns None if it was never written there.
+    fn read_streamed_chunk(&mut self, chunk: Vector2Di32) -> Option<Region> {
+        match self.chunk_store()?.load((chunk.x, chunk.y)) {
+            Ok(chunks) => chunks.and_then(|chunks| Region::from_chunks(&chunks)),
+            Err(error) => {
+                godot_error!("Could not read chunk {}, {}: {}", chunk.x, chunk.y, error);
+                None
+            }
+        }
+    }
+
+    /// Recreates the field for the current `field_radius`. Cells that exist before and after keep
+    /// their data, new cells start flat at height 0.
+    fn resize_field(&mut self) {
+        let cells: Vec<Vector2Di32> = self.hexagon_map.keys().copied().collect();
+        let region = self.capture_region(&cells, Vector2Di32::zero());
+
+        self.reset_terrain();
+        if self.lazy_field {
+            self.nodes.clear();
+            self.hexagon_map.clear();
+            self.vertex_map = ChunkMap::default();
+            self.sorted_vertex_keys = RefCell::default();
+            self.generate_cells(&cells);
+        } else {
+            self.create_hex_nodes();
+        }
+        self.restore_region(&region);
+    }
+
+    /// Creates the cells of the field that are wanted by `lazy_field`, at most
+    /// `lazy_cells_per_frame` of them, nearest first.
+    fn step_lazy_field(&mut self, owner: TRef<'_, Spatial>) {
+        if !self.lazy_field || self.infinite {
+            return;
+        }
+        let mut cells = self.wanted_cells(owner);
+        if cells.is_empty() {
+            return;
+        }
+        cells.truncate(self.lazy_cells_per_frame.max(1) as usize);
+
+        let start = Instant::now();
+        let created = self.generate_cells(&cells);
+        self.telemetry.record("lazy cells", start.elapsed());
+        self.telemetry.count("cells created", created as u64);
+        if created > 0 {
+            self.vertices_dirty = true;
+        }
+    }
+
+    /// Returns the missing cells of the field within `lazy_radius` of the tracked nodes, or of the
+    /// center if no node is tracked, nearest first.
+    fn wanted_cells(&mut self, owner: TRef<'_, Spatial>) -> Vec<Vector2Di32> {
+        self.tracked_nodes
+            .retain(|node| unsafe { node.assume_safe_if_sane() }.is_some());
+        let mut centers: Vec<Vector2Di32> = self
+            .tracked_nodes
+            .iter()
+            .map(|node| {
+                let position =
+                    owner.to_local(unsafe { node.assume_safe() }.global_transform().origin);
+                hex::nearest_cell(position.x / self.hex_radius, position.z / self.hex_radius)
+            })
+            .collect();
+        if centers.is_empty() {
+            centers.push(Vector2Di32::zero());
+        }
+
+        let mut cells: Vec<(i32, Vector2Di32)> = Vec::new();
+        for center in centers {
+            let axial = hex::cell_to_axial(center);
+            for cell in hex::cell_spiral(center, self.lazy_radius) {
+                if self.in_field(cell) && !self.hexagon_map.contains_key(&cell) {
+                    let distance = hex::axial_distance(axial, hex::cell_to_axial(cell));
+                    cells.push((distance, cell));
+                }
+            }
+        }
+        cells.sort_by_key(|(distance, cell)| (*distance, cell.x, cell.y));
+        let mut seen = HashSet::new();
+        cells
+            .into_iter()
+            .map(|(_, cell)| cell)
+            .filter(|cell| seen.insert(*cell))
+            .collect()
+    }
+
+    /// Returns whether the cell is within `field_radius` of the center.
+    fn in_field(&self, cell: Vector2Di32) -> bool {
+        hex::axial_distance(hex::cell_to_axial(cell), Vector2Di32::zero())
+            <= self.field_radius as i32
+    }
+
+    /// Creates the given cells of the field that do not exist yet and connects their vertices with
+    /// the existing ones. Corners shared with existing cells keep their height, other new vertices
+    /// start at height 0. Returns the number of created cells.
+    fn generate_cells(&mut self, cells: &[Vector2Di32]) -> usize {
+        if self.infinite {
+            return 0;
+        }
+        let mut buffers = HexagonBuffers::with_capacity(cells.len());
+        for cell in cells {
+            if self.in_field(*cell)
+                && !self.hexagon_map.contains_key(cell)
+                && !buffers.hexagons.contains_key(cell)
+            {
+                Self::add_hexagon_data(*cell, self.hex_radius, &mut buffers);
+            }
+        }
+        let created = buffers.hexagons.len();
+        if created == 0 {
+            return 0;
+        }
+        self.connect_nodes(&buffers.nodes);
+        self.nodes.extend(buffers.nodes);
+        self.sorted_vertex_keys = RefCell::default();
+        self.columns = VertexColumns::default();
+        self.hexagon_map.extend(buffers.hexagons);
+        self.vertex_map.extend(
+            buffers
+                .vertices
+                .iter()
+                .map(|(key, position)| (*key, *position)),
+        );
+        created
+    }
+
+    /// Creates the given cells if `lazy_field` has not created them yet. Returns the number of
+    /// created cells.
+    #[export]
+    pub fn create_cells(&mut self, _owner: TRef<'_, Spatial>, cells: Vector2Array) -> i64 {
+        let created = self.generate_cells(&Self::cells_from_array(&cells));
+        if created > 0 {
+            self.vertices_dirty = true;
+        }
+        created as i64
+    }
+
+    /// Connects the nodes of every triangle with each other.
+    fn connect_nodes(&mut self, nodes: &[TerrainNode]) {
+        let start = Instant::now();
+        for triangle in nodes.chunks(3) {
+            for (first, second) in [(0, 1), (0, 2), (1, 2)].iter() {
+                self.terrain
+                    .add_connected_nodes(triangle[*first].key, triangle[*second].key);
+            }
+        }
+        self.telemetry.record("graph", start.elapsed());
+    }
+
+    /// Restores a region at absolute positions without changing surrounding vertices. Data of
+    /// vertices and cells that do not exist is dropped.
+    fn restore_region(&mut self, region: &Region) {
+        for (key, height) in &region.heights {
+            self.terrain.set_height(*key, *height);
+        }
+        for (cell, terrain_type) in &region.terrain_types {
+            self.terrain.set_terrain_type(*cell, *terrain_type);
+        }
+        for (cell, hole) in &region.holes {
+            self.terrain.set_hole(*cell, *hole);
+        }
+        for (cell, deck_height) in &region.deck_heights {
+            self.terrain.set_deck_height(*cell, *deck_height);
+        }
+        for (first, second, feature) in &region.edge_features {
+            self.terrain.set_edge_feature(*first, *second, *feature);
+        }
+    }
+
+    /// Recreates the labels of the debug overlay. Hexagons are labeled with their coordinates, or
+    /// every vertex with its key and height if `debug_overlay_vertices` is set.
+    fn update_debug_overlay(&mut self, owner: TRef<'_, Spatial>) {
+        for (_, label) in self.debug_labels.drain(..) {
+            if let Some(label) = unsafe { label.assume_safe_if_sane() } {
+                label.queue_free();
+            }
+        }
+        self.debug_overlay_state = (self.debug_overlay, self.debug_overlay_vertices);
+
+        if !self.debug_overlay {
+            return;
+        }
+
+        let overlay = owner
+            .get_node("DebugOverlay")
+            .and_then(|node| unsafe { node.assume_safe_if_sane() });
+        let overlay: TRef<'_, GodotNode> = match overlay {
+            Some(overlay) => overlay,
+            None => {
+                let overlay = CanvasLayer::new();
+                overlay.set_name("DebugOverlay");
+                let overlay = unsafe { overlay.into_shared().assume_safe() };
+                owner.add_child(overlay, false);
+                overlay.upcast::<GodotNode>()
+            }
+        };
+
+        let label_height = 0.1;
+        let mut labels = Vec::new();
+        if self.debug_overlay_vertices {
+            for key in self.vertex_map.keys() {
+                let height = self.terrain.get_height_of_node(*key).unwrap_or(0);
+                labels.push((*key, format!("{}, {}: {}", key.x, key.y, height)));
+            }
+        } else {
+            for key in self.hexagon_map.keys() {
+                labels.push((*key, format!("{}, {}", key.x, key.y)));
+            }
+        }
+
+        for (key, text) in labels {
+            let vertex = self.vertex_map[&key];
+            let height = self.terrain.get_height_of_node(key).unwrap_or(0);
+            let position = Vector3::new(
+                vertex.x,
+                height as f32 * self.node_height + label_height,
+                vertex.y,
+            );
+
+            let label = Label::new();
+            label.set_text(text);
+            let label = unsafe { label.into_shared().assume_safe() };
+            overlay.add_child(label, false);
+            self.debug_labels.push((position, label.claim()));
+        }
+
+        self.place_debug_labels(owner);
+    }
+
+    /// Shows the summary of the telemetry in the corner of the screen while `perf_overlay` is set.
+    fn update_perf_overlay(&mut self, owner: TRef<'_, Spatial>) {
+        let label = self
+            .perf_label
+            .and_then(|label| unsafe { label.assume_safe_if_sane() });
+        match (self.perf_overlay, label) {
+            (true, Some(label)) => label.set_text(self.telemetry.summary()),
+            (true, None) => {
+                let overlay = CanvasLayer::new();
+                overlay.set_name("PerfOverlay");
+                let label = Label::new();
+                label.set_text(self.telemetry.summary());
+                let label = label.into_shared();
+                overlay.add_child(label, false);
+                owner.add_child(overlay, false);
+                self.perf_label = Some(label);
+            }
+            (false, Some(label)) => {
+                if let Some(overlay) = label.get_parent() {
+                    unsafe { overlay.assume_safe() }.queue_free();
                 }
+                self.perf_label = None;
             }
+            (false, None) => self.perf_label = None,
+        }
+    }
+
+    /// Returns the telemetry as a dictionary with an entry per kind of work, a dictionary with
+    /// `count` and `last_ms`, `average_ms`, `max_ms` and `total_ms`, and an entry per counter.
+    /// The `terrain` entry has the `propagations`, `nodes_visited` and `heights_changed` of the
+    /// terrain, the `mesh` entry has `rebuilds`, how often the mesh was built, and
+    /// `triangles_rebuilt`, the triangles of all these builds.
+    #[export]
+    pub fn get_perf_stats(&self, _owner: TRef<'_, Spatial>) -> Dictionary {
+        let milliseconds = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let stats = Dictionary::new();
+        for (name, timing) in self.telemetry.timings() {
+            let entry = Dictionary::new();
+            entry.insert("count", timing.count as i64);
+            entry.insert("last_ms", milliseconds(timing.last));
+            entry.insert("average_ms", milliseconds(timing.average()));
+            entry.insert("max_ms", milliseconds(timing.max));
+            entry.insert("total_ms", milliseconds(timing.total));
+            stats.insert(*name, entry.into_shared());
+        }
+        for (name, count) in self.telemetry.counters() {
+            stats.insert(*name, *count as i64);
+        }
+        let terrain_stats = self.terrain.stats();
+        let terrain = Dictionary::new();
+        terrain.insert("propagations", terrain_stats.propagations as i64);
+        terrain.insert("nodes_visited", terrain_stats.nodes_visited as i64);
+        terrain.insert("heights_changed", terrain_stats.heights_changed as i64);
+        stats.insert("terrain", terrain.into_shared());
+        let mesh = Dictionary::new();
+        let rebuilds = self
+            .telemetry
+            .timings()
+            .iter()
+            .find(|(name, _)| *name == "mesh")
+            .map_or(0, |(_, timing)| timing.count);
+        mesh.insert("rebuilds", rebuilds as i64);
+        mesh.insert(
+            "triangles_rebuilt",
+            self.telemetry.counter("triangles rebuilt") as i64,
+        );
+        stats.insert("mesh", mesh.into_shared());
+        stats.into_shared()
+    }
 
-            received = true;
+    #[export]
+    pub fn reset_perf_stats(&mut self, _owner: TRef<'_, Spatial>) {
+        self.telemetry.reset();
+        self.terrain.reset_stats();
+    }
 
-            while received {
-                match vertex_data_receiver.try_recv() {
-                    Ok(mut vertex_data) => {
-                        hexagons.insert(vertex_data.0.center, vertex_data.0);
-                        vertices_data.extend(vertex_data.1);
-                        nodes_data.append(&mut vertex_data.2);
-                        finished_threads += 1;
-                    }
-                    Err(_) => {
-                        received = false;
-                    }
-                }
-                thread::sleep(Duration::from_millis(10));
+    /// Moves the labels of the debug overlay to the screen position of their vertices.
+    fn place_debug_labels(&self, owner: TRef<'_, Spatial>) {
+        let camera = owner
+            .get_viewport()
+            .and_then(|viewport| unsafe { viewport.assume_safe() }.get_camera())
+            .and_then(|camera| unsafe { camera.assume_safe_if_sane() });
+        let camera: TRef<'_, Camera> = match camera {
+            None => return,
+            Some(camera) => camera,
+        };
+
+        for (position, label) in &self.debug_labels {
+            let label = unsafe { label.assume_safe() };
+            let position = owner.to_global(*position);
+            if camera.is_position_behind(position) {
+                label.hide();
+            } else {
+                label.show();
+                label.set_position(camera.unproject_position(position), false);
+            }
+        }
+    }
+
+    /// Drops the instance of `indicator_scene` that marks the vertex under the mouse cursor, so
+    /// the scene is loaded again when the cursor is next over the terrain, e.g. after it was
+    /// changed on disk. Changes of `indicator_scene` are picked up without calling this.
+    #[export]
+    pub fn invalidate_indicator_template(&mut self, _owner: TRef<'_, Spatial>) {
+        if let Some((_, indicator)) = self.indicator.take() {
+            if let Some(indicator) = unsafe { indicator.assume_safe_if_sane() } {
+                indicator.queue_free();
             }
-            //godot_print!("{}-{}", threads.len(), finished_threads);
-            thread::sleep(Duration::from_millis(10));
         }
-        self.nodes = nodes_data;
-        self.hexagon_map = hexagons;
-        self.vertex_map = vertices_data;
     }
 
-    fn create_hex_vertices(
-        center: Vector2Di32,
-        radius: u32,
-        hex_radius: f32,
-        vertex_data_sender: Sender<HexagonData>,
-        node_sender: Sender<NodeData>,
+    /// Handles input on the picking body: clicking raises the vertex closest to the click like
+    /// `node_increase`, or lowers it like `node_decrease` while shift is held, and moving the
+    /// mouse places the instance of `indicator_scene` on that vertex.
+    #[export]
+    pub fn _on_picking_body_input_event(
+        &mut self,
+        owner: TRef<'_, Spatial>,
+        _camera: Variant,
+        event: Variant,
+        click_position: Vector3,
+        _click_normal: Vector3,
+        _shape_index: i64,
     ) {
-        let left = center + LEFT;
-        let top_left = center + TOP_LEFT;
-        let top_right = center + TOP_RIGHT;
-        let right = center + RIGHT;
-        let bottom_right = center + BOTTOM_RIGHT;
-        let bottom_left = center + BOTTOM_LEFT;
-
-        let mut hexagon = Hexagon::new(center);
-        hexagon.left = left;
-        hexagon.top_left = top_left;
-        hexagon.top_right = top_right;
-        hexagon.right = right;
-        hexagon.bottom_right = bottom_right;
-        hexagon.bottom_left = bottom_left;
-
-        if radius > 0 {
-            node_sender.send((left + TOP_LEFT, radius - 1)).unwrap();
-            node_sender
-                .send((top_left + TOP_RIGHT, radius - 1))
-                .unwrap();
-            node_sender.send((top_right + RIGHT, radius - 1)).unwrap();
-            node_sender
-                .send((right + BOTTOM_RIGHT, radius - 1))
+        let event = match event.try_to_object::<InputEvent>() {
+            None => return,
+            Some(event) => unsafe { event.assume_safe() },
+        };
+        let key = match self.nearest_vertex(owner.to_local(click_position)) {
+            None => return,
+            Some(key) => key,
+        };
+        if let Some(event) = event.cast::<InputEventMouseButton>() {
+            if event.is_pressed() {
+                if event.shift() {
+                    self.node_decrease(owner, key.x.into(), key.y.into());
+                } else {
+                    self.node_increase(owner, key.x.into(), key.y.into());
+                }
+            }
+        } else if event.cast::<InputEventMouseMotion>().is_some() {
+            let position = self.vertex_position(key);
+            if let Some(indicator) = self.indicator(owner) {
+                indicator.set_translation(position);
+                indicator.show();
+            }
+        }
+    }
+
+    #[export]
+    pub fn _on_picking_body_mouse_exited(&mut self, _owner: TRef<'_, Spatial>) {
+        if let Some((_, indicator)) = &self.indicator {
+            if let Some(indicator) = unsafe { indicator.assume_safe_if_sane() } {
+                indicator.hide();
+            }
+        }
+    }
+
+    /// Returns the vertex of the cell under the point that is closest to it.
+    fn nearest_vertex(&self, point: Vector3) -> Option<Vector2Di32> {
+        let cell = hex::nearest_cell(point.x / self.hex_radius, point.z / self.hex_radius);
+        self.hexagon_map
+            .get(&cell)?
+            .keys()
+            .iter()
+            .map(|key| (*key, (self.vertex_position(*key) - point).length()))
+            .min_by(|first, second| first.1.partial_cmp(&second.1).unwrap())
+            .map(|(key, _)| key)
+    }
+
+    /// Returns the instance of `indicator_scene` that marks the vertex under the mouse cursor,
+    /// loading it only if there is none for the current scene. Its collision is disabled, so it
+    /// does not take the clicks meant for the terrain. Returns None if the scene cannot be used.
+    fn indicator(&mut self, owner: TRef<'_, Spatial>) -> Option<TRef<'_, Spatial>> {
+        let cached = matches!(&self.indicator, Some((scene, _)) if *scene == self.indicator_scene);
+        if !cached {
+            self.invalidate_indicator_template(owner);
+            match Self::load_indicator(self.indicator_scene.clone()) {
+                None => {
+                    godot_error!("indicator_scene is no scene of a Spatial");
+                    return None;
+                }
+                Some(indicator) => {
+                    let nodes_node = unsafe { owner.get_node("Nodes")?.assume_safe() };
+                    nodes_node.add_child(indicator.clone(), false);
+                    self.indicator = Some((self.indicator_scene.clone(), indicator));
+                }
+            }
+        }
+        self.indicator
+            .as_ref()
+            .and_then(|(_, indicator)| unsafe { indicator.assume_safe_if_sane() })
+    }
+
+    /// Loads the indicator scene and disables the collision of its instance.
+    fn load_indicator(scene: GodotString) -> Option<Ref<Spatial>> {
+        let resource_loader = ResourceLoader::godot_singleton();
+        let scene = resource_loader
+            .load(scene, "PackedScene", false)?
+            .cast::<PackedScene>()?;
+        let scene: TRef<'_, PackedScene> = unsafe { scene.assume_safe() };
+
+        let indicator = unsafe { scene.instance(0)?.assume_safe() };
+        let indicator: TRef<'_, Spatial> = indicator.cast::<Spatial>()?;
+        if let Some(collision) = indicator.get_node("Collision") {
+            if let Some(collision) = unsafe { collision.assume_safe() }.cast::<CollisionShape>() {
+                collision.set_disabled(true);
+            }
+        }
+        Some(indicator.claim())
+    }
+
+    /// Gives the terrain one static body with the given triangles, three corners each, through
+    /// which clicks edit the vertices, see `_on_picking_body_input_event`. The body is freed while
+    /// `direct_editing` is enabled, which picks without physics.
+    fn update_picking_body(&mut self, owner: TRef<'_, Spatial>, faces: Vec<Vector3>) {
+        let start = Instant::now();
+        if self.direct_editing {
+            if let Some((body, _)) = self.picking_body.take() {
+                if let Some(body) = unsafe { body.assume_safe_if_sane() } {
+                    body.queue_free();
+                }
+            }
+            return;
+        }
+
+        let shape = match &self.picking_body {
+            Some((_, shape)) => shape.clone(),
+            None => {
+                let shape = ConcavePolygonShape::new().into_shared();
+                let collision = CollisionShape::new();
+                collision.set_shape(shape.clone());
+                let body = StaticBody::new();
+                body.set_name("PickingBody");
+                body.add_child(collision, false);
+                body.connect(
+                    "input_event",
+                    owner,
+                    "_on_picking_body_input_event",
+                    VariantArray::new_shared(),
+                    0,
+                )
                 .unwrap();
-            node_sender
-                .send((bottom_right + BOTTOM_LEFT, radius - 1))
+                body.connect(
+                    "mouse_exited",
+                    owner,
+                    "_on_picking_body_mouse_exited",
+                    VariantArray::new_shared(),
+                    0,
+                )
                 .unwrap();
-            node_sender.send((bottom_left + LEFT, radius - 1)).unwrap();
+                let body = body.into_shared();
+                owner.add_child(body.clone(), false);
+                self.picking_body = Some((body, shape.clone()));
+                shape
+            }
+        };
+        unsafe { shape.assume_safe() }.set_faces(Vector3Array::from_vec(faces));
+        self.telemetry.record("picking", start.elapsed());
+    }
+
+    fn update_vertices(&mut self, owner: TRef<'_, Spatial>) {
+        let start = Instant::now();
+        // The triangles of every chunk if the mesh is chunked, else all in one part.
+        let mut parts = HashMap::<Vector2Di32, MeshArrays>::new();
+        let chunk_size = self.chunk_size.max(1) as i32;
+        let chunked = self.chunked_mesh;
+        let part_of = |cell: Vector2Di32| {
+            if chunked {
+                hex::chunk_of_cell(cell, chunk_size)
+            } else {
+                Vector2Di32::zero()
+            }
+        };
+        let mut picking_faces = Vec::new();
+
+        let changed: HashSet<Vector2Di32> = self.terrain.take_dirty().into_iter().collect();
+
+        // The second UV spans the whole map, like the one of `export_gltf`.
+        let keys = self.vertex_map.keys();
+        let min_x = keys.clone().map(|key| key.x).min().unwrap_or(0) as f32;
+        let max_x = keys.clone().map(|key| key.x).max().unwrap_or(0) as f32;
+        let min_y = keys.clone().map(|key| key.y).min().unwrap_or(0) as f32;
+        let max_y = keys.map(|key| key.y).max().unwrap_or(0) as f32;
+        let corner = |node_data: &TerrainNode, position: [f32; 3]| Corner {
+            uv: [node_data.uv.x, node_data.uv.y],
+            uv2: [
+                (node_data.key.x as f32 - min_x) / (max_x - min_x).max(1.0),
+                (node_data.key.y as f32 - min_y) / (max_y - min_y).max(1.0),
+            ],
+            ..Corner::new(position)
+        };
+
+        // The positions of all nodes are computed in one pass over plain arrays instead of one by
+        // one while adding the triangles.
+        let refilled = self.update_columns(&changed);
+        let positions = self.columns.positions(self.node_height);
+
+        // With `gpu_displacement`, the mesh is flat and only built again when its triangles
+        // change, height edits only change the height texture.
+        let flat = self.gpu_displacement;
+        let shape = if flat { self.mesh_shape() } else { Vec::new() };
+        let build_mesh = !flat
+            || refilled
+            || self
+                .displacement
+                .as_ref()
+                .map_or(true, |displacement| displacement.shape != shape);
+        let mesh_position = |position: [f32; 3]| {
+            if flat {
+                [position[0], 0.0, position[2]]
+            } else {
+                position
+            }
+        };
+
+        // Every triangle starts with the center of its hexagon.
+        for (triangle, triangle_positions) in self
+            .nodes
+            .chunks(3)
+            .zip(positions.chunks(3))
+            .filter(|(triangle, _)| !self.terrain.is_hole(triangle[0].key))
+        {
+            let mut corners = [Corner::new([0.0; 3]); 3];
+            for ((corner_data, node_data), position) in
+                corners.iter_mut().zip(triangle).zip(triangle_positions)
+            {
+                *corner_data = corner(node_data, mesh_position(*position));
+
+                if !self.direct_editing {
+                    picking_faces.push(Vector3::new(position[0], position[1], position[2]));
+                }
+            }
+            if build_mesh {
+                parts
+                    .entry(part_of(triangle[0].key))
+                    .or_default()
+                    .add_triangle(corners);
+            }
+        }
+
+        for (triangle, triangle_positions) in self
+            .nodes
+            .chunks(3)
+            .zip(positions.chunks(3))
+            .filter(|_| build_mesh)
+        {
+            if let Some(deck_height) = self.terrain.get_deck_height(triangle[0].key) {
+                let mut corners = [Corner::new([0.0; 3]); 3];
+                for ((corner_data, node_data), position) in
+                    corners.iter_mut().zip(triangle).zip(triangle_positions)
+                {
+                    let deck_position = [
+                        position[0],
+                        deck_height as f32 * self.node_height,
+                        position[2],
+                    ];
+                    *corner_data = corner(node_data, deck_position);
+                    // Decks are not displaced by the shader of `gpu_displacement`.
+                    if flat {
+                        corner_data.color[3] = 0.0;
+                    }
+                }
+                parts
+                    .entry(part_of(triangle[0].key))
+                    .or_default()
+                    .add_triangle(corners);
+            }
+        }
+
+        let triangles: usize = parts.values().map(|part| part.indices.len() / 3).sum();
+        let (chunks, mut parts): (Vec<Vector2Di32>, Vec<MeshArrays>) = parts.into_iter().unzip();
+        let tmp_mesh = if chunked {
+            mesh::share_normals(&mut parts);
+            ArrayMesh::new()
+        } else {
+            let arrays = parts.pop().unwrap_or_default();
+            Self::array_mesh(&arrays, Mesh::PRIMITIVE_TRIANGLES, self.mesh_compression)
+        };
+        let chunk_meshes: Vec<(Vector2Di32, Ref<ArrayMesh, Unique>, Bounds)> = chunks
+            .into_iter()
+            .zip(&parts)
+            .filter_map(|(chunk, arrays)| {
+                let bounds = arrays.bounds()?;
+                let mesh =
+                    Self::array_mesh(arrays, Mesh::PRIMITIVE_TRIANGLES, self.mesh_compression);
+                Some((chunk, mesh, bounds))
+            })
+            .collect();
+        self.telemetry.record("mesh", start.elapsed());
+        self.telemetry.count("triangles rebuilt", triangles as u64);
+
+        self.update_picking_body(owner, picking_faces);
+
+        self.update_debug_overlay(owner);
+
+        let mesh_instance = owner
+            .get_node("HexMesh")
+            .and_then(|node| unsafe { node.assume_safe_if_sane() })
+            .and_then(|node| node.cast::<MeshInstance>());
+        match mesh_instance {
+            None => {}
+            Some(mesh_instance) => {
+                if build_mesh {
+                    mesh_instance.set_mesh(tmp_mesh);
+                }
+                self.update_displacement(mesh_instance, &changed, shape, refilled);
+                if build_mesh {
+                    self.update_chunk_meshes(mesh_instance, chunk_meshes);
+                }
+            }
         }
 
-        let mut vertices_data = HashMap::<Vector2Di32, Vector2>::new();
+        let changed_cells: Vec<Vector2Di32> = self
+            .hexagon_map
+            .iter()
+            .filter(|(_, hexagon)| hexagon.keys().iter().any(|key| changed.contains(key)))
+            .map(|(cell, _)| *cell)
+            .collect();
+        self.update_grid_cells(owner, &changed_cells);
+        self.update_minimap();
+        self.telemetry.record("rebuild", start.elapsed());
+    }
+
+    /// Returns the cells that are holes or have a deck, which change the triangles of the mesh.
+    fn mesh_shape(&self) -> Vec<(Vector2Di32, bool, Option<i32>)> {
+        let mut shape: Vec<(Vector2Di32, bool, Option<i32>)> = self
+            .hexagon_map
+            .keys()
+            .map(|cell| {
+                (
+                    *cell,
+                    self.terrain.is_hole(*cell),
+                    self.terrain.get_deck_height(*cell),
+                )
+            })
+            .filter(|(_, hole, deck_height)| *hole || deck_height.is_some())
+            .collect();
+        shape.sort_unstable_by_key(|(cell, _, _)| (cell.x, cell.y));
+        shape
+    }
+
+    /// Writes the heights of the changed nodes into the height texture of `gpu_displacement`, or
+    /// of all nodes if it is new, `refilled` is set or it does not fit the keys anymore, and gives
+    /// the terrain mesh the displacement shader. Restores the previous material of the terrain
+    /// mesh if `gpu_displacement` is not set.
+    fn update_displacement(
+        &mut self,
+        hex_mesh: TRef<'_, MeshInstance>,
+        changed: &HashSet<Vector2Di32>,
+        shape: Vec<(Vector2Di32, bool, Option<i32>)>,
+        refilled: bool,
+    ) {
+        if !self.gpu_displacement {
+            if let Some(displacement) = self.displacement.take() {
+                match displacement.previous_material {
+                    Some(material) => hex_mesh.set_material_override(material),
+                    None => hex_mesh.set_material_override(Null::<Material>::null()),
+                }
+            }
+            return;
+        }
 
-        vertices_data.insert(
-            center,
-            Vector2::new(center.x as f32 * hex_radius, center.y as f32 * hex_radius),
+        let keys = self.vertex_map.keys();
+        let min = Vector2Di32::new(
+            keys.clone().map(|key| key.x).min().unwrap_or(0),
+            keys.clone().map(|key| key.y).min().unwrap_or(0),
         );
-        let mut center_node_data = TerrainNode::new(center, Vector2::new(0.5, 0.5));
-        center_node_data.connections.push(left);
-        center_node_data.connections.push(top_left);
-        center_node_data.connections.push(top_right);
-        center_node_data.connections.push(right);
-        center_node_data.connections.push(bottom_right);
-        center_node_data.connections.push(bottom_left);
-
-        vertices_data.insert(
-            left,
-            Vector2::new(left.x as f32 * hex_radius, left.y as f32 * hex_radius),
+        let max = Vector2Di32::new(
+            keys.clone().map(|key| key.x).max().unwrap_or(0),
+            keys.map(|key| key.y).max().unwrap_or(0),
         );
-        let mut left_data = TerrainNode::new(left, Vector2::new(0.0, 0.5));
-        left_data.connections.push(top_left);
-        left_data.connections.push(bottom_left);
-
-        vertices_data.insert(
-            top_left,
-            Vector2::new(
-                top_left.x as f32 * hex_radius,
-                top_left.y as f32 * hex_radius,
-            ),
+        let created = self.displacement.is_none();
+        let mut displacement = match self.displacement.take() {
+            Some(displacement) => displacement,
+            None => {
+                let shader = Shader::new();
+                shader.set_code(displacement::SHADER);
+                let material = ShaderMaterial::new();
+                material.set_shader(shader);
+                Displacement {
+                    heights: HeightTexture::new(min, max),
+                    texture: ImageTexture::new().into_shared(),
+                    material: material.into_shared(),
+                    previous_material: hex_mesh.material_override(),
+                    shape: Vec::new(),
+                }
+            }
+        };
+
+        let resized = !displacement.heights.covers(min, max);
+        if resized {
+            displacement.heights = HeightTexture::new(min, max);
+        }
+        let mut written = 0;
+        if created || resized || refilled {
+            for (key, height) in self.terrain.heights() {
+                displacement.heights.set(key, height);
+                written += 1;
+            }
+        } else {
+            for key in changed {
+                if let Some(height) = self.terrain.get_height_of_node(*key) {
+                    displacement.heights.set(*key, height);
+                    written += 1;
+                }
+            }
+        }
+        self.telemetry.count("height texels written", written);
+
+        let image = Image::new();
+        image.create_from_data(
+            displacement.heights.width,
+            displacement.heights.height,
+            false,
+            Image::FORMAT_RF,
+            ByteArray::from_vec(displacement.heights.bytes()),
         );
-        let mut top_left_data = TerrainNode::new(top_left, Vector2::new(0.25, 0.0));
-        top_left_data.connections.push(left);
-        top_left_data.connections.push(top_right);
-
-        vertices_data.insert(
-            top_right,
-            Vector2::new(
-                top_right.x as f32 * hex_radius,
-                top_right.y as f32 * hex_radius,
-            ),
+        let texture = unsafe { displacement.texture.assume_safe() };
+        if resized || texture.get_width() != displacement.heights.width {
+            texture.create_from_image(image, 0);
+        } else {
+            texture.set_data(image);
+        }
+
+        let material = unsafe { displacement.material.assume_safe() };
+        material.set_shader_param("heights", displacement.texture.clone());
+        material.set_shader_param(
+            "key_range",
+            Vector2::new((max.x - min.x) as f32, (max.y - min.y) as f32),
         );
-        let mut top_right_data = TerrainNode::new(top_right, Vector2::new(0.75, 0.00));
-        top_right_data.connections.push(top_left);
-        top_right_data.connections.push(right);
+        material.set_shader_param("node_height", self.node_height);
+        hex_mesh.set_material_override(displacement.material.clone());
+        displacement.shape = shape;
+        self.displacement = Some(displacement);
+    }
 
-        vertices_data.insert(
-            right,
-            Vector2::new(right.x as f32 * hex_radius, right.y as f32 * hex_radius),
+    /// Replaces the meshes of the chunks of a chunked mesh, which are children of the terrain mesh
+    /// and drawn with its material. Chunks without a mesh are freed.
+    fn update_chunk_meshes(
+        &mut self,
+        hex_mesh: TRef<'_, MeshInstance>,
+        meshes: Vec<(Vector2Di32, Ref<ArrayMesh, Unique>, Bounds)>,
+    ) {
+        let count = self.chunk_meshes.len();
+        let kept: HashSet<Vector2Di32> = meshes.iter().map(|(chunk, _, _)| *chunk).collect();
+        self.chunk_meshes.retain(|chunk, (mesh_instance, _)| {
+            if kept.contains(chunk) {
+                return true;
+            }
+            if let Some(mesh_instance) = unsafe { mesh_instance.assume_safe_if_sane() } {
+                mesh_instance.queue_free();
+            }
+            false
+        });
+        self.telemetry.count(
+            "chunk meshes freed",
+            (count - self.chunk_meshes.len()) as u64,
         );
-        let mut right_data = TerrainNode::new(right, Vector2::new(1.0, 0.5));
-        right_data.connections.push(top_right);
-        right_data.connections.push(bottom_right);
-
-        vertices_data.insert(
-            bottom_right,
-            Vector2::new(
-                bottom_right.x as f32 * hex_radius,
-                bottom_right.y as f32 * hex_radius,
+
+        let material = hex_mesh.material_override();
+        for (chunk, mesh, bounds) in meshes {
+            let mesh_instance = match self.chunk_meshes.get(&chunk) {
+                Some((mesh_instance, _)) => *mesh_instance,
+                None => {
+                    let mesh_instance = MeshInstance::new().into_shared();
+                    hex_mesh.add_child(mesh_instance, false);
+                    self.telemetry.count("chunk meshes created", 1);
+                    mesh_instance
+                }
+            };
+            let size = [
+                bounds.max[0] - bounds.min[0],
+                bounds.max[1] - bounds.min[1],
+                bounds.max[2] - bounds.min[2],
+            ];
+            let instance = unsafe { mesh_instance.assume_safe() };
+            instance.set_mesh(mesh);
+            if let Some(material) = &material {
+                instance.set_material_override(material.clone());
+            }
+            instance.set_custom_aabb(Aabb {
+                position: Vector3::new(bounds.min[0], bounds.min[1], bounds.min[2]),
+                size: Vector3::new(size[0], size[1], size[2]),
+            });
+            self.chunk_meshes.insert(chunk, (mesh_instance, bounds));
+        }
+    }
+
+    /// Shows the chunks of a chunked mesh that are within `draw_distance` of the camera and, if
+    /// `frustum_culling` is set, in its view, and hides the others.
+    fn update_chunk_visibility(&self, owner: TRef<'_, Spatial>) {
+        if self.chunk_meshes.is_empty() {
+            return;
+        }
+        let camera = owner
+            .get_viewport()
+            .and_then(|viewport| unsafe { viewport.assume_safe() }.get_camera())
+            .and_then(|camera| unsafe { camera.assume_safe_if_sane() });
+        let camera: TRef<'_, Camera> = match camera {
+            None => return,
+            Some(camera) => camera,
+        };
+        let origin = camera.global_transform().origin;
+        let planes: Vec<Plane> = if self.frustum_culling {
+            camera
+                .get_frustum()
+                .iter()
+                .filter_map(|plane| plane.try_to_plane())
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        for (mesh_instance, bounds) in self.chunk_meshes.values() {
+            let mesh_instance = match unsafe { mesh_instance.assume_safe_if_sane() } {
+                None => continue,
+                Some(mesh_instance) => mesh_instance,
+            };
+            let mut global: Option<Bounds> = None;
+            for corner in bounds.corners().iter() {
+                let corner = mesh_instance.to_global(Vector3::new(corner[0], corner[1], corner[2]));
+                let corner = [corner.x, corner.y, corner.z];
+                match global.as_mut() {
+                    None => {
+                        global = Some(Bounds {
+                            min: corner,
+                            max: corner,
+                        })
+                    }
+                    Some(global) => global.add(corner),
+                }
+            }
+            let global = match global {
+                None => continue,
+                Some(global) => global,
+            };
+            let near = self.draw_distance <= 0.0
+                || global.distance_to([origin.x, origin.y, origin.z]) <= self.draw_distance;
+            let in_view = !planes.iter().any(|plane| {
+                global.is_in_front_of([plane.normal.x, plane.normal.y, plane.normal.z], plane.d)
+            });
+            mesh_instance.set_visible(near && in_view);
+        }
+    }
+
+    /// Brings the heights in `columns` up to date with the changed nodes, or fills it again if
+    /// `nodes` changed since. Returns whether it was filled again.
+    fn update_columns(&mut self, changed: &HashSet<Vector2Di32>) -> bool {
+        if self.columns.heights.len() == self.nodes.len() {
+            for key in changed {
+                if let (Some(slots), Some(height)) = (
+                    self.column_slots.get(key),
+                    self.terrain.get_height_of_node(*key),
+                ) {
+                    for slot in slots {
+                        self.columns.heights[*slot] = height;
+                    }
+                }
+            }
+            return false;
+        }
+
+        self.columns = VertexColumns::with_capacity(self.nodes.len());
+        self.column_slots.clear();
+        for (slot, node_data) in self.nodes.iter().enumerate() {
+            let vector_data = self.vertex_map[&node_data.key];
+            let height: i32 = match self.terrain.get_height_of_node(node_data.key) {
+                None => panic!(),
+                Some(height) => height,
+            };
+            self.columns.push(vector_data.x, vector_data.y, height);
+            self.column_slots
+                .entry(node_data.key)
+                .or_insert_with(Vec::new)
+                .push(slot);
+        }
+        true
+    }
+
+    /// Creates a mesh with the arrays as its only surface of the primitive type and with the format
+    /// flags, or without surfaces if the arrays are empty.
+    fn array_mesh(arrays: &MeshArrays, primitive: i64, flags: i64) -> Ref<ArrayMesh, Unique> {
+        let mesh = ArrayMesh::new();
+        if arrays.is_empty() {
+            return mesh;
+        }
+        let vector3s = |values: &[[f32; 3]]| {
+            Vector3Array::from_vec(
+                values
+                    .iter()
+                    .map(|value| Vector3::new(value[0], value[1], value[2]))
+                    .collect(),
+            )
+        };
+        let vector2s = |values: &[[f32; 2]]| {
+            Vector2Array::from_vec(
+                values
+                    .iter()
+                    .map(|value| Vector2::new(value[0], value[1]))
+                    .collect(),
+            )
+        };
+
+        let surface = VariantArray::new();
+        surface.resize(Mesh::ARRAY_MAX as i32);
+        surface.set(Mesh::ARRAY_VERTEX as i32, vector3s(&arrays.positions));
+        surface.set(Mesh::ARRAY_NORMAL as i32, vector3s(&arrays.unit_normals()));
+        surface.set(Mesh::ARRAY_TEX_UV as i32, vector2s(&arrays.uvs));
+        surface.set(Mesh::ARRAY_TEX_UV2 as i32, vector2s(&arrays.uv2s));
+        surface.set(
+            Mesh::ARRAY_COLOR as i32,
+            ColorArray::from_vec(
+                arrays
+                    .colors
+                    .iter()
+                    .map(|color| Color::rgba(color[0], color[1], color[2], color[3]))
+                    .collect(),
             ),
         );
-        let mut bottom_right_data = TerrainNode::new(bottom_right, Vector2::new(0.75, 1.0));
-        bottom_right_data.connections.push(right);
-        bottom_right_data.connections.push(bottom_left);
-
-        vertices_data.insert(
-            bottom_left,
-            Vector2::new(
-                bottom_left.x as f32 * hex_radius,
-                bottom_left.y as f32 * hex_radius,
-            ),
+        surface.set(
+            Mesh::ARRAY_INDEX as i32,
+            Int32Array::from_slice(&arrays.indices),
+        );
+        mesh.add_surface_from_arrays(
+            primitive,
+            surface.into_shared(),
+            VariantArray::new_shared(),
+            flags,
         );
-        let mut bottom_left_data = TerrainNode::new(bottom_left, Vector2::new(0.25, 1.0));
-        bottom_left_data.connections.push(bottom_right);
-        bottom_left_data.connections.push(left);
+        mesh
+    }
 
-        let mut nodes_data = Vec::<TerrainNode>::new();
-        nodes_data.push(center_node_data.clone());
-        nodes_data.push(left_data.clone());
-        nodes_data.push(top_left_data.clone());
+    /// Recreates the grid meshes from the current terrain.
+    fn update_grid(&mut self, owner: TRef<'_, Spatial>) {
+        let grid_node = owner
+            .get_node("Grid")
+            .and_then(|node| unsafe { node.assume_safe_if_sane() });
+        let grid_node: TRef<'_, GodotNode> = match grid_node {
+            None => panic!(),
+            Some(grid_node) => grid_node,
+        };
 
-        nodes_data.push(center_node_data.clone());
-        nodes_data.push(top_left_data);
-        nodes_data.push(top_right_data.clone());
+        for child in grid_node.get_children().iter() {
+            let child: Variant = child;
+            let child = child.try_to_object::<GodotNode>().unwrap();
+            let child = unsafe { child.assume_safe() };
+            grid_node.remove_child(child);
+            child.queue_free();
+        }
+        self.grid_meshes.clear();
 
-        nodes_data.push(center_node_data.clone());
-        nodes_data.push(top_right_data);
-        nodes_data.push(right_data.clone());
+        let cells: Vec<Vector2Di32> = self.hexagon_map.keys().copied().collect();
+        self.update_grid_cells(owner, &cells);
+    }
 
-        nodes_data.push(center_node_data.clone());
-        nodes_data.push(right_data);
-        nodes_data.push(bottom_right_data.clone());
+    /// Recreates the grid meshes of the chunks of the given cells and frees those of chunks whose
+    /// cells are gone.
+    fn update_grid_cells(&mut self, owner: TRef<'_, Spatial>, cells: &[Vector2Di32]) {
+        let start = Instant::now();
+        let grid_node = owner
+            .get_node("Grid")
+            .and_then(|node| unsafe { node.assume_safe_if_sane() });
+        let grid_node: TRef<'_, GodotNode> = match grid_node {
+            None => panic!(),
+            Some(grid_node) => grid_node,
+        };
 
-        nodes_data.push(center_node_data.clone());
-        nodes_data.push(bottom_right_data);
-        nodes_data.push(bottom_left_data.clone());
+        let count = self.grid_meshes.len();
+        let hexagon_map = &self.hexagon_map;
+        self.grid_meshes.retain(|chunk, mesh_instance| {
+            if hex::cells_of_chunk(*chunk, GRID_CHUNK_SIZE)
+                .iter()
+                .any(|cell| hexagon_map.contains_key(cell))
+            {
+                return true;
+            }
+            if let Some(mesh_instance) = unsafe { mesh_instance.assume_safe_if_sane() } {
+                mesh_instance.queue_free();
+            }
+            false
+        });
+        let mut freed = count - self.grid_meshes.len();
 
-        nodes_data.push(center_node_data);
-        nodes_data.push(bottom_left_data);
-        nodes_data.push(left_data);
+        let chunks: HashSet<Vector2Di32> = cells
+            .iter()
+            .map(|cell| hex::chunk_of_cell(*cell, GRID_CHUNK_SIZE))
+            .collect();
+        let mut outlines: Vec<(Vector2Di32, Vec<Vec<[f32; 3]>>)> = Vec::with_capacity(chunks.len());
+        for chunk in chunks {
+            if let Some(mesh_instance) = self.grid_meshes.remove(&chunk) {
+                if let Some(mesh_instance) = unsafe { mesh_instance.assume_safe_if_sane() } {
+                    mesh_instance.queue_free();
+                }
+                freed += 1;
+            }
+            let chunk_outlines: Vec<Vec<[f32; 3]>> = hex::cells_of_chunk(chunk, GRID_CHUNK_SIZE)
+                .into_iter()
+                .filter_map(|cell| self.grid_outline(cell))
+                .collect();
+            if !chunk_outlines.is_empty() {
+                outlines.push((chunk, chunk_outlines));
+            }
+        }
 
-        match vertex_data_sender.send((hexagon, vertices_data, nodes_data)) {
-            Ok(_) => {}
-            Err(err) => godot_print!("Could not send vertex data: {}", err),
+        // Only the arrays are built in parallel, the meshes have to be created on this thread.
+        let thickness = self.grid_thickness;
+        let grid_arrays = mesh::build_parallel(&outlines, |(_, chunk_outlines)| {
+            let mut arrays = MeshArrays::default();
+            for corners in chunk_outlines {
+                arrays.add_outline(corners, thickness);
+            }
+            arrays
+        });
+        let primitive = if thickness > 0.0 {
+            Mesh::PRIMITIVE_TRIANGLES
+        } else {
+            Mesh::PRIMITIVE_LINES
         };
+        for ((chunk, _), arrays) in outlines.iter().zip(&grid_arrays) {
+            let grid_mesh = Self::array_mesh(arrays, primitive, self.mesh_compression);
+            let mesh_instance = MeshInstance::new();
+
+            mesh_instance.set_mesh(grid_mesh);
+            mesh_instance.set_material_override(self.grid_material.clone());
+
+            let mesh_instance = mesh_instance.into_shared();
+            grid_node.add_child(mesh_instance, false);
+            self.grid_meshes.insert(*chunk, mesh_instance);
+            self.telemetry.count("grid meshes created", 1);
+        }
+        self.telemetry.count("grid meshes freed", freed as u64);
+
+        self.apply_grid_appearance(owner);
+        self.telemetry.record("grid", start.elapsed());
+    }
+
+    /// Returns the corners of the grid outline of a cell, None if it does not exist or is a hole.
+    fn grid_outline(&self, cell: Vector2Di32) -> Option<Vec<[f32; 3]>> {
+        let hexagon = self.hexagon_map.get(&cell)?;
+        if self.terrain.is_hole(hexagon.center) {
+            return None;
+        }
+        Some(
+            [
+                hexagon.left,
+                hexagon.top_left,
+                hexagon.top_right,
+                hexagon.right,
+                hexagon.bottom_right,
+                hexagon.bottom_left,
+            ]
+            .iter()
+            .map(|key| {
+                let vertex = self.grid_vertex(*key);
+                [vertex.x, vertex.y, vertex.z]
+            })
+            .collect(),
+        )
+    }
+
+    /// Applies the grid properties that do not require the grid meshes to be recreated.
+    fn apply_grid_appearance(&mut self, owner: TRef<'_, Spatial>) {
+        let material = unsafe { self.grid_material.assume_safe() };
+        material.set_albedo(self.grid_color);
+        material.set_feature(
+            SpatialMaterial::FEATURE_TRANSPARENT,
+            self.grid_color.a < 1.0,
+        );
+
+        if let Some(grid_node) = owner
+            .get_node("Grid")
+            .and_then(|node| unsafe { node.assume_safe_if_sane() })
+            .and_then(|node| node.cast::<Spatial>())
+        {
+            grid_node.set_visible(self.grid_visible);
+        }
+
+        self.grid_state = (
+            self.grid_visible,
+            self.grid_color,
+            self.grid_thickness,
+            self.grid_height_offset,
+        );
+    }
+
+    fn grid_vertex(&self, key: Vector2Di32) -> Vector3 {
+        self.vertex_position(key) + Vector3::new(0.0, self.grid_height_offset, 0.0)
+    }
+
+    /// Returns the position of the vertex relative to the terrain node.
+    fn vertex_position(&self, key: Vector2Di32) -> Vector3 {
+        let vertex = self.vertex_map[&key];
+        let vertex_height = self.terrain.get_height_of_node(key).unwrap() as f32 * self.node_height;
+        Vector3::new(vertex.x, vertex_height, vertex.y)
+    }
+
+    /// Returns the vertex closest to the point where the ray from the camera through the screen
+    /// position hits the terrain.
+    fn pick_vertex(
+        &self,
+        owner: TRef<'_, Spatial>,
+        screen_position: Vector2,
+    ) -> Option<Vector2Di32> {
+        let (hit, triangle) = self.pick_triangle(owner, screen_position)?;
+        triangle
+            .iter()
+            .map(|node| (node.key, (self.vertex_position(node.key) - hit).length()))
+            .min_by(|first, second| first.1.partial_cmp(&second.1).unwrap())
+            .map(|(key, _)| key)
+    }
+
+    /// Returns the center of the cell that is hit by the ray from the camera through the screen
+    /// position.
+    fn pick_cell(&self, owner: TRef<'_, Spatial>, screen_position: Vector2) -> Option<Vector2Di32> {
+        // Every triangle starts with the center of its hexagon.
+        self.pick_triangle(owner, screen_position)
+            .map(|(_, triangle)| triangle[0].key)
+    }
+
+    /// Returns the point where the ray from the camera through the screen position hits the
+    /// terrain, together with the triangle that was hit.
+    fn pick_triangle(
+        &self,
+        owner: TRef<'_, Spatial>,
+        screen_position: Vector2,
+    ) -> Option<(Vector3, &[TerrainNode])> {
+        let camera = owner
+            .get_viewport()
+            .and_then(|viewport| unsafe { viewport.assume_safe() }.get_camera())
+            .and_then(|camera| unsafe { camera.assume_safe_if_sane() })?;
+
+        let ray_origin = camera.project_ray_origin(screen_position);
+        let ray_end = ray_origin + camera.project_ray_normal(screen_position);
+        let origin = owner.to_local(ray_origin);
+        let direction = owner.to_local(ray_end) - origin;
+
+        let mut closest: Option<(f32, &[TerrainNode])> = None;
+        for triangle in self.nodes.chunks(3) {
+            if self.terrain.is_hole(triangle[0].key) {
+                continue;
+            }
+            let distance = Self::intersect_triangle(
+                origin,
+                direction,
+                self.vertex_position(triangle[0].key),
+                self.vertex_position(triangle[1].key),
+                self.vertex_position(triangle[2].key),
+            );
+            if let Some(distance) = distance {
+                if closest.map_or(true, |(closest_distance, _)| distance < closest_distance) {
+                    closest = Some((distance, triangle));
+                }
+            }
+        }
+
+        let (distance, triangle) = closest?;
+        Some((origin + direction * distance, triangle))
+    }
+
+    /// Returns how far along the ray the triangle is hit, regardless of its winding.
+    fn intersect_triangle(
+        origin: Vector3,
+        direction: Vector3,
+        first: Vector3,
+        second: Vector3,
+        third: Vector3,
+    ) -> Option<f32> {
+        let edge_1 = second - first;
+        let edge_2 = third - first;
+        let p = direction.cross(edge_2);
+        let determinant = edge_1.dot(p);
+        if determinant.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let t = origin - first;
+        let u = t.dot(p) / determinant;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = t.cross(edge_1);
+        let v = direction.dot(q) / determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let distance = edge_2.dot(q) / determinant;
+        if distance < 0.0 {
+            return None;
+        }
+        Some(distance)
+    }
+
+    /// Creates the hexagons of all cells within `field_radius` of the center, ring by ring from
+    /// the center, so equal fields give equal data in the same order, and connects their vertices
+    /// in the terrain, which has to be empty.
+    fn create_hex_nodes(&mut self) {
+        let cells = hex::cell_spiral(Vector2Di32::zero(), self.field_radius);
+        let mut buffers = HexagonBuffers::with_capacity(cells.len());
+        for cell in cells {
+            Self::add_hexagon_data(cell, self.hex_radius, &mut buffers);
+        }
+        self.connect_nodes(&buffers.nodes);
+        self.nodes = buffers.nodes;
+        self.hexagon_map = buffers.hexagons;
+        self.vertex_map = buffers.vertices;
+        self.sorted_vertex_keys = RefCell::default();
+        self.columns = VertexColumns::default();
+    }
+
+    /// Adds the hexagon of a cell, its vertices and the nodes of its six triangles, which start
+    /// with the center and go clockwise from the left corner.
+    fn add_hexagon_data(center: Vector2Di32, hex_radius: f32, buffers: &mut HexagonBuffers) {
+        let hexagon = Hexagon::new(center);
+        for key in hexagon.keys().iter() {
+            buffers.vertices.insert(
+                *key,
+                Vector2::new(key.x as f32 * hex_radius, key.y as f32 * hex_radius),
+            );
+        }
+        for index in 0..CORNERS.len() {
+            let next = (index + 1) % CORNERS.len();
+            buffers.nodes.push(TerrainNode::new(center, CENTER_UV));
+            buffers
+                .nodes
+                .push(TerrainNode::new(center + CORNERS[index], CORNER_UVS[index]));
+            buffers
+                .nodes
+                .push(TerrainNode::new(center + CORNERS[next], CORNER_UVS[next]));
+        }
+        buffers.hexagons.insert(center, hexagon);
     }
 }
 
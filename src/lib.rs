@@ -15,16 +15,50 @@
     unused_qualifications
 )]
 
+mod autosave;
+mod camera;
+mod clipboard;
+mod displacement;
+mod generation;
+mod gizmo;
+mod heightmap;
+mod hex;
 mod hex_terrain;
+mod map_format;
+mod map_render;
+mod mesh;
+mod minimap;
+mod preset;
+mod rebuild;
+mod region;
+mod stamp;
+mod stamp_library;
+mod telemetry;
+mod world;
 
 use gdnative::prelude::*;
 
 // Function that registers all exposed classes to Godot
 fn init(handle: InitHandle) {
     handle.add_class::<hex_terrain::HexTerrain>();
+    handle.add_class::<clipboard::HexTerrainClipboard>();
+    handle.add_class::<camera::HexTerrainCamera>();
+    handle.add_class::<stamp::HexStamp>();
+    handle.add_class::<stamp_library::HexStampLibrary>();
+    handle.add_class::<generation::HexGeneratorPass>();
+    handle.add_class::<preset::HexGenPreset>();
+    handle.add_class::<gizmo::HexTerrainGizmoPlugin>();
+    handle.add_class::<map_format::HexMap>();
+    handle.add_class::<map_format::HexMapLoader>();
+    handle.add_class::<map_format::HexMapSaver>();
+    handle.add_tool_class::<map_format::HexMapFormats>();
+}
+
+fn terminate(_info: &gdnative::TerminateInfo) {
+    map_format::unregister_formats();
 }
 
 // macros that create the entry-points of the dynamic library.
 godot_gdnative_init!();
 godot_nativescript_init!(init);
-godot_gdnative_terminate!();
+godot_gdnative_terminate!(terminate);
@@ -0,0 +1,196 @@
+use crate::autosave::MAP_EXTENSION;
+use gdnative::api::{
+    File, ResourceFormatLoader, ResourceFormatSaver, ResourceLoader, ResourceSaver,
+};
+use gdnative::prelude::*;
+use std::cell::RefCell;
+
+thread_local! {
+    static FORMATS: RefCell<Option<(Ref<ResourceFormatLoader>, Ref<ResourceFormatSaver>)>> =
+        RefCell::new(None);
+}
+
+/// A binary map file, as written by `HexTerrain::save_map`. Assigned to the `terrain_data`
+/// property of a `HexTerrain`, it replaces the field.
+#[derive(NativeClass)]
+#[inherit(Resource)]
+pub struct HexMap {
+    #[property]
+    pub data: ByteArray,
+}
+
+#[methods]
+impl HexMap {
+    pub fn new(_owner: TRef<'_, Resource>) -> Self {
+        Self {
+            data: ByteArray::new(),
+        }
+    }
+}
+
+/// Loads `.hexmap` files as `HexMap` resources, so they show up in the FileSystem dock and are
+/// exported with the game.
+#[derive(NativeClass)]
+#[inherit(ResourceFormatLoader)]
+pub struct HexMapLoader;
+
+#[methods]
+impl HexMapLoader {
+    pub fn new(_owner: TRef<'_, ResourceFormatLoader>) -> Self {
+        Self
+    }
+
+    #[export]
+    pub fn get_recognized_extensions(&self, _owner: TRef<'_, ResourceFormatLoader>) -> StringArray {
+        StringArray::from_vec(vec![GodotString::from(MAP_EXTENSION)])
+    }
+
+    #[export]
+    pub fn handles_type(
+        &self,
+        _owner: TRef<'_, ResourceFormatLoader>,
+        typename: GodotString,
+    ) -> bool {
+        typename == GodotString::from("Resource")
+    }
+
+    #[export]
+    pub fn get_resource_type(
+        &self,
+        _owner: TRef<'_, ResourceFormatLoader>,
+        path: GodotString,
+    ) -> GodotString {
+        if path.to_string().ends_with(&format!(".{}", MAP_EXTENSION)) {
+            GodotString::from("Resource")
+        } else {
+            GodotString::new()
+        }
+    }
+
+    /// Returns the loaded `HexMap`, or the Godot error code if the file cannot be opened.
+    #[export]
+    pub fn load(
+        &self,
+        _owner: TRef<'_, ResourceFormatLoader>,
+        path: GodotString,
+        _original_path: GodotString,
+    ) -> Variant {
+        let file = File::new();
+        if let Err(error) = file.open(path, File::READ) {
+            return (error as i64).to_variant();
+        }
+        let data = file.get_buffer(file.get_len());
+        file.close();
+        Instance::emplace(HexMap { data }).owned_to_variant()
+    }
+}
+
+/// Saves `HexMap` resources as `.hexmap` files.
+#[derive(NativeClass)]
+#[inherit(ResourceFormatSaver)]
+pub struct HexMapSaver;
+
+#[methods]
+impl HexMapSaver {
+    pub fn new(_owner: TRef<'_, ResourceFormatSaver>) -> Self {
+        Self
+    }
+
+    #[export]
+    pub fn get_recognized_extensions(
+        &self,
+        _owner: TRef<'_, ResourceFormatSaver>,
+        resource: Ref<Resource>,
+    ) -> StringArray {
+        if Self::map_data(resource).is_some() {
+            StringArray::from_vec(vec![GodotString::from(MAP_EXTENSION)])
+        } else {
+            StringArray::new()
+        }
+    }
+
+    #[export]
+    pub fn recognize(
+        &self,
+        _owner: TRef<'_, ResourceFormatSaver>,
+        resource: Ref<Resource>,
+    ) -> bool {
+        Self::map_data(resource).is_some()
+    }
+
+    /// Writes the data of a `HexMap`. Returns the Godot error code, 0 on success.
+    #[export]
+    pub fn save(
+        &self,
+        _owner: TRef<'_, ResourceFormatSaver>,
+        path: GodotString,
+        resource: Ref<Resource>,
+        _flags: i64,
+    ) -> i64 {
+        let data = match Self::map_data(resource) {
+            None => return GodotError::InvalidParameter as i64,
+            Some(data) => data,
+        };
+        let file = File::new();
+        if let Err(error) = file.open(path, File::WRITE) {
+            return error as i64;
+        }
+        file.store_buffer(data);
+        file.close();
+        0
+    }
+
+    fn map_data(resource: Ref<Resource>) -> Option<ByteArray> {
+        let map = Instance::<HexMap, Shared>::try_from_base(resource)?;
+        unsafe { map.assume_safe() }
+            .map(|map, _| map.data.clone())
+            .ok()
+    }
+}
+
+/// Adds the loader and the saver of map files to Godot while it is in the tree. Added as
+/// autoload, so maps can be loaded before the main scene and in the editor.
+#[derive(NativeClass)]
+#[inherit(Node)]
+pub struct HexMapFormats;
+
+#[methods]
+impl HexMapFormats {
+    pub fn new(_owner: TRef<'_, Node>) -> Self {
+        Self
+    }
+
+    #[export]
+    pub fn _ready(&self, _owner: TRef<'_, Node>) {
+        register_formats();
+    }
+
+    #[export]
+    pub fn _exit_tree(&self, _owner: TRef<'_, Node>) {
+        unregister_formats();
+    }
+}
+
+/// Adds the loader and the saver of map files to Godot, unless they are added already.
+fn register_formats() {
+    if FORMATS.with(|formats| formats.borrow().is_some()) {
+        return;
+    }
+    let loader = Instance::<HexMapLoader, Unique>::new()
+        .into_base()
+        .into_shared();
+    let saver = Instance::<HexMapSaver, Unique>::new()
+        .into_base()
+        .into_shared();
+    ResourceLoader::godot_singleton().add_resource_format_loader(loader.clone(), false);
+    ResourceSaver::godot_singleton().add_resource_format_saver(saver.clone(), false);
+    FORMATS.with(|formats| *formats.borrow_mut() = Some((loader, saver)));
+}
+
+/// Removes the loader and the saver again before the library is unloaded.
+pub fn unregister_formats() {
+    if let Some((loader, saver)) = FORMATS.with(|formats| formats.borrow_mut().take()) {
+        ResourceLoader::godot_singleton().remove_resource_format_loader(loader);
+        ResourceSaver::godot_singleton().remove_resource_format_saver(saver);
+    }
+}
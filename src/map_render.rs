@@ -0,0 +1,220 @@
+use crate::hex::Vector2Di32;
+
+/// Direction the light of `shade` comes from, from the top left and above.
+const LIGHT: [f32; 3] = [-0.4, 0.8, -0.4];
+
+/// An RGBA image that shapes are drawn onto in key space, seen from above. Everything is drawn on
+/// the CPU, so it works without a viewport, e.g. on a headless server.
+pub struct Canvas {
+    pub width: i64,
+    pub height: i64,
+    pub pixels: Vec<u8>,
+    /// Key at the top left corner of the image.
+    origin: (f32, f32),
+    /// Key units per pixel.
+    scale: f32,
+}
+
+impl Canvas {
+    /// Creates a transparent image that fits the keys, keeping their aspect ratio.
+    pub fn new(width: i64, height: i64, keys: &[Vector2Di32]) -> Canvas {
+        let min_x = keys.iter().map(|key| key.x).min().unwrap_or(0) as f32;
+        let max_x = keys.iter().map(|key| key.x).max().unwrap_or(0) as f32;
+        let min_y = keys.iter().map(|key| key.y).min().unwrap_or(0) as f32;
+        let max_y = keys.iter().map(|key| key.y).max().unwrap_or(0) as f32;
+        let scale = ((max_x - min_x) / width as f32)
+            .max((max_y - min_y) / height as f32)
+            .max(f32::EPSILON);
+        Canvas {
+            width,
+            height,
+            pixels: vec![0; (width * height * 4) as usize],
+            origin: (
+                (min_x + max_x - width as f32 * scale) / 2.0,
+                (min_y + max_y - height as f32 * scale) / 2.0,
+            ),
+            scale,
+        }
+    }
+
+    /// Returns the size of a pixel in key units.
+    pub fn pixel_size(&self) -> f32 {
+        self.scale
+    }
+
+    /// Fills a triangle with corners in key space.
+    pub fn fill_triangle(&mut self, corners: [(f32, f32); 3], color: [u8; 4]) {
+        let corners = corners.map(|corner| self.to_pixel(corner));
+        let edge = |a: (f32, f32), b: (f32, f32), point: (f32, f32)| {
+            (b.0 - a.0) * (point.1 - a.1) - (b.1 - a.1) * (point.0 - a.0)
+        };
+        let area = edge(corners[0], corners[1], corners[2]);
+        if area == 0.0 {
+            return;
+        }
+        let xs = corners.iter().map(|corner| corner.0);
+        let ys = corners.iter().map(|corner| corner.1);
+        for y in self.rows(
+            ys.clone().fold(f32::MAX, f32::min),
+            ys.fold(f32::MIN, f32::max),
+        ) {
+            for x in self.columns(
+                xs.clone().fold(f32::MAX, f32::min),
+                xs.clone().fold(f32::MIN, f32::max),
+            ) {
+                let point = (x as f32 + 0.5, y as f32 + 0.5);
+                let inside = (0..3).all(|index| {
+                    edge(corners[index], corners[(index + 1) % 3], point) * area.signum() >= 0.0
+                });
+                if inside {
+                    self.blend(x, y, color);
+                }
+            }
+        }
+    }
+
+    /// Draws a line between two points in key space, `thickness` key units wide but at least one
+    /// pixel.
+    pub fn draw_line(&mut self, from: (f32, f32), to: (f32, f32), thickness: f32, color: [u8; 4]) {
+        let (from, to) = (self.to_pixel(from), self.to_pixel(to));
+        let radius = (thickness / self.scale).max(1.0) / 2.0;
+        let length = (to.0 - from.0).powi(2) + (to.1 - from.1).powi(2);
+        for y in self.rows(from.1.min(to.1) - radius, from.1.max(to.1) + radius) {
+            for x in self.columns(from.0.min(to.0) - radius, from.0.max(to.0) + radius) {
+                let point = (x as f32 + 0.5, y as f32 + 0.5);
+                let along = if length == 0.0 {
+                    0.0
+                } else {
+                    (((point.0 - from.0) * (to.0 - from.0) + (point.1 - from.1) * (to.1 - from.1))
+                        / length)
+                        .clamp(0.0, 1.0)
+                };
+                let closest = (
+                    from.0 + along * (to.0 - from.0),
+                    from.1 + along * (to.1 - from.1),
+                );
+                if (point.0 - closest.0).powi(2) + (point.1 - closest.1).powi(2) <= radius * radius
+                {
+                    self.blend(x, y, color);
+                }
+            }
+        }
+    }
+
+    fn to_pixel(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        (
+            (x - self.origin.0) / self.scale,
+            (y - self.origin.1) / self.scale,
+        )
+    }
+
+    fn rows(&self, top: f32, bottom: f32) -> std::ops::Range<i64> {
+        (top.floor() as i64).max(0)..(bottom.ceil() as i64).min(self.height)
+    }
+
+    fn columns(&self, left: f32, right: f32) -> std::ops::Range<i64> {
+        (left.floor() as i64).max(0)..(right.ceil() as i64).min(self.width)
+    }
+
+    /// Draws a color over a pixel, mixing by its alpha.
+    fn blend(&mut self, x: i64, y: i64, color: [u8; 4]) {
+        let index = ((y * self.width + x) * 4) as usize;
+        let alpha = color[3] as f32 / 255.0;
+        for channel in 0..3 {
+            let old = self.pixels[index + channel] as f32;
+            self.pixels[index + channel] =
+                (old + (color[channel] as f32 - old) * alpha).round() as u8;
+        }
+        let old = self.pixels[index + 3] as f32;
+        self.pixels[index + 3] = (old + (255.0 - old) * alpha).round() as u8;
+    }
+}
+
+/// Lights a color by the slope of a triangle with 3D corners, so slopes facing away from the
+/// light are darker.
+pub fn shade(color: [u8; 4], corners: [[f32; 3]; 3]) -> [u8; 4] {
+    let [a, b, c] = corners;
+    let first = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let second = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+    let mut normal = [
+        first[1] * second[2] - first[2] * second[1],
+        first[2] * second[0] - first[0] * second[2],
+        first[0] * second[1] - first[1] * second[0],
+    ];
+    if normal[1] < 0.0 {
+        normal = normal.map(|component| -component);
+    }
+    let length = normal
+        .iter()
+        .map(|component| component * component)
+        .sum::<f32>()
+        .sqrt();
+    let light_length = LIGHT
+        .iter()
+        .map(|component| component * component)
+        .sum::<f32>()
+        .sqrt();
+    let lit = if length == 0.0 {
+        1.0
+    } else {
+        normal.iter().zip(&LIGHT).map(|(n, l)| n * l).sum::<f32>() / length / light_length
+    };
+    // Flat ground keeps its color.
+    let brightness = (0.4 + 0.6 * lit / (LIGHT[1] / light_length)).clamp(0.0, 1.2);
+    [
+        (color[0] as f32 * brightness).round().min(255.0) as u8,
+        (color[1] as f32 * brightness).round().min(255.0) as u8,
+        (color[2] as f32 * brightness).round().min(255.0) as u8,
+        color[3],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(canvas: &Canvas, x: i64, y: i64) -> &[u8] {
+        let index = ((y * canvas.width + x) * 4) as usize;
+        &canvas.pixels[index..index + 4]
+    }
+
+    fn canvas() -> Canvas {
+        Canvas::new(10, 10, &[Vector2Di32::new(0, 0), Vector2Di32::new(10, 10)])
+    }
+
+    #[test]
+    fn fill_triangle_fills_pixels_inside() {
+        let mut canvas = canvas();
+        let red = [255, 0, 0, 255];
+
+        canvas.fill_triangle([(0.0, 0.0), (0.0, 10.0), (10.0, 0.0)], red);
+
+        assert_eq!(&red, pixel(&canvas, 1, 1));
+        assert_eq!(&red, pixel(&canvas, 8, 0));
+        assert_eq!(&[0, 0, 0, 0], pixel(&canvas, 8, 8));
+    }
+
+    #[test]
+    fn draw_line_blends_color() {
+        let mut canvas = canvas();
+        canvas.fill_triangle([(0.0, 0.0), (0.0, 10.0), (10.0, 0.0)], [0, 0, 0, 255]);
+
+        canvas.draw_line((0.0, 5.0), (10.0, 5.0), 1.0, [255, 255, 255, 128]);
+
+        assert_eq!(&[128, 128, 128, 255], pixel(&canvas, 2, 4));
+        assert_eq!(&[128, 128, 128, 128], pixel(&canvas, 8, 4));
+        assert_eq!(&[0, 0, 0, 255], pixel(&canvas, 2, 2));
+    }
+
+    #[test]
+    fn shade_darkens_slopes_facing_away_from_light() {
+        let color = [200, 200, 200, 255];
+        let flat = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]];
+        let towards = [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 1.0]];
+        let away = [[0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]];
+
+        assert_eq!(color, shade(color, flat));
+        assert!(shade(color, towards)[0] > 200);
+        assert!(shade(color, away)[0] < 200);
+    }
+}
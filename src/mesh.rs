@@ -0,0 +1,521 @@
+use std::collections::HashMap;
+use std::thread;
+
+/// Fewest items `build_parallel` gives to a thread, as starting threads for less costs more than
+/// it saves.
+const PARALLEL_CHUNK: usize = 64;
+
+/// A corner of a triangle or line with everything that is stored per vertex.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Corner {
+    pub position: [f32; 3],
+    pub uv: [f32; 2],
+    /// Second UV, e.g. the position on a texture that spans the whole map.
+    pub uv2: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl Corner {
+    /// Returns a white corner at the position, with both UVs at the origin.
+    pub fn new(position: [f32; 3]) -> Corner {
+        Corner {
+            position,
+            uv: [0.0; 2],
+            uv2: [0.0; 2],
+            color: [1.0; 4],
+        }
+    }
+}
+
+/// The arrays of an indexed surface of either triangles or lines, built without touching Godot,
+/// so they can be built on any thread and handed to an `ArrayMesh` in one call. Equal corners
+/// share a vertex.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshArrays {
+    pub positions: Vec<[f32; 3]>,
+    /// Sums of the normals of the triangles of every vertex, see `unit_normals`.
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub uv2s: Vec<[f32; 2]>,
+    pub colors: Vec<[f32; 4]>,
+    /// Three vertices per triangle, clockwise seen from the front as Godot expects, or two per
+    /// line.
+    pub indices: Vec<i32>,
+    vertices: HashMap<[u32; 11], i32>,
+}
+
+impl MeshArrays {
+    /// Adds a triangle from its corners, clockwise seen from the front, and adds its normal to
+    /// the normals of its vertices.
+    pub fn add_triangle(&mut self, corners: [Corner; 3]) {
+        let [a, b, c] = [
+            corners[0].position,
+            corners[1].position,
+            corners[2].position,
+        ];
+        let normal = cross(subtract(a, c), subtract(a, b));
+        for corner in corners.iter() {
+            let index = self.vertex(*corner);
+            let sum = &mut self.normals[index as usize];
+            for (sum, component) in sum.iter_mut().zip(normal.iter()) {
+                *sum += component;
+            }
+            self.indices.push(index);
+        }
+    }
+
+    /// Adds a line between two corners.
+    pub fn add_line(&mut self, from: Corner, to: Corner) {
+        let from = self.vertex(from);
+        let to = self.vertex(to);
+        self.indices.push(from);
+        self.indices.push(to);
+    }
+
+    /// Adds a flat quad of the given width from one point to another as two triangles that face
+    /// up.
+    pub fn add_ribbon(&mut self, from: [f32; 3], to: [f32; 3], width: f32) {
+        let side = [from[2] - to[2], 0.0, to[0] - from[0]];
+        let length = (side[0] * side[0] + side[2] * side[2]).sqrt();
+        if length == 0.0 {
+            return;
+        }
+        let side = [
+            side[0] / length * width / 2.0,
+            0.0,
+            side[2] / length * width / 2.0,
+        ];
+        let corner = |point: [f32; 3], sign: f32| {
+            Corner::new([
+                point[0] + side[0] * sign,
+                point[1],
+                point[2] + side[2] * sign,
+            ])
+        };
+
+        self.add_triangle([corner(from, 1.0), corner(from, -1.0), corner(to, -1.0)]);
+        self.add_triangle([corner(from, 1.0), corner(to, -1.0), corner(to, 1.0)]);
+    }
+
+    /// Adds a closed outline through the points, as lines if the width is 0 and else as ribbons of
+    /// the width.
+    pub fn add_outline(&mut self, points: &[[f32; 3]], width: f32) {
+        for (index, from) in points.iter().enumerate() {
+            let to = points[(index + 1) % points.len()];
+            if width > 0.0 {
+                self.add_ribbon(*from, to, width);
+            } else {
+                self.add_line(Corner::new(*from), Corner::new(to));
+            }
+        }
+    }
+
+    /// Adds the triangles of other arrays, e.g. of parts that were built on other threads.
+    pub fn append(&mut self, other: &MeshArrays) {
+        for triangle in other.indices.chunks(3) {
+            let mut corners = [Corner::new([0.0; 3]); 3];
+            for (corner, index) in corners.iter_mut().zip(triangle) {
+                *corner = other.corner(*index as usize);
+            }
+            self.add_triangle(corners);
+        }
+    }
+
+    /// Returns the normals of the vertices, the average of the normals of their triangles.
+    pub fn unit_normals(&self) -> Vec<[f32; 3]> {
+        self.normals
+            .iter()
+            .map(|normal| {
+                let length = normal.iter().map(|value| value * value).sum::<f32>().sqrt();
+                if length > 0.0 {
+                    [normal[0] / length, normal[1] / length, normal[2] / length]
+                } else {
+                    [0.0, 1.0, 0.0]
+                }
+            })
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Returns the smallest box around the vertices, None if there are none.
+    pub fn bounds(&self) -> Option<Bounds> {
+        let first = *self.positions.first()?;
+        let mut bounds = Bounds {
+            min: first,
+            max: first,
+        };
+        for position in &self.positions {
+            bounds.add(*position);
+        }
+        Some(bounds)
+    }
+
+    fn corner(&self, index: usize) -> Corner {
+        Corner {
+            position: self.positions[index],
+            uv: self.uvs[index],
+            uv2: self.uv2s[index],
+            color: self.colors[index],
+        }
+    }
+
+    /// Returns the index of the vertex of the corner, adding it if there is none.
+    fn vertex(&mut self, corner: Corner) -> i32 {
+        let mut key = [0; 11];
+        let values = corner
+            .position
+            .iter()
+            .chain(&corner.uv)
+            .chain(&corner.uv2)
+            .chain(&corner.color);
+        for (key, value) in key.iter_mut().zip(values) {
+            *key = value.to_bits();
+        }
+        let next = self.positions.len() as i32;
+        let index = *self.vertices.entry(key).or_insert(next);
+        if index == next {
+            self.positions.push(corner.position);
+            self.normals.push([0.0; 3]);
+            self.uvs.push(corner.uv);
+            self.uv2s.push(corner.uv2);
+            self.colors.push(corner.color);
+        }
+        index
+    }
+}
+
+/// Sums the normals of equal vertices in all parts, so parts of one surface that are drawn as
+/// separate meshes are shaded without seams where they meet.
+pub fn share_normals(parts: &mut [MeshArrays]) {
+    let mut sums: HashMap<[u32; 11], [f32; 3]> = HashMap::new();
+    for part in parts.iter() {
+        for (key, index) in &part.vertices {
+            let sum = sums.entry(*key).or_insert([0.0; 3]);
+            for (sum, component) in sum.iter_mut().zip(&part.normals[*index as usize]) {
+                *sum += component;
+            }
+        }
+    }
+    for part in parts.iter_mut() {
+        let MeshArrays {
+            vertices, normals, ..
+        } = part;
+        for (key, index) in vertices.iter() {
+            normals[*index as usize] = sums[key];
+        }
+    }
+}
+
+/// An axis-aligned box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Bounds {
+    /// Grows the box to contain the point.
+    pub fn add(&mut self, point: [f32; 3]) {
+        for axis in 0..3 {
+            self.min[axis] = self.min[axis].min(point[axis]);
+            self.max[axis] = self.max[axis].max(point[axis]);
+        }
+    }
+
+    /// Returns the eight corners of the box.
+    pub fn corners(&self) -> [[f32; 3]; 8] {
+        let mut corners = [[0.0; 3]; 8];
+        for (index, corner) in corners.iter_mut().enumerate() {
+            for (axis, value) in corner.iter_mut().enumerate() {
+                *value = if index & (1 << axis) == 0 {
+                    self.min[axis]
+                } else {
+                    self.max[axis]
+                };
+            }
+        }
+        corners
+    }
+
+    /// Returns the distance from the point to the nearest point of the box, 0 if it is inside.
+    pub fn distance_to(&self, point: [f32; 3]) -> f32 {
+        let mut sum = 0.0;
+        for axis in 0..3 {
+            let outside = (self.min[axis] - point[axis]).max(point[axis] - self.max[axis]);
+            sum += outside.max(0.0).powi(2);
+        }
+        sum.sqrt()
+    }
+
+    /// Returns whether the box lies completely in front of the plane of the normal and the
+    /// distance from the origin, e.g. outside of a camera frustum whose planes face outwards.
+    pub fn is_in_front_of(&self, normal: [f32; 3], distance: f32) -> bool {
+        // The corner that is furthest behind the plane.
+        let mut dot = 0.0;
+        for axis in 0..3 {
+            let value = if normal[axis] > 0.0 {
+                self.min[axis]
+            } else {
+                self.max[axis]
+            };
+            dot += normal[axis] * value;
+        }
+        dot > distance
+    }
+}
+
+/// Builds arrays for every item, spread over the available threads if there are enough items.
+/// The arrays are returned in the order of the items.
+pub fn build_parallel<T: Sync>(
+    items: &[T],
+    build: impl Fn(&T) -> MeshArrays + Sync,
+) -> Vec<MeshArrays> {
+    let threads = thread::available_parallelism().map_or(1, |count| count.get());
+    let chunk_size = ((items.len() + threads - 1) / threads).max(PARALLEL_CHUNK);
+    if items.len() <= chunk_size {
+        return items.iter().map(&build).collect();
+    }
+    let build = &build;
+    thread::scope(|scope| {
+        let threads: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(build).collect::<Vec<_>>()))
+            .collect();
+        threads
+            .into_iter()
+            .flat_map(|thread| thread.join().unwrap())
+            .collect()
+    })
+}
+
+/// The horizontal positions and heights of vertices in separate arrays, so their positions can be
+/// computed in one loop over contiguous values that the compiler can vectorize.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VertexColumns {
+    pub xs: Vec<f32>,
+    pub zs: Vec<f32>,
+    pub heights: Vec<i32>,
+}
+
+impl VertexColumns {
+    pub fn with_capacity(capacity: usize) -> VertexColumns {
+        VertexColumns {
+            xs: Vec::with_capacity(capacity),
+            zs: Vec::with_capacity(capacity),
+            heights: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn push(&mut self, x: f32, z: f32, height: i32) {
+        self.xs.push(x);
+        self.zs.push(z);
+        self.heights.push(height);
+    }
+
+    /// Returns the positions of the vertices, with the heights scaled by `node_height`.
+    pub fn positions(&self, node_height: f32) -> Vec<[f32; 3]> {
+        let ys: Vec<f32> = self
+            .heights
+            .iter()
+            .map(|height| *height as f32 * node_height)
+            .collect();
+        self.xs
+            .iter()
+            .zip(&ys)
+            .zip(&self.zs)
+            .map(|((x, y), z)| [*x, *y, *z])
+            .collect()
+    }
+}
+
+fn subtract(first: [f32; 3], second: [f32; 3]) -> [f32; 3] {
+    [
+        first[0] - second[0],
+        first[1] - second[1],
+        first[2] - second[2],
+    ]
+}
+
+fn cross(first: [f32; 3], second: [f32; 3]) -> [f32; 3] {
+    [
+        first[1] * second[2] - first[2] * second[1],
+        first[2] * second[0] - first[0] * second[2],
+        first[0] * second[1] - first[1] * second[0],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corner(position: [f32; 3]) -> Corner {
+        Corner {
+            uv: [position[0], position[2]],
+            ..Corner::new(position)
+        }
+    }
+
+    #[test]
+    fn add_triangle_shares_vertices_and_averages_normals() {
+        let mut arrays = MeshArrays::default();
+        arrays.add_triangle([
+            corner([0.0, 0.0, 0.0]),
+            corner([1.0, 0.0, 0.0]),
+            corner([0.0, 0.0, 1.0]),
+        ]);
+        arrays.add_triangle([
+            corner([1.0, 0.0, 0.0]),
+            corner([1.0, 1.0, 1.0]),
+            corner([0.0, 0.0, 1.0]),
+        ]);
+
+        assert_eq!(4, arrays.positions.len());
+        assert_eq!(vec![0, 1, 2, 1, 3, 2], arrays.indices);
+        let normals = arrays.unit_normals();
+        assert_eq!([0.0, 1.0, 0.0], normals[0]);
+        assert!(normals[1][1] > 0.0 && normals[1][0] < 0.0);
+    }
+
+    #[test]
+    fn add_triangle_keeps_corners_with_other_colors_apart() {
+        let mut arrays = MeshArrays::default();
+        let red = Corner {
+            color: [1.0, 0.0, 0.0, 1.0],
+            ..corner([0.0, 0.0, 0.0])
+        };
+
+        arrays.add_triangle([
+            corner([0.0, 0.0, 0.0]),
+            corner([1.0, 0.0, 0.0]),
+            corner([0.0, 0.0, 1.0]),
+        ]);
+        arrays.add_triangle([red, corner([1.0, 0.0, 0.0]), corner([0.0, 0.0, 1.0])]);
+
+        assert_eq!(vec![0, 1, 2, 3, 1, 2], arrays.indices);
+        assert_eq!([1.0, 0.0, 0.0, 1.0], arrays.colors[3]);
+    }
+
+    #[test]
+    fn append_merges_shared_vertices() {
+        let triangle = [
+            corner([0.0, 0.0, 0.0]),
+            corner([1.0, 0.0, 0.0]),
+            corner([0.0, 0.0, 1.0]),
+        ];
+        let mut first = MeshArrays::default();
+        first.add_triangle(triangle);
+        let mut second = MeshArrays::default();
+        second.add_triangle(triangle);
+
+        first.append(&second);
+
+        assert_eq!(3, first.positions.len());
+        assert_eq!(vec![0, 1, 2, 0, 1, 2], first.indices);
+        assert_eq!([0.0, 2.0, 0.0], first.normals[0]);
+    }
+
+    #[test]
+    fn add_ribbon_adds_quad_facing_up() {
+        let mut arrays = MeshArrays::default();
+
+        arrays.add_ribbon([0.0, 0.0, 0.0], [2.0, 0.0, 0.0], 1.0);
+
+        assert_eq!(
+            vec![
+                [0.0, 0.0, 0.5],
+                [0.0, 0.0, -0.5],
+                [2.0, 0.0, -0.5],
+                [2.0, 0.0, 0.5]
+            ],
+            arrays.positions
+        );
+        assert_eq!(vec![0, 1, 2, 0, 2, 3], arrays.indices);
+        assert!(arrays.unit_normals().iter().all(|normal| normal[1] > 0.0));
+    }
+
+    #[test]
+    fn add_outline_closes_the_loop() {
+        let points = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]];
+        let mut lines = MeshArrays::default();
+        let mut ribbons = MeshArrays::default();
+
+        lines.add_outline(&points, 0.0);
+        ribbons.add_outline(&points, 0.1);
+
+        assert_eq!(vec![0, 1, 1, 2, 2, 0], lines.indices);
+        assert_eq!(18, ribbons.indices.len());
+    }
+
+    #[test]
+    fn build_parallel_keeps_order_of_items() {
+        let items: Vec<f32> = (0..1000).map(|item| item as f32).collect();
+
+        let arrays = build_parallel(&items, |item| {
+            let mut arrays = MeshArrays::default();
+            arrays.add_line(Corner::new([*item; 3]), Corner::new([0.0; 3]));
+            arrays
+        });
+
+        assert_eq!(items.len(), arrays.len());
+        for (item, arrays) in items.iter().zip(&arrays) {
+            assert_eq!([*item; 3], arrays.positions[0]);
+        }
+    }
+
+    #[test]
+    fn share_normals_sums_normals_of_equal_vertices() {
+        let mut flat = MeshArrays::default();
+        flat.add_triangle([
+            corner([0.0, 0.0, 0.0]),
+            corner([1.0, 0.0, 0.0]),
+            corner([0.0, 0.0, 1.0]),
+        ]);
+        let mut steep = MeshArrays::default();
+        steep.add_triangle([
+            corner([1.0, 0.0, 0.0]),
+            corner([1.0, 1.0, 1.0]),
+            corner([0.0, 0.0, 1.0]),
+        ]);
+        let mut whole = flat.clone();
+        whole.append(&steep);
+
+        let mut parts = [flat, steep];
+        share_normals(&mut parts);
+
+        assert_eq!(whole.normals[1], parts[0].normals[1]);
+        assert_eq!(whole.normals[1], parts[1].normals[0]);
+        assert_eq!(whole.normals[0], parts[0].normals[0]);
+    }
+
+    #[test]
+    fn bounds_measure_distance_and_planes() {
+        let mut arrays = MeshArrays::default();
+        arrays.add_line(corner([0.0, 0.0, 0.0]), corner([2.0, 1.0, 2.0]));
+        let bounds = arrays.bounds().unwrap();
+
+        assert_eq!([0.0, 0.0, 0.0], bounds.min);
+        assert_eq!([2.0, 1.0, 2.0], bounds.max);
+        assert_eq!([2.0, 1.0, 2.0], bounds.corners()[7]);
+        assert_eq!(0.0, bounds.distance_to([1.0, 0.5, 1.0]));
+        assert_eq!(5.0, bounds.distance_to([-3.0, 5.0, 1.0]));
+        assert!(bounds.is_in_front_of([1.0, 0.0, 0.0], -1.0));
+        assert!(!bounds.is_in_front_of([1.0, 0.0, 0.0], 1.0));
+        assert!(!bounds.is_in_front_of([-1.0, 0.0, 0.0], -1.0));
+        assert_eq!(None, MeshArrays::default().bounds());
+    }
+
+    #[test]
+    fn positions_scale_heights() {
+        let mut columns = VertexColumns::with_capacity(2);
+        columns.push(1.0, 2.0, 3);
+        columns.push(-1.0, 0.5, -2);
+
+        assert_eq!(
+            vec![[1.0, 1.5, 2.0], [-1.0, -1.0, 0.5]],
+            columns.positions(0.5)
+        );
+    }
+}
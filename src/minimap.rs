@@ -0,0 +1,135 @@
+use crate::hex;
+use crate::hex::Vector2Di32;
+use std::collections::HashMap;
+
+/// Color of the shallowest water on the minimap.
+const WATER: [u8; 4] = [40, 100, 180, 255];
+
+/// A square top-down RGBA image of the cells, one color per cell, pixels outside of the cells
+/// are transparent. It remembers the color of every cell, so after an edit only the cells whose
+/// color changed are painted again.
+pub struct Minimap {
+    pub size: i64,
+    pub pixels: Vec<u8>,
+    /// Key at the top left corner of the image.
+    origin: (f32, f32),
+    /// Key units per pixel.
+    scale: f32,
+    colors: HashMap<Vector2Di32, [u8; 4]>,
+}
+
+impl Minimap {
+    /// Creates a transparent image of `size` by `size` pixels that fits the cells, keeping their
+    /// aspect ratio.
+    pub fn new(size: i64, cells: &[Vector2Di32]) -> Minimap {
+        // The corners of a cell are two key units away from its center along both axes.
+        let min_x = cells.iter().map(|cell| cell.x).min().unwrap_or(0) - 2;
+        let max_x = cells.iter().map(|cell| cell.x).max().unwrap_or(0) + 2;
+        let min_y = cells.iter().map(|cell| cell.y).min().unwrap_or(0) - 2;
+        let max_y = cells.iter().map(|cell| cell.y).max().unwrap_or(0) + 2;
+        let extent = (max_x - min_x).max(max_y - min_y) as f32;
+        Minimap {
+            size,
+            pixels: vec![0; (size * size * 4) as usize],
+            origin: (
+                (min_x + max_x) as f32 / 2.0 - extent / 2.0,
+                (min_y + max_y) as f32 / 2.0 - extent / 2.0,
+            ),
+            scale: extent / size as f32,
+            colors: HashMap::new(),
+        }
+    }
+
+    /// Paints the cells whose color differs from the last time they were painted. Returns
+    /// whether any cell was painted.
+    pub fn update(&mut self, colors: impl Iterator<Item = (Vector2Di32, [u8; 4])>) -> bool {
+        let mut changed = false;
+        for (cell, color) in colors {
+            if self.colors.insert(cell, color) != Some(color) {
+                self.paint(cell, color);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    fn paint(&mut self, cell: Vector2Di32, color: [u8; 4]) {
+        let (origin, scale, last) = (self.origin, self.scale, self.size - 1);
+        let pixel = |key: i32, origin: f32| ((key as f32 - origin) / scale).floor() as i64;
+        let rows = pixel(cell.y - 2, origin.1).max(0)..=pixel(cell.y + 2, origin.1).min(last);
+        let columns = pixel(cell.x - 2, origin.0).max(0)..=pixel(cell.x + 2, origin.0).min(last);
+        for y in rows {
+            for x in columns.clone() {
+                let key_x = origin.0 + (x as f32 + 0.5) * scale;
+                let key_y = origin.1 + (y as f32 + 0.5) * scale;
+                if hex::nearest_cell(key_x, key_y) == cell {
+                    let index = ((y * self.size + x) * 4) as usize;
+                    self.pixels[index..index + 4].copy_from_slice(&color);
+                }
+            }
+        }
+    }
+}
+
+/// Returns the color of a cell on the minimap. Cells at or below the water level are blue, land
+/// has the color of its terrain type. Both get darker towards the lowest height.
+pub fn cell_color(
+    type_color: [u8; 4],
+    height: i32,
+    water_level: Option<i32>,
+    (lowest, highest): (i32, i32),
+) -> [u8; 4] {
+    let (color, low, high) = match water_level {
+        Some(water_level) if height <= water_level => (WATER, lowest, water_level),
+        _ => (type_color, water_level.unwrap_or(lowest), highest),
+    };
+    let brightness =
+        0.5 + 0.5 * ((height - low) as f32 / (high - low).max(1) as f32).clamp(0.0, 1.0);
+    [
+        (color[0] as f32 * brightness).round() as u8,
+        (color[1] as f32 * brightness).round() as u8,
+        (color[2] as f32 * brightness).round() as u8,
+        color[3],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(minimap: &Minimap, x: i64, y: i64) -> &[u8] {
+        let index = ((y * minimap.size + x) * 4) as usize;
+        &minimap.pixels[index..index + 4]
+    }
+
+    #[test]
+    fn update_paints_changed_cells() {
+        let cells = hex::cells_in_range(Vector2Di32::zero(), 1);
+        let mut minimap = Minimap::new(32, &cells);
+        let red = [255, 0, 0, 255];
+
+        assert!(minimap.update(cells.iter().map(|cell| (*cell, red))));
+        assert_eq!(&red, pixel(&minimap, 16, 16));
+        assert_eq!(&[0, 0, 0, 0], pixel(&minimap, 0, 0));
+
+        assert!(!minimap.update(cells.iter().map(|cell| (*cell, red))));
+        let blue = [0, 0, 255, 255];
+        assert!(minimap.update(vec![(Vector2Di32::zero(), blue)].into_iter()));
+        assert_eq!(&blue, pixel(&minimap, 16, 16));
+        assert_eq!(&red, pixel(&minimap, 16, 3));
+    }
+
+    #[test]
+    fn cell_color_shades_land_and_water() {
+        let grass = [0, 200, 0, 255];
+
+        assert_eq!([0, 200, 0, 255], cell_color(grass, 10, Some(0), (-10, 10)));
+        assert_eq!([0, 110, 0, 255], cell_color(grass, 1, Some(0), (-10, 10)));
+        assert_eq!(WATER, cell_color(grass, 0, Some(0), (-10, 10)));
+        assert_eq!(
+            [20, 50, 90, 255],
+            cell_color(grass, -10, Some(0), (-10, 10))
+        );
+        assert_eq!([0, 100, 0, 255], cell_color(grass, -10, None, (-10, 10)));
+    }
+}
@@ -0,0 +1,104 @@
+use gdnative::prelude::*;
+
+/// The settings of the generators, so they can be saved as resources, shared and swapped, e.g.
+/// for "archipelago" or "highlands" maps. See the properties of the same names on `HexTerrain`.
+/// `sea_level` is the height of the "water" elevation level.
+#[derive(NativeClass)]
+#[inherit(Resource)]
+pub struct HexGenPreset {
+    #[property]
+    pub generation_steps: VariantArray,
+    #[property]
+    pub noise_octaves: i64,
+    #[property]
+    pub noise_lacunarity: f64,
+    #[property]
+    pub noise_gain: f64,
+    #[property]
+    pub noise_warp: f64,
+    #[property]
+    pub island_distortion: f64,
+    #[property]
+    pub island_depth: i64,
+    #[property]
+    pub sea_level: i64,
+    #[property]
+    pub biome_types: Int32Array,
+    #[property]
+    pub biome_temperatures: Float32Array,
+    #[property]
+    pub biome_moistures: Float32Array,
+    #[property]
+    pub biome_height_cooling: f64,
+    #[property]
+    pub biome_variation: f64,
+    #[property]
+    pub wfc_module_types: Int32Array,
+    #[property]
+    pub wfc_module_heights: Int32Array,
+    #[property]
+    pub wfc_module_weights: Float32Array,
+    #[property]
+    pub wfc_rules: Int32Array,
+}
+
+#[methods]
+impl HexGenPreset {
+    pub fn new(_owner: TRef<'_, Resource>) -> Self {
+        Self::default()
+    }
+
+    /// Returns the steps `HexTerrain::regenerate` runs by default: noise, an island mask, erosion
+    /// and biomes.
+    fn default_generation_steps() -> VariantArray {
+        let step = |values: &[Variant]| {
+            let step = VariantArray::new();
+            for value in values {
+                step.push(value);
+            }
+            step.into_shared().to_variant()
+        };
+        let steps = VariantArray::new();
+        steps.push(step(&[
+            "noise".to_variant(),
+            0.1.to_variant(),
+            4.0.to_variant(),
+        ]));
+        steps.push(step(&[
+            "island".to_variant(),
+            0.5.to_variant(),
+            2.0.to_variant(),
+        ]));
+        steps.push(step(&[
+            "erosion".to_variant(),
+            35.0.to_variant(),
+            5_i64.to_variant(),
+        ]));
+        steps.push(step(&["biomes".to_variant()]));
+        steps.into_shared()
+    }
+}
+
+impl Default for HexGenPreset {
+    fn default() -> Self {
+        Self {
+            generation_steps: Self::default_generation_steps(),
+            noise_octaves: 4,
+            noise_lacunarity: 2.0,
+            noise_gain: 0.5,
+            noise_warp: 0.0,
+            island_distortion: 0.3,
+            island_depth: 3,
+            sea_level: -1,
+            biome_types: Int32Array::from_vec(vec![0, 1, 2, 3, 4]),
+            biome_temperatures: Float32Array::from_vec(vec![0.6, 0.9, 0.6, 0.2, 0.0]),
+            biome_moistures: Float32Array::from_vec(vec![0.5, 0.1, 0.9, 0.4, 0.5]),
+            biome_height_cooling: 0.1,
+            biome_variation: 0.2,
+            wfc_module_types: Int32Array::from_vec(vec![0, 1, 2]),
+            wfc_module_heights: Int32Array::from_vec(vec![-1, 0, 1]),
+            wfc_module_weights: Float32Array::from_vec(vec![1.0, 1.0, 1.0]),
+            wfc_rules: Int32Array::from_vec(vec![0, -1, 0, 0, -1, 1, 1, -1, 1, 1, -1, 2, 2, -1, 2]),
+        }
+    }
+}
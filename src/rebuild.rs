@@ -0,0 +1,102 @@
+use std::thread;
+use std::thread::JoinHandle;
+
+/// Computes a new state from a copy of the committed one on a background thread. The committed
+/// state stays untouched until the rebuild is done and swapped in as a whole, so queries never see
+/// a half finished edit.
+pub struct Rebuild<V> {
+    worker: Option<(String, u64, JoinHandle<V>)>,
+}
+
+impl<V> Default for Rebuild<V> {
+    fn default() -> Rebuild<V> {
+        Rebuild { worker: None }
+    }
+}
+
+impl<V: Send + 'static> Rebuild<V> {
+    /// Returns whether a rebuild is still computed.
+    pub fn is_running(&self) -> bool {
+        self.worker.is_some()
+    }
+
+    /// Returns whether a rebuild was started and is done, so `finish` returns it.
+    pub fn is_done(&self) -> bool {
+        matches!(&self.worker, Some((_, _, worker)) if worker.is_finished())
+    }
+
+    /// Applies `edit` to `copy`, a copy of the committed state, on a background thread.
+    /// `version` identifies the committed state, so `finish` can tell whether it changed in the
+    /// meantime. Returns false if another rebuild is still computed.
+    pub fn start<F>(&mut self, operation: &str, version: u64, mut copy: V, edit: F) -> bool
+    where
+        F: FnOnce(&mut V) + Send + 'static,
+    {
+        if self.is_running() {
+            return false;
+        }
+        let worker = thread::spawn(move || {
+            edit(&mut copy);
+            copy
+        });
+        self.worker = Some((operation.to_owned(), version, worker));
+        true
+    }
+
+    /// Returns the name of the operation and the new state once the rebuild is done, None while it
+    /// is still computed. There is no new state if the rebuild failed or the committed state is no
+    /// longer the one with `version`, as the result would undo its changes.
+    pub fn finish(&mut self, version: u64) -> Option<(String, Option<V>)> {
+        if !self.is_done() {
+            return None;
+        }
+        let (operation, started, worker) = self.worker.take()?;
+        match worker.join() {
+            Ok(value) if started == version => Some((operation, Some(value))),
+            _ => Some((operation, None)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wait<V: Send + 'static>(
+        rebuild: &mut Rebuild<V>,
+        version: u64,
+    ) -> Option<(String, Option<V>)> {
+        while rebuild.is_running() && !rebuild.is_done() {
+            thread::yield_now();
+        }
+        rebuild.finish(version)
+    }
+
+    #[test]
+    fn finish_returns_edited_copy() {
+        let committed = vec![1, 2, 3];
+        let mut rebuild = Rebuild::default();
+
+        assert!(
+            rebuild.start("double", 7, committed.clone(), |values: &mut Vec<i32>| {
+                values.iter_mut().for_each(|value| *value *= 2)
+            })
+        );
+        assert!(!rebuild.start("other", 7, committed.clone(), |_| {}));
+        assert_eq!(vec![1, 2, 3], committed);
+
+        let (operation, value) = wait(&mut rebuild, 7).unwrap();
+        assert_eq!("double", operation);
+        assert_eq!(Some(vec![2, 4, 6]), value);
+        assert!(!rebuild.is_running());
+        assert_eq!(None, rebuild.finish(7));
+    }
+
+    #[test]
+    fn finish_drops_result_when_committed_state_changed() {
+        let mut rebuild = Rebuild::default();
+        rebuild.start("double", 1, 2, |value: &mut i32| *value *= 2);
+
+        assert_eq!(Some(("double".to_owned(), None)), wait(&mut rebuild, 2));
+    }
+}
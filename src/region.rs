@@ -0,0 +1,201 @@
+use crate::hex::Vector2Di32;
+use terrain::save::Chunk;
+
+const HEIGHTS: [u8; 4] = *b"RHGT";
+const TYPES: [u8; 4] = *b"RTYP";
+const HOLES: [u8; 4] = *b"RHOL";
+const DECKS: [u8; 4] = *b"RDCK";
+const EDGES: [u8; 4] = *b"REDG";
+
+/// Heights, terrain types, holes, bridges and edge features of a set of cells, relative to an
+/// anchor cell.
+#[derive(Clone, Default)]
+pub struct Region {
+    pub heights: Vec<(Vector2Di32, i32)>,
+    pub terrain_types: Vec<(Vector2Di32, i32)>,
+    pub holes: Vec<(Vector2Di32, bool)>,
+    pub deck_heights: Vec<(Vector2Di32, Option<i32>)>,
+    pub edge_features: Vec<(Vector2Di32, Vector2Di32, i32)>,
+}
+
+impl Region {
+    /// Returns a copy of the region with every offset transformed.
+    pub fn transformed(&self, transform: impl Fn(Vector2Di32) -> Vector2Di32) -> Region {
+        Region {
+            heights: self
+                .heights
+                .iter()
+                .map(|(offset, height)| (transform(*offset), *height))
+                .collect(),
+            terrain_types: self
+                .terrain_types
+                .iter()
+                .map(|(offset, terrain_type)| (transform(*offset), *terrain_type))
+                .collect(),
+            holes: self
+                .holes
+                .iter()
+                .map(|(offset, hole)| (transform(*offset), *hole))
+                .collect(),
+            deck_heights: self
+                .deck_heights
+                .iter()
+                .map(|(offset, deck_height)| (transform(*offset), *deck_height))
+                .collect(),
+            edge_features: self
+                .edge_features
+                .iter()
+                .map(|(first, second, feature)| (transform(*first), transform(*second), *feature))
+                .collect(),
+        }
+    }
+
+    /// Returns the region as chunks of a save, e.g. to stream it to a file.
+    pub fn to_chunks(&self) -> Vec<Chunk> {
+        let mut heights = Vec::new();
+        for (key, height) in &self.heights {
+            write_ints(&mut heights, &[key.x, key.y, *height]);
+        }
+        let mut types = Vec::new();
+        for (cell, terrain_type) in &self.terrain_types {
+            write_ints(&mut types, &[cell.x, cell.y, *terrain_type]);
+        }
+        let mut holes = Vec::new();
+        for (cell, hole) in &self.holes {
+            write_ints(&mut holes, &[cell.x, cell.y, *hole as i32]);
+        }
+        let mut decks = Vec::new();
+        for (cell, deck_height) in &self.deck_heights {
+            let (has_deck, deck_height) = deck_height.map_or((0, 0), |height| (1, height));
+            write_ints(&mut decks, &[cell.x, cell.y, has_deck, deck_height]);
+        }
+        let mut edges = Vec::new();
+        for (first, second, feature) in &self.edge_features {
+            write_ints(
+                &mut edges,
+                &[first.x, first.y, second.x, second.y, *feature],
+            );
+        }
+        vec![
+            Chunk {
+                id: HEIGHTS,
+                data: heights,
+            },
+            Chunk {
+                id: TYPES,
+                data: types,
+            },
+            Chunk {
+                id: HOLES,
+                data: holes,
+            },
+            Chunk {
+                id: DECKS,
+                data: decks,
+            },
+            Chunk {
+                id: EDGES,
+                data: edges,
+            },
+        ]
+    }
+
+    /// Reads a region from the chunks of `to_chunks`. Returns None if a chunk is missing or
+    /// damaged.
+    pub fn from_chunks(chunks: &[Chunk]) -> Option<Region> {
+        let key = |values: &[i32], index: usize| Vector2Di32::new(values[index], values[index + 1]);
+        Some(Region {
+            heights: read_ints(chunks, HEIGHTS, 3)?
+                .map(|values| (key(&values, 0), values[2]))
+                .collect(),
+            terrain_types: read_ints(chunks, TYPES, 3)?
+                .map(|values| (key(&values, 0), values[2]))
+                .collect(),
+            holes: read_ints(chunks, HOLES, 3)?
+                .map(|values| (key(&values, 0), values[2] != 0))
+                .collect(),
+            deck_heights: read_ints(chunks, DECKS, 4)?
+                .map(|values| (key(&values, 0), Some(values[3]).filter(|_| values[2] != 0)))
+                .collect(),
+            edge_features: read_ints(chunks, EDGES, 5)?
+                .map(|values| (key(&values, 0), key(&values, 2), values[4]))
+                .collect(),
+        })
+    }
+}
+
+fn write_ints(data: &mut Vec<u8>, values: &[i32]) {
+    for value in values {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// Returns the records of `count` integers of a chunk, None if the chunk is missing or its length
+/// does not fit.
+fn read_ints(
+    chunks: &[Chunk],
+    id: [u8; 4],
+    count: usize,
+) -> Option<impl Iterator<Item = Vec<i32>> + '_> {
+    let data = &chunks.iter().find(|chunk| chunk.id == id)?.data;
+    if data.len() % (count * 4) != 0 {
+        return None;
+    }
+    Some(data.chunks(count * 4).map(|record| {
+        record
+            .chunks(4)
+            .map(|value| i32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+            .collect()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transformed_transforms_all_offsets() {
+        let region = Region {
+            heights: vec![(Vector2Di32::new(1, 2), 3)],
+            terrain_types: vec![(Vector2Di32::new(0, 0), 4)],
+            holes: vec![(Vector2Di32::new(0, 0), true)],
+            deck_heights: vec![(Vector2Di32::new(0, 0), Some(6))],
+            edge_features: vec![(Vector2Di32::new(0, 0), Vector2Di32::new(1, 2), 5)],
+        };
+        let offset = Vector2Di32::new(3, -2);
+
+        let transformed = region.transformed(|key| key + offset);
+
+        assert_eq!(vec![(Vector2Di32::new(4, 0), 3)], transformed.heights);
+        assert_eq!(vec![(offset, 4)], transformed.terrain_types);
+        assert_eq!(vec![(offset, true)], transformed.holes);
+        assert_eq!(vec![(offset, Some(6))], transformed.deck_heights);
+        assert_eq!(
+            vec![(offset, Vector2Di32::new(4, 0), 5)],
+            transformed.edge_features
+        );
+    }
+
+    #[test]
+    fn from_chunks_returns_region_of_to_chunks() {
+        let region = Region {
+            heights: vec![(Vector2Di32::new(1, -2), 3)],
+            terrain_types: vec![(Vector2Di32::new(0, 0), 4)],
+            holes: vec![(Vector2Di32::new(0, 0), true)],
+            deck_heights: vec![
+                (Vector2Di32::new(0, 0), Some(6)),
+                (Vector2Di32::new(2, 0), None),
+            ],
+            edge_features: vec![(Vector2Di32::new(0, 0), Vector2Di32::new(1, -2), 5)],
+        };
+
+        let read = Region::from_chunks(&region.to_chunks()).unwrap();
+
+        assert_eq!(region.heights, read.heights);
+        assert_eq!(region.terrain_types, read.terrain_types);
+        assert_eq!(region.holes, read.holes);
+        assert_eq!(region.deck_heights, read.deck_heights);
+        assert_eq!(region.edge_features, read.edge_features);
+        assert!(Region::from_chunks(&region.to_chunks()[1..]).is_none());
+    }
+}
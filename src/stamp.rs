@@ -0,0 +1,103 @@
+use crate::hex::Vector2Di32;
+use crate::region::Region;
+use gdnative::prelude::*;
+
+/// A reusable piece of terrain. Heights are relative to the height of the anchor cell, offsets are
+/// relative to its center. The data is kept in exported arrays, so stamps can be saved as
+/// resources.
+#[derive(NativeClass)]
+#[inherit(Resource)]
+pub struct HexStamp {
+    #[property]
+    pub vertex_offsets: Vector2Array,
+    #[property]
+    pub vertex_heights: Int32Array,
+    #[property]
+    pub cell_offsets: Vector2Array,
+    #[property]
+    pub cell_types: Int32Array,
+    /// Five values per feature: the offsets of both vertices followed by the feature.
+    #[property]
+    pub edge_features: Int32Array,
+}
+
+#[methods]
+impl HexStamp {
+    pub fn new(_owner: TRef<'_, Resource>) -> Self {
+        Self::from_region(&Region::default())
+    }
+
+    pub fn from_region(region: &Region) -> Self {
+        let mut edge_features = Vec::new();
+        for (first, second, feature) in &region.edge_features {
+            edge_features.extend_from_slice(&[first.x, first.y, second.x, second.y, *feature]);
+        }
+
+        Self {
+            vertex_offsets: Self::offsets_to_array(region.heights.iter().map(|(key, _)| *key)),
+            vertex_heights: Int32Array::from_vec(
+                region.heights.iter().map(|(_, height)| *height).collect(),
+            ),
+            cell_offsets: Self::offsets_to_array(
+                region.terrain_types.iter().map(|(cell, _)| *cell),
+            ),
+            cell_types: Int32Array::from_vec(
+                region
+                    .terrain_types
+                    .iter()
+                    .map(|(_, terrain_type)| *terrain_type)
+                    .collect(),
+            ),
+            edge_features: Int32Array::from_vec(edge_features),
+        }
+    }
+
+    /// Returns the heights, terrain types and edge features of the stamp. Holes and bridges are
+    /// not part of stamps.
+    pub fn to_region(&self) -> Region {
+        let edge_features = self.edge_features.read();
+        Region {
+            heights: Self::offsets_from_array(&self.vertex_offsets)
+                .into_iter()
+                .zip(self.vertex_heights.read().iter().copied())
+                .collect(),
+            terrain_types: Self::offsets_from_array(&self.cell_offsets)
+                .into_iter()
+                .zip(self.cell_types.read().iter().copied())
+                .collect(),
+            holes: Vec::new(),
+            deck_heights: Vec::new(),
+            edge_features: edge_features
+                .chunks_exact(5)
+                .map(|feature| {
+                    (
+                        Vector2Di32::new(feature[0], feature[1]),
+                        Vector2Di32::new(feature[2], feature[3]),
+                        feature[4],
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[export]
+    pub fn is_empty(&self, _owner: TRef<'_, Resource>) -> bool {
+        self.vertex_offsets.len() == 0
+    }
+
+    fn offsets_to_array(offsets: impl Iterator<Item = Vector2Di32>) -> Vector2Array {
+        Vector2Array::from_vec(
+            offsets
+                .map(|offset| Vector2::new(offset.x as f32, offset.y as f32))
+                .collect(),
+        )
+    }
+
+    fn offsets_from_array(offsets: &Vector2Array) -> Vec<Vector2Di32> {
+        offsets
+            .read()
+            .iter()
+            .map(|offset| Vector2Di32::new(offset.x.round() as i32, offset.y.round() as i32))
+            .collect()
+    }
+}
@@ -0,0 +1,145 @@
+use crate::heightmap::Heightmap;
+use crate::hex_terrain::HexTerrain;
+use crate::stamp::HexStamp;
+use gdnative::api::{ImageTexture, ResourceSaver};
+use gdnative::prelude::*;
+
+/// A named collection of stamps with thumbnails for editor palettes. The thumbnails are rendered
+/// like `HexTerrain.export_heightmap`.
+#[derive(NativeClass)]
+#[inherit(Resource)]
+pub struct HexStampLibrary {
+    #[property]
+    names: StringArray,
+    #[property]
+    stamps: VariantArray,
+    #[property]
+    thumbnails: VariantArray,
+}
+
+#[methods]
+impl HexStampLibrary {
+    pub fn new(_owner: TRef<'_, Resource>) -> Self {
+        Self {
+            names: StringArray::new(),
+            stamps: VariantArray::new_shared(),
+            thumbnails: VariantArray::new_shared(),
+        }
+    }
+
+    #[export]
+    pub fn get_names(&self, _owner: TRef<'_, Resource>) -> StringArray {
+        self.names.clone()
+    }
+
+    /// Adds a stamp with a generated thumbnail. A stamp with the same name is replaced.
+    #[export]
+    pub fn add_stamp(
+        &mut self,
+        _owner: TRef<'_, Resource>,
+        name: GodotString,
+        stamp: Instance<HexStamp, Shared>,
+    ) {
+        let heights = unsafe { stamp.assume_safe() }
+            .map(|stamp, _| stamp.to_region().heights)
+            .unwrap_or_default();
+        let texture = ImageTexture::new();
+        texture.create_from_image(HexTerrain::heightmap_image(&Heightmap::render(&heights)), 0);
+
+        self.remove(&name);
+        self.names.push(name);
+        self.stamps = Self::pushed(&self.stamps, stamp.owned_to_variant());
+        self.thumbnails = Self::pushed(&self.thumbnails, texture.owned_to_variant());
+    }
+
+    /// Removes the named stamp. Returns whether it existed.
+    #[export]
+    pub fn remove_stamp(&mut self, _owner: TRef<'_, Resource>, name: GodotString) -> bool {
+        self.remove(&name)
+    }
+
+    /// Returns the named stamp, or null if there is none.
+    #[export]
+    pub fn get_stamp(&self, _owner: TRef<'_, Resource>, name: GodotString) -> Variant {
+        self.index_of(&name)
+            .map_or_else(Variant::new, |index| self.stamps.get(index as i32))
+    }
+
+    /// Returns the thumbnail texture of the named stamp, or null if there is none.
+    #[export]
+    pub fn get_thumbnail(&self, _owner: TRef<'_, Resource>, name: GodotString) -> Variant {
+        self.index_of(&name)
+            .map_or_else(Variant::new, |index| self.thumbnails.get(index as i32))
+    }
+
+    /// Saves the library to a resource file. Returns the Godot error code, 0 on success.
+    #[export]
+    pub fn save(&self, owner: TRef<'_, Resource>, path: GodotString) -> i64 {
+        match ResourceSaver::godot_singleton().save(path, owner, 0) {
+            Ok(_) => 0,
+            Err(error) => error as i64,
+        }
+    }
+
+    /// Replaces the content of the library with a library loaded from a resource file. Returns
+    /// whether it could be loaded.
+    #[export]
+    pub fn load(&mut self, _owner: TRef<'_, Resource>, path: GodotString) -> bool {
+        let resource = match ResourceLoader::godot_singleton().load(path, "Resource", false) {
+            None => return false,
+            Some(resource) => resource,
+        };
+        let library = match Instance::<HexStampLibrary, Shared>::try_from_base(resource) {
+            None => return false,
+            Some(library) => library,
+        };
+
+        let content = unsafe { library.assume_safe() }.map(|library, _| {
+            (
+                library.names.clone(),
+                library.stamps.duplicate().into_shared(),
+                library.thumbnails.duplicate().into_shared(),
+            )
+        });
+        match content {
+            Err(_) => false,
+            Ok((names, stamps, thumbnails)) => {
+                self.names = names;
+                self.stamps = stamps;
+                self.thumbnails = thumbnails;
+                true
+            }
+        }
+    }
+
+    fn index_of(&self, name: &GodotString) -> Option<usize> {
+        self.names
+            .read()
+            .iter()
+            .position(|existing| existing == name)
+    }
+
+    fn remove(&mut self, name: &GodotString) -> bool {
+        let index = match self.index_of(name) {
+            None => return false,
+            Some(index) => index,
+        };
+
+        self.names.remove(index as i32);
+        self.stamps = Self::without(&self.stamps, index);
+        self.thumbnails = Self::without(&self.thumbnails, index);
+        true
+    }
+
+    fn pushed(array: &VariantArray, value: Variant) -> VariantArray {
+        let result = array.duplicate();
+        result.push(value);
+        result.into_shared()
+    }
+
+    fn without(array: &VariantArray, index: usize) -> VariantArray {
+        let result = array.duplicate();
+        result.remove(index as i32);
+        result.into_shared()
+    }
+}
@@ -0,0 +1,159 @@
+use std::fmt::Write;
+use std::time::Duration;
+
+/// How long one kind of work took.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Timing {
+    pub count: u64,
+    pub last: Duration,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl Timing {
+    pub fn average(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::default();
+        }
+        self.total / self.count as u32
+    }
+}
+
+/// Collects how long the kinds of work take and how often things happen, e.g. how many nodes are
+/// created, so it can be seen where the time goes without a profiler. Kinds are listed in the
+/// order they were first recorded.
+#[derive(Clone, Debug, Default)]
+pub struct Telemetry {
+    timings: Vec<(&'static str, Timing)>,
+    counters: Vec<(&'static str, u64)>,
+}
+
+impl Telemetry {
+    /// Records that a kind of work took the duration.
+    pub fn record(&mut self, name: &'static str, duration: Duration) {
+        let timing = match self.timings.iter_mut().find(|(other, _)| *other == name) {
+            Some((_, timing)) => timing,
+            None => {
+                self.timings.push((name, Timing::default()));
+                &mut self.timings.last_mut().unwrap().1
+            }
+        };
+        timing.count += 1;
+        timing.last = duration;
+        timing.total += duration;
+        timing.max = timing.max.max(duration);
+    }
+
+    /// Adds the amount to a counter.
+    pub fn count(&mut self, name: &'static str, amount: u64) {
+        if amount == 0 {
+            return;
+        }
+        match self.counters.iter_mut().find(|(other, _)| *other == name) {
+            Some((_, count)) => *count += amount,
+            None => self.counters.push((name, amount)),
+        }
+    }
+
+    pub fn timings(&self) -> &[(&'static str, Timing)] {
+        &self.timings
+    }
+
+    pub fn counters(&self) -> &[(&'static str, u64)] {
+        &self.counters
+    }
+
+    /// Returns the value of a counter, 0 if nothing was counted.
+    pub fn counter(&self, name: &str) -> u64 {
+        self.counters
+            .iter()
+            .find(|(other, _)| *other == name)
+            .map_or(0, |(_, count)| *count)
+    }
+
+    pub fn reset(&mut self) {
+        self.timings.clear();
+        self.counters.clear();
+    }
+
+    /// Returns one line per timing and counter, e.g. for an overlay.
+    pub fn summary(&self) -> String {
+        let milliseconds = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let mut text = String::new();
+        for (name, timing) in &self.timings {
+            writeln!(
+                text,
+                "{}: {:.2} ms (average {:.2} ms, max {:.2} ms, {} times)",
+                name,
+                milliseconds(timing.last),
+                milliseconds(timing.average()),
+                milliseconds(timing.max),
+                timing.count
+            )
+            .unwrap();
+        }
+        for (name, count) in &self.counters {
+            writeln!(text, "{}: {}", name, count).unwrap();
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_collects_timings_by_name() {
+        let mut telemetry = Telemetry::default();
+
+        telemetry.record("mesh", Duration::from_millis(4));
+        telemetry.record("grid", Duration::from_millis(1));
+        telemetry.record("mesh", Duration::from_millis(2));
+
+        assert_eq!(
+            vec![
+                (
+                    "mesh",
+                    Timing {
+                        count: 2,
+                        last: Duration::from_millis(2),
+                        total: Duration::from_millis(6),
+                        max: Duration::from_millis(4),
+                    }
+                ),
+                (
+                    "grid",
+                    Timing {
+                        count: 1,
+                        last: Duration::from_millis(1),
+                        total: Duration::from_millis(1),
+                        max: Duration::from_millis(1),
+                    }
+                ),
+            ],
+            telemetry.timings()
+        );
+        assert_eq!(Duration::from_millis(3), telemetry.timings()[0].1.average());
+    }
+
+    #[test]
+    fn summary_lists_timings_and_counters() {
+        let mut telemetry = Telemetry::default();
+        telemetry.record("mesh", Duration::from_millis(4));
+        telemetry.count("nodes created", 3);
+        telemetry.count("nodes created", 2);
+        telemetry.count("nodes freed", 0);
+
+        assert_eq!(5, telemetry.counter("nodes created"));
+        assert_eq!(0, telemetry.counter("nodes freed"));
+        assert_eq!(
+            "mesh: 4.00 ms (average 4.00 ms, max 4.00 ms, 1 times)\n\
+             nodes created: 5\n",
+            telemetry.summary()
+        );
+
+        telemetry.reset();
+        assert_eq!("", telemetry.summary());
+    }
+}
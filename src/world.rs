@@ -0,0 +1,782 @@
+use crate::hex;
+use crate::hex::{Hexagon, Vector2Di32, CELL_DISTANCE};
+use std::collections::{HashMap, HashSet};
+use terrain::automaton;
+use terrain::automaton::Rules;
+use terrain::climate;
+use terrain::climate::Biome;
+use terrain::maze;
+use terrain::noise;
+use terrain::noise::{Fractal, Noise};
+use terrain::provinces;
+use terrain::random::Random;
+use terrain::scatter;
+use terrain::terrain::Terrain;
+use terrain::tools;
+use terrain::wfc;
+use terrain::wfc::Module;
+
+/// Length of the shortest connections between vertices in key units, from the center of a
+/// hexagon to its left and right corners.
+const SHORT_CONNECTION: f32 = 2.0;
+
+/// Number of climate noise features per cell used by `generate_biomes` and `generate_rivers`.
+const BIOME_FREQUENCY: f32 = 0.1;
+
+/// Number of noise features per cell that distort the shore of `apply_island_mask`.
+const ISLAND_FREQUENCY: f32 = 0.15;
+
+/// Share of the excess height that slides down per iteration of thermal erosion.
+pub const EROSION_RATE: f32 = 0.5;
+
+/// Number of seeds `generate_wfc` tries before giving up.
+const WFC_ATTEMPTS: u64 = 10;
+
+/// Returns the height difference in steps at which material slides down a connection of the
+/// shortest length, for a slope of `talus_angle` degrees. None if steps have no height.
+pub fn talus(talus_angle: f32, hex_radius: f32, node_height: f32) -> Option<f32> {
+    if node_height <= 0.0 {
+        return None;
+    }
+    Some(talus_angle.to_radians().tan() * SHORT_CONNECTION * hex_radius / node_height)
+}
+
+/// The settings of the field that the generators read.
+#[derive(Clone)]
+pub struct Settings {
+    pub hex_radius: f32,
+    pub node_height: f32,
+    /// The layers of `generate_noise`.
+    pub noise: Fractal,
+    pub island_distortion: f32,
+    pub island_depth: i32,
+    /// Height of the "water" elevation level, -1 if there is none.
+    pub sea_level: i32,
+    pub river_feature: i32,
+    pub wfc_modules: Vec<Module>,
+    pub wfc_module_types: Vec<i32>,
+    pub wfc_module_heights: Vec<i32>,
+    pub biomes: Vec<Biome>,
+    pub biome_variation: f32,
+    pub biome_height_cooling: f32,
+    pub symmetry: hex::Symmetry,
+    pub symmetry_center: Vector2Di32,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            hex_radius: 1.0,
+            node_height: 1.0,
+            noise: Fractal::default(),
+            island_distortion: 0.0,
+            island_depth: 0,
+            sea_level: -1,
+            river_feature: 1,
+            wfc_modules: Vec::new(),
+            wfc_module_types: Vec::new(),
+            wfc_module_heights: Vec::new(),
+            biomes: Vec::new(),
+            biome_variation: 0.0,
+            biome_height_cooling: 0.0,
+            symmetry: hex::Symmetry::None,
+            symmetry_center: Vector2Di32::zero(),
+        }
+    }
+}
+
+/// The field as the generators see it: the terrain, the cells and vertices, the settings and the
+/// cell data the generators produce. It holds no Godot types, so a generation can run on a copy
+/// on a background thread while the field keeps showing its terrain.
+#[derive(Clone)]
+pub struct World {
+    pub terrain: Terrain<Vector2Di32>,
+    /// Cells of the field, sorted by row and column.
+    cells: Vec<Vector2Di32>,
+    cell_set: HashSet<Vector2Di32>,
+    /// Vertices of the field, sorted by row and column.
+    keys: Vec<Vector2Di32>,
+    key_set: HashSet<Vector2Di32>,
+    pub settings: Settings,
+    /// Moisture of cells from `simulate_rain_shadow`.
+    pub moisture: HashMap<Vector2Di32, f32>,
+    /// Resource of cells from `place_resources`.
+    pub resources: HashMap<Vector2Di32, String>,
+    /// Province of cells from `generate_provinces`.
+    pub provinces: HashMap<Vector2Di32, i32>,
+}
+
+impl World {
+    /// Creates a world of the cells and vertices of a field with its terrain.
+    pub fn new(
+        terrain: Terrain<Vector2Di32>,
+        cells: impl Iterator<Item = Vector2Di32>,
+        keys: impl Iterator<Item = Vector2Di32>,
+        settings: Settings,
+    ) -> World {
+        let mut cells: Vec<Vector2Di32> = cells.collect();
+        cells.sort_unstable_by_key(|cell| (cell.y, cell.x));
+        let mut keys: Vec<Vector2Di32> = keys.collect();
+        keys.sort_unstable_by_key(|key| (key.y, key.x));
+        World {
+            terrain,
+            cell_set: cells.iter().copied().collect(),
+            cells,
+            key_set: keys.iter().copied().collect(),
+            keys,
+            settings,
+            moisture: HashMap::new(),
+            resources: HashMap::new(),
+            provinces: HashMap::new(),
+        }
+    }
+
+    /// Returns the keys of the centers and corners of the given existing cells, without
+    /// duplicates.
+    fn keys_of_cells(&self, cells: &[Vector2Di32]) -> Vec<Vector2Di32> {
+        let mut keys = Vec::new();
+        let mut processed_keys = HashSet::new();
+        for cell in cells.iter().filter(|cell| self.cell_set.contains(cell)) {
+            for key in Hexagon::new(*cell).keys().iter() {
+                if processed_keys.insert(*key) {
+                    keys.push(*key);
+                }
+            }
+        }
+        keys
+    }
+
+    /// Resets the heights, terrain types and edge features of all cells that are not locked and
+    /// everything else the generators produce, moisture, resources and provinces, before
+    /// generating a new terrain.
+    pub fn reset(&mut self) {
+        let flat: Vec<(Vector2Di32, i32)> = self.keys.iter().map(|key| (*key, 0)).collect();
+        self.terrain.set_generated_heights(&flat);
+        for key in &self.keys {
+            if !self.terrain.is_locked(*key) {
+                self.terrain.set_terrain_type(*key, 0);
+            }
+        }
+        let features: Vec<(Vector2Di32, Vector2Di32)> = self
+            .terrain
+            .edge_features()
+            .map(|(first, second, _)| (first, second))
+            .collect();
+        for (first, second) in features {
+            self.terrain.set_edge_feature(first, second, 0);
+        }
+        self.moisture.clear();
+        self.resources.clear();
+        self.provinces.clear();
+    }
+
+    /// See `HexTerrain::generate_noise`.
+    pub fn generate_noise(&mut self, seed: i64, frequency: f64, amplitude: f64) {
+        let nodes: Vec<(Vector2Di32, f32, f32)> = self
+            .keys
+            .iter()
+            .map(|key| {
+                (
+                    *key,
+                    key.x as f32 / CELL_DISTANCE,
+                    key.y as f32 / CELL_DISTANCE,
+                )
+            })
+            .collect();
+        let heights = noise::noise_heights(
+            &nodes,
+            seed as u64,
+            frequency as f32,
+            amplitude as f32,
+            &self.settings.noise,
+        );
+        self.terrain.set_generated_heights(&heights);
+    }
+
+    /// See `HexTerrain::generate_midpoint`.
+    pub fn generate_midpoint(&mut self, seed: i64, size: i64, amplitude: f64, roughness: f64) {
+        let nodes: Vec<(Vector2Di32, i32, i32)> = self
+            .keys
+            .iter()
+            .map(|key| {
+                let lattice = hex::key_to_lattice(*key);
+                (*key, lattice.x, lattice.y)
+            })
+            .collect();
+        let heights = noise::midpoint_heights(
+            &nodes,
+            seed as u64,
+            (size as i32).saturating_mul(2),
+            amplitude as f32,
+            roughness as f32,
+        );
+        self.terrain.set_generated_heights(&heights);
+    }
+
+    /// See `HexTerrain::apply_island_mask`.
+    pub fn apply_island_mask(&mut self, seed: i64, land_fraction: f64, falloff: f64) {
+        let count = self.keys.len().max(1) as f32;
+        let center_x = self.keys.iter().map(|key| key.x as f32).sum::<f32>() / count;
+        let center_y = self.keys.iter().map(|key| key.y as f32).sum::<f32>() / count;
+        let distance = |key: &Vector2Di32| {
+            let (x, y) = (key.x as f32 - center_x, key.y as f32 - center_y);
+            (x * x + y * y).sqrt()
+        };
+        let radius = self.keys.iter().map(distance).fold(0.0, f32::max).max(1.0);
+
+        let noise = Noise::new(seed as u64);
+        let fractal = Fractal {
+            octaves: 3,
+            ..Fractal::default()
+        };
+        let nodes: Vec<(Vector2Di32, i32, f32)> = self
+            .keys
+            .iter()
+            .filter_map(|key| {
+                let x = key.x as f32 / CELL_DISTANCE * ISLAND_FREQUENCY;
+                let y = key.y as f32 / CELL_DISTANCE * ISLAND_FREQUENCY;
+                let distortion = noise.fractal(x, y, &fractal) * self.settings.island_distortion;
+                let height = self.terrain.get_height_of_node(*key)?;
+                Some((*key, height, distance(key) / radius + distortion))
+            })
+            .collect();
+
+        let heights = noise::island_heights(
+            &nodes,
+            land_fraction as f32,
+            falloff as f32,
+            self.settings.island_depth,
+        );
+        self.terrain.set_generated_heights(&heights);
+    }
+
+    /// See `HexTerrain::generate_lakes`. Returns the water cells.
+    pub fn generate_lakes(
+        &mut self,
+        seed: i64,
+        fill: f64,
+        birth: i64,
+        survival: i64,
+        iterations: i64,
+    ) -> Vec<Vector2Di32> {
+        let rules = Rules {
+            fill: fill as f32,
+            birth: birth.max(0) as usize,
+            survival: survival.max(0) as usize,
+            iterations: iterations.max(0) as u32,
+        };
+        let water = automaton::run(
+            &self.cells,
+            |cell| hex::neighbouring_cells(cell).to_vec(),
+            &rules,
+            seed as u64,
+        );
+        let lakes: Vec<Vector2Di32> = self
+            .cells
+            .iter()
+            .copied()
+            .filter(|cell| water.contains(cell))
+            .collect();
+
+        let heights: Vec<(Vector2Di32, i32)> = self
+            .keys_of_cells(&lakes)
+            .into_iter()
+            .filter_map(|key| {
+                let height = self.terrain.get_height_of_node(key)?;
+                Some((key, height.min(self.settings.sea_level)))
+            })
+            .collect();
+        self.terrain.set_heights(&heights);
+        lakes
+    }
+
+    /// See `HexTerrain::generate_ridges`.
+    pub fn generate_ridges(&mut self, seed: i64, count: i64, height: i64, width: f64) {
+        if self.cells.is_empty() {
+            return;
+        }
+
+        let mut random = Random::new(seed as u64);
+        let max_length = ((self.cells.len() as f32).sqrt() * 1.5).ceil() as i32;
+        let mut ridge_cells = Vec::new();
+        for _ in 0..count.max(0) {
+            let start = self.cells[random.range(0, self.cells.len() as i32 - 1) as usize];
+            let direction = random.range(0, 5);
+            let length = random.range(max_length / 2, max_length);
+            // Going straight is as likely as turning, so ridges meander without curling up.
+            let turns: Vec<i32> = (0..length)
+                .map(|_| [-1, 0, 0, 1][random.range(0, 3) as usize])
+                .collect();
+            ridge_cells.extend(hex::winding_line(start, direction, &turns));
+        }
+
+        let nodes: Vec<(Vector2Di32, i32, f32)> = self
+            .keys
+            .iter()
+            .filter_map(|key| {
+                let height = self.terrain.get_height_of_node(*key)?;
+                let distance = ridge_cells
+                    .iter()
+                    .map(|cell| (*cell - *key).to_f32().length())
+                    .fold(f32::MAX, f32::min);
+                Some((*key, height, distance / CELL_DISTANCE))
+            })
+            .collect();
+
+        let heights = tools::ridge_heights(&nodes, height as i32, width as f32);
+        self.terrain.set_generated_heights(&heights);
+    }
+
+    /// See `HexTerrain::generate_rivers`. Returns the number of connections that were marked.
+    pub fn generate_rivers(&mut self, seed: i64, threshold: f64) -> i64 {
+        let sea_level = self.settings.sea_level;
+        let feature = self.settings.river_feature;
+        let rivers: Vec<(Vector2Di32, Vector2Di32)> = self
+            .terrain
+            .edge_features()
+            .filter(|(_, _, edge_feature)| *edge_feature == feature)
+            .map(|(first, second, _)| (first, second))
+            .collect();
+        for (first, second) in rivers {
+            self.terrain.set_edge_feature(first, second, 0);
+        }
+
+        let outlets: Vec<Vector2Di32> = self
+            .keys
+            .iter()
+            .copied()
+            .filter(|key| {
+                matches!(
+                    self.terrain.get_height_of_node(*key),
+                    Some(height) if height <= sea_level
+                )
+            })
+            .collect();
+        let noise = Noise::new(seed as u64);
+        let fractal = Fractal {
+            octaves: 3,
+            ..Fractal::default()
+        };
+        let flow = self.terrain.river_flow(&outlets, |key| {
+            let x = key.x as f32 / CELL_DISTANCE * BIOME_FREQUENCY;
+            let y = key.y as f32 / CELL_DISTANCE * BIOME_FREQUENCY;
+            (noise.fractal(x, y, &fractal) + 1.0) / 2.0
+        });
+
+        let mut count = 0;
+        for (from, to, water) in flow {
+            let above_sea =
+                matches!(self.terrain.get_height_of_node(from), Some(height) if height > sea_level);
+            if above_sea
+                && water >= threshold as f32
+                && self.terrain.set_edge_feature(from, to, feature)
+            {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// See `HexTerrain::place_resources`. `probabilities` maps terrain types to the chance that a
+    /// cell of that type gets the resource. Returns the cells that got the resource.
+    pub fn place_resources(
+        &mut self,
+        resource: &str,
+        probabilities: &HashMap<i32, f32>,
+        min_spacing: i64,
+        seed: i64,
+    ) -> Vec<Vector2Di32> {
+        let (taken, free): (Vec<Vector2Di32>, Vec<Vector2Di32>) = self
+            .cells
+            .iter()
+            .partition(|cell| self.resources.contains_key(*cell));
+        let candidates: Vec<(Vector2Di32, f32)> = free
+            .into_iter()
+            .filter_map(|cell| {
+                let terrain_type = self.terrain.get_terrain_type(cell)?;
+                Some((cell, *probabilities.get(&terrain_type)?))
+            })
+            .collect();
+
+        let placed = scatter::scatter(
+            &candidates,
+            &taken,
+            min_spacing as f32,
+            |first, second| {
+                hex::axial_distance(hex::cell_to_axial(first), hex::cell_to_axial(second)) as f32
+            },
+            seed as u64,
+        );
+        for cell in &placed {
+            self.resources.insert(*cell, resource.to_owned());
+        }
+        placed
+    }
+
+    /// See `HexTerrain::generate_maze`.
+    pub fn generate_maze(&mut self, seed: i64, wall_height: i64) {
+        let even = |cell: Vector2Di32| {
+            let axial = hex::cell_to_axial(cell);
+            axial.x % 2 == 0 && axial.y % 2 == 0
+        };
+        let rooms: Vec<Vector2Di32> = self
+            .cells
+            .iter()
+            .copied()
+            .filter(|cell| even(*cell))
+            .collect();
+        let start = match rooms.first() {
+            None => return,
+            Some(start) => *start,
+        };
+
+        let passages = maze::backtracker(
+            start,
+            |room| {
+                hex::neighbouring_cells(room)
+                    .iter()
+                    .map(|passage| (*passage, *passage + (*passage - room)))
+                    .filter(|(passage, next)| {
+                        self.cell_set.contains(passage) && self.cell_set.contains(next)
+                    })
+                    .map(|(_, next)| next)
+                    .collect()
+            },
+            seed as u64,
+        );
+        let mut corridors: HashSet<Vector2Di32> = rooms.iter().copied().collect();
+        for (from, to) in passages {
+            corridors.insert(from + (to - from) / 2);
+        }
+        let (corridors, walls): (Vec<Vector2Di32>, Vec<Vector2Di32>) =
+            self.cells.iter().partition(|cell| corridors.contains(cell));
+
+        let wall_heights: Vec<(Vector2Di32, i32)> = self
+            .keys_of_cells(&walls)
+            .into_iter()
+            .map(|key| (key, wall_height as i32))
+            .collect();
+        self.terrain.set_heights(&wall_heights);
+        let corridor_heights: Vec<(Vector2Di32, i32)> = self
+            .keys_of_cells(&corridors)
+            .into_iter()
+            .map(|key| (key, 0))
+            .collect();
+        self.terrain.set_heights(&corridor_heights);
+    }
+
+    /// See `HexTerrain::generate_wfc`. Returns false if no seed succeeded.
+    pub fn generate_wfc(&mut self, seed: i64) -> bool {
+        let settings = &self.settings;
+        let neighbours = |cell: Vector2Di32| -> Vec<(usize, Vector2Di32)> {
+            hex::neighbouring_cells(cell)
+                .iter()
+                .copied()
+                .enumerate()
+                .collect()
+        };
+        let result = (0..WFC_ATTEMPTS).find_map(|attempt| {
+            wfc::collapse(
+                &self.cells,
+                neighbours,
+                &settings.wfc_modules,
+                (seed as u64).wrapping_add(attempt),
+            )
+        });
+        let result = match result {
+            None => return false,
+            Some(result) => result,
+        };
+
+        let mut sums: HashMap<Vector2Di32, (i32, i32)> = HashMap::new();
+        for (cell, module) in &result {
+            self.terrain
+                .set_terrain_type(*cell, settings.wfc_module_types[*module]);
+            if self.cell_set.contains(cell) {
+                for key in Hexagon::new(*cell).keys().iter() {
+                    let sum = sums.entry(*key).or_insert((0, 0));
+                    sum.0 += settings.wfc_module_heights[*module];
+                    sum.1 += 1;
+                }
+            }
+        }
+        let mut heights: Vec<(Vector2Di32, i32)> = sums
+            .into_iter()
+            .map(|(key, (sum, count))| (key, (sum as f32 / count as f32).round() as i32))
+            .collect();
+        heights.sort_unstable_by_key(|(key, _)| (key.y, key.x));
+        self.terrain.set_generated_heights(&heights);
+        true
+    }
+
+    /// See `HexTerrain::generate_provinces`.
+    pub fn generate_provinces(&mut self, seed: i64, count: i64) {
+        let cells: Vec<Vector2Di32> = self
+            .cells
+            .iter()
+            .copied()
+            .filter(|cell| !self.terrain.is_hole(*cell))
+            .collect();
+        self.provinces = provinces::grow(
+            &cells,
+            |cell| hex::neighbouring_cells(cell).to_vec(),
+            count.max(0) as usize,
+            seed as u64,
+        );
+    }
+
+    /// See `HexTerrain::simulate_rain_shadow`.
+    pub fn simulate_rain_shadow(&mut self, wind_direction: f64, rainfall: f64, uplift: f64) {
+        // The air blows away from `wind_direction`, so it moves along the opposite direction.
+        // Cells are projected at their world positions, which are their keys times `hex_radius`.
+        let angle = (wind_direction as f32).to_radians();
+        let wind = (-angle.cos(), -angle.sin());
+        let radius = self.settings.hex_radius;
+        let along = |cell: Vector2Di32| (cell.x as f32 * wind.0 + cell.y as f32 * wind.1) * radius;
+
+        let cells: Vec<(Vector2Di32, f32, i32)> = self
+            .cells
+            .iter()
+            .map(|cell| {
+                let height = self.terrain.get_height_of_node(*cell).unwrap_or(0);
+                (*cell, along(*cell), height)
+            })
+            .collect();
+        let moisture = climate::rain_shadow(
+            &cells,
+            |cell| {
+                hex::neighbouring_cells(cell)
+                    .iter()
+                    .copied()
+                    .filter(|neighbour| along(*neighbour) < along(cell) - f32::EPSILON)
+                    .collect()
+            },
+            self.settings.sea_level,
+            rainfall as f32,
+            uplift as f32,
+        );
+        for (cell, _, _) in cells {
+            self.moisture.insert(cell, moisture[&cell]);
+        }
+    }
+
+    /// See `HexTerrain::generate_biomes`.
+    pub fn generate_biomes(&mut self, seed: i64) {
+        let (top, bottom) = match (self.cells.first(), self.cells.last()) {
+            (Some(top), Some(bottom)) => (top.y as f32, bottom.y as f32),
+            _ => return,
+        };
+        let equator = (top + bottom) / 2.0;
+        let half_height = ((bottom - top) / 2.0).max(1.0);
+
+        let temperature_noise = Noise::new(seed as u64);
+        let moisture_noise = Noise::new((seed as u64).wrapping_add(1));
+        let fractal = Fractal {
+            octaves: 3,
+            ..Fractal::default()
+        };
+        for cell in &self.cells {
+            let x = cell.x as f32 / CELL_DISTANCE * BIOME_FREQUENCY;
+            let y = cell.y as f32 / CELL_DISTANCE * BIOME_FREQUENCY;
+            let variation =
+                temperature_noise.fractal(x, y, &fractal) * self.settings.biome_variation;
+            let temperature = climate::temperature(
+                (cell.y as f32 - equator) / half_height,
+                self.terrain.get_height_of_node(*cell).unwrap_or(0),
+                self.settings.biome_height_cooling,
+                variation,
+            );
+            let moisture = match self.moisture.get(cell) {
+                Some(moisture) => *moisture,
+                None => (moisture_noise.fractal(x, y, &fractal) + 1.0) / 2.0,
+            };
+            if let Some(terrain_type) =
+                climate::biome_for(&self.settings.biomes, temperature, moisture)
+            {
+                self.terrain.set_terrain_type(*cell, terrain_type);
+            }
+        }
+    }
+
+    /// See `HexTerrain::thermal_erosion`.
+    pub fn erode(&mut self, talus_angle: f64, iterations: i64) {
+        let settings = &self.settings;
+        if let Some(talus) = talus(
+            talus_angle as f32,
+            settings.hex_radius,
+            settings.node_height,
+        ) {
+            self.terrain
+                .erode(talus, EROSION_RATE, iterations.max(0) as u32);
+        }
+    }
+
+    /// See `HexTerrain::weather`.
+    pub fn weather(&mut self, seed: i64, intensity: f64, iterations: i64) {
+        self.terrain
+            .weather(intensity as f32, iterations.max(0) as u32, seed as u64);
+    }
+
+    /// Returns for every vertex the vertex it copies in `symmetrize`: of all vertices the
+    /// symmetry maps onto each other, the first by row and column.
+    pub fn symmetry_sources(&self) -> HashMap<Vector2Di32, Vector2Di32> {
+        let (symmetry, center) = (self.settings.symmetry, self.settings.symmetry_center);
+        let mut sources = HashMap::new();
+        for key in &self.keys {
+            if sources.contains_key(key) {
+                continue;
+            }
+            for image in hex::symmetric_keys(*key, center, symmetry) {
+                if self.key_set.contains(&image) {
+                    sources.entry(image).or_insert(*key);
+                }
+            }
+        }
+        sources
+    }
+
+    /// See `HexTerrain::symmetrize`. The moisture and resources of cells are repeated as well.
+    pub fn symmetrize(&mut self) {
+        let (symmetry, center) = (self.settings.symmetry, self.settings.symmetry_center);
+        let sources = self.symmetry_sources();
+
+        let heights: Vec<(Vector2Di32, i32)> = self
+            .keys
+            .iter()
+            .filter_map(|key| Some((*key, self.terrain.get_height_of_node(sources[key])?)))
+            .collect();
+        self.terrain.set_generated_heights(&heights);
+        for key in &self.keys {
+            let source = sources[key];
+            if source == *key {
+                continue;
+            }
+            if let Some(terrain_type) = self.terrain.get_terrain_type(source) {
+                self.terrain.set_terrain_type(*key, terrain_type);
+            }
+            match self.moisture.get(&source).copied() {
+                None => self.moisture.remove(key),
+                Some(moisture) => self.moisture.insert(*key, moisture),
+            };
+            match self.resources.get(&source).cloned() {
+                None => self.resources.remove(key),
+                Some(resource) => self.resources.insert(*key, resource),
+            };
+        }
+
+        let features: Vec<(Vector2Di32, Vector2Di32, i32)> = self.terrain.edge_features().collect();
+        for (first, second, _) in &features {
+            self.terrain.set_edge_feature(*first, *second, 0);
+        }
+        for (first, second, feature) in features {
+            if sources.get(&first) != Some(&first) {
+                continue;
+            }
+            let firsts = hex::symmetric_images(first, center, symmetry);
+            let seconds = hex::symmetric_images(second, center, symmetry);
+            for (first, second) in firsts.into_iter().zip(seconds) {
+                self.terrain.set_edge_feature(first, second, feature);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hex::CORNERS;
+
+    /// Returns a world of the cells within `radius` of the center, connected like the field.
+    fn world(radius: u32) -> World {
+        let cells = hex::cell_spiral(Vector2Di32::zero(), radius);
+        let mut terrain = Terrain::new(1);
+        let mut keys = HashSet::new();
+        for cell in &cells {
+            for index in 0..CORNERS.len() {
+                let corner = *cell + CORNERS[index];
+                let next = *cell + CORNERS[(index + 1) % CORNERS.len()];
+                terrain.add_connected_nodes(*cell, corner);
+                terrain.add_connected_nodes(*cell, next);
+                terrain.add_connected_nodes(corner, next);
+            }
+            keys.extend(Hexagon::new(*cell).keys().iter());
+        }
+        World::new(
+            terrain,
+            cells.into_iter(),
+            keys.into_iter(),
+            Settings::default(),
+        )
+    }
+
+    /// Resets the world and runs generators that produce all kinds of results on it.
+    fn regenerate(world: &mut World, seed: i64) {
+        let probabilities: HashMap<i32, f32> = vec![(0, 0.5)].into_iter().collect();
+        world.reset();
+        world.generate_noise(seed, 0.5, 5.0);
+        world.generate_rivers(seed, 0.5);
+        world.simulate_rain_shadow(0.0, 0.1, 0.1);
+        world.place_resources("ore", &probabilities, 2, seed);
+        world.generate_provinces(seed, 3);
+    }
+
+    #[test]
+    fn regenerating_with_same_seed_gives_same_world() {
+        let mut world = world(3);
+        world.settings.sea_level = 0;
+        let mut first = world.clone();
+        regenerate(&mut first, 7);
+
+        regenerate(&mut world, 8);
+        regenerate(&mut world, 7);
+        regenerate(&mut world, 7);
+
+        let checksum = |world: &World| world.terrain.checksum(|key| (key.x, key.y));
+        assert_eq!(checksum(&first), checksum(&world));
+        assert_eq!(first.moisture, world.moisture);
+        assert_eq!(first.resources, world.resources);
+        assert_eq!(first.provinces, world.provinces);
+        assert!(!world.resources.is_empty());
+    }
+
+    #[test]
+    fn rain_shadow_dries_side_of_ridge_away_from_wind() {
+        let mut world = world(4);
+        for cell in world.cells.clone() {
+            if cell.x == 0 {
+                world.terrain.set_height(cell, 10);
+            }
+        }
+        let side_moisture = |world: &World, east: bool| {
+            let side: Vec<f32> = world
+                .moisture
+                .iter()
+                .filter(|(cell, _)| cell.x != 0 && (cell.x > 0) == east)
+                .map(|(_, moisture)| *moisture)
+                .collect();
+            side.iter().sum::<f32>() / side.len() as f32
+        };
+
+        // Wind from the east, along the positive X axis.
+        world.simulate_rain_shadow(0.0, 0.05, 0.1);
+        assert!(side_moisture(&world, false) < side_moisture(&world, true));
+
+        // Wind from the west.
+        world.simulate_rain_shadow(180.0, 0.05, 0.1);
+        assert!(side_moisture(&world, true) < side_moisture(&world, false));
+    }
+
+    #[test]
+    fn generators_run_on_copy_without_changing_original() {
+        let original = world(3);
+        let mut copy = original.clone();
+
+        copy.generate_noise(4, 0.5, 5.0);
+        copy.generate_provinces(4, 3);
+
+        assert!(original.terrain.heights().all(|(_, height)| height == 0));
+        assert!(original.provinces.is_empty());
+        assert!(copy.terrain.heights().any(|(_, height)| height != 0));
+        assert_eq!(37, copy.provinces.len());
+    }
+}
@@ -0,0 +1,123 @@
+//! Benchmarks of the expensive terrain operations. Run with `cargo bench`; a name filter can be
+//! given, e.g. `cargo bench -- propagation`. Besides the timings of Criterion, every benchmark
+//! prints the work the terrain counted for one iteration, see `Terrain::stats`, so a regression
+//! shows whether the code got slower or does more work.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use terrain::noise;
+use terrain::noise::Fractal;
+use terrain::terrain::{Stats, Terrain};
+
+/// Nodes per side of the square lattice of the benchmarks.
+const SIZE: i32 = 100;
+
+/// Returns a flat terrain of `SIZE` by `SIZE` nodes, each connected to its neighbours on a
+/// triangular lattice like the vertices of the hexagons.
+fn lattice() -> Terrain<(i32, i32)> {
+    let mut terrain = Terrain::new(1);
+    for x in 0..SIZE {
+        for y in 0..SIZE {
+            for (dx, dy) in &[(1, 0), (0, 1), (1, -1)] {
+                let (other_x, other_y) = (x + dx, y + dy);
+                if (0..SIZE).contains(&other_x) && (0..SIZE).contains(&other_y) {
+                    terrain.add_connected_nodes((x, y), (other_x, other_y));
+                }
+            }
+        }
+    }
+    terrain.reset_stats();
+    terrain
+}
+
+/// Measures `run` on a fresh value of `setup` every iteration and prints the work counted by the
+/// terrain in one iteration.
+fn bench<S>(
+    criterion: &mut Criterion,
+    name: &str,
+    mut setup: impl FnMut() -> S,
+    mut run: impl FnMut(&mut S) -> Stats,
+) {
+    let stats = run(&mut setup());
+    println!(
+        "{}: propagations {}, nodes visited {}, heights changed {}",
+        name, stats.propagations, stats.nodes_visited, stats.heights_changed
+    );
+    criterion.bench_function(name, |bencher| {
+        bencher.iter_batched_ref(&mut setup, &mut run, BatchSize::LargeInput)
+    });
+}
+
+fn benches(criterion: &mut Criterion) {
+    let center = (SIZE / 2, SIZE / 2);
+    let positions: Vec<(i32, i32)> = (0..SIZE)
+        .flat_map(|x| (0..SIZE).map(move |y| (x, y)))
+        .collect();
+
+    bench(criterion, "lattice", || (), |_| lattice().stats());
+
+    bench(criterion, "propagation cascade", lattice, |terrain| {
+        for _ in 0..20 {
+            terrain.increase_height(center);
+        }
+        terrain.stats()
+    });
+
+    bench(criterion, "propagation batch", lattice, |terrain| {
+        let nodes: Vec<(i32, i32)> = positions.iter().copied().step_by(7).collect();
+        for _ in 0..5 {
+            terrain.increase_heights(&nodes);
+        }
+        terrain.stats()
+    });
+
+    bench(criterion, "bulk set heights", lattice, |terrain| {
+        let heights: Vec<((i32, i32), i32)> = positions
+            .iter()
+            .filter(|(x, y)| (x - center.0).abs() < 10 && (y - center.1).abs() < 10)
+            .map(|position| (*position, 15))
+            .collect();
+        terrain.set_heights(&heights);
+        terrain.stats()
+    });
+
+    bench(criterion, "noise generation", lattice, |terrain| {
+        let nodes: Vec<((i32, i32), f32, f32)> = positions
+            .iter()
+            .map(|position| (*position, position.0 as f32, position.1 as f32))
+            .collect();
+        let fractal = Fractal {
+            octaves: 4,
+            ..Fractal::default()
+        };
+        let heights = noise::noise_heights(&nodes, 1, 0.05, 20.0, &fractal);
+        terrain.set_generated_heights(&heights);
+        terrain.stats()
+    });
+
+    bench(
+        criterion,
+        "erosion",
+        || {
+            let mut terrain = lattice();
+            let heights: Vec<((i32, i32), i32)> = positions
+                .iter()
+                .map(|position| (*position, (position.0 * 7 + position.1 * 3) % 11))
+                .collect();
+            terrain.set_generated_heights(&heights);
+            terrain.reset_stats();
+            terrain
+        },
+        |terrain| {
+            terrain.erode(0.5, 0.5, 10);
+            terrain.stats()
+        },
+    );
+}
+
+criterion_group! {
+    name = terrain;
+    // The operations take milliseconds each, so fewer samples than the default keep a run short.
+    config = Criterion::default().sample_size(10);
+    targets = benches
+}
+criterion_main!(terrain);
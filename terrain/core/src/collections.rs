@@ -0,0 +1,37 @@
+//! Hash maps and sets of std, or of hashbrown with FNV hashing without std. Create them with
+//! `default()`, which works for both.
+#[cfg(not(feature = "std"))]
+use core::hash::{BuildHasherDefault, Hasher};
+#[cfg(feature = "std")]
+pub type HashMap<K, V> = std::collections::HashMap<K, V>;
+#[cfg(feature = "std")]
+pub type HashSet<T> = std::collections::HashSet<T>;
+
+#[cfg(not(feature = "std"))]
+pub type HashMap<K, V> = hashbrown::HashMap<K, V, BuildHasherDefault<Fnv>>;
+#[cfg(not(feature = "std"))]
+pub type HashSet<T> = hashbrown::HashSet<T, BuildHasherDefault<Fnv>>;
+
+/// FNV-1a hashing, as there is no randomly seeded hasher without std.
+#[cfg(not(feature = "std"))]
+pub struct Fnv(u64);
+
+#[cfg(not(feature = "std"))]
+impl Default for Fnv {
+    fn default() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
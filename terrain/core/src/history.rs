@@ -0,0 +1,302 @@
+use crate::collections::HashMap;
+use crate::terrain::{Changes, Layers, Terrain};
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::hash::Hash;
+
+/// An edit of the terrain, with the old and new height of every changed node, the old and new
+/// layers of every node whose terrain type, hole mark or bridge deck changed and the old and new
+/// feature of every changed connection.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edit<T> {
+    pub operation: String,
+    pub changes: Vec<(T, i32, i32)>,
+    pub layers: Vec<(T, Layers, Layers)>,
+    pub features: Vec<(T, T, i32, i32)>,
+}
+
+impl<T: Eq + Hash + Copy> Edit<T> {
+    /// Creates an edit from the changes of a terrain, see `Terrain::take_changes`.
+    pub fn new(operation: &str, changes: Changes<T>) -> Edit<T> {
+        Edit {
+            operation: operation.to_owned(),
+            changes: changes.heights,
+            layers: changes.layers,
+            features: changes.features,
+        }
+    }
+
+    /// Creates an edit from the heights before and after it. Nodes that did not exist before are
+    /// ignored.
+    pub fn from_heights(
+        operation: &str,
+        before: &HashMap<T, i32>,
+        after: impl Iterator<Item = (T, i32)>,
+    ) -> Edit<T> {
+        Edit {
+            operation: operation.to_owned(),
+            changes: after
+                .filter_map(|(node, new_height)| match before.get(&node) {
+                    Some(old_height) if *old_height != new_height => {
+                        Some((node, *old_height, new_height))
+                    }
+                    _ => None,
+                })
+                .collect(),
+            layers: Vec::new(),
+            features: Vec::new(),
+        }
+    }
+
+    /// Returns the edit that reverts this one.
+    pub fn reversed(&self, operation: &str) -> Edit<T> {
+        Edit {
+            operation: operation.to_owned(),
+            changes: self
+                .changes
+                .iter()
+                .map(|(node, old_height, new_height)| (*node, *new_height, *old_height))
+                .collect(),
+            layers: self
+                .layers
+                .iter()
+                .map(|(node, old_layers, new_layers)| (*node, *new_layers, *old_layers))
+                .collect(),
+            features: self
+                .features
+                .iter()
+                .map(|(first, second, old, new)| (*first, *second, *new, *old))
+                .collect(),
+        }
+    }
+
+    /// Sets the new heights, layers and edge features of the edit, without propagating heights to
+    /// connected nodes.
+    pub fn apply(&self, terrain: &mut Terrain<T>) {
+        for (node, _, height) in &self.changes {
+            terrain.set_height(*node, *height);
+        }
+        for (node, _, layers) in &self.layers {
+            terrain.set_terrain_type(*node, layers.terrain_type);
+            terrain.set_hole(*node, layers.hole);
+            terrain.set_deck_height(*node, layers.deck_height);
+        }
+        for (first, second, _, feature) in &self.features {
+            terrain.set_edge_feature(*first, *second, *feature);
+        }
+    }
+
+    /// Returns whether the edit changes nothing.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.layers.is_empty() && self.features.is_empty()
+    }
+
+    /// Returns the heights before the edit.
+    pub fn old_heights(&self) -> Vec<(T, i32)> {
+        self.changes
+            .iter()
+            .map(|(node, old_height, _)| (*node, *old_height))
+            .collect()
+    }
+
+    /// Returns the heights after the edit.
+    pub fn new_heights(&self) -> Vec<(T, i32)> {
+        self.changes
+            .iter()
+            .map(|(node, _, new_height)| (*node, *new_height))
+            .collect()
+    }
+}
+
+/// A list of the most recent edits that can be undone and redone.
+pub struct History<T> {
+    edits: Vec<Edit<T>>,
+    position: usize,
+    capacity: usize,
+}
+
+impl<T: Eq + Hash + Copy> History<T> {
+    pub fn new(capacity: usize) -> History<T> {
+        History {
+            edits: Vec::new(),
+            position: 0,
+            capacity,
+        }
+    }
+
+    /// Returns all edits, oldest first. Edits from `position()` on have been undone.
+    pub fn edits(&self) -> &[Edit<T>] {
+        &self.edits
+    }
+
+    /// Returns the number of edits that are applied.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Adds an edit and drops the edits that were undone. Edits without changes are ignored. If
+    /// there are more edits than the capacity allows, the oldest are dropped.
+    pub fn push(&mut self, edit: Edit<T>) {
+        if edit.is_empty() {
+            return;
+        }
+        self.edits.truncate(self.position);
+        self.edits.push(edit);
+        self.set_capacity(self.capacity);
+        self.position = self.edits.len();
+    }
+
+    /// Returns the edit that has to be reverted, None if there is nothing to undo.
+    pub fn undo(&mut self) -> Option<&Edit<T>> {
+        if self.position == 0 {
+            return None;
+        }
+        self.position -= 1;
+        self.edits.get(self.position)
+    }
+
+    /// Returns the edit that has to be applied again, None if there is nothing to redo.
+    pub fn redo(&mut self) -> Option<&Edit<T>> {
+        let edit = self.edits.get(self.position)?;
+        self.position += 1;
+        Some(edit)
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        if self.edits.len() > capacity {
+            let dropped = self.edits.len() - capacity;
+            self.edits.drain(..dropped);
+            self.position = self.position.saturating_sub(dropped);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.edits.clear();
+        self.position = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(operation: &str, node: i32) -> Edit<i32> {
+        Edit {
+            operation: operation.to_owned(),
+            changes: vec![(node, 0, 1)],
+            layers: Vec::new(),
+            features: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn from_heights_returns_changed_nodes() {
+        let before: HashMap<i32, i32> = vec![(0, 1), (1, 2)].into_iter().collect();
+
+        let edit = Edit::from_heights("raise", &before, vec![(0, 1), (1, 3), (2, 5)].into_iter());
+
+        assert_eq!("raise", edit.operation);
+        assert_eq!(vec![(1, 2, 3)], edit.changes);
+        assert_eq!(vec![(1, 2)], edit.old_heights());
+        assert_eq!(vec![(1, 3)], edit.new_heights());
+    }
+
+    #[test]
+    fn reversed_swaps_old_and_new_values() {
+        let layers = Layers {
+            terrain_type: 0,
+            hole: false,
+            deck_height: None,
+        };
+        let hole = Layers {
+            hole: true,
+            ..layers
+        };
+        let edit = Edit {
+            operation: "paint".to_owned(),
+            changes: vec![(0, 1, 2)],
+            layers: vec![(1, layers, hole)],
+            features: vec![(0, 1, 0, 3)],
+        };
+
+        let reversed = edit.reversed("undo");
+
+        assert_eq!("undo", reversed.operation);
+        assert_eq!(vec![(0, 2, 1)], reversed.changes);
+        assert_eq!(vec![(1, hole, layers)], reversed.layers);
+        assert_eq!(vec![(0, 1, 3, 0)], reversed.features);
+        assert_eq!(edit.changes, reversed.reversed("redo").changes);
+    }
+
+    #[test]
+    fn undoing_reverts_terrain_type_hole_and_edge_feature() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+        terrain.take_changes();
+        terrain.set_terrain_type(0, 2);
+        terrain.set_hole(1, true);
+        terrain.set_edge_feature(0, 1, 3);
+        let mut history = History::new(10);
+        history.push(Edit::new("paint", terrain.take_changes()));
+
+        history.undo().unwrap().reversed("undo").apply(&mut terrain);
+
+        assert_eq!(Some(0), terrain.get_terrain_type(0));
+        assert!(!terrain.is_hole(1));
+        assert_eq!(0, terrain.get_edge_feature(0, 1));
+        history.redo().unwrap().apply(&mut terrain);
+        assert_eq!(Some(2), terrain.get_terrain_type(0));
+        assert!(terrain.is_hole(1));
+        assert_eq!(3, terrain.get_edge_feature(0, 1));
+    }
+
+    #[test]
+    fn undo_and_redo_move_through_edits() {
+        let mut history = History::new(10);
+        history.push(edit("first", 0));
+        history.push(edit("second", 1));
+
+        assert_eq!("second", history.undo().unwrap().operation);
+        assert_eq!("first", history.undo().unwrap().operation);
+        assert_eq!(None, history.undo());
+        assert_eq!("first", history.redo().unwrap().operation);
+        assert_eq!(1, history.position());
+    }
+
+    #[test]
+    fn push_drops_undone_edits() {
+        let mut history = History::new(10);
+        history.push(edit("first", 0));
+        history.push(edit("second", 1));
+        history.undo();
+
+        history.push(edit("third", 2));
+
+        assert_eq!(2, history.edits().len());
+        assert_eq!("third", history.edits()[1].operation);
+        assert_eq!(None, history.redo());
+    }
+
+    #[test]
+    fn push_ignores_edits_without_changes() {
+        let mut history: History<i32> = History::new(10);
+
+        history.push(Edit::new("nothing", Changes::default()));
+
+        assert!(history.edits().is_empty());
+    }
+
+    #[test]
+    fn push_drops_oldest_edits_over_capacity() {
+        let mut history = History::new(2);
+        history.push(edit("first", 0));
+        history.push(edit("second", 1));
+        history.push(edit("third", 2));
+
+        assert_eq!(2, history.edits().len());
+        assert_eq!("second", history.edits()[0].operation);
+        assert_eq!(2, history.position());
+    }
+}
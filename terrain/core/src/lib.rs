@@ -0,0 +1,16 @@
+//! The terrain graph and its height logic, without std so it can be used on embedded servers and
+//! in sandboxed plugins. The `terrain` crate builds everything else on top of it.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(not(any(feature = "std", feature = "alloc")))]
+compile_error!("terrain_core needs either the \"std\" or the \"alloc\" feature");
+
+pub mod collections;
+pub mod history;
+pub mod path;
+pub mod random;
+pub mod regions;
+pub mod ron;
+pub mod terrain;
@@ -0,0 +1,129 @@
+use crate::collections::HashMap;
+use alloc::collections::BinaryHeap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::hash::Hash;
+
+/// Entry of the open list, ordered so the binary heap returns the lowest cost first.
+struct Open<T> {
+    cost: f32,
+    sequence: usize,
+    node: T,
+}
+
+impl<T> PartialEq for Open<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Open<T> {}
+
+impl<T> PartialOrd for Open<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Open<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .partial_cmp(&self.cost)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+/// Returns the cheapest path from `start` to `goal`, including both, or None if the goal can not
+/// be reached. `neighbours` returns the nodes that can be entered from a node with the cost of
+/// the step, which must not be negative.
+pub fn cheapest_path<T: Eq + Hash + Copy>(
+    start: T,
+    goal: T,
+    neighbours: impl Fn(T) -> Vec<(T, f32)>,
+) -> Option<Vec<T>> {
+    let mut costs = HashMap::default();
+    let mut previous = HashMap::default();
+    let mut open = BinaryHeap::new();
+    let mut sequence = 0;
+    costs.insert(start, 0.0);
+    open.push(Open {
+        cost: 0.0,
+        sequence,
+        node: start,
+    });
+
+    while let Some(Open { cost, node, .. }) = open.pop() {
+        if node == goal {
+            let mut path = vec![goal];
+            let mut node = goal;
+            while let Some(before) = previous.get(&node) {
+                path.push(*before);
+                node = *before;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if matches!(costs.get(&node), Some(best) if cost > *best) {
+            continue;
+        }
+
+        for (neighbour, step) in neighbours(node) {
+            let cost = cost + step.max(0.0);
+            if !matches!(costs.get(&neighbour), Some(best) if cost >= *best) {
+                costs.insert(neighbour, cost);
+                previous.insert(neighbour, node);
+                sequence += 1;
+                open.push(Open {
+                    cost,
+                    sequence,
+                    node: neighbour,
+                });
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line_neighbours(node: i32) -> Vec<(i32, f32)> {
+        vec![(node - 1, 1.0), (node + 1, 1.0)]
+    }
+
+    #[test]
+    fn cheapest_path_returns_path_including_start_and_goal() {
+        assert_eq!(Some(vec![2, 3, 4, 5]), cheapest_path(2, 5, line_neighbours));
+        assert_eq!(Some(vec![2]), cheapest_path(2, 2, line_neighbours));
+    }
+
+    #[test]
+    fn cheapest_path_avoids_expensive_steps() {
+        // 0 connects to 3 directly at a high cost, or through 1 and 2 cheaply.
+        let neighbours = |node: i32| match node {
+            0 => vec![(1, 1.0), (3, 10.0)],
+            1 => vec![(2, 1.0)],
+            2 => vec![(3, 1.0)],
+            _ => Vec::new(),
+        };
+
+        assert_eq!(Some(vec![0, 1, 2, 3]), cheapest_path(0, 3, neighbours));
+    }
+
+    #[test]
+    fn cheapest_path_returns_none_for_unreachable_goal() {
+        let neighbours = |node: i32| {
+            if node < 5 {
+                vec![(node + 1, 1.0)]
+            } else {
+                Vec::new()
+            }
+        };
+
+        assert_eq!(None, cheapest_path(0, 7, neighbours));
+    }
+}
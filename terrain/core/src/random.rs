@@ -0,0 +1,86 @@
+/// Small and deterministic pseudo random number generator (SplitMix64), so the same seed produces
+/// the same results on every platform.
+#[derive(Clone, Copy, Debug)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random { state: seed }
+    }
+
+    /// Creates a generator for a position, so results do not depend on the order in which
+    /// positions are visited.
+    pub fn for_position(seed: u64, x: i32, y: i32) -> Random {
+        let mut random = Random::new(seed ^ ((x as u32 as u64) << 32 | y as u32 as u64));
+        random.next_u64();
+        random
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut value = self.state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        value ^ (value >> 31)
+    }
+
+    /// Returns a value between 0 (inclusive) and 1 (exclusive).
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Returns a value between `min` and `max`, both inclusive.
+    pub fn range(&mut self, min: i32, max: i32) -> i32 {
+        if max <= min {
+            return min;
+        }
+        let count = (max as i64 - min as i64 + 1) as u64;
+        (min as i64 + (self.next_u64() % count) as i64) as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_produces_same_values() {
+        let mut first = Random::new(42);
+        let mut second = Random::new(42);
+
+        for _ in 0..10 {
+            assert_eq!(first.next_u64(), second.next_u64());
+        }
+    }
+
+    #[test]
+    fn for_position_differs_between_positions() {
+        let first = Random::for_position(1, 0, 1).next_u64();
+        let second = Random::for_position(1, 1, 0).next_u64();
+
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn next_f32_is_between_zero_and_one() {
+        let mut random = Random::new(7);
+        for _ in 0..1000 {
+            let value = random.next_f32();
+            assert!((0.0..1.0).contains(&value));
+        }
+    }
+
+    #[test]
+    fn range_is_inclusive() {
+        let mut random = Random::new(3);
+        let mut seen = [false; 3];
+        for _ in 0..100 {
+            let value = random.range(-1, 1);
+            assert!((-1..=1).contains(&value));
+            seen[(value + 1) as usize] = true;
+        }
+        assert_eq!([true; 3], seen);
+    }
+}
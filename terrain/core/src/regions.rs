@@ -0,0 +1,62 @@
+use crate::collections::{HashMap, HashSet};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::hash::Hash;
+
+/// Splits the cells into connected regions. Two cells are in the same region if there is a chain
+/// of `neighbours` between them that stays within `cells`. Regions are sorted from the largest to
+/// the smallest, and the cells of every region are in the order of `cells`.
+pub fn connected_regions<T: Eq + Hash + Copy>(
+    cells: &[T],
+    neighbours: impl Fn(T) -> Vec<T>,
+) -> Vec<Vec<T>> {
+    let order: HashMap<T, usize> = cells
+        .iter()
+        .enumerate()
+        .map(|(index, cell)| (*cell, index))
+        .collect();
+    let mut visited = HashSet::default();
+    let mut regions = Vec::new();
+    for cell in cells {
+        if !visited.insert(*cell) {
+            continue;
+        }
+        let mut region = vec![*cell];
+        let mut open = vec![*cell];
+        while let Some(cell) = open.pop() {
+            for neighbour in neighbours(cell) {
+                if order.contains_key(&neighbour) && visited.insert(neighbour) {
+                    region.push(neighbour);
+                    open.push(neighbour);
+                }
+            }
+        }
+        region.sort_unstable_by_key(|cell| order[cell]);
+        regions.push(region);
+    }
+    regions.sort_by_key(|region| core::cmp::Reverse(region.len()));
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line_neighbours(cell: i32) -> Vec<i32> {
+        vec![cell - 1, cell + 1]
+    }
+
+    #[test]
+    fn connected_regions_splits_at_gaps() {
+        let regions = connected_regions(&[5, 1, 2, 7, 6, 8, 3], line_neighbours);
+
+        assert_eq!(vec![vec![5, 7, 6, 8], vec![1, 2, 3]], regions);
+    }
+
+    #[test]
+    fn connected_regions_keeps_single_cells() {
+        let regions = connected_regions(&[0, 2, 4], line_neighbours);
+
+        assert_eq!(vec![vec![0], vec![2], vec![4]], regions);
+    }
+}
@@ -0,0 +1,429 @@
+use crate::terrain::Terrain;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::hash::Hash;
+
+type Position = (i32, i32);
+
+/// A value of the RON subset terrains are written in: whole numbers, booleans, tuples, lists and
+/// structs with named fields.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Ron {
+    Int(i64),
+    Bool(bool),
+    Tuple(Vec<Ron>),
+    List(Vec<Ron>),
+    Struct(Vec<(String, Ron)>),
+}
+
+impl Ron {
+    /// Returns the value of a field of a struct, None for other values or missing fields.
+    pub fn get(&self, field: &str) -> Option<&Ron> {
+        match self {
+            Ron::Struct(fields) => fields
+                .iter()
+                .find(|(name, _)| name == field)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Returns the number as i32, None if it is no number in the range of i32.
+    pub fn as_i32(&self) -> Option<i32> {
+        match self {
+            Ron::Int(number) if *number >= i32::MIN as i64 && *number <= i32::MAX as i64 => {
+                Some(*number as i32)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Ron::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the values of a tuple or a list.
+    pub fn as_slice(&self) -> Option<&[Ron]> {
+        match self {
+            Ron::Tuple(values) | Ron::List(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    fn as_position(&self) -> Option<Position> {
+        match self.as_slice()? {
+            [x, y] => Some((x.as_i32()?, y.as_i32()?)),
+            _ => None,
+        }
+    }
+}
+
+/// Parses RON text. Line and block comments and trailing commas are allowed, and structs may be
+/// prefixed with a name. Returns None if the text is no valid RON of the supported subset.
+pub fn parse(text: &str) -> Option<Ron> {
+    let mut parser = Parser {
+        characters: text.chars().collect(),
+        offset: 0,
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace()?;
+    if parser.offset < parser.characters.len() {
+        return None;
+    }
+    Some(value)
+}
+
+struct Parser {
+    characters: Vec<char>,
+    offset: usize,
+}
+
+impl Parser {
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.characters.get(self.offset + offset).copied()
+    }
+
+    /// Skips whitespace and comments. Returns None for unclosed block comments.
+    fn skip_whitespace(&mut self) -> Option<()> {
+        loop {
+            match (self.peek_at(0), self.peek_at(1)) {
+                (Some(character), _) if character.is_whitespace() => self.offset += 1,
+                (Some('/'), Some('/')) => {
+                    while !matches!(self.peek_at(0), None | Some('\n')) {
+                        self.offset += 1;
+                    }
+                }
+                (Some('/'), Some('*')) => {
+                    self.offset += 2;
+                    while (self.peek_at(0)?, self.peek_at(1)?) != ('*', '/') {
+                        self.offset += 1;
+                    }
+                    self.offset += 2;
+                }
+                _ => return Some(()),
+            }
+        }
+    }
+
+    fn identifier(&mut self) -> String {
+        let start = self.offset;
+        while let Some(character) = self.peek_at(0) {
+            if !character.is_alphanumeric() && character != '_' {
+                break;
+            }
+            self.offset += 1;
+        }
+        self.characters[start..self.offset].iter().collect()
+    }
+
+    fn value(&mut self) -> Option<Ron> {
+        self.skip_whitespace()?;
+        match self.peek_at(0)? {
+            '[' => {
+                self.offset += 1;
+                Some(Ron::List(self.values(']')?))
+            }
+            '(' => self.parenthesized(),
+            '-' | '0'..='9' => {
+                let start = self.offset;
+                self.offset += 1;
+                while matches!(self.peek_at(0), Some('0'..='9') | Some('_')) {
+                    self.offset += 1;
+                }
+                let text: String = self.characters[start..self.offset]
+                    .iter()
+                    .filter(|character| **character != '_')
+                    .collect();
+                text.parse().ok().map(Ron::Int)
+            }
+            _ => match self.identifier().as_str() {
+                "true" => Some(Ron::Bool(true)),
+                "false" => Some(Ron::Bool(false)),
+                "" => None,
+                _ => {
+                    self.skip_whitespace()?;
+                    if self.peek_at(0)? != '(' {
+                        return None;
+                    }
+                    self.parenthesized()
+                }
+            },
+        }
+    }
+
+    /// Parses a tuple or a struct, starting at its opening parenthesis.
+    fn parenthesized(&mut self) -> Option<Ron> {
+        self.offset += 1;
+        self.skip_whitespace()?;
+        let start = self.offset;
+        let name = self.identifier();
+        self.skip_whitespace()?;
+        let is_struct = !name.is_empty() && self.peek_at(0)? == ':';
+        self.offset = start;
+        if !is_struct {
+            return Some(Ron::Tuple(self.values(')')?));
+        }
+
+        let mut fields = Vec::new();
+        loop {
+            self.skip_whitespace()?;
+            if self.peek_at(0)? == ')' {
+                self.offset += 1;
+                return Some(Ron::Struct(fields));
+            }
+            let name = self.identifier();
+            if name.is_empty() {
+                return None;
+            }
+            self.skip_whitespace()?;
+            if self.peek_at(0)? != ':' {
+                return None;
+            }
+            self.offset += 1;
+            fields.push((name, self.value()?));
+            if !self.separator(')')? {
+                self.offset += 1;
+                return Some(Ron::Struct(fields));
+            }
+        }
+    }
+
+    /// Parses comma separated values up to `end`.
+    fn values(&mut self, end: char) -> Option<Vec<Ron>> {
+        let mut values = Vec::new();
+        loop {
+            self.skip_whitespace()?;
+            if self.peek_at(0)? == end {
+                self.offset += 1;
+                return Some(values);
+            }
+            values.push(self.value()?);
+            if !self.separator(end)? {
+                self.offset += 1;
+                return Some(values);
+            }
+        }
+    }
+
+    /// Skips a comma and returns true, or returns false if `end` follows instead.
+    fn separator(&mut self, end: char) -> Option<bool> {
+        self.skip_whitespace()?;
+        match self.peek_at(0)? {
+            ',' => {
+                self.offset += 1;
+                Some(true)
+            }
+            character if character == end => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// Writes the terrain as RON, one node per line, e.g. for test fixtures or maps that are edited by
+/// hand. `position` converts positions to two numbers. Fields with default values are left out,
+/// and nodes are ordered by position, so equal terrains give equal text.
+pub fn to_ron<T: Eq + Hash + Copy>(
+    terrain: &Terrain<T>,
+    position: impl Fn(T) -> (i32, i32),
+) -> String {
+    let mut text = String::new();
+    let (min_height, max_height) = terrain.height_limits();
+    writeln!(text, "(").unwrap();
+    writeln!(text, "    height_step: {},", terrain.height_step()).unwrap();
+    if (min_height, max_height) != (i32::MIN, i32::MAX) {
+        writeln!(text, "    height_limits: ({}, {}),", min_height, max_height).unwrap();
+    }
+
+    let mut nodes: Vec<(T, i32)> = terrain.heights().collect();
+    nodes.sort_unstable_by_key(|(node, _)| position(*node));
+    writeln!(text, "    nodes: [").unwrap();
+    for (node, height) in nodes {
+        let (x, y) = position(node);
+        write!(
+            text,
+            "        (position: ({}, {}), height: {}",
+            x, y, height
+        )
+        .unwrap();
+        match terrain.get_terrain_type(node) {
+            Some(0) | None => {}
+            Some(terrain_type) => write!(text, ", terrain_type: {}", terrain_type).unwrap(),
+        }
+        if terrain.is_hole(node) {
+            write!(text, ", hole: true").unwrap();
+        }
+        if terrain.is_locked(node) {
+            write!(text, ", locked: true").unwrap();
+        }
+        if let Some(deck_height) = terrain.get_deck_height(node) {
+            write!(text, ", deck_height: {}", deck_height).unwrap();
+        }
+        writeln!(text, "),").unwrap();
+    }
+    writeln!(text, "    ],").unwrap();
+
+    let mut connections: Vec<(Position, Position)> = terrain
+        .connections()
+        .into_iter()
+        .map(|(first, second)| {
+            let (first, second) = (position(first), position(second));
+            (first.min(second), first.max(second))
+        })
+        .collect();
+    connections.sort_unstable();
+    writeln!(text, "    connections: [").unwrap();
+    for (first, second) in connections {
+        writeln!(text, "        ({:?}, {:?}),", first, second).unwrap();
+    }
+    writeln!(text, "    ],").unwrap();
+
+    let mut features: Vec<(Position, Position, i32)> = terrain
+        .edge_features()
+        .map(|(first, second, feature)| (position(first), position(second), feature))
+        .filter(|(first, second, _)| first < second)
+        .collect();
+    if !features.is_empty() {
+        features.sort_unstable();
+        writeln!(text, "    edge_features: [").unwrap();
+        for (first, second, feature) in features {
+            writeln!(text, "        ({:?}, {:?}, {}),", first, second, feature).unwrap();
+        }
+        writeln!(text, "    ],").unwrap();
+    }
+    writeln!(text, ")").unwrap();
+    text
+}
+
+/// Reads a terrain from RON text like `to_ron` writes. Only `nodes` is needed, nodes without a
+/// height are at 0 and nodes that are only named in `connections` are created. `position`
+/// converts two numbers back to a position. Returns None if the text is no such RON.
+pub fn from_ron<T: Eq + Hash + Copy>(
+    text: &str,
+    position: impl Fn(i32, i32) -> T,
+) -> Option<Terrain<T>> {
+    let ron = parse(text)?;
+    let height_step = match ron.get("height_step") {
+        None => 1,
+        Some(height_step) => height_step.as_i32()?,
+    };
+    let mut terrain = Terrain::new(height_step);
+    if let Some(limits) = ron.get("height_limits") {
+        let (min_height, max_height) = limits.as_position()?;
+        terrain.set_height_limits(min_height, max_height);
+    }
+
+    let node_position = |value: &Ron| {
+        let (x, y) = value.as_position()?;
+        Some(position(x, y))
+    };
+    for entry in ron.get("nodes")?.as_slice()? {
+        let node = node_position(entry.get("position")?)?;
+        terrain.add_node(node);
+        if let Some(height) = entry.get("height") {
+            terrain.set_height(node, height.as_i32()?);
+        }
+        if let Some(terrain_type) = entry.get("terrain_type") {
+            terrain.set_terrain_type(node, terrain_type.as_i32()?);
+        }
+        if let Some(hole) = entry.get("hole") {
+            terrain.set_hole(node, hole.as_bool()?);
+        }
+        if let Some(locked) = entry.get("locked") {
+            terrain.set_locked(node, locked.as_bool()?);
+        }
+        if let Some(deck_height) = entry.get("deck_height") {
+            terrain.set_deck_height(node, Some(deck_height.as_i32()?));
+        }
+    }
+    if let Some(connections) = ron.get("connections") {
+        for connection in connections.as_slice()? {
+            match connection.as_slice()? {
+                [first, second] => {
+                    terrain.add_connected_nodes(node_position(first)?, node_position(second)?)
+                }
+                _ => return None,
+            }
+        }
+    }
+    if let Some(features) = ron.get("edge_features") {
+        for feature in features.as_slice()? {
+            match feature.as_slice()? {
+                [first, second, feature] => {
+                    terrain.set_edge_feature(
+                        node_position(first)?,
+                        node_position(second)?,
+                        feature.as_i32()?,
+                    );
+                }
+                _ => return None,
+            }
+        }
+    }
+    Some(terrain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_ron_reads_hand_written_terrain() {
+        let terrain: Terrain<(i32, i32)> = Terrain::from_ron_str(
+            "
+            // A slope with a bridge.
+            Terrain(
+                height_limits: (-2, 2),
+                nodes: [
+                    (position: (0, 0)),
+                    (position: (1, 0), height: 1, deck_height: 3, /* clamped */),
+                    (position: (2, 0), height: 2, terrain_type: 4, locked: true),
+                ],
+                connections: [((0, 0), (1, 0)), ((1, 0), (2, 0)), ((2, 0), (3, 0))],
+                edge_features: [((1, 0), (2, 0), 5)],
+            )",
+        )
+        .unwrap();
+
+        let mut heights: Vec<((i32, i32), i32)> = terrain.heights().collect();
+        heights.sort_unstable();
+        assert_eq!(
+            vec![((0, 0), 0), ((1, 0), 1), ((2, 0), 2), ((3, 0), 0)],
+            heights
+        );
+        assert_eq!(Some(2), terrain.get_deck_height((1, 0)));
+        assert_eq!(Some(4), terrain.get_terrain_type((2, 0)));
+        assert!(terrain.is_locked((2, 0)));
+        assert_eq!(5, terrain.get_edge_feature((2, 0), (1, 0)));
+    }
+
+    #[test]
+    fn from_ron_returns_terrain_of_to_ron() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+        terrain.add_connected_nodes(1, 2);
+        terrain.set_heights(&[(2, 2)]);
+        terrain.set_hole(0, true);
+        terrain.set_edge_feature(0, 1, 3);
+
+        let text = to_ron(&terrain, |node| (node, 0));
+        let read = from_ron(&text, |x, _| x).unwrap();
+
+        assert_eq!(text, to_ron(&read, |node| (node, 0)));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_ron() {
+        assert_eq!(None, parse("(a: 1"));
+        assert_eq!(None, parse("(a 1)"));
+        assert_eq!(None, parse("[1, 2] 3"));
+        assert_eq!(None, parse("(/* open)"));
+        assert_eq!(
+            Some(Ron::List(vec![Ron::Int(-1_000), Ron::Bool(true)])),
+            parse("[-1_000, true,]")
+        );
+    }
+}
@@ -0,0 +1,1805 @@
+use crate::collections::{HashMap, HashSet};
+use crate::random::Random;
+use crate::ron;
+use alloc::collections::{BinaryHeap, VecDeque};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+
+#[derive(Clone)]
+pub struct Node {
+    height: i32,
+    terrain_type: i32,
+    hole: bool,
+    deck_height: Option<i32>,
+    locked: bool,
+    nodes: Vec<usize>,
+}
+
+impl Node {
+    pub fn new(height: i32) -> Node {
+        Node {
+            height,
+            terrain_type: 0,
+            hole: false,
+            deck_height: None,
+            locked: false,
+            nodes: Vec::new(),
+        }
+    }
+
+    pub fn zero() -> Node {
+        Node {
+            height: 0,
+            terrain_type: 0,
+            hole: false,
+            deck_height: None,
+            locked: false,
+            nodes: Vec::new(),
+        }
+    }
+
+    fn layers(&self) -> Layers {
+        Layers {
+            terrain_type: self.terrain_type,
+            hole: self.hole,
+            deck_height: self.deck_height,
+        }
+    }
+}
+
+/// The layers of a node besides its height.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Layers {
+    pub terrain_type: i32,
+    pub hole: bool,
+    /// Height of the bridge deck above the node, None if there is no bridge.
+    pub deck_height: Option<i32>,
+}
+
+/// What changed in a terrain, see `Terrain::take_changes`. Every change holds the value before
+/// and after.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Changes<T> {
+    pub heights: Vec<(T, i32, i32)>,
+    pub layers: Vec<(T, Layers, Layers)>,
+    /// Changed edge features, every connection once.
+    pub features: Vec<(T, T, i32, i32)>,
+}
+
+impl<T> Default for Changes<T> {
+    fn default() -> Changes<T> {
+        Changes {
+            heights: Vec::new(),
+            layers: Vec::new(),
+            features: Vec::new(),
+        }
+    }
+}
+
+/// Counts of the work a terrain did, so the cost of edits can be measured.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Edits that moved connected nodes along, like raising a node or setting heights.
+    pub propagations: u64,
+    /// Nodes whose connected nodes were checked while propagating.
+    pub nodes_visited: u64,
+    /// Heights that were changed.
+    pub heights_changed: u64,
+}
+
+#[derive(Clone)]
+pub struct Terrain<T: core::cmp::Eq + core::hash::Hash + Clone + Copy> {
+    height_step: i32,
+    min_height: i32,
+    max_height: i32,
+    node_map: HashMap<T, usize>,
+    nodes: Vec<Node>,
+    /// Position of every node by index, None where a node was removed.
+    keys: Vec<Option<T>>,
+    /// Indices of removed nodes, reused by `add_node` so the indices of other nodes never move.
+    free: Vec<usize>,
+    edge_features: HashMap<(T, T), i32>,
+    /// Indices of the nodes that changed since the last `take_dirty`.
+    dirty: HashSet<usize>,
+    /// Height and layers of nodes before their first change since the last `take_changes`, by
+    /// index.
+    changed: HashMap<usize, (i32, Layers)>,
+    /// Edge features before their first change since the last `take_changes`.
+    changed_features: HashMap<(T, T), i32>,
+    /// Number of edits since the terrain was created.
+    edits: u64,
+    stats: Stats,
+}
+
+impl<T: core::cmp::Eq + core::hash::Hash + Clone + Copy> Terrain<T> {
+    pub fn new(height_step: i32) -> Terrain<T> {
+        Terrain {
+            height_step,
+            min_height: i32::MIN,
+            max_height: i32::MAX,
+            node_map: HashMap::default(),
+            nodes: Vec::new(),
+            keys: Vec::new(),
+            free: Vec::new(),
+            edge_features: HashMap::default(),
+            dirty: HashSet::default(),
+            changed: HashMap::default(),
+            changed_features: HashMap::default(),
+            edits: 0,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Creates a terrain from RON text, e.g. a hand-written test fixture or a modded map. See
+    /// `ron::from_ron` for the format. Returns None if the text cannot be read.
+    pub fn from_ron_str(text: &str) -> Option<Terrain<T>>
+    where
+        T: From<(i32, i32)>,
+    {
+        ron::from_ron(text, |x, y| T::from((x, y)))
+    }
+
+    /// Limits all heights to `min_height..=max_height`. Nodes outside of the range are moved into
+    /// it and later edits are clamped.
+    pub fn set_height_limits(&mut self, min_height: i32, max_height: i32) {
+        self.edits += 1;
+        let max_height = max_height.max(min_height);
+        self.min_height = min_height;
+        self.max_height = max_height;
+        for index in 0..self.nodes.len() {
+            let node = &self.nodes[index];
+            let height = node.height.clamp(min_height, max_height);
+            let deck_height = node
+                .deck_height
+                .map(|deck_height| deck_height.clamp(min_height, max_height));
+            if (height, deck_height) != (node.height, node.deck_height) {
+                self.record(index);
+                self.nodes[index].height = height;
+                self.nodes[index].deck_height = deck_height;
+                self.dirty.insert(index);
+            }
+        }
+    }
+
+    /// Returns the height of one step.
+    pub fn height_step(&self) -> i32 {
+        self.height_step
+    }
+
+    /// Returns the lowest and the highest height nodes may have.
+    pub fn height_limits(&self) -> (i32, i32) {
+        (self.min_height, self.max_height)
+    }
+
+    fn clamp_height(&self, height: i32) -> i32 {
+        height.clamp(self.min_height, self.max_height)
+    }
+
+    /// Sets the height of the node at the index and marks it as dirty if that changes it.
+    fn write_height(&mut self, index: usize, height: i32) {
+        if self.nodes[index].height != height {
+            self.record(index);
+            self.nodes[index].height = height;
+            self.dirty.insert(index);
+            self.stats.heights_changed += 1;
+        }
+    }
+
+    /// Remembers the height and layers of the node at the index before it is changed, unless it
+    /// was already changed since the last `take_changes`.
+    fn record(&mut self, index: usize) {
+        let node = &self.nodes[index];
+        let state = (node.height, node.layers());
+        self.changed.entry(index).or_insert(state);
+    }
+
+    /// Returns the work done since the terrain was created or the stats were reset.
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::default();
+    }
+
+    /// Returns how often the terrain was edited since it was created, counting every call that
+    /// may change it. A copy can tell by it whether the terrain it was made from changed since.
+    pub fn edits(&self) -> u64 {
+        self.edits
+    }
+
+    /// Returns the nodes that were added or whose height, terrain type, hole mark, deck or edge
+    /// features changed since the last call, e.g. to rebuild only the parts of a mesh that show
+    /// them. Nodes are returned in the order of their indices.
+    pub fn take_dirty(&mut self) -> Vec<T> {
+        let mut dirty: Vec<usize> = self.dirty.drain().collect();
+        dirty.sort_unstable();
+        dirty
+            .into_iter()
+            .filter_map(|index| self.keys.get(index).copied().flatten())
+            .collect()
+    }
+
+    /// Returns the heights and layers of nodes and the edge features that changed since the last
+    /// call, each with the value before and after, e.g. to record an edit that can be undone.
+    /// Values that were changed back are left out. Nodes are returned in the order of their
+    /// indices. Unlike `take_dirty`, this only costs as much as the changes.
+    pub fn take_changes(&mut self) -> Changes<T> {
+        let mut changed: Vec<(usize, (i32, Layers))> = self.changed.drain().collect();
+        changed.sort_unstable_by_key(|(index, _)| *index);
+        let mut changes = Changes::default();
+        for (index, (height, layers)) in changed {
+            let position = match self.keys.get(index).copied().flatten() {
+                None => continue,
+                Some(position) => position,
+            };
+            let node = &self.nodes[index];
+            if node.height != height {
+                changes.heights.push((position, height, node.height));
+            }
+            if node.layers() != layers {
+                changes.layers.push((position, layers, node.layers()));
+            }
+        }
+        let features: Vec<((T, T), i32)> = self.changed_features.drain().collect();
+        for ((first, second), feature) in features {
+            let new_feature = self.get_edge_feature(first, second);
+            if new_feature != feature {
+                changes.features.push((first, second, feature, new_feature));
+            }
+        }
+        changes
+    }
+
+    pub fn get_index_of_node(self, position: T) -> Option<usize> {
+        self.node_map.get(&position).copied()
+    }
+
+    pub fn get_height_of_node(&self, position: T) -> Option<i32> {
+        self.node_map
+            .get(&position)
+            .map(|index| self.nodes[*index].height)
+    }
+
+    /// Returns the average height of the nodes connected to a node, None if the node does not exist
+    /// or has no connections.
+    pub fn get_average_connected_height(&self, position: T) -> Option<f32> {
+        let node = &self.nodes[*self.node_map.get(&position)?];
+        if node.nodes.is_empty() {
+            return None;
+        }
+        let sum: i32 = node
+            .nodes
+            .iter()
+            .map(|index| self.nodes[*index].height)
+            .sum();
+        Some(sum as f32 / node.nodes.len() as f32)
+    }
+
+    /// Returns the positions and heights of all nodes, in the order the nodes were added. A node
+    /// added after a removal takes the place of the removed node.
+    pub fn heights(&self) -> impl Iterator<Item = (T, i32)> + '_ {
+        self.positions()
+            .into_iter()
+            .zip(&self.nodes)
+            .filter_map(|(position, node)| Some((position?, node.height)))
+    }
+
+    /// Returns a checksum of the heights, terrain types, holes, locks, decks and connections of
+    /// all nodes and of the edge features. `coordinates` converts a position to two numbers, by
+    /// which the nodes are ordered, so the checksum does not depend on the order in which the
+    /// nodes were added. It is the same on every machine for identical terrains, so peers in a
+    /// lockstep game can compare it to detect desyncs.
+    pub fn checksum<F>(&self, coordinates: F) -> u64
+    where
+        F: Fn(T) -> (i32, i32),
+    {
+        // FNV-1a, which unlike the hasher of the standard library is specified.
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut add = |value: i32| {
+            for byte in &value.to_le_bytes() {
+                hash = (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        let positions: Vec<Option<(i32, i32)>> =
+            self.keys.iter().map(|key| key.map(&coordinates)).collect();
+        let mut order: Vec<((i32, i32), usize)> = positions
+            .iter()
+            .enumerate()
+            .filter_map(|(index, position)| Some(((*position)?, index)))
+            .collect();
+        order.sort_unstable();
+
+        for ((x, y), index) in order {
+            let node = &self.nodes[index];
+            add(x);
+            add(y);
+            add(node.height);
+            add(node.terrain_type);
+            add(node.hole as i32 | (node.locked as i32) << 1);
+            add(node.deck_height.unwrap_or(i32::MIN));
+            let mut connected: Vec<(i32, i32)> = node
+                .nodes
+                .iter()
+                .filter_map(|connected| positions[*connected])
+                .collect();
+            connected.sort_unstable();
+            add(connected.len() as i32);
+            for (x, y) in connected {
+                add(x);
+                add(y);
+            }
+        }
+
+        let mut features: Vec<_> = self
+            .edge_features()
+            .map(|(first, second, feature)| {
+                let (first, second) = (coordinates(first), coordinates(second));
+                (first.min(second), first.max(second), feature)
+            })
+            .collect();
+        features.sort_unstable();
+        for (first, second, feature) in features {
+            add(first.0);
+            add(first.1);
+            add(second.0);
+            add(second.1);
+            add(feature);
+        }
+        hash
+    }
+
+    /// Returns all connections between nodes, each once.
+    pub fn connections(&self) -> Vec<(T, T)> {
+        let positions = self.positions();
+        let mut connections = Vec::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            for connected in &node.nodes {
+                if *connected <= index {
+                    continue;
+                }
+                if let (Some(first), Some(second)) = (positions[index], positions[*connected]) {
+                    connections.push((first, second));
+                }
+            }
+        }
+        connections
+    }
+
+    /// Returns the position of every node by index.
+    fn positions(&self) -> Vec<Option<T>> {
+        self.keys.clone()
+    }
+
+    /// Sets the height of node without changing connected nodes. Returns whether the node exists.
+    pub fn set_height(&mut self, position: T, height: i32) -> bool {
+        self.edits += 1;
+        let height = self.clamp_height(height);
+        match self.node_map.get(&position).copied() {
+            None => false,
+            Some(index) => {
+                self.write_height(index, height);
+                true
+            }
+        }
+    }
+
+    pub fn get_terrain_type(&self, position: T) -> Option<i32> {
+        self.node_map
+            .get(&position)
+            .map(|index| self.nodes[*index].terrain_type)
+    }
+
+    /// Sets the terrain type of node. Returns whether the node exists.
+    pub fn set_terrain_type(&mut self, position: T, terrain_type: i32) -> bool {
+        self.edits += 1;
+        match self.node_map.get(&position) {
+            None => false,
+            Some(index) => {
+                let index = *index;
+                if self.nodes[index].terrain_type != terrain_type {
+                    self.record(index);
+                    self.nodes[index].terrain_type = terrain_type;
+                    self.dirty.insert(index);
+                }
+                true
+            }
+        }
+    }
+
+    /// Returns whether the node is marked as hole. Nodes that do not exist are no holes.
+    pub fn is_hole(&self, position: T) -> bool {
+        matches!(self.node_map.get(&position), Some(index) if self.nodes[*index].hole)
+    }
+
+    /// Marks the node as hole or removes the mark. Returns whether the node exists.
+    pub fn set_hole(&mut self, position: T, hole: bool) -> bool {
+        self.edits += 1;
+        match self.node_map.get(&position) {
+            None => false,
+            Some(index) => {
+                let index = *index;
+                if self.nodes[index].hole != hole {
+                    self.record(index);
+                    self.nodes[index].hole = hole;
+                    self.dirty.insert(index);
+                }
+                true
+            }
+        }
+    }
+
+    /// Returns whether the node is locked. Nodes that do not exist are not locked.
+    pub fn is_locked(&self, position: T) -> bool {
+        matches!(self.node_map.get(&position), Some(index) if self.nodes[*index].locked)
+    }
+
+    /// Locks the node, so generators like `erode` leave its height unchanged, or unlocks it.
+    /// Returns whether the node exists.
+    pub fn set_locked(&mut self, position: T, locked: bool) -> bool {
+        self.edits += 1;
+        match self.node_map.get(&position) {
+            None => false,
+            Some(index) => {
+                self.nodes[*index].locked = locked;
+                true
+            }
+        }
+    }
+
+    /// Returns the height of the bridge deck above the node, None if there is no bridge.
+    pub fn get_deck_height(&self, position: T) -> Option<i32> {
+        self.node_map
+            .get(&position)
+            .and_then(|index| self.nodes[*index].deck_height)
+    }
+
+    /// Returns the terrain type, hole mark and bridge deck of the node, None if it does not exist.
+    pub fn get_layers(&self, position: T) -> Option<Layers> {
+        self.node_map
+            .get(&position)
+            .map(|index| self.nodes[*index].layers())
+    }
+
+    /// Sets the height of a bridge deck above the node, None removes the bridge. The deck is a
+    /// second layer at the node and does not affect the height of the node itself. Returns whether
+    /// the node exists.
+    pub fn set_deck_height(&mut self, position: T, deck_height: Option<i32>) -> bool {
+        self.edits += 1;
+        let deck_height = deck_height.map(|deck_height| self.clamp_height(deck_height));
+        match self.node_map.get(&position) {
+            None => false,
+            Some(index) => {
+                let index = *index;
+                if self.nodes[index].deck_height != deck_height {
+                    self.record(index);
+                    self.nodes[index].deck_height = deck_height;
+                    self.dirty.insert(index);
+                }
+                true
+            }
+        }
+    }
+
+    /// Returns the feature of the connection between two nodes, 0 if there is none.
+    pub fn get_edge_feature(&self, first: T, second: T) -> i32 {
+        self.edge_features
+            .get(&(first, second))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Sets the feature of the connection between two nodes, 0 removes it. Returns whether the
+    /// nodes are connected.
+    pub fn set_edge_feature(&mut self, first: T, second: T, feature: i32) -> bool {
+        self.edits += 1;
+        let (first_index, second_index) =
+            match (self.node_map.get(&first), self.node_map.get(&second)) {
+                (Some(first), Some(second)) if self.nodes[*first].nodes.contains(second) => {
+                    (*first, *second)
+                }
+                _ => return false,
+            };
+        let old_feature = self.get_edge_feature(first, second);
+        if old_feature != feature {
+            if !self.changed_features.contains_key(&(second, first)) {
+                self.changed_features
+                    .entry((first, second))
+                    .or_insert(old_feature);
+            }
+            self.dirty.insert(first_index);
+            self.dirty.insert(second_index);
+        }
+
+        if feature == 0 {
+            self.edge_features.remove(&(first, second));
+            self.edge_features.remove(&(second, first));
+        } else {
+            self.edge_features.insert((first, second), feature);
+            self.edge_features.insert((second, first), feature);
+        }
+        true
+    }
+
+    /// Returns all connections with a feature. Every connection is returned in both directions.
+    pub fn edge_features(&self) -> impl Iterator<Item = (T, T, i32)> + '_ {
+        self.edge_features
+            .iter()
+            .map(|((first, second), feature)| (*first, *second, *feature))
+    }
+
+    /// Sets the heights of the given nodes. Other nodes are raised or lowered just enough that no
+    /// connected nodes differ by more than one step, as if the heights were edited step by step.
+    pub fn set_heights(&mut self, heights: &[(T, i32)]) {
+        self.edits += 1;
+        let mut fixed = HashSet::default();
+        for (position, height) in heights {
+            if let Some(index) = self.node_map.get(position).copied() {
+                self.write_height(index, self.clamp_height(*height));
+                fixed.insert(index);
+            }
+        }
+
+        self.propagate_heights(&fixed);
+    }
+
+    /// Sets the heights of the given nodes, e.g. from a generator, and then raises nodes just
+    /// enough that no connected nodes differ by more than one step. Unlike `set_heights`, the
+    /// given nodes are adjusted as well, so the heights can be rough. Locked nodes keep their
+    /// height.
+    pub fn set_generated_heights(&mut self, heights: &[(T, i32)]) {
+        self.edits += 1;
+        for (position, height) in heights {
+            if let Some(index) = self.node_map.get(position).copied() {
+                if !self.nodes[index].locked {
+                    self.write_height(index, self.clamp_height(*height));
+                }
+            }
+        }
+
+        let locked: HashSet<usize> = (0..self.nodes.len())
+            .filter(|index| self.nodes[*index].locked)
+            .collect();
+        let all: Vec<usize> = (0..self.nodes.len()).collect();
+        self.propagate_heights_from(&all, &locked);
+    }
+
+    /// Routes water from every node to the outlets, e.g. the nodes at or below sea level. Water
+    /// flows downhill along the connections, through flats and over the lowest rim of
+    /// depressions. Every node adds its `rainfall` to the water that flows through it. Returns
+    /// every node that drains to an outlet with the node its water flows to and the total amount
+    /// of water that flows along that connection. Nodes that are not connected to an outlet are
+    /// left out.
+    pub fn river_flow(&self, outlets: &[T], rainfall: impl Fn(T) -> f32) -> Vec<(T, T, f32)> {
+        let positions = self.positions();
+
+        // Priority flood: nodes are visited from the lowest up, each one from the neighbour it
+        // drains into.
+        let mut downstream = vec![None; self.nodes.len()];
+        let mut visited = vec![false; self.nodes.len()];
+        let mut order = Vec::new();
+        let mut open = BinaryHeap::new();
+        // Nodes at the same level are visited in the order they were found.
+        let mut sequence = 0;
+        for index in outlets
+            .iter()
+            .filter_map(|outlet| self.node_map.get(outlet))
+        {
+            if !visited[*index] {
+                visited[*index] = true;
+                open.push(Reverse((self.nodes[*index].height, sequence, *index)));
+                sequence += 1;
+            }
+        }
+        while let Some(Reverse((level, _, index))) = open.pop() {
+            order.push(index);
+            for connected in &self.nodes[index].nodes {
+                if !visited[*connected] {
+                    visited[*connected] = true;
+                    downstream[*connected] = Some(index);
+                    let height = self.nodes[*connected].height.max(level);
+                    open.push(Reverse((height, sequence, *connected)));
+                    sequence += 1;
+                }
+            }
+        }
+
+        let mut flow: Vec<f32> = positions
+            .iter()
+            .map(|position| position.map_or(0.0, &rainfall))
+            .collect();
+        let mut result = Vec::new();
+        for index in order.into_iter().rev() {
+            if let Some(next) = downstream[index] {
+                flow[next] += flow[index];
+                if let (Some(from), Some(to)) = (positions[index], positions[next]) {
+                    result.push((from, to, flow[index]));
+                }
+            }
+        }
+        result
+    }
+
+    /// Applies thermal erosion: wherever a node is more than `talus` above a connected node,
+    /// material slides down to it. `rate` is the share of the excess height that is moved per
+    /// iteration, between 0 and 1. The heights are simulated continuously and rounded to steps at
+    /// the end, after which connected nodes differ by at most one step again. Locked nodes neither
+    /// lose nor receive material.
+    pub fn erode(&mut self, talus: f32, rate: f32, iterations: u32) {
+        self.edits += 1;
+        let mut heights: Vec<f32> = self.nodes.iter().map(|node| node.height as f32).collect();
+        let rate = rate.clamp(0.0, 1.0);
+        for _ in 0..iterations {
+            let mut changes = vec![0.0; heights.len()];
+            for (index, node) in self.nodes.iter().enumerate() {
+                if node.locked {
+                    continue;
+                }
+                // Every connection gets at most its share of the excess, and only half of the
+                // difference is moved, so nodes do not swap heights.
+                let share = rate / (2 * node.nodes.len()) as f32;
+                for connected in &node.nodes {
+                    let difference = heights[index] - heights[*connected];
+                    if difference > talus && !self.nodes[*connected].locked {
+                        let amount = (difference - talus) * share;
+                        changes[index] -= amount;
+                        changes[*connected] += amount;
+                    }
+                }
+            }
+            for (height, change) in heights.iter_mut().zip(changes) {
+                *height += change;
+            }
+        }
+
+        let step = self.height_step as f32;
+        let mut locked = HashSet::default();
+        for (index, height) in heights.into_iter().enumerate() {
+            if self.nodes[index].locked {
+                locked.insert(index);
+            } else {
+                let height = round(height / step) * self.height_step;
+                self.write_height(index, self.clamp_height(height));
+            }
+        }
+
+        let all: Vec<usize> = (0..self.nodes.len()).collect();
+        self.propagate_heights_from(&all, &locked);
+    }
+
+    /// Ages the terrain: in every iteration, the nodes are visited in order, and peaks that are
+    /// higher than all connected nodes are lowered by one step, pits that are lower than all
+    /// connected nodes are raised by one step, and flat nodes whose connected nodes all have the
+    /// same height are moved one step up or down. Each change happens with the probability
+    /// `intensity`, between 0 and 1. Locked nodes are not changed.
+    pub fn weather(&mut self, intensity: f32, iterations: u32, seed: u64) {
+        self.edits += 1;
+        let mut random = Random::new(seed);
+        let mut locked = HashSet::default();
+        for (index, node) in self.nodes.iter().enumerate() {
+            if node.locked {
+                locked.insert(index);
+            }
+        }
+
+        for _ in 0..iterations {
+            for index in 0..self.nodes.len() {
+                let node = &self.nodes[index];
+                if node.locked || node.nodes.is_empty() || random.next_f32() >= intensity {
+                    continue;
+                }
+                let height = node.height;
+                let connected = node
+                    .nodes
+                    .iter()
+                    .map(|connected| self.nodes[*connected].height);
+                let change = if connected.clone().all(|other| other < height) {
+                    -self.height_step
+                } else if connected.clone().all(|other| other > height) {
+                    self.height_step
+                } else if connected.clone().all(|other| other == height) {
+                    if random.next_f32() < 0.5 {
+                        -self.height_step
+                    } else {
+                        self.height_step
+                    }
+                } else {
+                    continue;
+                };
+                self.write_height(index, self.clamp_height(height + change));
+            }
+
+            let all: Vec<usize> = (0..self.nodes.len()).collect();
+            self.propagate_heights_from(&all, &locked);
+        }
+    }
+
+    /// Moves the nodes that are connected to the fixed nodes so that the height difference
+    /// between connected nodes is at most one step. Fixed nodes are not changed.
+    fn propagate_heights(&mut self, fixed: &HashSet<usize>) {
+        // Sorted, so the nodes are visited in the same order on every machine.
+        let mut start: Vec<usize> = fixed.iter().copied().collect();
+        start.sort_unstable();
+        self.propagate_heights_from(&start, fixed);
+    }
+
+    /// Like `propagate_heights`, but starts at the given nodes instead of the fixed ones.
+    fn propagate_heights_from(&mut self, start: &[usize], fixed: &HashSet<usize>) {
+        self.stats.propagations += 1;
+        let mut open = start.to_vec();
+        while let Some(index) = open.pop() {
+            self.stats.nodes_visited += 1;
+            let minimum = self.nodes[index].height - self.height_step;
+            for connected in self.nodes[index].nodes.clone() {
+                if !fixed.contains(&connected) && self.nodes[connected].height < minimum {
+                    self.write_height(connected, minimum);
+                    open.push(connected);
+                }
+            }
+        }
+
+        let mut open = start.to_vec();
+        while let Some(index) = open.pop() {
+            self.stats.nodes_visited += 1;
+            let maximum = self.nodes[index].height + self.height_step;
+            for connected in self.nodes[index].nodes.clone() {
+                if !fixed.contains(&connected) && self.nodes[connected].height > maximum {
+                    self.write_height(connected, maximum);
+                    open.push(connected);
+                }
+            }
+        }
+    }
+
+    /// Adds node to terrain if it does not already exist. Returns whether it was added or not.
+    pub fn add_node(&mut self, position: T) -> bool {
+        self.edits += 1;
+        if self.node_map.contains_key(&position) {
+            return false;
+        }
+        let node = Node::zero();
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                self.keys[index] = Some(position);
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.keys.push(Some(position));
+                self.nodes.len() - 1
+            }
+        };
+        self.node_map.insert(position, index);
+        self.dirty.insert(index);
+
+        true
+    }
+
+    /// Remove node from terrain if it exists, together with its connections and their edge
+    /// features. The nodes it was connected to are marked as dirty. Returns whether it could be
+    /// removed or not.
+    pub fn remove_node(&mut self, position: T) -> bool {
+        self.edits += 1;
+        let index = match self.node_map.remove(&position) {
+            None => return false,
+            Some(index) => index,
+        };
+        let node = core::mem::replace(&mut self.nodes[index], Node::zero());
+        for connected in node.nodes {
+            self.nodes[connected].nodes.retain(|other| *other != index);
+            self.dirty.insert(connected);
+        }
+        self.edge_features
+            .retain(|(first, second), _| *first != position && *second != position);
+        self.keys[index] = None;
+        self.free.push(index);
+        self.dirty.remove(&index);
+        self.changed.remove(&index);
+        self.changed_features
+            .retain(|(first, second), _| *first != position && *second != position);
+        true
+    }
+
+    /// Adds nodes that are connected. If either node is not present it will be created. Nodes
+    /// that are already connected stay connected once.
+    pub fn add_connected_nodes(&mut self, first: T, second: T) {
+        self.edits += 1;
+        if !self.node_map.contains_key(&first) {
+            self.add_node(first);
+        }
+        if !self.node_map.contains_key(&second) {
+            self.add_node(second);
+        }
+
+        let first = self.node_map[&first];
+        let second = self.node_map[&second];
+        if self.nodes[first].nodes.contains(&second) {
+            return;
+        }
+        self.nodes[first].nodes.push(second);
+        self.nodes[second].nodes.push(first);
+    }
+
+    pub fn increase_height(&mut self, node: T) {
+        self.edits += 1;
+        let index = self.node_map[&node];
+
+        self.stats.propagations += 1;
+        if let Some(height) = self.step_up(index) {
+            self.raise_node(index, height);
+        }
+    }
+
+    /// Returns the height one step above the node at the index, None if that is above the limit.
+    fn step_up(&self, index: usize) -> Option<i32> {
+        self.nodes[index]
+            .height
+            .checked_add(self.height_step)
+            .filter(|height| *height <= self.max_height)
+    }
+
+    /// Returns the height one step below the node at the index, None if that is below the limit.
+    fn step_down(&self, index: usize) -> Option<i32> {
+        self.nodes[index]
+            .height
+            .checked_sub(self.height_step)
+            .filter(|height| *height >= self.min_height)
+    }
+
+    /// Raises the node at the index to the height, if it is lower, and then the connected nodes
+    /// just enough that none of them is more than one step lower than a node it is connected to.
+    /// The nodes are worked through in a queue that holds every node at most once, so long
+    /// cascades cannot overflow the stack and cycles of connections end.
+    fn raise_node(&mut self, index: usize, height: i32) {
+        if self.nodes[index].height >= height {
+            return;
+        }
+        self.write_height(index, height);
+        let mut open = VecDeque::new();
+        let mut queued = HashSet::default();
+        open.push_back(index);
+        queued.insert(index);
+        while let Some(index) = open.pop_front() {
+            queued.remove(&index);
+            self.stats.nodes_visited += 1;
+            let minimum = self.nodes[index].height - self.height_step;
+            for position in 0..self.nodes[index].nodes.len() {
+                let connected = self.nodes[index].nodes[position];
+                if self.nodes[connected].height < minimum {
+                    self.write_height(connected, minimum);
+                    if queued.insert(connected) {
+                        open.push_back(connected);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Like `raise_node`, but lowers the node and then the connected nodes.
+    fn lower_node(&mut self, index: usize, height: i32) {
+        if self.nodes[index].height <= height {
+            return;
+        }
+        self.write_height(index, height);
+        let mut open = VecDeque::new();
+        let mut queued = HashSet::default();
+        open.push_back(index);
+        queued.insert(index);
+        while let Some(index) = open.pop_front() {
+            queued.remove(&index);
+            self.stats.nodes_visited += 1;
+            let maximum = self.nodes[index].height + self.height_step;
+            for position in 0..self.nodes[index].nodes.len() {
+                let connected = self.nodes[index].nodes[position];
+                if self.nodes[connected].height > maximum {
+                    self.write_height(connected, maximum);
+                    if queued.insert(connected) {
+                        open.push_back(connected);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Increases the height of all nodes by one step. Nodes that were already raised by propagation
+    /// from another node of the batch are not raised any further.
+    pub fn increase_heights(&mut self, nodes: &[T]) {
+        self.edits += 1;
+        let targets: Vec<(usize, i32)> = nodes
+            .iter()
+            .filter_map(|node| self.node_map.get(node))
+            .filter_map(|index| Some((*index, self.step_up(*index)?)))
+            .collect();
+        self.stats.propagations += 1;
+
+        for (index, target) in targets {
+            self.raise_node(index, target);
+        }
+    }
+
+    pub fn decrease_height(&mut self, node: T) {
+        self.edits += 1;
+        let index = self.node_map[&node];
+
+        self.stats.propagations += 1;
+        if let Some(height) = self.step_down(index) {
+            self.lower_node(index, height);
+        }
+    }
+
+    /// Decreases the height of all nodes by one step. Nodes that were already lowered by
+    /// propagation from another node of the batch are not lowered any further.
+    pub fn decrease_heights(&mut self, nodes: &[T]) {
+        self.edits += 1;
+        let targets: Vec<(usize, i32)> = nodes
+            .iter()
+            .filter_map(|node| self.node_map.get(node))
+            .filter_map(|index| Some((*index, self.step_down(*index)?)))
+            .collect();
+        self.stats.propagations += 1;
+
+        for (index, target) in targets {
+            self.lower_node(index, target);
+        }
+    }
+}
+
+/// Rounds half away from zero like `f32::round`, which needs std.
+fn round(value: f32) -> i32 {
+    if value < 0.0 {
+        (value - 0.5) as i32
+    } else {
+        (value + 0.5) as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_node_adds_new_node_and_returns_true() {
+        let mut terrain = Terrain::new(1);
+        let return_value: bool = terrain.add_node(0);
+
+        assert!(return_value);
+        assert!(terrain.node_map.contains_key(&0));
+        assert_eq!(0, terrain.nodes[0].height);
+    }
+
+    #[test]
+    fn add_node_does_not_overwrite_existing_node_and_returns_false() {
+        let mut terrain = Terrain::new(1);
+        let mut node = Node::new(0);
+        node.nodes.push(0);
+        terrain.nodes.push(node);
+        terrain.node_map.insert(0, 0);
+        let return_value: bool = terrain.add_node(0);
+
+        assert!(!return_value);
+        assert_eq!(0, terrain.nodes[0].nodes[0]);
+    }
+
+    #[test]
+    fn remove_node_removes_existing_node_and_returns_true() {
+        let mut terrain = Terrain::new(1);
+        terrain.nodes.push(Node::zero());
+        terrain.keys.push(Some(0));
+        terrain.node_map.insert(0, 0);
+        let return_value: bool = terrain.remove_node(0);
+
+        assert!(return_value);
+        assert!(!terrain.node_map.contains_key(&0));
+        assert_eq!(vec![0], terrain.free);
+    }
+
+    #[test]
+    fn edits_count_every_change() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+        let edits = terrain.edits();
+
+        terrain.set_edge_feature(0, 1, 2);
+        terrain.increase_height(0);
+        terrain.take_dirty();
+
+        assert_eq!(edits + 2, terrain.edits());
+    }
+
+    #[test]
+    fn take_changes_returns_old_and_new_values_once() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+        terrain.add_connected_nodes(1, 2);
+        terrain.set_height(2, 3);
+        terrain.take_changes();
+
+        terrain.increase_height(0);
+        terrain.set_height(2, 5);
+        terrain.set_height(2, 3);
+        terrain.set_terrain_type(1, 4);
+        terrain.set_deck_height(1, Some(2));
+        terrain.set_edge_feature(0, 1, 2);
+        terrain.set_edge_feature(1, 0, 3);
+        let changes = terrain.take_changes();
+
+        assert_eq!(vec![(0, 0, 1)], changes.heights);
+        let layers = Layers {
+            terrain_type: 0,
+            hole: false,
+            deck_height: None,
+        };
+        let new_layers = Layers {
+            terrain_type: 4,
+            deck_height: Some(2),
+            ..layers
+        };
+        assert_eq!(vec![(1, layers, new_layers)], changes.layers);
+        assert_eq!(vec![(0, 1, 0, 3)], changes.features);
+        assert_eq!(Changes::default(), terrain.take_changes());
+    }
+
+    #[test]
+    fn checksum_does_not_depend_on_order_of_nodes() {
+        let mut first = Terrain::new(1);
+        first.add_connected_nodes(0, 1);
+        first.add_connected_nodes(1, 2);
+        let mut second = Terrain::new(1);
+        second.add_node(5);
+        second.add_connected_nodes(2, 1);
+        second.add_connected_nodes(1, 0);
+        second.remove_node(5);
+        let checksum = |terrain: &Terrain<i32>| terrain.checksum(|node| (node, 0));
+
+        assert_eq!(checksum(&first), checksum(&second));
+        second.set_edge_feature(1, 2, 1);
+        assert_ne!(checksum(&first), checksum(&second));
+        first.set_edge_feature(2, 1, 1);
+        assert_eq!(checksum(&first), checksum(&second));
+    }
+
+    #[test]
+    fn remove_node_keeps_other_nodes_and_reuses_its_index() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+        terrain.add_connected_nodes(1, 2);
+        terrain.set_edge_feature(0, 1, 3);
+        terrain.set_height(2, 1);
+        terrain.take_dirty();
+
+        assert!(terrain.remove_node(0));
+        assert_eq!(vec![1], terrain.take_dirty());
+        assert_eq!(vec![(1, 2)], terrain.connections());
+        assert_eq!(0, terrain.edge_features().count());
+        assert_eq!(Some(1), terrain.get_height_of_node(2));
+
+        assert!(terrain.add_node(3));
+        assert_eq!(Some(0), terrain.node_map.get(&3).copied());
+        assert_eq!(
+            vec![(3, 0), (1, 0), (2, 1)],
+            terrain.heights().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn remove_node_returns_false_if_node_does_not_exist() {
+        let mut terrain = Terrain::new(1);
+        let return_value: bool = terrain.remove_node(0);
+
+        assert!(!return_value);
+    }
+
+    #[test]
+    fn heights_returns_height_of_every_node() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+        terrain.nodes[1].height = 2;
+
+        let mut heights: Vec<(i32, i32)> = terrain.heights().collect();
+        heights.sort_unstable();
+
+        assert_eq!(vec![(0, 0), (1, 2)], heights);
+    }
+
+    #[test]
+    fn heights_are_in_order_of_insertion() {
+        let mut terrain = Terrain::new(1);
+        for (first, second) in &[(4, 2), (2, 9), (9, 1)] {
+            terrain.add_connected_nodes(*first, *second);
+        }
+
+        let nodes: Vec<i32> = terrain.heights().map(|(node, _)| node).collect();
+
+        assert_eq!(vec![4, 2, 9, 1], nodes);
+    }
+
+    #[test]
+    fn get_average_connected_height_returns_average_of_connected_nodes() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+        terrain.add_connected_nodes(0, 2);
+        terrain.add_node(3);
+        terrain.nodes[1].height = 2;
+        terrain.nodes[2].height = 3;
+
+        assert_eq!(Some(2.5), terrain.get_average_connected_height(0));
+        assert_eq!(None, terrain.get_average_connected_height(3));
+        assert_eq!(None, terrain.get_average_connected_height(4));
+    }
+
+    #[test]
+    fn set_hole_marks_existing_node_and_returns_true() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_node(0);
+
+        assert!(terrain.set_hole(0, true));
+        assert!(terrain.is_hole(0));
+        assert!(terrain.set_hole(0, false));
+        assert!(!terrain.is_hole(0));
+    }
+
+    #[test]
+    fn set_hole_returns_false_for_missing_node() {
+        let mut terrain: Terrain<i32> = Terrain::new(1);
+
+        assert!(!terrain.set_hole(0, true));
+        assert!(!terrain.is_hole(0));
+    }
+
+    #[test]
+    fn set_locked_marks_existing_node_and_returns_true() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_node(0);
+
+        assert!(terrain.set_locked(0, true));
+        assert!(terrain.is_locked(0));
+        assert!(!terrain.set_locked(1, true));
+    }
+
+    fn star(center_height: i32) -> Terrain<i32> {
+        let mut terrain = Terrain::new(1);
+        for node in 1..=6 {
+            terrain.add_connected_nodes(0, node);
+        }
+        terrain.set_height(0, center_height);
+        terrain
+    }
+
+    #[test]
+    fn erode_flattens_spikes() {
+        let mut terrain = star(1);
+
+        terrain.erode(0.25, 1.0, 50);
+
+        for node in 0..=6 {
+            assert_eq!(Some(0), terrain.get_height_of_node(node));
+        }
+    }
+
+    #[test]
+    fn erode_keeps_slopes_within_talus() {
+        let mut terrain = star(1);
+
+        terrain.erode(1.0, 1.0, 20);
+
+        assert_eq!(Some(1), terrain.get_height_of_node(0));
+    }
+
+    #[test]
+    fn erode_does_not_change_locked_nodes() {
+        let mut terrain = star(1);
+        terrain.set_locked(0, true);
+
+        terrain.erode(0.25, 1.0, 50);
+
+        assert_eq!(Some(1), terrain.get_height_of_node(0));
+    }
+
+    #[test]
+    fn weather_lowers_peaks_and_raises_pits() {
+        let mut peak = star(1);
+        let mut pit = star(-1);
+
+        peak.weather(1.0, 1, 3);
+        pit.weather(1.0, 1, 3);
+
+        assert_eq!(Some(0), peak.get_height_of_node(0));
+        assert_eq!(Some(0), pit.get_height_of_node(0));
+    }
+
+    #[test]
+    fn weather_roughens_flat_areas() {
+        let mut terrain = star(0);
+
+        terrain.weather(1.0, 1, 3);
+
+        let changed = (0..=6).any(|node| terrain.get_height_of_node(node) != Some(0));
+        assert!(changed);
+    }
+
+    #[test]
+    fn weather_does_not_change_locked_nodes() {
+        let mut terrain = star(1);
+        terrain.set_locked(0, true);
+
+        terrain.weather(1.0, 5, 3);
+
+        assert_eq!(Some(1), terrain.get_height_of_node(0));
+    }
+
+    fn slope() -> Terrain<i32> {
+        let mut terrain = Terrain::new(1);
+        for node in 0..4 {
+            terrain.add_connected_nodes(node, node + 1);
+        }
+        terrain.set_heights(&[(0, 0), (1, 1), (2, 1), (3, 2), (4, 3)]);
+        terrain
+    }
+
+    #[test]
+    fn river_flow_accumulates_towards_outlets() {
+        let flow = slope().river_flow(&[0], |_| 1.0);
+
+        assert_eq!(
+            vec![(4, 3, 1.0), (3, 2, 2.0), (2, 1, 3.0), (1, 0, 4.0)],
+            flow
+        );
+    }
+
+    #[test]
+    fn river_flow_crosses_depressions() {
+        let mut terrain = slope();
+        terrain.set_height(2, 0);
+
+        let flow = terrain.river_flow(&[0], |_| 1.0);
+
+        assert!(flow.contains(&(3, 2, 2.0)));
+        assert!(flow.contains(&(1, 0, 4.0)));
+    }
+
+    #[test]
+    fn river_flow_ignores_nodes_without_outlet() {
+        let mut terrain = slope();
+        terrain.add_connected_nodes(10, 11);
+
+        let flow = terrain.river_flow(&[0], |_| 1.0);
+
+        assert!(flow.iter().all(|(node, _, _)| *node < 10));
+    }
+
+    #[test]
+    fn set_generated_heights_keeps_connected_nodes_within_one_step() {
+        let mut terrain = slope();
+        terrain.set_locked(4, true);
+
+        terrain.set_generated_heights(&[(0, 5), (1, 0), (2, 0), (3, 0), (4, 0)]);
+
+        assert_eq!(Some(5), terrain.get_height_of_node(0));
+        assert_eq!(Some(4), terrain.get_height_of_node(1));
+        assert_eq!(Some(2), terrain.get_height_of_node(3));
+        assert_eq!(Some(3), terrain.get_height_of_node(4));
+    }
+
+    #[test]
+    fn erode_keeps_connected_nodes_within_one_step() {
+        let mut terrain = Terrain::new(1);
+        for node in 0..4 {
+            terrain.add_connected_nodes(node, node + 1);
+        }
+        terrain.set_heights(&[(0, 0), (4, 4)]);
+        terrain.set_locked(0, true);
+        terrain.set_locked(4, true);
+
+        terrain.erode(0.2, 1.0, 50);
+
+        for node in 0..4 {
+            let difference = terrain.get_height_of_node(node).unwrap()
+                - terrain.get_height_of_node(node + 1).unwrap();
+            assert!(difference.abs() <= 1);
+        }
+    }
+
+    #[test]
+    fn set_deck_height_adds_and_removes_deck() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+
+        assert!(terrain.set_deck_height(0, Some(3)));
+        assert_eq!(Some(3), terrain.get_deck_height(0));
+        assert_eq!(Some(0), terrain.get_height_of_node(0));
+        assert!(terrain.set_deck_height(0, None));
+        assert_eq!(None, terrain.get_deck_height(0));
+    }
+
+    #[test]
+    fn set_deck_height_does_not_propagate_to_connected_nodes() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+
+        terrain.set_deck_height(0, Some(5));
+        terrain.set_heights(&[(0, 1)]);
+
+        assert_eq!(Some(5), terrain.get_deck_height(0));
+        assert_eq!(Some(0), terrain.get_height_of_node(1));
+    }
+
+    #[test]
+    fn set_deck_height_returns_false_for_missing_node() {
+        let mut terrain: Terrain<i32> = Terrain::new(1);
+
+        assert!(!terrain.set_deck_height(0, Some(1)));
+    }
+
+    #[test]
+    fn set_height_sets_height_of_existing_node_and_returns_true() {
+        let mut terrain = Terrain::new(1);
+        terrain.nodes.push(Node::new(0));
+        terrain.node_map.insert(0, 0);
+        let return_value: bool = terrain.set_height(0, 5);
+
+        assert!(return_value);
+        assert_eq!(5, terrain.nodes[0].height);
+    }
+
+    #[test]
+    fn set_height_does_not_change_connected_nodes() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+        terrain.set_height(0, 5);
+
+        assert_eq!(0, terrain.nodes[1].height);
+    }
+
+    #[test]
+    fn set_height_returns_false_if_node_does_not_exist() {
+        let mut terrain = Terrain::new(1);
+        let return_value: bool = terrain.set_height(0, 5);
+
+        assert!(!return_value);
+    }
+
+    #[test]
+    fn add_connected_nodes_connects_existing_nodes() {
+        let mut terrain = Terrain::new(1);
+        terrain.nodes.push(Node::new(0));
+        let node1 = 0;
+        terrain.node_map.insert(node1, 0);
+
+        terrain.nodes.push(Node::new(0));
+        let node2 = 1;
+        terrain.node_map.insert(node2, 1);
+
+        terrain.add_connected_nodes(node1, node2);
+
+        assert_eq!(1, terrain.nodes[0].nodes.len());
+        assert_eq!(1, terrain.nodes[1].nodes.len());
+        assert_eq!(1, terrain.nodes[0].nodes[0]);
+        assert_eq!(0, terrain.nodes[1].nodes[0]);
+    }
+
+    #[test]
+    fn add_connected_nodes_adds_and_connects_node_that_does_not_exist() {
+        let mut terrain = Terrain::new(1);
+        terrain.nodes.push(Node::new(0));
+        let node1 = 0;
+
+        terrain.node_map.insert(node1, 0);
+
+        let node2 = 1;
+        terrain.add_connected_nodes(node1, node2);
+
+        assert_eq!(0, terrain.nodes[1].height);
+        assert_eq!(1, terrain.node_map[&node2]);
+        assert_eq!(1, terrain.nodes[0].nodes.len());
+        assert_eq!(1, terrain.nodes[1].nodes.len());
+        assert_eq!(1, terrain.nodes[0].nodes[0]);
+        assert_eq!(0, terrain.nodes[1].nodes[0]);
+    }
+
+    #[test]
+    fn add_connected_nodes_adds_and_connects_nodes_that_do_not_exist() {
+        let mut terrain = Terrain::new(1);
+        let node1 = 0;
+        let node2 = 1;
+
+        terrain.add_connected_nodes(node1, node2);
+
+        assert_eq!(0, terrain.nodes[0].height);
+        assert_eq!(0, terrain.node_map[&node1]);
+        assert_eq!(0, terrain.nodes[1].height);
+        assert_eq!(1, terrain.node_map[&node2]);
+        assert_eq!(1, terrain.nodes[0].nodes.len());
+        assert_eq!(1, terrain.nodes[1].nodes.len());
+        assert_eq!(1, terrain.nodes[0].nodes[0]);
+        assert_eq!(0, terrain.nodes[1].nodes[0]);
+    }
+
+    #[test]
+    fn increase_height_increases_height_of_node_by_step() {
+        let mut terrain = Terrain::new(1);
+        terrain.nodes.push(Node::new(0));
+        let node = 0;
+        terrain.node_map.insert(node, 0);
+
+        terrain.increase_height(node);
+
+        assert_eq!(1, terrain.nodes[0].height);
+        terrain.increase_height(node);
+        assert_eq!(2, terrain.nodes[0].height);
+    }
+
+    #[test]
+    fn increase_height_increases_height_of_connected_nodes() {
+        // Setup is:
+        // node: root node
+        // connected_node_1: First node that is connected to node
+        // connected_node_1_1: First node that is connected to connected_node_2
+        // connected_node_2: Second node that is connected to node
+        // connected_node_2_1: First node that is connected to connected_node_2
+
+        let mut terrain = Terrain::new(1);
+
+        terrain.nodes.push(Node::new(0));
+        let node = 0;
+        terrain.node_map.insert(node, 0);
+
+        terrain.nodes.push(Node::new(0));
+        let connected_node_1 = 1;
+        terrain.node_map.insert(connected_node_1, 0);
+        terrain.nodes[0].nodes.push(1);
+        terrain.nodes[1].nodes.push(0);
+
+        terrain.nodes.push(Node::new(2));
+        let connected_node_1_1 = 2;
+        terrain.node_map.insert(connected_node_1_1, 0);
+        terrain.nodes[1].nodes.push(2);
+        terrain.nodes[2].nodes.push(1);
+
+        terrain.nodes.push(Node::new(0));
+        let connected_node_2 = 3;
+        terrain.node_map.insert(connected_node_2, 0);
+        terrain.nodes[0].nodes.push(3);
+        terrain.nodes[3].nodes.push(0);
+
+        terrain.nodes.push(Node::new(0));
+        let connected_node_2_1 = 4;
+        terrain.node_map.insert(connected_node_2_1, 0);
+
+        terrain.nodes[3].nodes.push(4);
+        terrain.nodes[4].nodes.push(3);
+
+        // 3 calls should result in the following
+        // root node is increased to 3
+        // Directly connected nodes are increased, or stay at 2 or higher
+        // Nodes that are connected to directly connected nodes are increased or stay at 1 or higher
+
+        terrain.increase_height(node);
+        terrain.increase_height(node);
+        terrain.increase_height(node);
+
+        assert_eq!(3, terrain.nodes[0].height);
+        assert_eq!(2, terrain.nodes[1].height);
+        assert_eq!(2, terrain.nodes[2].height);
+        assert_eq!(2, terrain.nodes[3].height);
+        assert_eq!(1, terrain.nodes[4].height);
+    }
+
+    #[test]
+    fn decrease_height_decreases_height_of_node_by_step() {
+        let mut terrain = Terrain::new(1);
+        terrain.nodes.push(Node::new(3));
+        let node = 0;
+        terrain.node_map.insert(node, 0);
+
+        terrain.decrease_height(node);
+
+        assert_eq!(2, terrain.nodes[0].height);
+        terrain.decrease_height(node);
+        assert_eq!(1, terrain.nodes[0].height);
+    }
+
+    #[test]
+    fn decrease_height_decreases_height_of_connected_nodes() {
+        // Setup is:
+        // node: root node
+        // connected_node_1: First node that is connected to node
+        // connected_node_1_1: First node that is connected to connected_node_2
+        // connected_node_2: Second node that is connected to node
+        // connected_node_2_1: First node that is connected to connected_node_2
+
+        let mut terrain = Terrain::new(1);
+
+        terrain.nodes.push(Node::new(4));
+        let node = 0;
+        terrain.node_map.insert(node, 0);
+
+        terrain.nodes.push(Node::new(3));
+        let connected_node_1 = 1;
+        terrain.node_map.insert(connected_node_1, 0);
+        terrain.nodes[0].nodes.push(1);
+        terrain.nodes[1].nodes.push(0);
+
+        terrain.nodes.push(Node::new(2));
+        let connected_node_1_1 = 2;
+        terrain.node_map.insert(connected_node_1_1, 0);
+        terrain.nodes[1].nodes.push(2);
+        terrain.nodes[2].nodes.push(1);
+
+        terrain.nodes.push(Node::new(4));
+        let connected_node_2 = 3;
+        terrain.node_map.insert(connected_node_2, 0);
+        terrain.nodes[0].nodes.push(3);
+        terrain.nodes[3].nodes.push(0);
+
+        terrain.nodes.push(Node::new(3));
+        let connected_node_2_1 = 4;
+        terrain.node_map.insert(connected_node_2_1, 0);
+
+        terrain.nodes[3].nodes.push(4);
+        terrain.nodes[4].nodes.push(3);
+
+        // 3 calls should result in the following
+        // root node is decreased to 1
+        // Directly connected nodes are decreased, or stay at 2 or higher
+        // Nodes that are connected to directly connected nodes are decreased or stay at 3 or higher
+
+        terrain.decrease_height(node);
+        terrain.decrease_height(node);
+        terrain.decrease_height(node);
+
+        assert_eq!(1, terrain.nodes[0].height);
+        assert_eq!(2, terrain.nodes[1].height);
+        assert_eq!(2, terrain.nodes[2].height);
+        assert_eq!(2, terrain.nodes[3].height);
+        assert_eq!(3, terrain.nodes[4].height);
+    }
+
+    #[test]
+    fn increase_heights_increases_every_node_by_one_step() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+        terrain.add_connected_nodes(1, 2);
+        terrain.nodes[1].height = -1;
+
+        terrain.increase_heights(&[0, 1]);
+
+        assert_eq!(1, terrain.nodes[0].height);
+        assert_eq!(0, terrain.nodes[1].height);
+        assert_eq!(0, terrain.nodes[2].height);
+    }
+
+    #[test]
+    fn increase_heights_does_not_raise_nodes_twice() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+        terrain.nodes[0].height = 1;
+
+        terrain.increase_heights(&[0, 1]);
+
+        assert_eq!(2, terrain.nodes[0].height);
+        assert_eq!(1, terrain.nodes[1].height);
+    }
+
+    #[test]
+    fn decrease_heights_decreases_every_node_by_one_step() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+        terrain.add_connected_nodes(1, 2);
+        terrain.nodes[0].height = 1;
+
+        terrain.decrease_heights(&[0, 1]);
+
+        assert_eq!(0, terrain.nodes[0].height);
+        assert_eq!(-1, terrain.nodes[1].height);
+        assert_eq!(0, terrain.nodes[2].height);
+    }
+
+    #[test]
+    fn set_height_limits_clamps_existing_heights() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+        terrain.nodes[0].height = 5;
+        terrain.nodes[1].height = -5;
+
+        terrain.set_height_limits(-2, 3);
+
+        assert_eq!(3, terrain.nodes[0].height);
+        assert_eq!(-2, terrain.nodes[1].height);
+    }
+
+    #[test]
+    fn edits_are_clamped_to_height_limits() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+        terrain.set_height_limits(-1, 1);
+
+        terrain.set_heights(&[(0, 5)]);
+        terrain.increase_height(1);
+        terrain.increase_heights(&[0, 1]);
+        assert_eq!(1, terrain.nodes[0].height);
+        assert_eq!(1, terrain.nodes[1].height);
+
+        terrain.set_height(0, -4);
+        terrain.decrease_height(1);
+        terrain.decrease_height(1);
+        terrain.decrease_heights(&[0, 1]);
+        assert_eq!(-1, terrain.nodes[0].height);
+        assert_eq!(-1, terrain.nodes[1].height);
+    }
+
+    #[test]
+    fn set_heights_sets_heights_of_nodes() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+
+        terrain.set_heights(&[(0, 3), (1, 2)]);
+
+        assert_eq!(3, terrain.nodes[0].height);
+        assert_eq!(2, terrain.nodes[1].height);
+    }
+
+    #[test]
+    fn set_heights_raises_and_lowers_connected_nodes() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+        terrain.add_connected_nodes(1, 2);
+        terrain.add_connected_nodes(3, 4);
+        terrain.add_connected_nodes(4, 5);
+
+        terrain.set_heights(&[(0, 3), (3, -3)]);
+
+        assert_eq!(2, terrain.nodes[1].height);
+        assert_eq!(1, terrain.nodes[2].height);
+        assert_eq!(-2, terrain.nodes[4].height);
+        assert_eq!(-1, terrain.nodes[5].height);
+    }
+
+    #[test]
+    fn set_heights_ignores_nodes_that_do_not_exist() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_node(0);
+
+        terrain.set_heights(&[(0, 1), (1, 2)]);
+
+        assert_eq!(1, terrain.nodes[0].height);
+        assert_eq!(1, terrain.nodes.len());
+    }
+
+    #[test]
+    fn set_terrain_type_sets_type_of_existing_node() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_node(0);
+
+        assert!(terrain.set_terrain_type(0, 3));
+        assert_eq!(Some(3), terrain.get_terrain_type(0));
+        assert!(!terrain.set_terrain_type(1, 3));
+        assert_eq!(None, terrain.get_terrain_type(1));
+    }
+
+    #[test]
+    fn set_edge_feature_sets_feature_in_both_directions() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+
+        assert!(terrain.set_edge_feature(0, 1, 2));
+        assert_eq!(2, terrain.get_edge_feature(0, 1));
+        assert_eq!(2, terrain.get_edge_feature(1, 0));
+
+        assert!(terrain.set_edge_feature(1, 0, 0));
+        assert_eq!(0, terrain.get_edge_feature(0, 1));
+        assert_eq!(0, terrain.edge_features().count());
+    }
+
+    #[test]
+    fn set_edge_feature_returns_false_if_nodes_are_not_connected() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_node(0);
+        terrain.add_node(1);
+
+        assert!(!terrain.set_edge_feature(0, 1, 2));
+        assert_eq!(0, terrain.get_edge_feature(0, 1));
+    }
+
+    #[test]
+    fn take_dirty_returns_changed_nodes_once() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+        terrain.add_connected_nodes(1, 2);
+        assert_eq!(vec![0, 1, 2], terrain.take_dirty());
+
+        terrain.set_height(2, 0);
+        terrain.set_terrain_type(1, 0);
+        assert!(terrain.take_dirty().is_empty());
+
+        terrain.set_height(2, 1);
+        terrain.increase_height(0);
+        assert_eq!(vec![0, 2], terrain.take_dirty());
+        assert!(terrain.take_dirty().is_empty());
+    }
+
+    #[test]
+    fn take_dirty_returns_only_connected_nodes_after_removal() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_node(0);
+        terrain.add_connected_nodes(1, 2);
+        terrain.take_dirty();
+
+        terrain.remove_node(0);
+        assert!(terrain.take_dirty().is_empty());
+
+        terrain.remove_node(1);
+        assert_eq!(vec![2], terrain.take_dirty());
+    }
+
+    #[test]
+    fn add_connected_nodes_does_not_duplicate_connections() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+        terrain.add_connected_nodes(1, 0);
+        terrain.add_connected_nodes(0, 1);
+
+        assert_eq!(vec![1], terrain.nodes[0].nodes);
+        assert_eq!(vec![0], terrain.nodes[1].nodes);
+    }
+
+    #[test]
+    fn increase_height_propagates_along_long_chains() {
+        let mut terrain = Terrain::new(1);
+        for node in 0..100_000 {
+            terrain.add_connected_nodes(node, node + 1);
+        }
+        terrain.set_height(0, 100_000);
+
+        terrain.increase_height(0);
+
+        assert_eq!(Some(100_001), terrain.get_height_of_node(0));
+        assert_eq!(Some(2), terrain.get_height_of_node(99_999));
+        assert_eq!(Some(1), terrain.get_height_of_node(100_000));
+    }
+
+    #[test]
+    fn height_changes_end_on_cycles() {
+        let mut terrain = Terrain::new(1);
+        for node in 0..4 {
+            terrain.add_connected_nodes(node, (node + 1) % 4);
+        }
+
+        for _ in 0..3 {
+            terrain.increase_height(0);
+        }
+        assert_eq!(
+            vec![(0, 3), (1, 2), (2, 1), (3, 2)],
+            terrain.heights().collect::<Vec<_>>()
+        );
+
+        terrain.decrease_heights(&[1, 3]);
+        terrain.decrease_height(0);
+        assert_eq!(
+            vec![(0, 1), (1, 1), (2, 1), (3, 1)],
+            terrain.heights().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn stats_count_propagation_work() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+        terrain.add_connected_nodes(1, 2);
+
+        terrain.increase_height(0);
+        terrain.increase_height(0);
+
+        assert_eq!(
+            Stats {
+                propagations: 2,
+                nodes_visited: 3,
+                heights_changed: 3,
+            },
+            terrain.stats()
+        );
+        terrain.reset_stats();
+        assert_eq!(Stats::default(), terrain.stats());
+    }
+}
@@ -0,0 +1,44 @@
+/* C API of the terrain crate, see src/ffi.rs. Link against the cdylib or staticlib built by
+ * `cargo build --release` in the terrain directory.
+ *
+ * `cargo build --release --target wasm32-unknown-unknown` builds terrain.wasm, which exports the
+ * same functions for browsers. Pointers are offsets into its memory, buffers can be allocated
+ * with `terrain_alloc`. */
+#ifndef TERRAIN_H
+#define TERRAIN_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct TerrainHandle TerrainHandle;
+
+TerrainHandle *terrain_create(int32_t height_step);
+void terrain_destroy(TerrainHandle *handle);
+
+int32_t *terrain_alloc(size_t count);
+void terrain_free(int32_t *buffer, size_t count);
+
+void terrain_connect(TerrainHandle *handle, int32_t first_x, int32_t first_y, int32_t second_x,
+                     int32_t second_y);
+
+bool terrain_get_height(const TerrainHandle *handle, int32_t x, int32_t y, int32_t *height);
+bool terrain_set_height(TerrainHandle *handle, int32_t x, int32_t y, int32_t height);
+/* `nodes` holds x, y and height of `count` nodes one after another. */
+void terrain_set_heights(TerrainHandle *handle, const int32_t *nodes, size_t count);
+
+/* Writes x and y of up to `capacity` nodes to `path`, returns the length of the whole path or
+ * -1 if there is none. */
+int64_t terrain_find_path(const TerrainHandle *handle, int32_t start_x, int32_t start_y,
+                          int32_t goal_x, int32_t goal_y, float slope_cost, int32_t *path,
+                          size_t capacity);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
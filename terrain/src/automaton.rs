@@ -0,0 +1,113 @@
+use crate::random::Random;
+use std::collections::HashSet;
+use std::hash::Hash;
+
+/// Rules of a cellular automaton. A dead cell comes alive if at least `birth` of its neighbours
+/// are alive, a living cell dies if fewer than `survival` of its neighbours are alive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rules {
+    /// Share of the cells that are alive at the start, between 0 and 1.
+    pub fill: f32,
+    pub birth: usize,
+    pub survival: usize,
+    pub iterations: u32,
+}
+
+/// Runs a cellular automaton on a graph of cells and returns the cells that are alive at the end.
+/// Neighbours that are not part of `cells` count as dead. The result only depends on the seed and
+/// the order of `cells`.
+pub fn run<T: Eq + Hash + Copy>(
+    cells: &[T],
+    neighbours: impl Fn(T) -> Vec<T>,
+    rules: &Rules,
+    seed: u64,
+) -> HashSet<T> {
+    let mut random = Random::new(seed);
+    let mut alive: HashSet<T> = cells
+        .iter()
+        .copied()
+        .filter(|_| random.next_f32() < rules.fill)
+        .collect();
+
+    for _ in 0..rules.iterations {
+        let next: HashSet<T> = cells
+            .iter()
+            .copied()
+            .filter(|cell| {
+                let living = neighbours(*cell)
+                    .iter()
+                    .filter(|neighbour| alive.contains(*neighbour))
+                    .count();
+                if alive.contains(cell) {
+                    living >= rules.survival
+                } else {
+                    living >= rules.birth
+                }
+            })
+            .collect();
+        if next == alive {
+            break;
+        }
+        alive = next;
+    }
+    alive
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line_neighbours(cell: i32) -> Vec<i32> {
+        vec![cell - 1, cell + 1]
+    }
+
+    #[test]
+    fn run_is_deterministic() {
+        let cells: Vec<i32> = (0..100).collect();
+        let rules = Rules {
+            fill: 0.5,
+            birth: 2,
+            survival: 1,
+            iterations: 3,
+        };
+
+        assert_eq!(
+            run(&cells, line_neighbours, &rules, 9),
+            run(&cells, line_neighbours, &rules, 9)
+        );
+    }
+
+    #[test]
+    fn run_without_iterations_returns_initial_fill() {
+        let cells: Vec<i32> = (0..100).collect();
+        let empty = Rules {
+            fill: 0.0,
+            birth: 1,
+            survival: 0,
+            iterations: 0,
+        };
+        let full = Rules { fill: 1.0, ..empty };
+
+        assert!(run(&cells, line_neighbours, &empty, 1).is_empty());
+        assert_eq!(100, run(&cells, line_neighbours, &full, 1).len());
+    }
+
+    #[test]
+    fn run_removes_isolated_cells() {
+        let cells: Vec<i32> = (0..100).collect();
+        let rules = Rules {
+            fill: 0.5,
+            birth: 3,
+            survival: 1,
+            iterations: 10,
+        };
+
+        let alive = run(&cells, line_neighbours, &rules, 4);
+
+        for cell in &alive {
+            assert!(line_neighbours(*cell)
+                .iter()
+                .any(|neighbour| alive.contains(neighbour)));
+        }
+    }
+}
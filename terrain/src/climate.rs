@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// An entry of a biome table. Cells get the terrain type of the biome whose climate is closest to
+/// theirs. Temperature and moisture range from 0 to 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Biome {
+    pub terrain_type: i32,
+    pub temperature: f32,
+    pub moisture: f32,
+}
+
+/// Returns the temperature of a cell, between 0 and 1. It is 1 at the equator (latitude 0) and 0
+/// at the poles (latitude 1), falls by `height_cooling` per step above sea level and is varied by
+/// `variation`.
+pub fn temperature(latitude: f32, height: i32, height_cooling: f32, variation: f32) -> f32 {
+    let latitude = latitude.abs().min(1.0);
+    (1.0 - latitude - height.max(0) as f32 * height_cooling + variation).clamp(0.0, 1.0)
+}
+
+/// Returns the terrain type of the biome with the closest climate, None if there are no biomes.
+pub fn biome_for(biomes: &[Biome], temperature: f32, moisture: f32) -> Option<i32> {
+    let distance = |biome: &Biome| {
+        let temperature = biome.temperature - temperature;
+        let moisture = biome.moisture - moisture;
+        temperature * temperature + moisture * moisture
+    };
+    biomes
+        .iter()
+        .min_by(|first, second| distance(first).partial_cmp(&distance(second)).unwrap())
+        .map(|biome| biome.terrain_type)
+}
+
+/// Blows moist air across the cells and returns the moisture of every cell, between 0 and 1.
+/// Every cell is given with its position along the wind and its height, and `upwind` returns the
+/// cells the wind comes from. Air enters the map saturated and is saturated again over cells at
+/// or below `sea_level`. Over land it loses `rainfall` of its moisture per cell, plus `uplift`
+/// per step it rises, so windward slopes get wet and the land behind mountains stays dry.
+pub fn rain_shadow<T: Eq + Hash + Copy>(
+    cells: &[(T, f32, i32)],
+    upwind: impl Fn(T) -> Vec<T>,
+    sea_level: i32,
+    rainfall: f32,
+    uplift: f32,
+) -> HashMap<T, f32> {
+    let mut order: Vec<&(T, f32, i32)> = cells.iter().collect();
+    order.sort_by(|first, second| first.1.partial_cmp(&second.1).unwrap());
+    let heights: HashMap<T, i32> = cells
+        .iter()
+        .map(|(cell, _, height)| (*cell, *height))
+        .collect();
+
+    // Moisture of the air that leaves a cell.
+    let mut air: HashMap<T, f32> = HashMap::new();
+    let mut moisture = HashMap::new();
+    for (cell, _, height) in order {
+        let sources: Vec<T> = upwind(*cell)
+            .into_iter()
+            .filter(|source| air.contains_key(source))
+            .collect();
+        if *height <= sea_level {
+            moisture.insert(*cell, 1.0);
+            air.insert(*cell, 1.0);
+            continue;
+        }
+        let (humidity, rise) = if sources.is_empty() {
+            (1.0, 0.0)
+        } else {
+            let count = sources.len() as f32;
+            let humidity = sources.iter().map(|source| air[source]).sum::<f32>() / count;
+            let upwind_height = sources
+                .iter()
+                .map(|source| heights[source] as f32)
+                .sum::<f32>()
+                / count;
+            (humidity, (*height as f32 - upwind_height).max(0.0))
+        };
+        let rain = (rainfall + uplift * rise).clamp(0.0, 1.0);
+        moisture.insert(*cell, (humidity * (1.0 + uplift * rise)).min(1.0));
+        air.insert(*cell, humidity * (1.0 - rain));
+    }
+    moisture
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temperature_falls_towards_poles_and_with_height() {
+        assert_eq!(1.0, temperature(0.0, 0, 0.1, 0.0));
+        assert_eq!(0.5, temperature(-0.5, 0, 0.1, 0.0));
+        assert_eq!(0.0, temperature(1.0, 0, 0.1, 0.0));
+        assert!((temperature(0.0, 3, 0.1, 0.0) - 0.7).abs() < 0.0001);
+    }
+
+    #[test]
+    fn temperature_ignores_height_below_sea_level() {
+        assert_eq!(0.5, temperature(0.5, -4, 0.1, 0.0));
+    }
+
+    #[test]
+    fn biome_for_returns_closest_biome() {
+        let biomes = [
+            Biome {
+                terrain_type: 1,
+                temperature: 0.9,
+                moisture: 0.1,
+            },
+            Biome {
+                terrain_type: 2,
+                temperature: 0.1,
+                moisture: 0.5,
+            },
+        ];
+
+        assert_eq!(Some(1), biome_for(&biomes, 0.8, 0.3));
+        assert_eq!(Some(2), biome_for(&biomes, 0.2, 0.3));
+        assert_eq!(None, biome_for(&[], 0.2, 0.3));
+    }
+
+    /// A row of cells with the wind blowing from 0 towards higher cells.
+    fn row(heights: &[i32]) -> HashMap<i32, f32> {
+        let cells: Vec<(i32, f32, i32)> = heights
+            .iter()
+            .enumerate()
+            .map(|(cell, height)| (cell as i32, cell as f32, *height))
+            .collect();
+        rain_shadow(&cells, |cell| vec![cell - 1], 0, 0.1, 0.3)
+    }
+
+    #[test]
+    fn rain_shadow_dries_air_over_land() {
+        let moisture = row(&[0, 1, 1, 1, 1]);
+
+        assert_eq!(1.0, moisture[&0]);
+        assert!(moisture[&2] > moisture[&3]);
+        assert!(moisture[&3] > moisture[&4]);
+    }
+
+    #[test]
+    fn rain_shadow_leaves_land_behind_mountains_dry() {
+        let flat = row(&[0, 1, 1, 1, 1, 1, 1]);
+        let mountain = row(&[0, 1, 2, 3, 2, 1, 1]);
+
+        assert!(mountain[&2] > flat[&2]);
+        assert!(mountain[&6] < flat[&6]);
+    }
+
+    #[test]
+    fn rain_shadow_restores_moisture_over_water() {
+        let moisture = row(&[0, 3, 3, 0, 1]);
+
+        assert_eq!(1.0, moisture[&3]);
+        assert_eq!(1.0, moisture[&4]);
+    }
+}
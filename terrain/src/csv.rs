@@ -0,0 +1,54 @@
+use crate::terrain::Terrain;
+use std::fmt::Write;
+use std::hash::Hash;
+
+/// Writes the height of every node as CSV with an `x,y,height` header, and with `types` the
+/// terrain type in a fourth column. `position` converts positions to two numbers. Rows are
+/// ordered by position, so equal terrains give equal text, e.g. to diff generated terrains.
+pub fn to_csv<T: Eq + Hash + Copy>(
+    terrain: &Terrain<T>,
+    position: impl Fn(T) -> (i32, i32),
+    types: bool,
+) -> String {
+    let mut text = String::from(if types {
+        "x,y,height,type\n"
+    } else {
+        "x,y,height\n"
+    });
+    let mut nodes: Vec<((i32, i32), T, i32)> = terrain
+        .heights()
+        .map(|(node, height)| (position(node), node, height))
+        .collect();
+    nodes.sort_unstable_by_key(|(position, _, _)| *position);
+    for ((x, y), node, height) in nodes {
+        write!(text, "{},{},{}", x, y, height).unwrap();
+        if types {
+            write!(text, ",{}", terrain.get_terrain_type(node).unwrap_or(0)).unwrap();
+        }
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_csv_writes_rows_ordered_by_position() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes((1, 0), (0, 2));
+        terrain.add_connected_nodes((0, 2), (0, -1));
+        terrain.set_height((0, 2), 1);
+        terrain.set_terrain_type((1, 0), 3);
+
+        assert_eq!(
+            "x,y,height\n0,-1,0\n0,2,1\n1,0,0\n",
+            to_csv(&terrain, |node| node, false)
+        );
+        assert_eq!(
+            "x,y,height,type\n0,-1,0,0\n0,2,1,0\n1,0,0,3\n",
+            to_csv(&terrain, |node| node, true)
+        );
+    }
+}
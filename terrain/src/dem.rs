@@ -0,0 +1,126 @@
+/// A digital elevation model: a grid of elevations in meters, e.g. from SRTM tiles. Samples
+/// without data are None.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dem {
+    pub width: usize,
+    pub height: usize,
+    pub elevations: Vec<Option<f32>>,
+}
+
+/// Value that marks samples without data in HGT and most raw DEM files.
+const VOID: i16 = -32768;
+
+impl Dem {
+    /// Reads a raw grid of signed 16 bit samples, row by row from the north-west corner.
+    /// Returns None if the data does not have the size of the grid.
+    pub fn from_raw(bytes: &[u8], width: usize, height: usize, big_endian: bool) -> Option<Dem> {
+        if width == 0 || height == 0 || bytes.len() != width * height * 2 {
+            return None;
+        }
+        let elevations = bytes
+            .chunks_exact(2)
+            .map(|sample| {
+                let value = if big_endian {
+                    i16::from_be_bytes([sample[0], sample[1]])
+                } else {
+                    i16::from_le_bytes([sample[0], sample[1]])
+                };
+                if value == VOID {
+                    None
+                } else {
+                    Some(value as f32)
+                }
+            })
+            .collect();
+        Some(Dem {
+            width,
+            height,
+            elevations,
+        })
+    }
+
+    /// Reads an HGT tile as used by SRTM: a square grid of big endian samples. Returns None if
+    /// the data is not square.
+    pub fn from_hgt(bytes: &[u8]) -> Option<Dem> {
+        let side = ((bytes.len() / 2) as f64).sqrt().round() as usize;
+        Dem::from_raw(bytes, side, side, true)
+    }
+
+    /// Returns the elevation at a position between (0, 0) at the north-west and (1, 1) at the
+    /// south-east corner, interpolated between the surrounding samples. Samples without data are
+    /// left out, None if all of them have no data.
+    pub fn sample(&self, x: f32, y: f32) -> Option<f32> {
+        let x = x.clamp(0.0, 1.0) * (self.width - 1) as f32;
+        let y = y.clamp(0.0, 1.0) * (self.height - 1) as f32;
+        let (left, top) = (x.floor() as usize, y.floor() as usize);
+        let (right, bottom) = (
+            (left + 1).min(self.width - 1),
+            (top + 1).min(self.height - 1),
+        );
+        let (fx, fy) = (x - left as f32, y - top as f32);
+
+        let mut sum = 0.0;
+        let mut weights = 0.0;
+        for (column, row, weight) in [
+            (left, top, (1.0 - fx) * (1.0 - fy)),
+            (right, top, fx * (1.0 - fy)),
+            (left, bottom, (1.0 - fx) * fy),
+            (right, bottom, fx * fy),
+        ]
+        .iter()
+        {
+            if let Some(elevation) = self.elevations[row * self.width + column] {
+                sum += elevation * weight;
+                weights += weight;
+            }
+        }
+        if weights > 0.0 {
+            Some(sum / weights)
+        } else {
+            self.elevations[top * self.width + left]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(values: &[i16]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_be_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn from_hgt_reads_square_big_endian_grid() {
+        let dem = Dem::from_hgt(&samples(&[1, 2, 3, VOID])).unwrap();
+
+        assert_eq!((2, 2), (dem.width, dem.height));
+        assert_eq!(vec![Some(1.0), Some(2.0), Some(3.0), None], dem.elevations);
+    }
+
+    #[test]
+    fn from_raw_rejects_wrong_size() {
+        assert_eq!(None, Dem::from_raw(&samples(&[1, 2, 3]), 2, 2, true));
+        assert!(Dem::from_raw(&[1, 0, 2, 0], 2, 1, false).is_some());
+    }
+
+    #[test]
+    fn sample_interpolates_between_samples() {
+        let dem = Dem::from_hgt(&samples(&[0, 10, 20, 30])).unwrap();
+
+        assert_eq!(Some(0.0), dem.sample(0.0, 0.0));
+        assert_eq!(Some(30.0), dem.sample(1.0, 1.0));
+        assert_eq!(Some(15.0), dem.sample(0.5, 0.5));
+    }
+
+    #[test]
+    fn sample_skips_samples_without_data() {
+        let dem = Dem::from_hgt(&samples(&[10, VOID, 10, VOID])).unwrap();
+
+        assert_eq!(Some(10.0), dem.sample(0.5, 0.5));
+        assert_eq!(None, dem.sample(1.0, 0.0));
+    }
+}
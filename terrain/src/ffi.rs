@@ -0,0 +1,261 @@
+use crate::path;
+use crate::terrain::Terrain;
+use std::collections::HashMap;
+use std::ptr;
+use std::slice;
+
+/// Terrain behind the handles of the C API, see `include/terrain.h`. Nodes are positioned by two
+/// integers like the vertex keys of the game.
+pub struct TerrainHandle {
+    terrain: Terrain<(i32, i32)>,
+}
+
+/// Creates an empty terrain with the given height step. Free it with `terrain_destroy`.
+#[no_mangle]
+pub extern "C" fn terrain_create(height_step: i32) -> *mut TerrainHandle {
+    Box::into_raw(Box::new(TerrainHandle {
+        terrain: Terrain::new(height_step),
+    }))
+}
+
+/// Frees a terrain. Null is ignored.
+///
+/// # Safety
+///
+/// `handle` must be null or come from `terrain_create` and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn terrain_destroy(handle: *mut TerrainHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Allocates `count` integers, e.g. for hosts like JavaScript that share the linear memory of a
+/// WebAssembly build and have no allocator of their own. Free them with `terrain_free`.
+#[no_mangle]
+pub extern "C" fn terrain_alloc(count: usize) -> *mut i32 {
+    let mut buffer = vec![0; count].into_boxed_slice();
+    let pointer = buffer.as_mut_ptr();
+    std::mem::forget(buffer);
+    pointer
+}
+
+/// Frees integers from `terrain_alloc`. Null is ignored.
+///
+/// # Safety
+///
+/// `buffer` must be null or come from `terrain_alloc` with the same `count` and must not be used
+/// afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn terrain_free(buffer: *mut i32, count: usize) {
+    if !buffer.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer, count)));
+    }
+}
+
+/// Connects two nodes, adding them if they do not exist.
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle from `terrain_create`.
+#[no_mangle]
+pub unsafe extern "C" fn terrain_connect(
+    handle: *mut TerrainHandle,
+    first_x: i32,
+    first_y: i32,
+    second_x: i32,
+    second_y: i32,
+) {
+    if let Some(handle) = handle.as_mut() {
+        handle
+            .terrain
+            .add_connected_nodes((first_x, first_y), (second_x, second_y));
+    }
+}
+
+/// Writes the height of a node to `height`. Returns whether the node exists.
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle from `terrain_create`, `height` must be null or point
+/// to a writable integer.
+#[no_mangle]
+pub unsafe extern "C" fn terrain_get_height(
+    handle: *const TerrainHandle,
+    x: i32,
+    y: i32,
+    height: *mut i32,
+) -> bool {
+    let node_height = handle
+        .as_ref()
+        .and_then(|handle| handle.terrain.get_height_of_node((x, y)));
+    match (node_height, height.as_mut()) {
+        (Some(node_height), Some(height)) => {
+            *height = node_height;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Sets the height of a node. Connected nodes follow like in the game, so no connected nodes
+/// differ by more than one step. Returns whether the node exists.
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle from `terrain_create`.
+#[no_mangle]
+pub unsafe extern "C" fn terrain_set_height(
+    handle: *mut TerrainHandle,
+    x: i32,
+    y: i32,
+    height: i32,
+) -> bool {
+    match handle.as_mut() {
+        Some(handle) if handle.terrain.get_height_of_node((x, y)).is_some() => {
+            handle.terrain.set_heights(&[((x, y), height)]);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Sets the heights of `count` nodes at once from `nodes`, which holds x, y and height of every
+/// node one after another. Nodes that do not exist are ignored, connected nodes follow like in
+/// `terrain_set_height`.
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle from `terrain_create`, `nodes` must be null or point to
+/// at least `3 * count` integers.
+#[no_mangle]
+pub unsafe extern "C" fn terrain_set_heights(
+    handle: *mut TerrainHandle,
+    nodes: *const i32,
+    count: usize,
+) {
+    if let (Some(handle), false) = (handle.as_mut(), nodes.is_null()) {
+        let heights: Vec<((i32, i32), i32)> = slice::from_raw_parts(nodes, count * 3)
+            .chunks(3)
+            .map(|node| ((node[0], node[1]), node[2]))
+            .collect();
+        handle.terrain.set_heights(&heights);
+    }
+}
+
+/// Finds the cheapest path between two nodes along their connections, skipping holes. Every step
+/// costs 1 plus `slope_cost` per step of height difference. Writes x and y of up to `capacity`
+/// nodes of the path, including start and goal, to `path`. Returns the number of nodes of the
+/// whole path, which can be more than `capacity`, or -1 if there is none.
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle from `terrain_create`, `path` must be null or point to
+/// at least `2 * capacity` writable integers.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn terrain_find_path(
+    handle: *const TerrainHandle,
+    start_x: i32,
+    start_y: i32,
+    goal_x: i32,
+    goal_y: i32,
+    slope_cost: f32,
+    path: *mut i32,
+    capacity: usize,
+) -> i64 {
+    let terrain = match handle.as_ref() {
+        None => return -1,
+        Some(handle) => &handle.terrain,
+    };
+    let (start, goal) = ((start_x, start_y), (goal_x, goal_y));
+    if terrain.get_height_of_node(start).is_none() || terrain.get_height_of_node(goal).is_none() {
+        return -1;
+    }
+
+    let mut connected: HashMap<(i32, i32), Vec<(i32, i32)>> = HashMap::new();
+    for (first, second) in terrain.connections() {
+        connected.entry(first).or_default().push(second);
+        connected.entry(second).or_default().push(first);
+    }
+    let nodes = path::cheapest_path(start, goal, |from| {
+        let from_height = terrain.get_height_of_node(from).unwrap_or(0);
+        connected
+            .get(&from)
+            .map_or(&[][..], |nodes| nodes)
+            .iter()
+            .filter(|to| !terrain.is_hole(**to))
+            .map(|to| {
+                let height = terrain.get_height_of_node(*to).unwrap_or(0);
+                (*to, 1.0 + slope_cost * (height - from_height).abs() as f32)
+            })
+            .collect()
+    });
+
+    match nodes {
+        None => -1,
+        Some(nodes) => {
+            if !path.is_null() {
+                let path = slice::from_raw_parts_mut(path, capacity * 2);
+                for (index, (x, y)) in nodes.iter().take(capacity).enumerate() {
+                    path[index * 2] = *x;
+                    path[index * 2 + 1] = *y;
+                }
+            }
+            nodes.len() as i64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handle_edits_heights_and_finds_paths() {
+        let handle = terrain_create(1);
+        unsafe {
+            for x in 0..3 {
+                terrain_connect(handle, x, 0, x + 1, 0);
+            }
+            terrain_connect(handle, 0, 0, 0, 1);
+            terrain_connect(handle, 0, 1, 3, 0);
+
+            assert!(terrain_set_height(handle, 1, 0, 2));
+            assert!(!terrain_set_height(handle, 5, 5, 2));
+            terrain_set_heights(handle, [0, 1, 0, 3, 0, 0].as_ptr(), 2);
+            let mut height = 0;
+            assert!(terrain_get_height(handle, 1, 0, &mut height));
+            assert_eq!(2, height);
+            assert!(terrain_get_height(handle, 2, 0, &mut height));
+            assert_eq!(1, height);
+            assert!(!terrain_get_height(handle, 5, 5, &mut height));
+
+            let mut path = [0; 4];
+            assert_eq!(
+                3,
+                terrain_find_path(handle, 0, 0, 3, 0, 1.0, path.as_mut_ptr(), 2)
+            );
+            assert_eq!([0, 0, 0, 1], path);
+            assert_eq!(
+                3,
+                terrain_find_path(handle, 0, 0, 3, 0, 0.0, ptr::null_mut(), 0)
+            );
+            assert_eq!(
+                -1,
+                terrain_find_path(handle, 0, 0, 5, 5, 0.0, ptr::null_mut(), 0)
+            );
+
+            terrain_destroy(handle);
+        }
+    }
+
+    #[test]
+    fn alloc_returns_zeroed_buffer() {
+        let buffer = terrain_alloc(3);
+        unsafe {
+            assert_eq!(&[0, 0, 0], slice::from_raw_parts(buffer, 3));
+            terrain_free(buffer, 3);
+        }
+    }
+}
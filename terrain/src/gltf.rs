@@ -0,0 +1,334 @@
+use crate::json::Json;
+
+/// Component type of accessors with 32 bit floats.
+const FLOAT: f64 = 5126.0;
+
+/// Buffer view target for vertex attributes.
+const ARRAY_BUFFER: f64 = 34962.0;
+
+/// A corner of a triangle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub uv: [f32; 2],
+    pub uv2: [f32; 2],
+    pub color: [f32; 4],
+}
+
+/// Triangles that share a material.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Surface {
+    pub material: String,
+    pub base_color: [f32; 4],
+    /// Corners of every triangle, counter-clockwise seen from the front.
+    pub triangles: Vec<[Vertex; 3]>,
+}
+
+/// Writes surfaces as one mesh of a glTF 2.0 file, with the buffer embedded, so the file can be
+/// opened on its own. Every surface becomes a primitive with its own material. Triangles get flat
+/// normals. Surfaces without triangles are left out.
+pub fn to_gltf(surfaces: &[Surface]) -> String {
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut views = Vec::new();
+    let mut accessors = Vec::new();
+    let mut primitives = Vec::new();
+    let mut materials = Vec::new();
+
+    for surface in surfaces
+        .iter()
+        .filter(|surface| !surface.triangles.is_empty())
+    {
+        let vertices: Vec<(Vertex, [f32; 3])> = surface
+            .triangles
+            .iter()
+            .flat_map(|triangle| {
+                let normal = normal(triangle);
+                triangle.iter().map(move |vertex| (*vertex, normal))
+            })
+            .collect();
+        let mut attribute = |values: Vec<Vec<f32>>, kind: &str, bounds: bool| {
+            let offset = buffer.len();
+            for value in values.iter().flatten() {
+                buffer.extend_from_slice(&value.to_le_bytes());
+            }
+            views.push(Json::Object(vec![
+                ("buffer".to_string(), Json::Number(0.0)),
+                ("byteOffset".to_string(), Json::Number(offset as f64)),
+                (
+                    "byteLength".to_string(),
+                    Json::Number((buffer.len() - offset) as f64),
+                ),
+                ("target".to_string(), Json::Number(ARRAY_BUFFER)),
+            ]));
+            let mut accessor = vec![
+                (
+                    "bufferView".to_string(),
+                    Json::Number(views.len() as f64 - 1.0),
+                ),
+                ("componentType".to_string(), Json::Number(FLOAT)),
+                ("count".to_string(), Json::Number(values.len() as f64)),
+                ("type".to_string(), Json::String(kind.to_string())),
+            ];
+            if bounds {
+                let bound = |pick: fn(f32, f32) -> f32| {
+                    let mut bound = values[0].clone();
+                    for value in &values {
+                        for (bound, component) in bound.iter_mut().zip(value) {
+                            *bound = pick(*bound, *component);
+                        }
+                    }
+                    numbers(&bound)
+                };
+                accessor.push(("min".to_string(), bound(f32::min)));
+                accessor.push(("max".to_string(), bound(f32::max)));
+            }
+            accessors.push(Json::Object(accessor));
+            Json::Number(accessors.len() as f64 - 1.0)
+        };
+
+        let attributes = vec![
+            (
+                "POSITION".to_string(),
+                attribute(
+                    vertices
+                        .iter()
+                        .map(|(vertex, _)| vertex.position.to_vec())
+                        .collect(),
+                    "VEC3",
+                    true,
+                ),
+            ),
+            (
+                "NORMAL".to_string(),
+                attribute(
+                    vertices.iter().map(|(_, normal)| normal.to_vec()).collect(),
+                    "VEC3",
+                    false,
+                ),
+            ),
+            (
+                "TEXCOORD_0".to_string(),
+                attribute(
+                    vertices
+                        .iter()
+                        .map(|(vertex, _)| vertex.uv.to_vec())
+                        .collect(),
+                    "VEC2",
+                    false,
+                ),
+            ),
+            (
+                "TEXCOORD_1".to_string(),
+                attribute(
+                    vertices
+                        .iter()
+                        .map(|(vertex, _)| vertex.uv2.to_vec())
+                        .collect(),
+                    "VEC2",
+                    false,
+                ),
+            ),
+            (
+                "COLOR_0".to_string(),
+                attribute(
+                    vertices
+                        .iter()
+                        .map(|(vertex, _)| vertex.color.to_vec())
+                        .collect(),
+                    "VEC4",
+                    false,
+                ),
+            ),
+        ];
+        primitives.push(Json::Object(vec![
+            ("attributes".to_string(), Json::Object(attributes)),
+            ("material".to_string(), Json::Number(materials.len() as f64)),
+        ]));
+        materials.push(Json::Object(vec![
+            ("name".to_string(), Json::String(surface.material.clone())),
+            (
+                "pbrMetallicRoughness".to_string(),
+                Json::Object(vec![
+                    ("baseColorFactor".to_string(), numbers(&surface.base_color)),
+                    ("metallicFactor".to_string(), Json::Number(0.0)),
+                ]),
+            ),
+        ]));
+    }
+
+    let mut gltf = vec![(
+        "asset".to_string(),
+        Json::Object(vec![
+            ("version".to_string(), Json::String("2.0".to_string())),
+            (
+                "generator".to_string(),
+                Json::String("HexTerrain".to_string()),
+            ),
+        ]),
+    )];
+    if !primitives.is_empty() {
+        let buffer = Json::Object(vec![
+            ("byteLength".to_string(), Json::Number(buffer.len() as f64)),
+            (
+                "uri".to_string(),
+                Json::String(format!(
+                    "data:application/octet-stream;base64,{}",
+                    base64(&buffer)
+                )),
+            ),
+        ]);
+        gltf.extend(vec![
+            ("scene".to_string(), Json::Number(0.0)),
+            (
+                "scenes".to_string(),
+                Json::Array(vec![Json::Object(vec![(
+                    "nodes".to_string(),
+                    Json::Array(vec![Json::Number(0.0)]),
+                )])]),
+            ),
+            (
+                "nodes".to_string(),
+                Json::Array(vec![Json::Object(vec![
+                    ("name".to_string(), Json::String("HexTerrain".to_string())),
+                    ("mesh".to_string(), Json::Number(0.0)),
+                ])]),
+            ),
+            (
+                "meshes".to_string(),
+                Json::Array(vec![Json::Object(vec![(
+                    "primitives".to_string(),
+                    Json::Array(primitives),
+                )])]),
+            ),
+            ("materials".to_string(), Json::Array(materials)),
+            ("accessors".to_string(), Json::Array(accessors)),
+            ("bufferViews".to_string(), Json::Array(views)),
+            ("buffers".to_string(), Json::Array(vec![buffer])),
+        ]);
+    }
+    Json::Object(gltf).to_string()
+}
+
+fn numbers(values: &[f32]) -> Json {
+    Json::Array(
+        values
+            .iter()
+            .map(|value| Json::Number(*value as f64))
+            .collect(),
+    )
+}
+
+/// Returns the unit normal of the front of a triangle.
+fn normal(triangle: &[Vertex; 3]) -> [f32; 3] {
+    let [a, b, c] = [
+        triangle[0].position,
+        triangle[1].position,
+        triangle[2].position,
+    ];
+    let first = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let second = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+    let cross = [
+        first[1] * second[2] - first[2] * second[1],
+        first[2] * second[0] - first[0] * second[2],
+        first[0] * second[1] - first[1] * second[0],
+    ];
+    let length = (cross[0] * cross[0] + cross[1] * cross[1] + cross[2] * cross[2]).sqrt();
+    if length == 0.0 {
+        return [0.0, 1.0, 0.0];
+    }
+    [cross[0] / length, cross[1] / length, cross[2] / length]
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::new();
+    for chunk in bytes.chunks(3) {
+        let value = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for index in 0..4 {
+            if index <= chunk.len() {
+                text.push(ALPHABET[(value >> (18 - 6 * index) & 63) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    fn vertex(x: f32, z: f32) -> Vertex {
+        Vertex {
+            position: [x, 1.0, z],
+            uv: [x, z],
+            uv2: [0.5, 0.5],
+            color: [1.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    fn surface(material: &str, triangles: usize) -> Surface {
+        Surface {
+            material: material.to_string(),
+            base_color: [0.2, 0.6, 0.2, 1.0],
+            triangles: vec![[vertex(0.0, 0.0), vertex(0.0, 1.0), vertex(1.0, 0.0)]; triangles],
+        }
+    }
+
+    #[test]
+    fn to_gltf_writes_primitive_per_surface() {
+        let gltf = json::parse(&to_gltf(&[
+            surface("grass", 2),
+            surface("empty", 0),
+            surface("rock", 1),
+        ]))
+        .unwrap();
+
+        let primitives = gltf.get("meshes").unwrap().as_array().unwrap()[0]
+            .get("primitives")
+            .unwrap()
+            .as_array()
+            .unwrap();
+        assert_eq!(2, primitives.len());
+        let materials = gltf.get("materials").unwrap().as_array().unwrap();
+        assert_eq!(
+            Some(&Json::String("rock".to_string())),
+            materials[1].get("name")
+        );
+
+        let accessors = gltf.get("accessors").unwrap().as_array().unwrap();
+        let position = primitives[0]
+            .get("attributes")
+            .unwrap()
+            .get("POSITION")
+            .unwrap()
+            .as_i32()
+            .unwrap() as usize;
+        assert_eq!(Some(6), accessors[position].get("count").unwrap().as_i32());
+        assert_eq!(
+            Some(&numbers(&[1.0, 1.0, 1.0])),
+            accessors[position].get("max")
+        );
+        // Every vertex of both surfaces has 3 + 3 + 2 + 2 + 4 floats.
+        let buffer = &gltf.get("buffers").unwrap().as_array().unwrap()[0];
+        assert_eq!(Some(9 * 14 * 4), buffer.get("byteLength").unwrap().as_i32());
+    }
+
+    #[test]
+    fn normal_points_to_front() {
+        let triangle = [vertex(0.0, 0.0), vertex(0.0, 1.0), vertex(1.0, 0.0)];
+
+        assert_eq!([0.0, 1.0, 0.0], normal(&triangle));
+    }
+
+    #[test]
+    fn base64_pads_last_chunk() {
+        assert_eq!("aGV4", base64(b"hex"));
+        assert_eq!("aGV4YQ==", base64(b"hexa"));
+        assert_eq!("aGV4YXM=", base64(b"hexas"));
+    }
+}
@@ -0,0 +1,146 @@
+use crate::terrain::Terrain;
+use std::fmt::Write;
+use std::hash::Hash;
+
+/// Returns the nodes ordered by position with their height and terrain type, and the connections
+/// between them ordered by the positions of their nodes.
+#[allow(clippy::type_complexity)]
+fn graph<T: Eq + Hash + Copy>(
+    terrain: &Terrain<T>,
+    position: impl Fn(T) -> (i32, i32),
+) -> (Vec<((i32, i32), i32, i32)>, Vec<((i32, i32), (i32, i32))>) {
+    let mut nodes: Vec<((i32, i32), i32, i32)> = terrain
+        .heights()
+        .map(|(node, height)| {
+            let terrain_type = terrain.get_terrain_type(node).unwrap_or(0);
+            (position(node), height, terrain_type)
+        })
+        .collect();
+    nodes.sort_unstable();
+    let mut connections: Vec<((i32, i32), (i32, i32))> = terrain
+        .connections()
+        .into_iter()
+        .map(|(first, second)| {
+            let (first, second) = (position(first), position(second));
+            (first.min(second), first.max(second))
+        })
+        .collect();
+    connections.sort_unstable();
+    (nodes, connections)
+}
+
+/// Writes the nodes and connections as an undirected Graphviz DOT graph, e.g. to inspect
+/// adjacency with `neato -n`. Nodes are named `"x,y"` after their position, are placed at it and
+/// have `height` and `type` attributes. `position` converts positions to two numbers. Equal
+/// terrains give equal text.
+pub fn to_dot<T: Eq + Hash + Copy>(
+    terrain: &Terrain<T>,
+    position: impl Fn(T) -> (i32, i32),
+) -> String {
+    let (nodes, connections) = graph(terrain, position);
+    let mut text = String::from("graph terrain {\n");
+    for ((x, y), height, terrain_type) in nodes {
+        writeln!(
+            text,
+            "  \"{},{}\" [pos=\"{},{}!\", height={}, type={}];",
+            x, y, x, y, height, terrain_type
+        )
+        .unwrap();
+    }
+    for ((first_x, first_y), (second_x, second_y)) in connections {
+        writeln!(
+            text,
+            "  \"{},{}\" -- \"{},{}\";",
+            first_x, first_y, second_x, second_y
+        )
+        .unwrap();
+    }
+    text.push_str("}\n");
+    text
+}
+
+/// Writes the nodes and connections as an undirected GraphML graph, e.g. for Gephi. Nodes have
+/// the id `x,y` after their position and `x`, `y`, `height` and `type` attributes. `position`
+/// converts positions to two numbers. Equal terrains give equal text.
+pub fn to_graphml<T: Eq + Hash + Copy>(
+    terrain: &Terrain<T>,
+    position: impl Fn(T) -> (i32, i32),
+) -> String {
+    let (nodes, connections) = graph(terrain, position);
+    let mut text = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+    );
+    for key in &["x", "y", "height", "type"] {
+        writeln!(
+            text,
+            "  <key id=\"{}\" for=\"node\" attr.name=\"{}\" attr.type=\"int\"/>",
+            key, key
+        )
+        .unwrap();
+    }
+    text.push_str("  <graph id=\"terrain\" edgedefault=\"undirected\">\n");
+    for ((x, y), height, terrain_type) in nodes {
+        writeln!(text, "    <node id=\"{},{}\">", x, y).unwrap();
+        for (key, value) in &[
+            ("x", x),
+            ("y", y),
+            ("height", height),
+            ("type", terrain_type),
+        ] {
+            writeln!(text, "      <data key=\"{}\">{}</data>", key, value).unwrap();
+        }
+        text.push_str("    </node>\n");
+    }
+    for ((first_x, first_y), (second_x, second_y)) in connections {
+        writeln!(
+            text,
+            "    <edge source=\"{},{}\" target=\"{},{}\"/>",
+            first_x, first_y, second_x, second_y
+        )
+        .unwrap();
+    }
+    text.push_str("  </graph>\n</graphml>\n");
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terrain() -> Terrain<(i32, i32)> {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes((1, 0), (0, 2));
+        terrain.set_height((0, 2), 1);
+        terrain.set_terrain_type((1, 0), 3);
+        terrain
+    }
+
+    #[test]
+    fn to_dot_writes_nodes_and_connections() {
+        assert_eq!(
+            "graph terrain {\n\
+             \x20 \"0,2\" [pos=\"0,2!\", height=1, type=0];\n\
+             \x20 \"1,0\" [pos=\"1,0!\", height=0, type=3];\n\
+             \x20 \"0,2\" -- \"1,0\";\n\
+             }\n",
+            to_dot(&terrain(), |node| node)
+        );
+    }
+
+    #[test]
+    fn to_graphml_writes_nodes_and_connections() {
+        let text = to_graphml(&terrain(), |node| node);
+
+        assert!(text.contains(
+            "    <node id=\"1,0\">\n\
+             \x20     <data key=\"x\">1</data>\n\
+             \x20     <data key=\"y\">0</data>\n\
+             \x20     <data key=\"height\">0</data>\n\
+             \x20     <data key=\"type\">3</data>\n\
+             \x20   </node>\n"
+        ));
+        assert!(text.contains("    <edge source=\"0,2\" target=\"1,0\"/>\n"));
+        assert!(text.ends_with("</graphml>\n"));
+    }
+}
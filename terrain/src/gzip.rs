@@ -0,0 +1,558 @@
+/// Bytes gzip data starts with, followed by the compression method (8 for deflate).
+const GZIP_HEADER: [u8; 3] = [0x1f, 0x8b, 8];
+
+/// Flags in the gzip header that announce optional fields.
+const HEADER_CRC: u8 = 2;
+const EXTRA: u8 = 4;
+const NAME: u8 = 8;
+const COMMENT: u8 = 16;
+
+/// Deflate only refers back to the last 32 KiB.
+const WINDOW: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+
+/// Number of earlier positions searched for the longest match, per compression level.
+const CHAIN_LENGTHS: [usize; 10] = [0, 4, 8, 16, 32, 64, 128, 256, 1024, 4096];
+
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Order in which dynamic blocks store the code lengths of the code length alphabet.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Compresses data to the gzip format. `level` ranges from 0 (stored without compression) to 9
+/// (smallest, slowest); higher levels search longer for repeated bytes.
+pub fn compress(data: &[u8], level: u32) -> Vec<u8> {
+    let level = level.min(9) as usize;
+    let mut bytes = GZIP_HEADER.to_vec();
+    // No flags, no modification time, the extra flags for the level and an unknown OS.
+    let extra_flags = match level {
+        9 => 2,
+        1 => 4,
+        _ => 0,
+    };
+    bytes.extend_from_slice(&[0, 0, 0, 0, 0, extra_flags, 255]);
+    if level == 0 {
+        store(data, &mut bytes);
+    } else {
+        let mut writer = BitWriter {
+            bytes,
+            buffer: 0,
+            count: 0,
+        };
+        deflate(data, CHAIN_LENGTHS[level], &mut writer);
+        bytes = writer.finish();
+    }
+    bytes.extend_from_slice(&crc32(data).to_le_bytes());
+    bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    bytes
+}
+
+/// Returns whether the data starts like gzip data.
+pub fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.starts_with(&GZIP_HEADER)
+}
+
+/// Decompresses gzip data, also from other tools. Returns None if the data is no gzip data, is
+/// damaged or cut off. Only the first member is read.
+pub fn decompress(bytes: &[u8]) -> Option<Vec<u8>> {
+    if !is_gzip(bytes) || bytes.len() < 10 {
+        return None;
+    }
+    let flags = bytes[3];
+    let mut offset = 10;
+    if flags & EXTRA != 0 {
+        let length = u16::from_le_bytes([*bytes.get(offset)?, *bytes.get(offset + 1)?]);
+        offset += 2 + length as usize;
+    }
+    for flag in &[NAME, COMMENT] {
+        if flags & flag != 0 {
+            offset += bytes.get(offset..)?.iter().position(|byte| *byte == 0)? + 1;
+        }
+    }
+    if flags & HEADER_CRC != 0 {
+        offset += 2;
+    }
+
+    let mut reader = BitReader {
+        bytes: bytes.get(offset..)?,
+        offset: 0,
+        buffer: 0,
+        count: 0,
+    };
+    let data = inflate(&mut reader)?;
+    let trailer = reader.bytes.get(reader.offset..reader.offset + 8)?;
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    if crc != crc32(&data) || size != data.len() as u32 {
+        return None;
+    }
+    Some(data)
+}
+
+/// Decompresses zlib data, the format gzip data is wrapped in by many other tools. Returns None
+/// if the data is no zlib data, needs a preset dictionary, is damaged or cut off.
+pub fn decompress_zlib(bytes: &[u8]) -> Option<Vec<u8>> {
+    let header = bytes.get(..2)?;
+    if header[0] & 0x0f != 8 || u16::from_be_bytes([header[0], header[1]]) % 31 != 0 {
+        return None;
+    }
+    if header[1] & 0x20 != 0 {
+        return None;
+    }
+
+    let mut reader = BitReader {
+        bytes: &bytes[2..],
+        offset: 0,
+        buffer: 0,
+        count: 0,
+    };
+    let data = inflate(&mut reader)?;
+    let trailer = reader.bytes.get(reader.offset..reader.offset + 4)?;
+    if u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]) != adler32(&data) {
+        return None;
+    }
+    Some(data)
+}
+
+/// Returns the Adler-32 checksum zlib uses.
+fn adler32(data: &[u8]) -> u32 {
+    let (mut first, mut second) = (1u32, 0u32);
+    for byte in data {
+        first = (first + *byte as u32) % 65521;
+        second = (second + first) % 65521;
+    }
+    second << 16 | first
+}
+
+/// Returns the CRC-32 checksum gzip uses.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Writes the data as stored deflate blocks.
+fn store(data: &[u8], bytes: &mut Vec<u8>) {
+    let mut blocks = data.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        bytes.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        bytes.push(if blocks.peek().is_none() { 1 } else { 0 });
+        let length = block.len() as u16;
+        bytes.extend_from_slice(&length.to_le_bytes());
+        bytes.extend_from_slice(&(!length).to_le_bytes());
+        bytes.extend_from_slice(block);
+    }
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    /// Writes the lowest `count` bits of value, lowest bit first.
+    fn bits(&mut self, value: u32, count: u32) {
+        self.buffer |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which is stored highest bit first.
+    fn code(&mut self, code: u32, length: u32) {
+        let reversed = code.reverse_bits() >> (32 - length);
+        self.bits(reversed, length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Writes a literal or length symbol with the fixed Huffman code.
+fn write_symbol(writer: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => writer.code(0x30 + symbol, 8),
+        144..=255 => writer.code(0x190 + symbol - 144, 9),
+        256..=279 => writer.code(symbol - 256, 7),
+        _ => writer.code(0xc0 + symbol - 280, 8),
+    }
+}
+
+fn write_match(writer: &mut BitWriter, length: usize, distance: usize) {
+    let code = LENGTH_BASES
+        .iter()
+        .rposition(|base| *base as usize <= length)
+        .unwrap();
+    write_symbol(writer, 257 + code as u32);
+    writer.bits(
+        (length - LENGTH_BASES[code] as usize) as u32,
+        LENGTH_EXTRA_BITS[code] as u32,
+    );
+    let code = DISTANCE_BASES
+        .iter()
+        .rposition(|base| *base as usize <= distance)
+        .unwrap();
+    writer.code(code as u32, 5);
+    writer.bits(
+        (distance - DISTANCE_BASES[code] as usize) as u32,
+        DISTANCE_EXTRA_BITS[code] as u32,
+    );
+}
+
+/// Number of hash chains `deflate` puts positions into.
+const HASH_SIZE: usize = 1 << 15;
+
+/// Returns the hash chain of the three bytes at the position.
+fn hash(data: &[u8], position: usize) -> usize {
+    let value = (data[position] as usize) << 16
+        | (data[position + 1] as usize) << 8
+        | data[position + 2] as usize;
+    (value.wrapping_mul(2_654_435_761) >> 7) % HASH_SIZE
+}
+
+/// Compresses the data into a single block with the fixed Huffman codes. Repeated bytes are
+/// found with hash chains over the last `WINDOW` bytes.
+fn deflate(data: &[u8], chain_length: usize, writer: &mut BitWriter) {
+    let mut heads = vec![usize::MAX; HASH_SIZE];
+    let mut previous = vec![usize::MAX; data.len()];
+    let insert = |position: usize, heads: &mut [usize], previous: &mut [usize]| {
+        if position + MIN_MATCH <= data.len() {
+            let hash = hash(data, position);
+            previous[position] = heads[hash];
+            heads[hash] = position;
+        }
+    };
+
+    // Final block with fixed Huffman codes.
+    writer.bits(1, 1);
+    writer.bits(1, 2);
+    let mut position = 0;
+    while position < data.len() {
+        let mut best = (0, 0);
+        if position + MIN_MATCH <= data.len() {
+            let max_length = MAX_MATCH.min(data.len() - position);
+            let mut candidate = heads[hash(data, position)];
+            let mut steps = 0;
+            while candidate != usize::MAX && position - candidate <= WINDOW && steps < chain_length
+            {
+                let length = (0..max_length)
+                    .take_while(|offset| data[candidate + offset] == data[position + offset])
+                    .count();
+                if length > best.0 {
+                    best = (length, position - candidate);
+                    if length == max_length {
+                        break;
+                    }
+                }
+                candidate = previous[candidate];
+                steps += 1;
+            }
+        }
+
+        let (length, distance) = best;
+        if length >= MIN_MATCH {
+            write_match(writer, length, distance);
+            for offset in 0..length {
+                insert(position + offset, &mut heads, &mut previous);
+            }
+            position += length;
+        } else {
+            write_symbol(writer, data[position] as u32);
+            insert(position, &mut heads, &mut previous);
+            position += 1;
+        }
+    }
+    write_symbol(writer, 256);
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    /// Reads `count` bits, lowest bit first.
+    fn bits(&mut self, count: u32) -> Option<u32> {
+        while self.count < count {
+            self.buffer |= (*self.bytes.get(self.offset)? as u32) << self.count;
+            self.offset += 1;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u64 << count) - 1) as u32;
+        self.buffer >>= count;
+        self.count -= count;
+        Some(value)
+    }
+
+    /// Drops the bits up to the next byte.
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+/// A canonical Huffman code, given by the number of codes per length and the symbols ordered by
+/// their codes.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// Builds the code from the code length of every symbol. Returns None if the lengths do not
+    /// form a valid code.
+    fn new(lengths: &[u8]) -> Option<Huffman> {
+        let mut counts = [0u16; 16];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut left: i32 = 1;
+        for count in &counts[1..] {
+            left = left * 2 - *count as i32;
+            if left < 0 {
+                return None;
+            }
+        }
+        let mut symbols: Vec<u16> = (0..lengths.len() as u16)
+            .filter(|symbol| lengths[*symbol as usize] != 0)
+            .collect();
+        symbols.sort_by_key(|symbol| lengths[*symbol as usize]);
+        Some(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader<'_>) -> Option<u16> {
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+        for count in &self.counts[1..] {
+            code |= reader.bits(1)? as i32;
+            let count = *count as i32;
+            if code - first < count {
+                return self.symbols.get((index + code - first) as usize).copied();
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        None
+    }
+}
+
+fn inflate(reader: &mut BitReader<'_>) -> Option<Vec<u8>> {
+    let mut data = Vec::new();
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let header = reader.bytes.get(reader.offset..reader.offset + 4)?;
+                let length = u16::from_le_bytes([header[0], header[1]]);
+                if length != !u16::from_le_bytes([header[2], header[3]]) {
+                    return None;
+                }
+                let start = reader.offset + 4;
+                let end = start + length as usize;
+                data.extend_from_slice(reader.bytes.get(start..end)?);
+                reader.offset = end;
+            }
+            1 => {
+                let mut lengths = [8; 288];
+                lengths[144..256].iter_mut().for_each(|length| *length = 9);
+                lengths[256..280].iter_mut().for_each(|length| *length = 7);
+                let literals = Huffman::new(&lengths)?;
+                let distances = Huffman::new(&[5; 30])?;
+                inflate_block(reader, &literals, &distances, &mut data)?;
+            }
+            2 => {
+                let (literals, distances) = read_dynamic_codes(reader)?;
+                inflate_block(reader, &literals, &distances, &mut data)?;
+            }
+            _ => return None,
+        }
+        if last {
+            // The trailer starts at the next byte.
+            reader.align();
+            return Some(data);
+        }
+    }
+}
+
+fn read_dynamic_codes(reader: &mut BitReader<'_>) -> Option<(Huffman, Huffman)> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+    let mut code_lengths = [0; 19];
+    for index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[*index] = reader.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths)?;
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (length, repeat) = match code_lengths.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last()?, 3 + reader.bits(2)?),
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        for _ in 0..repeat {
+            lengths.push(length);
+        }
+    }
+    if lengths.len() > literal_count + distance_count || lengths[256] == 0 {
+        return None;
+    }
+    Some((
+        Huffman::new(&lengths[..literal_count])?,
+        Huffman::new(&lengths[literal_count..])?,
+    ))
+}
+
+fn inflate_block(
+    reader: &mut BitReader<'_>,
+    literals: &Huffman,
+    distances: &Huffman,
+    data: &mut Vec<u8>,
+) -> Option<()> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        match symbol {
+            0..=255 => data.push(symbol as u8),
+            256 => return Some(()),
+            _ => {
+                let code = symbol - 257;
+                let length = *LENGTH_BASES.get(code)? as usize
+                    + reader.bits(LENGTH_EXTRA_BITS[code] as u32)? as usize;
+                let code = distances.decode(reader)? as usize;
+                let distance = *DISTANCE_BASES.get(code)? as usize
+                    + reader.bits(DISTANCE_EXTRA_BITS[code] as u32)? as usize;
+                if distance > data.len() {
+                    return None;
+                }
+                let start = data.len() - distance;
+                for offset in 0..length {
+                    data.push(data[start + offset]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+
+    fn samples() -> Vec<Vec<u8>> {
+        let mut random = Random::new(3);
+        let noise: Vec<u8> = (0..5000).map(|_| random.range(0, 255) as u8).collect();
+        let repeated: Vec<u8> = (0..100_000).map(|index| (index % 7 * 31) as u8).collect();
+        vec![
+            Vec::new(),
+            b"a".to_vec(),
+            b"abcabcabcabc".to_vec(),
+            noise,
+            repeated,
+        ]
+    }
+
+    #[test]
+    fn decompress_returns_compressed_data() {
+        for data in samples() {
+            for level in 0..=9 {
+                assert_eq!(Some(data.clone()), decompress(&compress(&data, level)));
+            }
+        }
+    }
+
+    #[test]
+    fn compress_shrinks_repeated_data() {
+        let data = samples().pop().unwrap();
+
+        assert!(compress(&data, 1).len() < data.len() / 10);
+        assert!(compress(&data, 9).len() <= compress(&data, 1).len());
+    }
+
+    #[test]
+    fn decompress_reads_dynamic_blocks_of_other_tools() {
+        // 120 random letters and spaces compressed by zlib, with a file name and dynamic Huffman
+        // codes. The checksum in the trailer makes sure all of them are right.
+        let bytes = [
+            0x1f, 0x8b, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x61, 0x00, 0x25, 0x8c,
+            0xc1, 0x11, 0x00, 0x41, 0x08, 0xc2, 0xfe, 0x56, 0x41, 0x6b, 0x04, 0xfb, 0xaf, 0xe1,
+            0x70, 0x6f, 0x7c, 0xa8, 0x40, 0xb0, 0xb1, 0x56, 0xb1, 0xe5, 0xc4, 0x8b, 0x2b, 0xd8,
+            0xe3, 0x74, 0xe4, 0xd5, 0xdc, 0x5f, 0x39, 0x73, 0x0e, 0x8a, 0x0c, 0x52, 0xfe, 0x3c,
+            0x43, 0x33, 0x85, 0x83, 0xaa, 0xf0, 0x7a, 0x8a, 0x97, 0x17, 0x2c, 0xb7, 0xad, 0x6b,
+            0x53, 0xef, 0xc2, 0x84, 0xba, 0xec, 0x07, 0x63, 0xd4, 0xdd, 0x77, 0x78, 0x00, 0x00,
+            0x00,
+        ];
+
+        let data = decompress(&bytes).unwrap();
+
+        assert_eq!(120, data.len());
+        assert!(data.starts_with(b"aaba d caa"));
+    }
+
+    #[test]
+    fn decompress_zlib_reads_zlib_data() {
+        // Compressed by zlib at level 9.
+        let bytes = [
+            120, 218, 99, 100, 98, 102, 97, 132, 226, 146, 204, 156, 212, 20, 0, 7, 181, 2, 49,
+        ];
+        let data = [&[1, 2, 3, 4, 1, 2, 3, 4, 1, 2, 3, 4][..], b"tiled"].concat();
+
+        assert_eq!(Some(data), decompress_zlib(&bytes));
+        assert_eq!(None, decompress_zlib(&bytes[..bytes.len() - 1]));
+        assert_eq!(None, decompress_zlib(&compress(b"gzip", 6)));
+    }
+
+    #[test]
+    fn decompress_rejects_damaged_data() {
+        let mut bytes = compress(b"some terrain data", 6);
+        let middle = bytes.len() / 2;
+        bytes[middle] ^= 0x55;
+
+        assert_eq!(None, decompress(&bytes));
+        assert_eq!(None, decompress(&compress(b"cut off", 6)[..15]));
+        assert_eq!(None, decompress(b"no gzip data"));
+    }
+}
@@ -0,0 +1,138 @@
+use crate::json;
+use crate::json::Json;
+use std::collections::HashMap;
+
+/// A map of a third-party hex map editor, with the palette entry of every tile.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EditorMap {
+    /// Whether every other column is shifted, otherwise every other row. The odd ones are
+    /// shifted in both editors.
+    pub stagger_columns: bool,
+    /// Column, row and palette entry of every tile that is not empty.
+    pub tiles: Vec<(u32, u32, String)>,
+}
+
+/// Reads a map saved by Hex Kit: an object with `width`, `orientation` ("COLUMNS" or "ROWS")
+/// and `layers`, each with `tiles` row by row that are null or objects with the image `source`
+/// of the tile. Tiles of later layers cover those of earlier layers. Returns None if the text is
+/// no such map.
+pub fn parse_hex_kit(text: &str) -> Option<EditorMap> {
+    let map = json::parse(text)?;
+    let width = map.get("width")?.as_i32()?.max(1) as u32;
+    let stagger_columns = match map.get("orientation") {
+        Some(Json::String(orientation)) => !orientation.eq_ignore_ascii_case("rows"),
+        _ => true,
+    };
+
+    let mut sources: HashMap<(u32, u32), &str> = HashMap::new();
+    for layer in map.get("layers")?.as_array()? {
+        for (index, tile) in layer.get("tiles")?.as_array()?.iter().enumerate() {
+            let source = match tile.get("source") {
+                Some(Json::String(source)) if !source.is_empty() => source,
+                _ => continue,
+            };
+            sources.insert((index as u32 % width, index as u32 / width), source);
+        }
+    }
+    let mut tiles: Vec<(u32, u32, String)> = sources
+        .into_iter()
+        .map(|((column, row), source)| (column, row, source.to_owned()))
+        .collect();
+    tiles.sort_unstable_by_key(|(column, row, _)| (*row, *column));
+    Some(EditorMap {
+        stagger_columns,
+        tiles,
+    })
+}
+
+/// Reads a map exported by Hexographer as CSV: one row of the map per line with the terrain name
+/// of every column, which may be quoted. Empty fields are empty tiles. Returns None if there are
+/// no tiles.
+pub fn parse_hexographer_csv(text: &str) -> Option<EditorMap> {
+    let mut tiles = Vec::new();
+    for (row, line) in text.lines().enumerate() {
+        for (column, field) in line.split(',').enumerate() {
+            let name = field.trim().trim_matches('"').trim();
+            if !name.is_empty() {
+                tiles.push((column as u32, row as u32, name.to_owned()));
+            }
+        }
+    }
+    if tiles.is_empty() {
+        return None;
+    }
+    Some(EditorMap {
+        stagger_columns: true,
+        tiles,
+    })
+}
+
+/// Returns the terrain type of a palette entry from a mapping table: the type of the whole entry,
+/// or else of its file name without folders and extension, e.g. "grass" for
+/// "Classic/grass.png".
+pub fn palette_type(entry: &str, mapping: impl Fn(&str) -> Option<i32>) -> Option<i32> {
+    mapping(entry).or_else(|| {
+        let name = entry.rsplit(['/', '\\']).next()?;
+        let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+        mapping(stem)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_hex_kit_covers_lower_layers() {
+        let text = r#"{
+            "width": 2, "height": 2, "orientation": "COLUMNS",
+            "layers": [
+                {"tiles": [{"source": "Classic/grass.png"}, {"source": "Classic/hills.png"},
+                           null, {"source": ""}]},
+                {"tiles": [null, {"source": "Classic/forest.png"},
+                           {"source": "Classic/water.png"}, null]}
+            ]
+        }"#;
+
+        assert_eq!(
+            Some(EditorMap {
+                stagger_columns: true,
+                tiles: vec![
+                    (0, 0, "Classic/grass.png".to_string()),
+                    (1, 0, "Classic/forest.png".to_string()),
+                    (0, 1, "Classic/water.png".to_string()),
+                ],
+            }),
+            parse_hex_kit(text)
+        );
+    }
+
+    #[test]
+    fn parse_hexographer_csv_skips_empty_fields() {
+        let map = parse_hexographer_csv("Grass,\"Hills\"\n,Water\n").unwrap();
+
+        assert_eq!(
+            vec![
+                (0, 0, "Grass".to_string()),
+                (1, 0, "Hills".to_string()),
+                (1, 1, "Water".to_string()),
+            ],
+            map.tiles
+        );
+        assert_eq!(None, parse_hexographer_csv("\n,\n"));
+    }
+
+    #[test]
+    fn palette_type_falls_back_to_file_name() {
+        let mapping = |entry: &str| match entry {
+            "grass" => Some(1),
+            "Classic/hills.png" => Some(2),
+            _ => None,
+        };
+
+        assert_eq!(Some(1), palette_type("Classic/grass.png", mapping));
+        assert_eq!(Some(2), palette_type("Classic/hills.png", mapping));
+        assert_eq!(Some(1), palette_type("grass", mapping));
+        assert_eq!(None, palette_type("Classic/water.png", mapping));
+    }
+}
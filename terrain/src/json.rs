@@ -0,0 +1,420 @@
+use crate::save::Save;
+use crate::terrain::Terrain;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+
+/// Version of the layout `to_json` creates.
+pub const VERSION: u32 = 1;
+
+/// A JSON value. Objects keep the order of their entries.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Returns the value of an entry of an object, None for other values or missing entries.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(entries) => entries
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    /// Returns the number as i32, None if it is no whole number in the range of i32.
+    pub fn as_i32(&self) -> Option<i32> {
+        let number = self.as_f64()?;
+        if number.fract() != 0.0 || number < i32::MIN as f64 || number > i32::MAX as f64 {
+            return None;
+        }
+        Some(number as i32)
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(formatter, "null"),
+            Json::Bool(value) => write!(formatter, "{}", value),
+            Json::Number(number) if number.is_finite() => write!(formatter, "{}", number),
+            Json::Number(_) => write!(formatter, "null"),
+            Json::String(text) => write_string(formatter, text),
+            Json::Array(values) => {
+                write!(formatter, "[")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(formatter, ",")?;
+                    }
+                    write!(formatter, "{}", value)?;
+                }
+                write!(formatter, "]")
+            }
+            Json::Object(entries) => {
+                write!(formatter, "{{")?;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        write!(formatter, ",")?;
+                    }
+                    write_string(formatter, key)?;
+                    write!(formatter, ":{}", value)?;
+                }
+                write!(formatter, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(formatter: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    write!(formatter, "\"")?;
+    for character in text.chars() {
+        match character {
+            '"' => write!(formatter, "\\\"")?,
+            '\\' => write!(formatter, "\\\\")?,
+            '\n' => write!(formatter, "\\n")?,
+            '\r' => write!(formatter, "\\r")?,
+            '\t' => write!(formatter, "\\t")?,
+            character if (character as u32) < 0x20 => {
+                write!(formatter, "\\u{:04x}", character as u32)?
+            }
+            character => write!(formatter, "{}", character)?,
+        }
+    }
+    write!(formatter, "\"")
+}
+
+/// Parses JSON text. Returns None if it is not valid JSON.
+pub fn parse(text: &str) -> Option<Json> {
+    let mut parser = Parser {
+        characters: text.chars().collect(),
+        offset: 0,
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.offset < parser.characters.len() {
+        return None;
+    }
+    Some(value)
+}
+
+struct Parser {
+    characters: Vec<char>,
+    offset: usize,
+}
+
+impl Parser {
+    fn skip_whitespace(&mut self) {
+        while matches!(
+            self.peek(),
+            Some(' ') | Some('\n') | Some('\r') | Some('\t')
+        ) {
+            self.offset += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.characters.get(self.offset).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let character = self.peek()?;
+        self.offset += 1;
+        Some(character)
+    }
+
+    fn expect(&mut self, word: &str) -> Option<()> {
+        for character in word.chars() {
+            if self.next()? != character {
+                return None;
+            }
+        }
+        Some(())
+    }
+
+    fn value(&mut self) -> Option<Json> {
+        self.skip_whitespace();
+        match self.peek()? {
+            'n' => self.expect("null").map(|_| Json::Null),
+            't' => self.expect("true").map(|_| Json::Bool(true)),
+            'f' => self.expect("false").map(|_| Json::Bool(false)),
+            '"' => self.string().map(Json::String),
+            '[' => {
+                self.offset += 1;
+                let mut values = Vec::new();
+                self.skip_whitespace();
+                if self.peek()? == ']' {
+                    self.offset += 1;
+                    return Some(Json::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.next()? {
+                        ',' => continue,
+                        ']' => return Some(Json::Array(values)),
+                        _ => return None,
+                    }
+                }
+            }
+            '{' => {
+                self.offset += 1;
+                let mut entries = Vec::new();
+                self.skip_whitespace();
+                if self.peek()? == '}' {
+                    self.offset += 1;
+                    return Some(Json::Object(entries));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.skip_whitespace();
+                    self.expect(":")?;
+                    entries.push((key, self.value()?));
+                    self.skip_whitespace();
+                    match self.next()? {
+                        ',' => continue,
+                        '}' => return Some(Json::Object(entries)),
+                        _ => return None,
+                    }
+                }
+            }
+            _ => self.number(),
+        }
+    }
+
+    fn number(&mut self) -> Option<Json> {
+        let start = self.offset;
+        while matches!(
+            self.peek(),
+            Some('0'..='9') | Some('-') | Some('+') | Some('.') | Some('e') | Some('E')
+        ) {
+            self.offset += 1;
+        }
+        let text: String = self.characters[start..self.offset].iter().collect();
+        text.parse().ok().map(Json::Number)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.expect("\"")?;
+        let mut text = String::new();
+        loop {
+            match self.next()? {
+                '"' => return Some(text),
+                '\\' => match self.next()? {
+                    'n' => text.push('\n'),
+                    'r' => text.push('\r'),
+                    't' => text.push('\t'),
+                    'b' => text.push('\u{8}'),
+                    'f' => text.push('\u{c}'),
+                    'u' => {
+                        let mut code = self.hex()?;
+                        if (0xd800..0xdc00).contains(&code) {
+                            self.expect("\\u")?;
+                            let low = self.hex()?;
+                            code = 0x10000 + ((code - 0xd800) << 10) + (low.checked_sub(0xdc00)?);
+                        }
+                        text.push(std::char::from_u32(code)?);
+                    }
+                    character => text.push(character),
+                },
+                character => text.push(character),
+            }
+        }
+    }
+
+    fn hex(&mut self) -> Option<u32> {
+        let mut code = 0;
+        for _ in 0..4 {
+            code = code * 16 + self.next()?.to_digit(16)?;
+        }
+        Some(code)
+    }
+}
+
+/// Converts the terrain and the metadata of its nodes to JSON text. `position` converts positions
+/// to two numbers. Nodes are ordered by position, so equal terrains give equal text.
+pub fn to_json<T: Eq + Hash + Copy>(
+    terrain: &Terrain<T>,
+    metadata: &[(T, Json)],
+    position: impl Fn(T) -> (i32, i32),
+) -> String {
+    let number = |value: i32| Json::Number(value as f64);
+    let mut heights: Vec<(T, i32)> = terrain.heights().collect();
+    heights.sort_unstable_by_key(|(node, _)| position(*node));
+    let indices: HashMap<T, usize> = heights
+        .iter()
+        .enumerate()
+        .map(|(index, (node, _))| (*node, index))
+        .collect();
+    let metadata: HashMap<T, &Json> = metadata.iter().map(|(node, data)| (*node, data)).collect();
+
+    let nodes = heights
+        .iter()
+        .map(|(node, height)| {
+            let (x, y) = position(*node);
+            let mut entries = vec![
+                ("x".to_string(), number(x)),
+                ("y".to_string(), number(y)),
+                ("height".to_string(), number(*height)),
+                (
+                    "type".to_string(),
+                    number(terrain.get_terrain_type(*node).unwrap_or(0)),
+                ),
+            ];
+            if let Some(data) = metadata.get(node) {
+                entries.push(("metadata".to_string(), (*data).clone()));
+            }
+            Json::Object(entries)
+        })
+        .collect();
+    let mut connections: Vec<(usize, usize)> = terrain
+        .connections()
+        .into_iter()
+        .map(|(first, second)| (indices[&first], indices[&second]))
+        .collect();
+    connections.sort_unstable();
+    let connections = connections
+        .into_iter()
+        .map(|(first, second)| {
+            Json::Array(vec![
+                Json::Number(first as f64),
+                Json::Number(second as f64),
+            ])
+        })
+        .collect();
+
+    let (min_height, max_height) = terrain.height_limits();
+    Json::Object(vec![
+        ("version".to_string(), Json::Number(VERSION as f64)),
+        ("height_step".to_string(), number(terrain.height_step())),
+        ("min_height".to_string(), number(min_height)),
+        ("max_height".to_string(), number(max_height)),
+        ("nodes".to_string(), Json::Array(nodes)),
+        ("connections".to_string(), Json::Array(connections)),
+    ])
+    .to_string()
+}
+
+/// Reads a terrain from JSON text written by `to_json`. `position` converts two numbers back to a
+/// position. Unknown entries are ignored. Returns None if the text is no such JSON or of a newer
+/// version.
+pub fn from_json<T: Eq + Hash + Copy>(
+    text: &str,
+    position: impl Fn(i32, i32) -> T,
+) -> Option<Save<T, Json>> {
+    let json = parse(text)?;
+    if json.get("version")?.as_f64()? > VERSION as f64 {
+        return None;
+    }
+    let mut terrain = Terrain::new(json.get("height_step")?.as_i32()?);
+    terrain.set_height_limits(
+        json.get("min_height")?.as_i32()?,
+        json.get("max_height")?.as_i32()?,
+    );
+
+    let mut nodes = Vec::new();
+    let mut metadata = Vec::new();
+    for entry in json.get("nodes")?.as_array()? {
+        let node = position(entry.get("x")?.as_i32()?, entry.get("y")?.as_i32()?);
+        terrain.add_node(node);
+        terrain.set_height(node, entry.get("height")?.as_i32()?);
+        if let Some(terrain_type) = entry.get("type") {
+            terrain.set_terrain_type(node, terrain_type.as_i32()?);
+        }
+        if let Some(data) = entry.get("metadata") {
+            metadata.push((node, data.clone()));
+        }
+        nodes.push(node);
+    }
+    if let Some(connections) = json.get("connections") {
+        for connection in connections.as_array()? {
+            let node =
+                |index: usize| nodes.get(connection.as_array()?.get(index)?.as_i32()? as usize);
+            terrain.add_connected_nodes(*node(0)?, *node(1)?);
+        }
+    }
+    Some(Save { terrain, metadata })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_written_values() {
+        let json = Json::Object(vec![
+            ("null".to_string(), Json::Null),
+            (
+                "list".to_string(),
+                Json::Array(vec![Json::Bool(true), Json::Number(-1.5)]),
+            ),
+            (
+                "text".to_string(),
+                Json::String("a \"b\"\n\u{1}ü".to_string()),
+            ),
+        ]);
+
+        assert_eq!(Some(json.clone()), parse(&json.to_string()));
+        assert_eq!(
+            Some(Json::Array(vec![
+                Json::String("\u{1f600}".to_string()),
+                Json::Number(100.0)
+            ])),
+            parse(" [ \"\\ud83d\\ude00\" , 1e2 ] ")
+        );
+    }
+
+    #[test]
+    fn parse_rejects_invalid_json() {
+        assert_eq!(None, parse("[1, 2"));
+        assert_eq!(None, parse("{\"a\" 1}"));
+        assert_eq!(None, parse("1 2"));
+        assert_eq!(None, parse("nul"));
+    }
+
+    #[test]
+    fn from_json_returns_terrain_of_to_json() {
+        let mut terrain = Terrain::new(1);
+        terrain.add_connected_nodes(0, 1);
+        terrain.add_connected_nodes(1, 2);
+        terrain.set_heights(&[(2, 2)]);
+        terrain.set_terrain_type(1, 3);
+        let metadata = vec![(2, Json::Object(vec![("a".to_string(), Json::Bool(true))]))];
+
+        let text = to_json(&terrain, &metadata, |node| (node, 0));
+        let save = from_json(&text, |x, _| x).unwrap();
+
+        let mut heights: Vec<(i32, i32)> = save.terrain.heights().collect();
+        heights.sort_unstable();
+        assert_eq!(vec![(0, 0), (1, 1), (2, 2)], heights);
+        assert_eq!(vec![(0, 1), (1, 2)], save.terrain.connections());
+        assert_eq!(Some(3), save.terrain.get_terrain_type(1));
+        assert_eq!(metadata, save.metadata);
+        assert_eq!(
+            text,
+            to_json(&save.terrain, &save.metadata, |node| (node, 0))
+        );
+    }
+}
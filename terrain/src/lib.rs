@@ -1 +1,27 @@
-pub mod terrain;
+pub mod automaton;
+pub mod climate;
+pub mod csv;
+#[cfg(feature = "dem")]
+pub mod dem;
+pub mod ffi;
+pub mod gltf;
+pub mod graph;
+pub mod gzip;
+pub mod hexmaps;
+pub mod json;
+pub mod lockstep;
+pub mod maze;
+pub mod migration;
+pub mod noise;
+pub mod provinces;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod replication;
+pub mod save;
+pub mod scatter;
+pub mod streaming;
+pub mod tiled;
+pub mod tools;
+pub mod wfc;
+
+pub use terrain_core::{history, path, random, regions, ron, terrain};
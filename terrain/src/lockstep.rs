@@ -0,0 +1,182 @@
+use crate::replication::{read_signed, read_unsigned, write_signed, write_unsigned};
+use crate::terrain::Terrain;
+use std::hash::Hash;
+
+/// Version of the command encoding.
+const VERSION: u8 = 1;
+
+const SET_HEIGHTS: u8 = 0;
+const INCREASE_HEIGHTS: u8 = 1;
+const DECREASE_HEIGHTS: u8 = 2;
+const SET_TERRAIN_TYPES: u8 = 3;
+
+/// An edit command for lockstep games. Applying the same commands in the same order to terrains
+/// that were built the same way gives identical terrains on every machine, which
+/// `Terrain::checksum` can confirm. Nodes are edited in the order they are listed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command<T> {
+    /// Sets the heights of nodes, see `Terrain::set_heights`.
+    SetHeights(Vec<(T, i32)>),
+    /// Raises nodes by one step, see `Terrain::increase_heights`.
+    IncreaseHeights(Vec<T>),
+    /// Lowers nodes by one step, see `Terrain::decrease_heights`.
+    DecreaseHeights(Vec<T>),
+    /// Sets the terrain types of nodes.
+    SetTerrainTypes(Vec<(T, i32)>),
+}
+
+impl<T: Eq + Hash + Copy> Command<T> {
+    /// Applies the command to a terrain.
+    pub fn apply(&self, terrain: &mut Terrain<T>) {
+        match self {
+            Command::SetHeights(heights) => terrain.set_heights(heights),
+            Command::IncreaseHeights(nodes) => terrain.increase_heights(nodes),
+            Command::DecreaseHeights(nodes) => terrain.decrease_heights(nodes),
+            Command::SetTerrainTypes(types) => {
+                for (node, terrain_type) in types {
+                    terrain.set_terrain_type(*node, *terrain_type);
+                }
+            }
+        }
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>, position: &impl Fn(T) -> (i32, i32)) {
+        let (kind, (nodes, values)): (u8, (Vec<T>, Vec<i32>)) = match self {
+            Command::SetHeights(values) => (SET_HEIGHTS, values.iter().copied().unzip()),
+            Command::IncreaseHeights(nodes) => (INCREASE_HEIGHTS, (nodes.clone(), Vec::new())),
+            Command::DecreaseHeights(nodes) => (DECREASE_HEIGHTS, (nodes.clone(), Vec::new())),
+            Command::SetTerrainTypes(values) => (SET_TERRAIN_TYPES, values.iter().copied().unzip()),
+        };
+        bytes.push(kind);
+        write_unsigned(bytes, nodes.len() as u64);
+        for (index, node) in nodes.into_iter().enumerate() {
+            let (x, y) = position(node);
+            write_signed(bytes, x);
+            write_signed(bytes, y);
+            if let Some(value) = values.get(index) {
+                write_signed(bytes, *value);
+            }
+        }
+    }
+
+    fn read(
+        bytes: &[u8],
+        offset: &mut usize,
+        position: &impl Fn(i32, i32) -> T,
+    ) -> Option<Command<T>> {
+        let kind = *bytes.get(*offset)?;
+        *offset += 1;
+        let count = read_unsigned(bytes, offset)?;
+        let mut nodes = Vec::new();
+        let mut values = Vec::new();
+        for _ in 0..count {
+            let x = read_signed(bytes, offset)?;
+            let y = read_signed(bytes, offset)?;
+            nodes.push(position(x, y));
+            if kind == SET_HEIGHTS || kind == SET_TERRAIN_TYPES {
+                values.push(read_signed(bytes, offset)?);
+            }
+        }
+        let pairs = || nodes.iter().copied().zip(values.iter().copied()).collect();
+        match kind {
+            SET_HEIGHTS => Some(Command::SetHeights(pairs())),
+            INCREASE_HEIGHTS => Some(Command::IncreaseHeights(nodes)),
+            DECREASE_HEIGHTS => Some(Command::DecreaseHeights(nodes)),
+            SET_TERRAIN_TYPES => Some(Command::SetTerrainTypes(pairs())),
+            _ => None,
+        }
+    }
+}
+
+/// Encodes commands, e.g. the commands of one turn, to send them to the other peers. `position`
+/// converts positions to two numbers.
+pub fn encode_commands<T: Eq + Hash + Copy>(
+    commands: &[Command<T>],
+    position: impl Fn(T) -> (i32, i32),
+) -> Vec<u8> {
+    let mut bytes = vec![VERSION];
+    write_unsigned(&mut bytes, commands.len() as u64);
+    for command in commands {
+        command.write(&mut bytes, &position);
+    }
+    bytes
+}
+
+/// Decodes commands from `encode_commands`. `position` converts two numbers back to a position.
+/// Returns None if the commands are damaged or of an unknown version.
+pub fn decode_commands<T: Eq + Hash + Copy>(
+    bytes: &[u8],
+    position: impl Fn(i32, i32) -> T,
+) -> Option<Vec<Command<T>>> {
+    if *bytes.first()? != VERSION {
+        return None;
+    }
+    let mut offset = 1;
+    let count = read_unsigned(bytes, &mut offset)?;
+    let mut commands = Vec::new();
+    for _ in 0..count {
+        commands.push(Command::read(bytes, &mut offset, &position)?);
+    }
+    if offset != bytes.len() {
+        return None;
+    }
+    Some(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(nodes: &[i32]) -> Terrain<i32> {
+        let mut terrain = Terrain::new(1);
+        for pair in nodes.windows(2) {
+            terrain.add_connected_nodes(pair[0], pair[1]);
+        }
+        terrain
+    }
+
+    fn commands() -> Vec<Command<i32>> {
+        vec![
+            Command::SetHeights(vec![(0, 5), (6, -3)]),
+            Command::IncreaseHeights(vec![3, 2]),
+            Command::DecreaseHeights(vec![4]),
+            Command::SetTerrainTypes(vec![(1, 2), (5, 7)]),
+        ]
+    }
+
+    #[test]
+    fn decode_commands_returns_encoded_commands() {
+        let bytes = encode_commands(&commands(), |node| (node, -node));
+
+        assert_eq!(Some(commands()), decode_commands(&bytes, |x, _| x));
+        assert_eq!(None, decode_commands(&bytes[..bytes.len() - 1], |x, _| x));
+        assert_eq!(None, decode_commands(&[1, 1, 9, 0], |x, _: i32| x));
+    }
+
+    #[test]
+    fn applying_commands_gives_identical_terrains() {
+        let mut first = line(&[0, 1, 2, 3, 4, 5, 6]);
+        let mut second = line(&[0, 1, 2, 3, 4, 5, 6]);
+        let bytes = encode_commands(&commands(), |node| (node, 0));
+
+        for command in commands() {
+            command.apply(&mut first);
+        }
+        for command in decode_commands(&bytes, |x, _| x).unwrap() {
+            command.apply(&mut second);
+        }
+
+        assert_eq!(
+            first.checksum(|node| (node, 0)),
+            second.checksum(|node| (node, 0))
+        );
+        assert_eq!(
+            first.heights().collect::<Vec<_>>(),
+            second.heights().collect::<Vec<_>>()
+        );
+        assert_ne!(
+            line(&[0, 1, 2, 3, 4, 5, 6]).checksum(|node| (node, 0)),
+            first.checksum(|node| (node, 0))
+        );
+    }
+}
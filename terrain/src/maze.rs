@@ -0,0 +1,70 @@
+use crate::random::Random;
+use std::collections::HashSet;
+use std::hash::Hash;
+
+/// Generates a maze with a randomized depth-first search (recursive backtracker). Returns the
+/// connections between nodes that are open, which form a spanning tree of all nodes that can be
+/// reached from `start`. The result only depends on the seed and the order of the neighbours.
+pub fn backtracker<T: Eq + Hash + Copy>(
+    start: T,
+    neighbours: impl Fn(T) -> Vec<T>,
+    seed: u64,
+) -> Vec<(T, T)> {
+    let mut random = Random::new(seed);
+    let mut visited = HashSet::new();
+    let mut stack = vec![start];
+    let mut passages = Vec::new();
+    visited.insert(start);
+
+    while let Some(node) = stack.last().copied() {
+        let unvisited: Vec<T> = neighbours(node)
+            .into_iter()
+            .filter(|neighbour| !visited.contains(neighbour))
+            .collect();
+        if unvisited.is_empty() {
+            stack.pop();
+            continue;
+        }
+
+        let next = unvisited[random.range(0, unvisited.len() as i32 - 1) as usize];
+        visited.insert(next);
+        passages.push((node, next));
+        stack.push(next);
+    }
+    passages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid_neighbours(node: (i32, i32)) -> Vec<(i32, i32)> {
+        let (x, y) = node;
+        vec![(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+            .into_iter()
+            .filter(|(x, y)| (0..5).contains(x) && (0..5).contains(y))
+            .collect()
+    }
+
+    #[test]
+    fn backtracker_connects_all_nodes_once() {
+        let passages = backtracker((0, 0), grid_neighbours, 3);
+
+        assert_eq!(24, passages.len());
+        let mut reached: HashSet<(i32, i32)> = passages.iter().map(|(_, to)| *to).collect();
+        reached.insert((0, 0));
+        assert_eq!(25, reached.len());
+    }
+
+    #[test]
+    fn backtracker_depends_on_seed() {
+        assert_eq!(
+            backtracker((0, 0), grid_neighbours, 3),
+            backtracker((0, 0), grid_neighbours, 3)
+        );
+        assert_ne!(
+            backtracker((0, 0), grid_neighbours, 3),
+            backtracker((0, 0), grid_neighbours, 4)
+        );
+    }
+}
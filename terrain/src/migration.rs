@@ -0,0 +1,212 @@
+use crate::save;
+use crate::save::Chunk;
+
+/// Upgrades the chunks of a save by one version. Returns None if they cannot be upgraded.
+type Step = Box<dyn Fn(&mut Vec<Chunk>) -> Option<()>>;
+
+/// Upgrades the chunks of older saves step by step to the current schema, so existing maps stay
+/// readable when the data model changes. The format has a version in the header of every save,
+/// see `save::VERSION`. Layers, like the chunks of a game next to the terrain, have their own
+/// versions, which are stored in the `save::VERSIONS` chunk; saves without it have version 0 of
+/// every layer.
+pub struct Migrations {
+    format: Vec<(u16, Step)>,
+    layers: Vec<([u8; 4], Vec<Step>)>,
+}
+
+impl Default for Migrations {
+    /// Returns the migrations of the format, without layers.
+    fn default() -> Migrations {
+        let mut migrations = Migrations {
+            format: Vec::new(),
+            layers: Vec::new(),
+        };
+        // Versions 2 and 3 only added the header flags and the checksums of the chunks, which
+        // `save::read_chunks` handles.
+        migrations.add_format_step(1, |_| Some(()));
+        migrations
+    }
+}
+
+impl Migrations {
+    /// Adds a step that upgrades the chunks of saves of format version `from` to the next
+    /// version. Steps of the same version run in the order they were added.
+    pub fn add_format_step(
+        &mut self,
+        from: u16,
+        step: impl Fn(&mut Vec<Chunk>) -> Option<()> + 'static,
+    ) {
+        self.format.push((from, Box::new(step)));
+    }
+
+    /// Adds the next step of a layer, which upgrades its chunks from the version the layer had
+    /// before the step to the next one. The current version of a layer is its number of steps.
+    pub fn add_layer_step(
+        &mut self,
+        layer: [u8; 4],
+        step: impl Fn(&mut Vec<Chunk>) -> Option<()> + 'static,
+    ) {
+        match self.layers.iter_mut().find(|(id, _)| *id == layer) {
+            Some((_, steps)) => steps.push(Box::new(step)),
+            None => self.layers.push((layer, vec![Box::new(step)])),
+        }
+    }
+
+    /// Returns the current version of a layer.
+    pub fn layer_version(&self, layer: [u8; 4]) -> u16 {
+        self.layers
+            .iter()
+            .find(|(id, _)| *id == layer)
+            .map_or(0, |(_, steps)| steps.len() as u16)
+    }
+
+    /// Returns the chunk with the current version of every layer, to write it with a save.
+    pub fn versions_chunk(&self) -> Chunk {
+        let mut data = Vec::new();
+        for (layer, steps) in &self.layers {
+            data.extend_from_slice(layer);
+            data.extend_from_slice(&(steps.len() as u16).to_le_bytes());
+        }
+        Chunk {
+            id: save::VERSIONS,
+            data,
+        }
+    }
+
+    /// Upgrades the chunks of a save of format `version` to the current format and every layer
+    /// to its current version, and records the layer versions. Returns None if a step fails, or
+    /// if the format or a layer is newer than the migrations know.
+    pub fn migrate(&self, version: u16, chunks: &mut Vec<Chunk>) -> Option<()> {
+        if version > save::VERSION {
+            return None;
+        }
+        for from in version..save::VERSION {
+            for (_, step) in self.format.iter().filter(|(step, _)| *step == from) {
+                step(chunks)?;
+            }
+        }
+
+        let saved = saved_versions(chunks)?;
+        for (layer, steps) in &self.layers {
+            let version = saved
+                .iter()
+                .find(|(id, _)| id == layer)
+                .map_or(0, |(_, version)| *version as usize);
+            for step in steps.get(version..)? {
+                step(chunks)?;
+            }
+        }
+
+        chunks.retain(|chunk| chunk.id != save::VERSIONS);
+        chunks.push(self.versions_chunk());
+        Some(())
+    }
+}
+
+/// Returns the layer versions stored in the chunks, None if they are damaged.
+fn saved_versions(chunks: &[Chunk]) -> Option<Vec<([u8; 4], u16)>> {
+    let chunk = match chunks.iter().find(|chunk| chunk.id == save::VERSIONS) {
+        None => return Some(Vec::new()),
+        Some(chunk) => chunk,
+    };
+    if chunk.data.len() % 6 != 0 {
+        return None;
+    }
+    Some(
+        chunk
+            .data
+            .chunks(6)
+            .map(|entry| {
+                let mut layer = [0; 4];
+                layer.copy_from_slice(&entry[..4]);
+                (layer, u16::from_le_bytes([entry[4], entry[5]]))
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAYER: [u8; 4] = *b"GAME";
+
+    /// Version 1 of the layer doubles its data, version 2 appends a byte.
+    fn migrations() -> Migrations {
+        let mut migrations = Migrations::default();
+        migrations.add_layer_step(LAYER, |chunks| {
+            let chunk = chunks.iter_mut().find(|chunk| chunk.id == LAYER)?;
+            chunk.data = chunk.data.iter().map(|byte| byte * 2).collect();
+            Some(())
+        });
+        migrations.add_layer_step(LAYER, |chunks| {
+            chunks
+                .iter_mut()
+                .find(|chunk| chunk.id == LAYER)?
+                .data
+                .push(0);
+            Some(())
+        });
+        migrations
+    }
+
+    fn layer(data: Vec<u8>) -> Chunk {
+        Chunk { id: LAYER, data }
+    }
+
+    #[test]
+    fn migrate_upgrades_layers_from_saved_version() {
+        let migrations = migrations();
+        let mut old = vec![layer(vec![1, 2])];
+        let mut partly = vec![layer(vec![1, 2]), {
+            let mut versions = Migrations::default();
+            versions.add_layer_step(LAYER, |_| Some(()));
+            versions.versions_chunk()
+        }];
+
+        migrations.migrate(1, &mut old).unwrap();
+        migrations.migrate(save::VERSION, &mut partly).unwrap();
+
+        assert_eq!(vec![layer(vec![2, 4, 0]), migrations.versions_chunk()], old);
+        assert_eq!(
+            vec![layer(vec![1, 2, 0]), migrations.versions_chunk()],
+            partly
+        );
+        assert_eq!(2, migrations.layer_version(LAYER));
+        assert_eq!(0, migrations.layer_version(*b"NONE"));
+    }
+
+    #[test]
+    fn migrate_rejects_newer_and_failed_saves() {
+        let mut newer = vec![migrations().versions_chunk()];
+        let mut missing = Vec::new();
+
+        assert_eq!(
+            None,
+            Migrations::default().migrate(save::VERSION + 1, &mut Vec::new())
+        );
+        assert_eq!(None, {
+            let mut older = Migrations::default();
+            older.add_layer_step(LAYER, |_| Some(()));
+            older.migrate(save::VERSION, &mut newer)
+        });
+        assert_eq!(None, migrations().migrate(save::VERSION, &mut missing));
+    }
+
+    #[test]
+    fn migrate_runs_format_steps_of_older_versions() {
+        let mut migrations = Migrations::default();
+        migrations.add_format_step(save::VERSION - 1, |chunks| {
+            chunks.push(layer(Vec::new()));
+            Some(())
+        });
+        let mut current = Vec::new();
+        let mut old = Vec::new();
+
+        migrations.migrate(save::VERSION, &mut current).unwrap();
+        migrations.migrate(save::VERSION - 1, &mut old).unwrap();
+
+        assert_eq!(1, current.len());
+        assert_eq!(vec![layer(Vec::new()), migrations.versions_chunk()], old);
+    }
+}
@@ -0,0 +1,388 @@
+use crate::random::Random;
+use std::collections::HashMap;
+
+/// Settings for fractal Brownian motion. Several octaves of noise are added up, each with
+/// `lacunarity` times the frequency and `gain` times the amplitude of the previous one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fractal {
+    pub octaves: u32,
+    pub lacunarity: f32,
+    pub gain: f32,
+    /// How far positions are moved by another layer of noise before sampling (domain warping),
+    /// which bends the features of the noise. 0 disables warping.
+    pub warp: f32,
+}
+
+impl Default for Fractal {
+    fn default() -> Fractal {
+        Fractal {
+            octaves: 1,
+            lacunarity: 2.0,
+            gain: 0.5,
+            warp: 0.0,
+        }
+    }
+}
+
+/// Seeded two dimensional gradient noise (improved Perlin noise).
+#[derive(Clone, Debug)]
+pub struct Noise {
+    permutation: [u8; 512],
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Noise {
+        let mut values: Vec<u8> = (0..=255).collect();
+        let mut random = Random::new(seed);
+        for index in (1..values.len()).rev() {
+            let other = random.range(0, index as i32) as usize;
+            values.swap(index, other);
+        }
+
+        let mut permutation = [0; 512];
+        for (index, value) in permutation.iter_mut().enumerate() {
+            *value = values[index & 255];
+        }
+        Noise { permutation }
+    }
+
+    /// Returns the noise at a position, between -1 and 1. The noise changes smoothly and is 0 at
+    /// integer positions.
+    pub fn get(&self, x: f32, y: f32) -> f32 {
+        let cell_x = x.floor();
+        let cell_y = y.floor();
+        let x = x - cell_x;
+        let y = y - cell_y;
+        let cell_x = (cell_x as i32 & 255) as usize;
+        let cell_y = (cell_y as i32 & 255) as usize;
+
+        let hash = |offset_x: usize, offset_y: usize| {
+            let row = self.permutation[cell_x + offset_x] as usize;
+            self.permutation[row + cell_y + offset_y]
+        };
+        let u = fade(x);
+        let v = fade(y);
+        let top = lerp(
+            u,
+            gradient(hash(0, 0), x, y),
+            gradient(hash(1, 0), x - 1.0, y),
+        );
+        let bottom = lerp(
+            u,
+            gradient(hash(0, 1), x, y - 1.0),
+            gradient(hash(1, 1), x - 1.0, y - 1.0),
+        );
+        lerp(v, top, bottom).clamp(-1.0, 1.0)
+    }
+
+    /// Returns fractal noise at a position, between -1 and 1.
+    pub fn fractal(&self, x: f32, y: f32, fractal: &Fractal) -> f32 {
+        if fractal.warp == 0.0 {
+            return self.octaves(x, y, fractal);
+        }
+
+        // The offsets decorrelate the warp of both axes from each other and from the result.
+        let warp_x = self.octaves(x + 5.2, y + 1.3, fractal);
+        let warp_y = self.octaves(x - 1.7, y + 9.2, fractal);
+        self.octaves(
+            x + fractal.warp * warp_x,
+            y + fractal.warp * warp_y,
+            fractal,
+        )
+    }
+
+    fn octaves(&self, x: f32, y: f32, fractal: &Fractal) -> f32 {
+        let mut sum = 0.0;
+        let mut total_amplitude = 0.0;
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        for _ in 0..fractal.octaves.max(1) {
+            sum += self.get(x * frequency, y * frequency) * amplitude;
+            total_amplitude += amplitude;
+            frequency *= fractal.lacunarity;
+            amplitude *= fractal.gain;
+        }
+        if total_amplitude > 0.0 {
+            sum / total_amplitude
+        } else {
+            0.0
+        }
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f32, from: f32, to: f32) -> f32 {
+    from + t * (to - from)
+}
+
+fn gradient(hash: u8, x: f32, y: f32) -> f32 {
+    match hash & 7 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+/// Returns heights from fractal noise for nodes at the given positions, between `-amplitude` and
+/// `amplitude`. The positions are multiplied with `frequency` before sampling the noise.
+pub fn noise_heights<T: Copy>(
+    nodes: &[(T, f32, f32)],
+    seed: u64,
+    frequency: f32,
+    amplitude: f32,
+    fractal: &Fractal,
+) -> Vec<(T, i32)> {
+    let noise = Noise::new(seed);
+    nodes
+        .iter()
+        .map(|(node, x, y)| {
+            let value = noise.fractal(x * frequency, y * frequency, fractal);
+            (*node, (value * amplitude).round() as i32)
+        })
+        .collect()
+}
+
+/// Applies a landmass mask to the heights of nodes. Every node is given with its height and its
+/// distance from the center of the map, where 1 is the edge. Nodes within the shore, which
+/// encloses `land_fraction` of the area, keep their height. Further out the heights fall below
+/// sea level (0) down to `-depth` at the edge. `falloff` shapes the descent: values above 1 keep
+/// the water shallow for longer, values below 1 drop off quickly.
+pub fn island_heights<T: Copy>(
+    nodes: &[(T, i32, f32)],
+    land_fraction: f32,
+    falloff: f32,
+    depth: i32,
+) -> Vec<(T, i32)> {
+    let shore = land_fraction.clamp(0.0, 1.0).sqrt();
+    let width = (1.0 - shore).max(f32::EPSILON);
+    let falloff = falloff.max(0.01);
+    nodes
+        .iter()
+        .map(|(node, height, distance)| {
+            if *distance <= shore {
+                return (*node, *height);
+            }
+            let t = ((distance - shore) / width).clamp(0.0, 1.0).powf(falloff);
+            let shallow = (*height).min(-1) as f32;
+            let deep = -depth.max(1) as f32;
+            (*node, (shallow + (deep - shallow) * t).round() as i32)
+        })
+        .collect()
+}
+
+/// Returns heights from midpoint displacement for nodes on a triangular lattice, given by their
+/// lattice coordinates. The neighbours of a node are at (±1, 0), (0, ±1), (1, -1) and (-1, 1).
+/// Nodes whose coordinates are multiples of `size`, rounded up to a power of two, get a random
+/// height between `-amplitude` and `amplitude`. Every finer level lies on the midpoints of the
+/// connections of the level above and gets their average height, displaced by a random value
+/// that is `roughness` times smaller than on the level above.
+pub fn midpoint_heights<T: Copy>(
+    nodes: &[(T, i32, i32)],
+    seed: u64,
+    size: i32,
+    amplitude: f32,
+    roughness: f32,
+) -> Vec<(T, i32)> {
+    let size = (size.max(1) as u32).next_power_of_two() as i32;
+    let mut values = HashMap::new();
+    nodes
+        .iter()
+        .map(|(node, a, b)| {
+            let value = midpoint_value(&mut values, *a, *b, seed, size, amplitude, roughness);
+            (*node, value.round() as i32)
+        })
+        .collect()
+}
+
+fn midpoint_value(
+    values: &mut HashMap<(i32, i32), f32>,
+    a: i32,
+    b: i32,
+    seed: u64,
+    size: i32,
+    amplitude: f32,
+    roughness: f32,
+) -> f32 {
+    if let Some(value) = values.get(&(a, b)) {
+        return *value;
+    }
+
+    let mut spacing = size;
+    let mut scale = amplitude;
+    while spacing > 1 && (a.rem_euclid(spacing) != 0 || b.rem_euclid(spacing) != 0) {
+        spacing /= 2;
+        scale *= roughness;
+    }
+    let displacement = (Random::for_position(seed, a, b).next_f32() * 2.0 - 1.0) * scale;
+    let value = if spacing == size {
+        displacement
+    } else {
+        let ((a1, b1), (a2, b2)) = match ((a / spacing) % 2 != 0, (b / spacing) % 2 != 0) {
+            (true, false) => ((a - spacing, b), (a + spacing, b)),
+            (false, true) => ((a, b - spacing), (a, b + spacing)),
+            _ => ((a - spacing, b + spacing), (a + spacing, b - spacing)),
+        };
+        let first = midpoint_value(values, a1, b1, seed, size, amplitude, roughness);
+        let second = midpoint_value(values, a2, b2, seed, size, amplitude, roughness);
+        (first + second) / 2.0 + displacement
+    };
+    values.insert((a, b), value);
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(noise: &Noise) -> Vec<f32> {
+        (0..100)
+            .map(|index| noise.get(index as f32 * 0.37, index as f32 * 0.21 - 5.0))
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_produces_same_noise() {
+        assert_eq!(samples(&Noise::new(3)), samples(&Noise::new(3)));
+    }
+
+    #[test]
+    fn different_seeds_produce_different_noise() {
+        assert_ne!(samples(&Noise::new(3)), samples(&Noise::new(4)));
+    }
+
+    #[test]
+    fn noise_stays_between_minus_one_and_one() {
+        let noise = Noise::new(7);
+
+        assert!(samples(&noise)
+            .iter()
+            .all(|value| (-1.0..=1.0).contains(value)));
+        assert!(samples(&noise).iter().any(|value| value.abs() > 0.01));
+    }
+
+    #[test]
+    fn noise_is_zero_at_integer_positions() {
+        let noise = Noise::new(7);
+
+        assert_eq!(0.0, noise.get(3.0, -2.0));
+    }
+
+    #[test]
+    fn noise_heights_are_scaled_by_amplitude() {
+        let nodes: Vec<(i32, f32, f32)> = (0..50)
+            .map(|index| (index, index as f32 * 0.3, index as f32 * 0.7))
+            .collect();
+
+        let heights = noise_heights(&nodes, 1, 1.0, 5.0, &Fractal::default());
+
+        assert_eq!(50, heights.len());
+        assert!(heights.iter().all(|(_, height)| height.abs() <= 5));
+        assert!(heights.iter().any(|(_, height)| *height != 0));
+    }
+
+    #[test]
+    fn single_octave_matches_plain_noise() {
+        let noise = Noise::new(2);
+
+        assert_eq!(
+            noise.get(1.3, 2.6),
+            noise.fractal(1.3, 2.6, &Fractal::default())
+        );
+    }
+
+    #[test]
+    fn fractal_noise_stays_between_minus_one_and_one() {
+        let noise = Noise::new(2);
+        let fractal = Fractal {
+            octaves: 6,
+            lacunarity: 2.0,
+            gain: 0.9,
+            warp: 2.0,
+        };
+
+        for index in 0..100 {
+            let value = noise.fractal(index as f32 * 0.37, index as f32 * 0.21, &fractal);
+            assert!((-1.0..=1.0).contains(&value));
+        }
+    }
+
+    #[test]
+    fn warp_moves_sampled_positions() {
+        let noise = Noise::new(2);
+        let warped = Fractal {
+            warp: 1.0,
+            ..Fractal::default()
+        };
+
+        assert_ne!(
+            noise.fractal(1.3, 2.6, &Fractal::default()),
+            noise.fractal(1.3, 2.6, &warped)
+        );
+    }
+
+    #[test]
+    fn island_heights_keep_land_within_shore() {
+        let heights = island_heights(&[(0, 3, 0.2), (1, 3, 0.55)], 0.25, 1.0, 4);
+
+        assert_eq!(vec![(0, 3), (1, -1)], heights);
+    }
+
+    #[test]
+    fn island_heights_fall_to_depth_at_edge() {
+        let heights = island_heights(&[(0, 2, 0.75), (1, 2, 1.0), (2, 2, 1.5)], 0.25, 1.0, 5);
+
+        assert_eq!(vec![(0, -3), (1, -5), (2, -5)], heights);
+    }
+
+    #[test]
+    fn island_heights_falloff_shapes_descent() {
+        let gentle = island_heights(&[(0, 0, 0.75)], 0.25, 3.0, 5);
+        let steep = island_heights(&[(0, 0, 0.75)], 0.25, 0.3, 5);
+
+        assert!(gentle[0].1 > steep[0].1);
+    }
+
+    #[test]
+    fn midpoint_heights_stay_within_amplitude_on_top_level() {
+        let nodes: Vec<(i32, i32, i32)> = (-3..4).map(|i| (i, i * 4, -i * 4)).collect();
+
+        for (_, height) in midpoint_heights(&nodes, 3, 4, 5.0, 0.5) {
+            assert!((-5..=5).contains(&height));
+        }
+    }
+
+    #[test]
+    fn midpoint_heights_without_roughness_interpolate() {
+        let nodes = [(0, 0, 0), (1, 4, 0), (2, 8, 0), (3, 6, 0)];
+
+        let heights: Vec<f32> = midpoint_heights(&nodes, 7, 8, 20.0, 0.0)
+            .into_iter()
+            .map(|(_, height)| height as f32)
+            .collect();
+
+        assert!(((heights[0] + heights[2]) / 2.0 - heights[1]).abs() <= 1.0);
+        assert!(((heights[1] + heights[2]) / 2.0 - heights[3]).abs() <= 1.0);
+    }
+
+    #[test]
+    fn midpoint_heights_are_deterministic() {
+        let nodes: Vec<(i32, i32, i32)> = (0..50).map(|i| (i, i % 7 - 3, i / 7 - 3)).collect();
+
+        assert_eq!(
+            midpoint_heights(&nodes, 9, 8, 10.0, 0.6),
+            midpoint_heights(&nodes, 9, 8, 10.0, 0.6)
+        );
+        assert_ne!(
+            midpoint_heights(&nodes, 9, 8, 10.0, 0.6),
+            midpoint_heights(&nodes, 10, 8, 10.0, 0.6)
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use crate::random::Random;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+
+/// Partitions cells into up to `count` contiguous provinces. The provinces grow from randomly
+/// chosen cells, one neighbour at a time, so every cell belongs to the province whose origin is
+/// the fewest steps away. Returns the province of every cell, numbered from 0. Cells that are not
+/// connected to any origin get no province. The result only depends on the seed and the order of
+/// `cells`.
+pub fn grow<T: Eq + Hash + Copy>(
+    cells: &[T],
+    neighbours: impl Fn(T) -> Vec<T>,
+    count: usize,
+    seed: u64,
+) -> HashMap<T, i32> {
+    let mut origins = cells.to_vec();
+    let mut random = Random::new(seed);
+    for index in (1..origins.len()).rev() {
+        let other = random.range(0, index as i32) as usize;
+        origins.swap(index, other);
+    }
+    origins.truncate(count);
+
+    let mut provinces = HashMap::new();
+    let mut open = VecDeque::new();
+    for (province, origin) in origins.into_iter().enumerate() {
+        provinces.insert(origin, province as i32);
+        open.push_back(origin);
+    }
+
+    let members: HashSet<T> = cells.iter().copied().collect();
+    while let Some(cell) = open.pop_front() {
+        let province = provinces[&cell];
+        for neighbour in neighbours(cell) {
+            if members.contains(&neighbour) && !provinces.contains_key(&neighbour) {
+                provinces.insert(neighbour, province);
+                open.push_back(neighbour);
+            }
+        }
+    }
+    provinces
+}
+
+/// Returns all pairs of provinces that share a border, the lower province first.
+pub fn adjacency<T: Eq + Hash + Copy>(
+    provinces: &HashMap<T, i32>,
+    neighbours: impl Fn(T) -> Vec<T>,
+) -> BTreeSet<(i32, i32)> {
+    let mut pairs = BTreeSet::new();
+    for (cell, province) in provinces {
+        for neighbour in neighbours(*cell) {
+            match provinces.get(&neighbour) {
+                Some(other) if other != province => {
+                    pairs.insert((*province.min(other), *province.max(other)));
+                }
+                _ => {}
+            }
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line_neighbours(cell: i32) -> Vec<i32> {
+        vec![cell - 1, cell + 1]
+    }
+
+    #[test]
+    fn grow_assigns_every_connected_cell() {
+        let cells: Vec<i32> = (0..50).collect();
+
+        let provinces = grow(&cells, line_neighbours, 4, 3);
+
+        assert_eq!(50, provinces.len());
+        let ids: BTreeSet<i32> = provinces.values().copied().collect();
+        assert_eq!(vec![0, 1, 2, 3], ids.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn grow_creates_contiguous_provinces() {
+        let cells: Vec<i32> = (0..50).collect();
+
+        let provinces = grow(&cells, line_neighbours, 5, 8);
+
+        // On a line, a contiguous province changes at most once per border.
+        let changes = (1..50)
+            .filter(|cell| provinces[cell] != provinces[&(cell - 1)])
+            .count();
+        assert_eq!(4, changes);
+    }
+
+    #[test]
+    fn grow_does_not_create_more_provinces_than_cells() {
+        let provinces = grow(&[0, 1], line_neighbours, 5, 1);
+
+        assert_eq!(2, provinces.len());
+    }
+
+    #[test]
+    fn adjacency_returns_bordering_provinces() {
+        let provinces: HashMap<i32, i32> =
+            vec![(0, 0), (1, 0), (2, 1), (3, 2)].into_iter().collect();
+
+        let pairs = adjacency(&provinces, line_neighbours);
+
+        assert_eq!(vec![(0, 1), (1, 2)], pairs.into_iter().collect::<Vec<_>>());
+    }
+}
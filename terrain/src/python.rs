@@ -0,0 +1,211 @@
+use crate::csv;
+use crate::noise;
+use crate::noise::Fractal;
+use crate::ron;
+use crate::save;
+use crate::terrain::Terrain;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+type Position = (i32, i32);
+
+/// Python class `terrain.Terrain`, with nodes positioned by tuples of two integers like the
+/// vertex keys of the game.
+#[pyclass(name = "Terrain")]
+pub struct PyTerrain {
+    terrain: Terrain<Position>,
+}
+
+#[pymethods]
+impl PyTerrain {
+    #[new]
+    #[pyo3(signature = (height_step = 1))]
+    fn new(height_step: i32) -> Self {
+        PyTerrain {
+            terrain: Terrain::new(height_step),
+        }
+    }
+
+    /// Connects two nodes, adding them if they do not exist.
+    fn connect(&mut self, first: Position, second: Position) {
+        self.terrain.add_connected_nodes(first, second);
+    }
+
+    fn connections(&self) -> Vec<(Position, Position)> {
+        self.terrain.connections()
+    }
+
+    fn get_height(&self, node: Position) -> Option<i32> {
+        self.terrain.get_height_of_node(node)
+    }
+
+    /// Returns `(node, height)` of all nodes in the order they were added.
+    fn heights(&self) -> Vec<(Position, i32)> {
+        self.terrain.heights().collect()
+    }
+
+    /// Sets heights from `(node, height)` pairs, see `Terrain::set_heights`.
+    fn set_heights(&mut self, heights: Vec<(Position, i32)>) {
+        self.terrain.set_heights(&heights);
+    }
+
+    /// Sets rough heights from a generator, see `Terrain::set_generated_heights`.
+    fn set_generated_heights(&mut self, heights: Vec<(Position, i32)>) {
+        self.terrain.set_generated_heights(&heights);
+    }
+
+    fn get_terrain_type(&self, node: Position) -> Option<i32> {
+        self.terrain.get_terrain_type(node)
+    }
+
+    fn set_terrain_type(&mut self, node: Position, terrain_type: i32) -> bool {
+        self.terrain.set_terrain_type(node, terrain_type)
+    }
+
+    fn erode(&mut self, talus: f32, rate: f32, iterations: u32) {
+        self.terrain.erode(talus, rate, iterations);
+    }
+
+    fn weather(&mut self, intensity: f32, iterations: u32, seed: u64) {
+        self.terrain.weather(intensity, iterations, seed);
+    }
+
+    fn checksum(&self) -> u64 {
+        self.terrain.checksum(|position| position)
+    }
+
+    fn to_ron(&self) -> String {
+        ron::to_ron(&self.terrain, |node| node)
+    }
+
+    #[staticmethod]
+    fn from_ron(text: &str) -> PyResult<Self> {
+        ron::from_ron(text, |x, y| (x, y))
+            .map(|terrain| PyTerrain { terrain })
+            .ok_or_else(|| PyValueError::new_err("invalid RON terrain"))
+    }
+
+    #[pyo3(signature = (types = false))]
+    fn to_csv(&self, types: bool) -> String {
+        csv::to_csv(&self.terrain, |node| node, types)
+    }
+
+    /// Returns the terrain in the map format of the game, without metadata.
+    #[pyo3(signature = (compression_level = 6))]
+    fn save<'py>(&self, py: Python<'py>, compression_level: u32) -> Bound<'py, PyBytes> {
+        PyBytes::new(
+            py,
+            &save::write(&self.terrain, &[], |node| node, compression_level),
+        )
+    }
+
+    /// Reads a terrain from the map format of the game, ignoring metadata.
+    #[staticmethod]
+    fn load(bytes: &[u8]) -> PyResult<Self> {
+        save::read(bytes, |x, y| (x, y))
+            .map(|save| PyTerrain {
+                terrain: save.terrain,
+            })
+            .ok_or_else(|| PyValueError::new_err("invalid terrain save"))
+    }
+}
+
+/// Returns `(node, height)` from fractal noise for `(node, x, y)`, see `noise::noise_heights`.
+#[pyfunction]
+#[pyo3(signature = (
+    nodes,
+    seed,
+    frequency,
+    amplitude,
+    octaves = 1,
+    lacunarity = 2.0,
+    gain = 0.5,
+    warp = 0.0
+))]
+#[allow(clippy::too_many_arguments)]
+fn noise_heights(
+    nodes: Vec<(Position, f32, f32)>,
+    seed: u64,
+    frequency: f32,
+    amplitude: f32,
+    octaves: u32,
+    lacunarity: f32,
+    gain: f32,
+    warp: f32,
+) -> Vec<(Position, i32)> {
+    let fractal = Fractal {
+        octaves,
+        lacunarity,
+        gain,
+        warp,
+    };
+    noise::noise_heights(&nodes, seed, frequency, amplitude, &fractal)
+}
+
+/// Masks `(node, height, distance)` to an island, see `noise::island_heights`.
+#[pyfunction]
+fn island_heights(
+    nodes: Vec<(Position, i32, f32)>,
+    land_fraction: f32,
+    falloff: f32,
+    depth: i32,
+) -> Vec<(Position, i32)> {
+    noise::island_heights(&nodes, land_fraction, falloff, depth)
+}
+
+/// Returns `(node, height)` from midpoint displacement for `(node, a, b)` lattice coordinates, see
+/// `noise::midpoint_heights`.
+#[pyfunction]
+fn midpoint_heights(
+    nodes: Vec<(Position, i32, i32)>,
+    seed: u64,
+    size: i32,
+    amplitude: f32,
+    roughness: f32,
+) -> Vec<(Position, i32)> {
+    noise::midpoint_heights(&nodes, seed, size, amplitude, roughness)
+}
+
+/// The Python module `terrain`.
+#[pymodule]
+fn terrain(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyTerrain>()?;
+    module.add_function(wrap_pyfunction!(noise_heights, module)?)?;
+    module.add_function(wrap_pyfunction!(island_heights, module)?)?;
+    module.add_function(wrap_pyfunction!(midpoint_heights, module)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn module_edits_and_saves_terrain() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = PyModule::new(py, "terrain").unwrap();
+            terrain(&module).unwrap();
+            let locals = pyo3::types::PyDict::new(py);
+            locals.set_item("terrain", module).unwrap();
+            py.run(
+                pyo3::ffi::c_str!(
+                    r#"
+t = terrain.Terrain()
+t.connect((0, 0), (1, 0))
+t.connect((1, 0), (2, 0))
+t.set_heights([((0, 0), 2)])
+assert t.heights() == [((0, 0), 2), ((1, 0), 1), ((2, 0), 0)]
+assert terrain.Terrain.load(t.save()).checksum() == t.checksum()
+assert terrain.Terrain.from_ron(t.to_ron()).heights() == t.heights()
+assert len(terrain.noise_heights([((0, 0), 0.5, 0.5)], 1, 1.0, 10.0)) == 1
+"#
+                ),
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+        });
+    }
+}
@@ -0,0 +1,395 @@
+use crate::history::Edit;
+use crate::terrain::{Layers, Terrain};
+use std::hash::Hash;
+
+/// Version of the delta encoding.
+const VERSION: u8 = 2;
+
+/// How a remote edit treats nodes that were changed locally since the remote edit started, i.e.
+/// whose height, layers or edge feature is no longer the old value of the edit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Conflict {
+    /// The remote value replaces the local one.
+    RemoteWins,
+    /// The node keeps its local value.
+    LocalWins,
+}
+
+/// Encodes an edit as a compact delta to send to other peers: the operation, the position, old
+/// and new height of every changed node, the old and new layers of every node whose terrain type,
+/// hole mark or bridge deck changed and the old and new feature of every changed connection.
+/// Numbers are stored as variable length integers, so small heights take a single byte.
+/// `position` converts positions to two numbers. Nodes are ordered by position, so equal edits
+/// give equal deltas.
+pub fn encode_delta<T: Eq + Hash + Copy>(
+    edit: &Edit<T>,
+    position: impl Fn(T) -> (i32, i32),
+) -> Vec<u8> {
+    let mut changes: Vec<((i32, i32), i32, i32)> = edit
+        .changes
+        .iter()
+        .map(|(node, old_height, new_height)| (position(*node), *old_height, *new_height))
+        .collect();
+    changes.sort_unstable();
+    let mut layers: Vec<((i32, i32), Layers, Layers)> = edit
+        .layers
+        .iter()
+        .map(|(node, old_layers, new_layers)| (position(*node), *old_layers, *new_layers))
+        .collect();
+    layers.sort_unstable_by_key(|(position, _, _)| *position);
+    let mut features = edit
+        .features
+        .iter()
+        .map(|(first, second, old, new)| (position(*first), position(*second), *old, *new))
+        .collect::<Vec<_>>();
+    features.sort_unstable();
+
+    let mut bytes = vec![VERSION];
+    write_unsigned(&mut bytes, edit.operation.len() as u64);
+    bytes.extend_from_slice(edit.operation.as_bytes());
+    write_unsigned(&mut bytes, changes.len() as u64);
+    for ((x, y), old_height, new_height) in changes {
+        for value in &[x, y, old_height, new_height - old_height] {
+            write_signed(&mut bytes, *value);
+        }
+    }
+    write_unsigned(&mut bytes, layers.len() as u64);
+    for ((x, y), old_layers, new_layers) in layers {
+        write_signed(&mut bytes, x);
+        write_signed(&mut bytes, y);
+        write_layers(&mut bytes, old_layers);
+        write_layers(&mut bytes, new_layers);
+    }
+    write_unsigned(&mut bytes, features.len() as u64);
+    for ((x1, y1), (x2, y2), old, new) in features {
+        for value in &[x1, y1, x2, y2, old, new] {
+            write_signed(&mut bytes, *value);
+        }
+    }
+    bytes
+}
+
+/// Decodes a delta from `encode_delta`. `position` converts two numbers back to a position.
+/// Returns None if the delta is damaged or of an unknown version.
+pub fn decode_delta<T: Eq + Hash + Copy>(
+    bytes: &[u8],
+    position: impl Fn(i32, i32) -> T,
+) -> Option<Edit<T>> {
+    let mut offset = 0;
+    if *bytes.first()? != VERSION {
+        return None;
+    }
+    offset += 1;
+    let length = read_unsigned(bytes, &mut offset)? as usize;
+    let operation = bytes.get(offset..offset.checked_add(length)?)?;
+    let operation = String::from_utf8(operation.to_vec()).ok()?;
+    offset += length;
+
+    let count = read_unsigned(bytes, &mut offset)?;
+    let mut changes = Vec::new();
+    for _ in 0..count {
+        let x = read_signed(bytes, &mut offset)?;
+        let y = read_signed(bytes, &mut offset)?;
+        let old_height = read_signed(bytes, &mut offset)?;
+        let new_height = old_height.checked_add(read_signed(bytes, &mut offset)?)?;
+        changes.push((position(x, y), old_height, new_height));
+    }
+    let count = read_unsigned(bytes, &mut offset)?;
+    let mut layers = Vec::new();
+    for _ in 0..count {
+        let x = read_signed(bytes, &mut offset)?;
+        let y = read_signed(bytes, &mut offset)?;
+        let old_layers = read_layers(bytes, &mut offset)?;
+        let new_layers = read_layers(bytes, &mut offset)?;
+        layers.push((position(x, y), old_layers, new_layers));
+    }
+    let count = read_unsigned(bytes, &mut offset)?;
+    let mut features = Vec::new();
+    for _ in 0..count {
+        let first = position(
+            read_signed(bytes, &mut offset)?,
+            read_signed(bytes, &mut offset)?,
+        );
+        let second = position(
+            read_signed(bytes, &mut offset)?,
+            read_signed(bytes, &mut offset)?,
+        );
+        let old = read_signed(bytes, &mut offset)?;
+        let new = read_signed(bytes, &mut offset)?;
+        features.push((first, second, old, new));
+    }
+    if offset != bytes.len() {
+        return None;
+    }
+    Some(Edit {
+        operation,
+        changes,
+        layers,
+        features,
+    })
+}
+
+/// Returns the part of a remote edit that applies locally and the nodes that conflict with local
+/// edits. The old values of the returned edit are the local ones, so undoing it restores them.
+/// Nodes that do not exist here are skipped. A conflicting connection is reported by its first
+/// node.
+pub fn resolve<T: Eq + Hash + Copy>(
+    edit: &Edit<T>,
+    terrain: &Terrain<T>,
+    rule: Conflict,
+) -> (Edit<T>, Vec<T>) {
+    let mut resolved = Edit {
+        operation: edit.operation.clone(),
+        changes: Vec::new(),
+        layers: Vec::new(),
+        features: Vec::new(),
+    };
+    let mut conflicts = Vec::new();
+    let mut applies = |node: T, conflict: bool| {
+        if conflict {
+            conflicts.push(node);
+        }
+        !conflict || rule == Conflict::RemoteWins
+    };
+    for (node, old_height, new_height) in &edit.changes {
+        if let Some(local_height) = terrain.get_height_of_node(*node) {
+            let conflict = local_height != *old_height && local_height != *new_height;
+            if applies(*node, conflict) {
+                resolved.changes.push((*node, local_height, *new_height));
+            }
+        }
+    }
+    for (node, old_layers, new_layers) in &edit.layers {
+        if let Some(local_layers) = terrain.get_layers(*node) {
+            let conflict = local_layers != *old_layers && local_layers != *new_layers;
+            if applies(*node, conflict) {
+                resolved.layers.push((*node, local_layers, *new_layers));
+            }
+        }
+    }
+    for (first, second, old, new) in &edit.features {
+        let local = terrain.get_edge_feature(*first, *second);
+        if applies(*first, local != *old && local != *new) {
+            resolved.features.push((*first, *second, local, *new));
+        }
+    }
+    (resolved, conflicts)
+}
+
+fn write_layers(bytes: &mut Vec<u8>, layers: Layers) {
+    write_signed(bytes, layers.terrain_type);
+    write_unsigned(bytes, layers.hole as u64);
+    match layers.deck_height {
+        None => write_unsigned(bytes, 0),
+        Some(deck_height) => {
+            write_unsigned(bytes, 1);
+            write_signed(bytes, deck_height);
+        }
+    }
+}
+
+fn read_layers(bytes: &[u8], offset: &mut usize) -> Option<Layers> {
+    let terrain_type = read_signed(bytes, offset)?;
+    let hole = match read_unsigned(bytes, offset)? {
+        0 => false,
+        1 => true,
+        _ => return None,
+    };
+    let deck_height = match read_unsigned(bytes, offset)? {
+        0 => None,
+        1 => Some(read_signed(bytes, offset)?),
+        _ => return None,
+    };
+    Some(Layers {
+        terrain_type,
+        hole,
+        deck_height,
+    })
+}
+
+pub(crate) fn write_unsigned(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Writes small negative numbers as small unsigned ones (zigzag encoding).
+pub(crate) fn write_signed(bytes: &mut Vec<u8>, value: i32) {
+    write_unsigned(bytes, ((value << 1) ^ (value >> 31)) as u32 as u64);
+}
+
+pub(crate) fn read_unsigned(bytes: &[u8], offset: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes.get(*offset)?;
+        *offset += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+pub(crate) fn read_signed(bytes: &[u8], offset: &mut usize) -> Option<i32> {
+    let value = read_unsigned(bytes, offset)?;
+    if value > u32::MAX as u64 {
+        return None;
+    }
+    let value = value as u32;
+    Some((value >> 1) as i32 ^ -((value & 1) as i32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit() -> Edit<i32> {
+        Edit {
+            operation: "raise".to_string(),
+            changes: vec![(3, 1, 2), (-7, -100_000, 5), (1, 0, -1)],
+            layers: Vec::new(),
+            features: Vec::new(),
+        }
+    }
+
+    fn layers(terrain_type: i32) -> Layers {
+        Layers {
+            terrain_type,
+            hole: false,
+            deck_height: None,
+        }
+    }
+
+    /// Returns a line of nodes 0 to 3, each connected to the next.
+    fn line() -> Terrain<i32> {
+        let mut terrain = Terrain::new(1);
+        for node in 0..3 {
+            terrain.add_connected_nodes(node, node + 1);
+        }
+        terrain.take_changes();
+        terrain
+    }
+
+    #[test]
+    fn decode_delta_returns_encoded_edit() {
+        let delta = encode_delta(&edit(), |node| (node, 0));
+
+        let decoded = decode_delta(&delta, |x, _| x).unwrap();
+
+        assert_eq!("raise", decoded.operation);
+        assert_eq!(
+            vec![(-7, -100_000, 5), (1, 0, -1), (3, 1, 2)],
+            decoded.changes
+        );
+    }
+
+    #[test]
+    fn decode_delta_returns_encoded_layers_and_features() {
+        let bridge = Layers {
+            hole: true,
+            deck_height: Some(-3),
+            ..layers(2)
+        };
+        let edit = Edit {
+            operation: "paint".to_string(),
+            changes: Vec::new(),
+            layers: vec![(5, layers(0), bridge), (-1, bridge, layers(7))],
+            features: vec![(2, 1, 0, 4), (0, 1, 3, 0)],
+        };
+        let delta = encode_delta(&edit, |node| (node, -node));
+
+        let decoded = decode_delta(&delta, |x, _| x).unwrap();
+
+        assert_eq!(
+            vec![(-1, bridge, layers(7)), (5, layers(0), bridge)],
+            decoded.layers
+        );
+        assert_eq!(vec![(0, 1, 3, 0), (2, 1, 0, 4)], decoded.features);
+    }
+
+    #[test]
+    fn encode_delta_is_compact() {
+        let edit = Edit {
+            operation: "raise".to_string(),
+            changes: vec![(3, 1, 2)],
+            layers: Vec::new(),
+            features: Vec::new(),
+        };
+
+        // Version, operation with its length, count and four single byte numbers, and the counts
+        // of layers and features.
+        assert_eq!(
+            1 + 6 + 1 + 4 + 2,
+            encode_delta(&edit, |node| (node, 0)).len()
+        );
+    }
+
+    #[test]
+    fn decode_delta_rejects_damaged_deltas() {
+        let delta = encode_delta(&edit(), |node| (node, 0));
+
+        assert_eq!(None, decode_delta(&delta[..delta.len() - 1], |x, _| x));
+        assert_eq!(
+            None,
+            decode_delta(&[delta.clone(), vec![0]].concat(), |x, _| x)
+        );
+        assert_eq!(None, decode_delta(&[1, 0, 0, 0, 0], |x, _: i32| x));
+    }
+
+    #[test]
+    fn resolve_applies_conflict_rule() {
+        let edit = Edit {
+            operation: "raise".to_string(),
+            changes: vec![(0, 0, 1), (1, 0, 1), (2, 0, 1), (4, 0, 1)],
+            layers: vec![(0, layers(0), layers(1)), (3, layers(0), layers(1))],
+            features: vec![(0, 1, 0, 2), (2, 3, 0, 2)],
+        };
+        // Node 1 was changed locally, node 2 already has the new height and 4 does not exist.
+        // Node 3 got another terrain type and the connection from 2 to 3 another feature.
+        let mut terrain = line();
+        terrain.set_height(1, -1);
+        terrain.set_height(2, 1);
+        terrain.set_terrain_type(3, 5);
+        terrain.set_edge_feature(2, 3, 6);
+
+        let (resolved, conflicts) = resolve(&edit, &terrain, Conflict::LocalWins);
+        assert_eq!(vec![(0, 0, 1), (2, 1, 1)], resolved.changes);
+        assert_eq!(vec![(0, layers(0), layers(1))], resolved.layers);
+        assert_eq!(vec![(0, 1, 0, 2)], resolved.features);
+        assert_eq!(vec![1, 3, 2], conflicts);
+
+        let (resolved, conflicts) = resolve(&edit, &terrain, Conflict::RemoteWins);
+        assert_eq!(vec![(0, 0, 1), (1, -1, 1), (2, 1, 1)], resolved.changes);
+        assert_eq!(
+            vec![(3, layers(5), layers(1))],
+            resolved.layers[1..].to_vec()
+        );
+        assert_eq!(vec![(2, 3, 6, 2)], resolved.features[1..].to_vec());
+        assert_eq!(vec![1, 3, 2], conflicts);
+    }
+
+    #[test]
+    fn local_wins_keeps_conflicting_node_next_to_changed_remote_node() {
+        let edit = Edit {
+            operation: "raise".to_string(),
+            changes: vec![(1, 0, 1), (2, 0, 3)],
+            layers: Vec::new(),
+            features: Vec::new(),
+        };
+        // Node 1 was lowered locally, so it is more than a step below the new height of node 2.
+        let mut terrain = line();
+        terrain.set_height(1, -1);
+
+        let (resolved, conflicts) = resolve(&edit, &terrain, Conflict::LocalWins);
+        resolved.apply(&mut terrain);
+
+        assert_eq!(vec![1], conflicts);
+        assert_eq!(Some(0), terrain.get_height_of_node(0));
+        assert_eq!(Some(-1), terrain.get_height_of_node(1));
+        assert_eq!(Some(3), terrain.get_height_of_node(2));
+        assert_eq!(Some(0), terrain.get_height_of_node(3));
+    }
+}
@@ -0,0 +1,664 @@
+use crate::gzip;
+use crate::migration::Migrations;
+use crate::terrain::Terrain;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Bytes every save starts with.
+pub const MAGIC: [u8; 4] = *b"HEXT";
+
+/// Version of the format `write` creates. Readers skip chunks they do not know, so adding chunks
+/// keeps the version. It only changes when the layout of the header or of existing chunks
+/// changes, and saves of newer versions are not read. Version 2 added the header flags, version 3
+/// a checksum of every chunk.
+pub const VERSION: u16 = 3;
+
+/// Header flag of saves whose chunks are compressed with gzip.
+pub const COMPRESSED: u16 = 1;
+
+/// Height step and height limits of the terrain.
+pub const SETTINGS: [u8; 4] = *b"SETS";
+/// Position and height of every node. Other chunks refer to nodes by their index in this chunk.
+pub const HEIGHTS: [u8; 4] = *b"HGHT";
+/// Pairs of connected nodes.
+pub const CONNECTIONS: [u8; 4] = *b"CONN";
+/// Terrain type of every node.
+pub const TYPES: [u8; 4] = *b"TYPE";
+/// Hole and lock flags of every node.
+pub const FLAGS: [u8; 4] = *b"FLAG";
+/// Nodes with a bridge deck and the height of the deck.
+pub const DECKS: [u8; 4] = *b"DECK";
+/// Connections with a feature and the feature.
+pub const EDGES: [u8; 4] = *b"EDGE";
+/// Nodes with metadata and the metadata, which is stored as it is given.
+pub const METADATA: [u8; 4] = *b"META";
+/// Id and version of every layer with migrations, see `Migrations`.
+pub const VERSIONS: [u8; 4] = *b"VERS";
+
+const HOLE: u8 = 1;
+const LOCKED: u8 = 2;
+
+/// A piece of a save, identified by four bytes.
+#[derive(Clone, Debug, Hash, PartialEq)]
+pub struct Chunk {
+    pub id: [u8; 4],
+    pub data: Vec<u8>,
+}
+
+/// Problems found while reading a save. `read_repaired` skips or fixes them, the other readers
+/// reject saves with any of them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    /// Chunks whose checksum does not match or whose data is damaged. They are skipped, or the
+    /// part that could be read is kept.
+    pub damaged_chunks: Vec<[u8; 4]>,
+    /// Whether the save ends in the middle of a chunk, which is skipped.
+    pub truncated: bool,
+    /// Connections and edge features of nodes that do not exist or are not connected.
+    pub dangling_connections: usize,
+    /// Heights outside of the height limits, which are clamped.
+    pub out_of_range_heights: usize,
+    /// Metadata and decks of nodes that do not exist.
+    pub orphan_metadata: usize,
+}
+
+impl Report {
+    /// Returns whether the save has no problems.
+    pub fn is_clean(&self) -> bool {
+        *self == Report::default()
+    }
+}
+
+/// A terrain read from a save, with the metadata of its nodes.
+pub struct Save<T: Eq + Hash + Copy, M = Vec<u8>> {
+    pub terrain: Terrain<T>,
+    pub metadata: Vec<(T, M)>,
+}
+
+/// Writes the header and the chunks. Every chunk is stored with its id and its length, so readers
+/// can skip it, and with the CRC-32 of its data, so damage is found when reading. With a
+/// `compression_level` from 1 (fastest) to 9 (smallest) the chunks are compressed with gzip, 0
+/// stores them uncompressed.
+pub fn write_chunks(chunks: &[Chunk], compression_level: u32) -> Vec<u8> {
+    let mut data = Vec::new();
+    for chunk in chunks {
+        data.extend_from_slice(&chunk.id);
+        data.extend_from_slice(&(chunk.data.len() as u32).to_le_bytes());
+        data.extend_from_slice(&gzip::crc32(&chunk.data).to_le_bytes());
+        data.extend_from_slice(&chunk.data);
+    }
+
+    let flags = if compression_level > 0 { COMPRESSED } else { 0 };
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&flags.to_le_bytes());
+    if flags & COMPRESSED != 0 {
+        bytes.extend(gzip::compress(&data, compression_level));
+    } else {
+        bytes.extend(data);
+    }
+    bytes
+}
+
+/// Returns the version and the chunks of a save. Returns None if the bytes are no save, are cut
+/// off, have a newer version or a chunk is damaged.
+pub fn read_chunks(bytes: &[u8]) -> Option<(u16, Vec<Chunk>)> {
+    let mut report = Report::default();
+    let chunks = parse_chunks(bytes, &mut report)?;
+    if report.is_clean() {
+        Some(chunks)
+    } else {
+        None
+    }
+}
+
+/// Returns the version and the chunks of a save, skipping damaged chunks and the rest of a save
+/// that is cut off and recording them in the report. Returns None if the header is damaged.
+fn parse_chunks(bytes: &[u8], report: &mut Report) -> Option<(u16, Vec<Chunk>)> {
+    let mut reader = Reader::new(bytes);
+    if reader.bytes(4)? != MAGIC {
+        return None;
+    }
+    let version = u16::from_le_bytes([reader.u8()?, reader.u8()?]);
+    if version > VERSION {
+        return None;
+    }
+    let flags = if version >= 2 {
+        u16::from_le_bytes([reader.u8()?, reader.u8()?])
+    } else {
+        0
+    };
+    let data;
+    if flags & COMPRESSED != 0 {
+        data = gzip::decompress(reader.bytes(bytes.len() - reader.offset)?)?;
+        reader = Reader::new(&data);
+    }
+
+    let mut chunks = Vec::new();
+    while !reader.is_finished() {
+        match read_chunk(&mut reader, version) {
+            None => {
+                report.truncated = true;
+                break;
+            }
+            Some((chunk, true)) => chunks.push(chunk),
+            Some((chunk, false)) => report.damaged_chunks.push(chunk.id),
+        }
+    }
+    Some((version, chunks))
+}
+
+/// Reads the next chunk and whether its checksum matches. Saves before version 3 have no
+/// checksums.
+fn read_chunk(reader: &mut Reader<'_>, version: u16) -> Option<(Chunk, bool)> {
+    let mut id = [0; 4];
+    id.copy_from_slice(reader.bytes(4)?);
+    let length = reader.u32()? as usize;
+    let checksum = if version >= 3 { reader.u32()? } else { 0 };
+    let data = reader.bytes(length)?.to_vec();
+    let valid = version < 3 || checksum == gzip::crc32(&data);
+    Some((Chunk { id, data }, valid))
+}
+
+/// Returns the chunks that store the terrain and the metadata of its nodes. `position` converts
+/// positions to two numbers. Nodes are stored ordered by position, so equal terrains give equal
+/// bytes.
+pub fn terrain_chunks<T: Eq + Hash + Copy>(
+    terrain: &Terrain<T>,
+    metadata: &[(T, Vec<u8>)],
+    position: impl Fn(T) -> (i32, i32),
+) -> Vec<Chunk> {
+    let mut nodes: Vec<(T, i32)> = terrain.heights().collect();
+    nodes.sort_unstable_by_key(|(node, _)| position(*node));
+    let indices: HashMap<T, u32> = nodes
+        .iter()
+        .enumerate()
+        .map(|(index, (node, _))| (*node, index as u32))
+        .collect();
+
+    let mut settings = Vec::new();
+    let (min_height, max_height) = terrain.height_limits();
+    for value in &[terrain.height_step(), min_height, max_height] {
+        settings.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let mut heights = Vec::new();
+    let mut types = Vec::new();
+    let mut flags = Vec::new();
+    let mut decks = Vec::new();
+    for (index, (node, height)) in nodes.iter().enumerate() {
+        let (x, y) = position(*node);
+        for value in &[x, y, *height] {
+            heights.extend_from_slice(&value.to_le_bytes());
+        }
+        let terrain_type = terrain.get_terrain_type(*node).unwrap_or(0);
+        types.extend_from_slice(&terrain_type.to_le_bytes());
+        let mut flag = 0;
+        if terrain.is_hole(*node) {
+            flag |= HOLE;
+        }
+        if terrain.is_locked(*node) {
+            flag |= LOCKED;
+        }
+        flags.push(flag);
+        if let Some(deck_height) = terrain.get_deck_height(*node) {
+            decks.extend_from_slice(&(index as u32).to_le_bytes());
+            decks.extend_from_slice(&deck_height.to_le_bytes());
+        }
+    }
+
+    let mut pairs: Vec<(u32, u32)> = terrain
+        .connections()
+        .into_iter()
+        .map(|(first, second)| (indices[&first], indices[&second]))
+        .collect();
+    pairs.sort_unstable();
+    let mut connections = Vec::new();
+    for (first, second) in pairs {
+        connections.extend_from_slice(&first.to_le_bytes());
+        connections.extend_from_slice(&second.to_le_bytes());
+    }
+
+    let mut features: Vec<(u32, u32, i32)> = terrain
+        .edge_features()
+        .map(|(first, second, feature)| (indices[&first], indices[&second], feature))
+        .filter(|(first, second, _)| first < second)
+        .collect();
+    features.sort_unstable();
+    let mut edges = Vec::new();
+    for (first, second, feature) in features {
+        edges.extend_from_slice(&first.to_le_bytes());
+        edges.extend_from_slice(&second.to_le_bytes());
+        edges.extend_from_slice(&feature.to_le_bytes());
+    }
+
+    let mut entries: Vec<(u32, &Vec<u8>)> = metadata
+        .iter()
+        .filter_map(|(node, data)| Some((*indices.get(node)?, data)))
+        .collect();
+    entries.sort_by_key(|(index, _)| *index);
+    let mut meta = Vec::new();
+    for (index, data) in entries {
+        meta.extend_from_slice(&index.to_le_bytes());
+        meta.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        meta.extend_from_slice(data);
+    }
+
+    vec![
+        Chunk {
+            id: SETTINGS,
+            data: settings,
+        },
+        Chunk {
+            id: HEIGHTS,
+            data: heights,
+        },
+        Chunk {
+            id: CONNECTIONS,
+            data: connections,
+        },
+        Chunk {
+            id: TYPES,
+            data: types,
+        },
+        Chunk {
+            id: FLAGS,
+            data: flags,
+        },
+        Chunk {
+            id: DECKS,
+            data: decks,
+        },
+        Chunk {
+            id: EDGES,
+            data: edges,
+        },
+        Chunk {
+            id: METADATA,
+            data: meta,
+        },
+    ]
+}
+
+/// Builds the terrain from its chunks and skips chunks it does not know. `position` converts two
+/// numbers back to a position. Returns None if the settings or the heights are missing, a chunk
+/// is damaged or the chunks do not fit together, see `Report`.
+pub fn terrain_from_chunks<T: Eq + Hash + Copy>(
+    chunks: &[Chunk],
+    position: impl Fn(i32, i32) -> T,
+) -> Option<Save<T>> {
+    let mut report = Report::default();
+    let save = repair_terrain(chunks, position, &mut report)?;
+    if report.is_clean() {
+        Some(save)
+    } else {
+        None
+    }
+}
+
+/// Builds the terrain from its chunks like `terrain_from_chunks`, but skips or fixes the parts
+/// that are damaged or do not fit together and records them in the report. Returns None if the
+/// settings or the heights are missing or damaged.
+fn repair_terrain<T: Eq + Hash + Copy>(
+    chunks: &[Chunk],
+    position: impl Fn(i32, i32) -> T,
+    report: &mut Report,
+) -> Option<Save<T>> {
+    let chunk = |id: [u8; 4]| chunks.iter().find(|chunk| chunk.id == id);
+
+    let mut settings = Reader::new(&chunk(SETTINGS)?.data);
+    let mut terrain = Terrain::new(settings.i32()?);
+    let (min_height, max_height) = (settings.i32()?, settings.i32()?);
+    terrain.set_height_limits(min_height, max_height);
+
+    let mut nodes = Vec::new();
+    let mut heights = Reader::new(&chunk(HEIGHTS)?.data);
+    while !heights.is_finished() {
+        let node = position(heights.i32()?, heights.i32()?);
+        let height = heights.i32()?;
+        if height < min_height || height > max_height {
+            report.out_of_range_heights += 1;
+        }
+        terrain.add_node(node);
+        terrain.set_height(node, height);
+        nodes.push(node);
+    }
+
+    let mut metadata = Vec::new();
+    for id in &[CONNECTIONS, TYPES, FLAGS, DECKS, EDGES, METADATA] {
+        if let Some(chunk) = chunk(*id) {
+            let reader = Reader::new(&chunk.data);
+            if read_node_chunk(
+                chunk.id,
+                reader,
+                &nodes,
+                &mut terrain,
+                &mut metadata,
+                report,
+            )
+            .is_none()
+            {
+                report.damaged_chunks.push(chunk.id);
+            }
+        }
+    }
+    Some(Save { terrain, metadata })
+}
+
+/// Reads a chunk that refers to the nodes of the heights chunk by their index into the terrain.
+/// Entries of nodes that do not exist are skipped and recorded in the report. Returns None if the
+/// chunk is cut off, after reading what it could.
+fn read_node_chunk<T: Eq + Hash + Copy>(
+    id: [u8; 4],
+    mut reader: Reader<'_>,
+    nodes: &[T],
+    terrain: &mut Terrain<T>,
+    metadata: &mut Vec<(T, Vec<u8>)>,
+    report: &mut Report,
+) -> Option<()> {
+    let node = |index: u32| nodes.get(index as usize).copied();
+    match id {
+        CONNECTIONS => {
+            while !reader.is_finished() {
+                match (node(reader.u32()?), node(reader.u32()?)) {
+                    (Some(first), Some(second)) => terrain.add_connected_nodes(first, second),
+                    _ => report.dangling_connections += 1,
+                }
+            }
+        }
+        TYPES => {
+            for node in nodes {
+                terrain.set_terrain_type(*node, reader.i32()?);
+            }
+        }
+        FLAGS => {
+            for node in nodes {
+                let flag = reader.u8()?;
+                terrain.set_hole(*node, flag & HOLE != 0);
+                terrain.set_locked(*node, flag & LOCKED != 0);
+            }
+        }
+        DECKS => {
+            while !reader.is_finished() {
+                let (index, deck_height) = (reader.u32()?, reader.i32()?);
+                match node(index) {
+                    Some(node) => {
+                        terrain.set_deck_height(node, Some(deck_height));
+                    }
+                    None => report.orphan_metadata += 1,
+                }
+            }
+        }
+        EDGES => {
+            while !reader.is_finished() {
+                let (first, second) = (node(reader.u32()?), node(reader.u32()?));
+                let feature = reader.i32()?;
+                let set = match (first, second) {
+                    (Some(first), Some(second)) => terrain.set_edge_feature(first, second, feature),
+                    _ => false,
+                };
+                if !set {
+                    report.dangling_connections += 1;
+                }
+            }
+        }
+        METADATA => {
+            while !reader.is_finished() {
+                let index = reader.u32()?;
+                let length = reader.u32()? as usize;
+                let data = reader.bytes(length)?.to_vec();
+                match node(index) {
+                    Some(node) => metadata.push((node, data)),
+                    None => report.orphan_metadata += 1,
+                }
+            }
+        }
+        _ => {}
+    }
+    Some(())
+}
+
+/// Saves the terrain and the metadata of its nodes, see `terrain_chunks` and `write_chunks`.
+pub fn write<T: Eq + Hash + Copy>(
+    terrain: &Terrain<T>,
+    metadata: &[(T, Vec<u8>)],
+    position: impl Fn(T) -> (i32, i32),
+    compression_level: u32,
+) -> Vec<u8> {
+    write_chunks(
+        &terrain_chunks(terrain, metadata, position),
+        compression_level,
+    )
+}
+
+/// Reads a terrain saved with `write`, upgraded by the migrations of the format, see
+/// `terrain_from_chunks`.
+pub fn read<T: Eq + Hash + Copy>(
+    bytes: &[u8],
+    position: impl Fn(i32, i32) -> T,
+) -> Option<Save<T>> {
+    read_migrated(bytes, position, &Migrations::default())
+}
+
+/// Reads a terrain saved with `write` and upgrades older saves with the migrations first.
+pub fn read_migrated<T: Eq + Hash + Copy>(
+    bytes: &[u8],
+    position: impl Fn(i32, i32) -> T,
+    migrations: &Migrations,
+) -> Option<Save<T>> {
+    let (version, mut chunks) = read_chunks(bytes)?;
+    migrations.migrate(version, &mut chunks)?;
+    terrain_from_chunks(&chunks, position)
+}
+
+/// Reads a terrain saved with `write` like `read_migrated`, but skips or fixes damaged chunks and
+/// parts of the terrain that do not fit together instead of rejecting the save, and returns what
+/// was found. Returns None if the header, the settings or the heights are damaged.
+pub fn read_repaired<T: Eq + Hash + Copy>(
+    bytes: &[u8],
+    position: impl Fn(i32, i32) -> T,
+    migrations: &Migrations,
+) -> Option<(Save<T>, Report)> {
+    let mut report = Report::default();
+    let (version, mut chunks) = parse_chunks(bytes, &mut report)?;
+    migrations.migrate(version, &mut chunks)?;
+    let save = repair_terrain(&chunks, position, &mut report)?;
+    Some((save, report))
+}
+
+/// Reads little endian numbers from bytes. Every read returns None once the bytes run out.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader { bytes, offset: 0 }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.offset >= self.bytes.len()
+    }
+
+    fn bytes(&mut self, length: usize) -> Option<&'a [u8]> {
+        let end = self.offset.checked_add(length)?;
+        let bytes = self.bytes.get(self.offset..end)?;
+        self.offset = end;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.bytes(4)?);
+        Some(u32::from_le_bytes(bytes))
+    }
+
+    fn i32(&mut self) -> Option<i32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.bytes(4)?);
+        Some(i32::from_le_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(node: i32) -> (i32, i32) {
+        (node, 0)
+    }
+
+    fn node(x: i32, _: i32) -> i32 {
+        x
+    }
+
+    fn terrain() -> Terrain<i32> {
+        let mut terrain = Terrain::new(1);
+        terrain.set_height_limits(-5, 5);
+        terrain.add_connected_nodes(0, 1);
+        terrain.add_connected_nodes(1, 2);
+        terrain.set_heights(&[(2, 2)]);
+        terrain.set_terrain_type(1, 3);
+        terrain.set_hole(0, true);
+        terrain.set_locked(2, true);
+        terrain.set_deck_height(1, Some(4));
+        terrain.set_edge_feature(1, 2, 7);
+        terrain
+    }
+
+    #[test]
+    fn read_returns_written_terrain() {
+        let bytes = write(&terrain(), &[(1, vec![1, 2, 3])], position, 0);
+
+        let save = read(&bytes, node).unwrap();
+
+        let mut heights: Vec<(i32, i32)> = save.terrain.heights().collect();
+        heights.sort_unstable();
+        assert_eq!(vec![(0, 0), (1, 1), (2, 2)], heights);
+        assert_eq!(vec![(0, 1), (1, 2)], save.terrain.connections());
+        assert_eq!((-5, 5), save.terrain.height_limits());
+        assert_eq!(Some(3), save.terrain.get_terrain_type(1));
+        assert!(save.terrain.is_hole(0));
+        assert!(save.terrain.is_locked(2));
+        assert_eq!(Some(4), save.terrain.get_deck_height(1));
+        assert_eq!(7, save.terrain.get_edge_feature(2, 1));
+        assert_eq!(vec![(1, vec![1, 2, 3])], save.metadata);
+        assert_eq!(bytes, write(&save.terrain, &save.metadata, position, 0));
+    }
+
+    #[test]
+    fn read_skips_unknown_chunks() {
+        let mut chunks = terrain_chunks(&terrain(), &[], position);
+        chunks.insert(
+            1,
+            Chunk {
+                id: *b"NEW!",
+                data: vec![9; 5],
+            },
+        );
+
+        let save = read(&write_chunks(&chunks, 0), node).unwrap();
+
+        assert_eq!(3, save.terrain.heights().count());
+    }
+
+    #[test]
+    fn read_rejects_damaged_and_newer_saves() {
+        let bytes = write(&terrain(), &[], position, 0);
+        let mut newer = bytes.clone();
+        newer[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+
+        assert!(read(&bytes[..bytes.len() - 1], node).is_none());
+        assert!(read(&bytes[1..], node).is_none());
+        assert!(read(&newer, node).is_none());
+    }
+
+    #[test]
+    fn read_repaired_skips_damaged_chunks() {
+        let mut bytes = write(&terrain(), &[], position, 0);
+        let types = bytes.windows(4).position(|id| id == TYPES).unwrap();
+        bytes[types + 12] ^= 1;
+
+        let (save, report) = read_repaired(&bytes, node, &Migrations::default()).unwrap();
+
+        assert!(read(&bytes, node).is_none());
+        assert_eq!(vec![TYPES], report.damaged_chunks);
+        assert_eq!(Some(0), save.terrain.get_terrain_type(1));
+        assert_eq!(Some(4), save.terrain.get_deck_height(1));
+        assert!(
+            read_repaired(&bytes[..bytes.len() - 1], node, &Migrations::default())
+                .unwrap()
+                .1
+                .truncated
+        );
+    }
+
+    #[test]
+    fn read_repaired_drops_entries_of_missing_nodes() {
+        let mut chunks = terrain_chunks(&terrain(), &[(1, vec![1])], position);
+        fn data(chunks: &mut [Chunk], id: [u8; 4]) -> &mut Vec<u8> {
+            &mut chunks.iter_mut().find(|chunk| chunk.id == id).unwrap().data
+        }
+        for value in &[0u32, 9, 9, 1] {
+            data(&mut chunks, CONNECTIONS).extend_from_slice(&value.to_le_bytes());
+        }
+        data(&mut chunks, HEIGHTS)[8..12].copy_from_slice(&9i32.to_le_bytes());
+        let metadata = data(&mut chunks, METADATA);
+        metadata.extend_from_slice(&9u32.to_le_bytes());
+        metadata.extend_from_slice(&0u32.to_le_bytes());
+        let bytes = write_chunks(&chunks, 0);
+
+        let (save, report) = read_repaired(&bytes, node, &Migrations::default()).unwrap();
+
+        assert!(read(&bytes, node).is_none());
+        assert_eq!(
+            Report {
+                dangling_connections: 2,
+                out_of_range_heights: 1,
+                orphan_metadata: 1,
+                ..Report::default()
+            },
+            report
+        );
+        assert_eq!(Some(5), save.terrain.get_height_of_node(0));
+        assert_eq!(vec![(0, 1), (1, 2)], save.terrain.connections());
+        assert_eq!(vec![(1, vec![1])], save.metadata);
+    }
+
+    #[test]
+    fn read_returns_compressed_terrain() {
+        let metadata = vec![(1, vec![5; 1000])];
+        let bytes = write(&terrain(), &metadata, position, 0);
+        let compressed = write(&terrain(), &metadata, position, 6);
+
+        let save = read(&compressed, node).unwrap();
+
+        assert!(compressed.len() < bytes.len());
+        assert_eq!(bytes, write(&save.terrain, &save.metadata, position, 0));
+    }
+
+    #[test]
+    fn read_chunks_reads_saves_without_flags() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(b"NEW!");
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.push(9);
+
+        let (version, chunks) = read_chunks(&bytes).unwrap();
+
+        assert_eq!(1, version);
+        assert_eq!(
+            vec![Chunk {
+                id: *b"NEW!",
+                data: vec![9]
+            }],
+            chunks
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use crate::random::Random;
+use std::collections::HashSet;
+use std::hash::Hash;
+
+/// Picks nodes at random. Nodes are visited in random order, and every node is taken with its
+/// probability, unless it is closer than `min_spacing` to a node that was taken before or to one
+/// of the `taken` nodes. The result only depends on the seed and the order of `nodes`.
+pub fn scatter<T: Copy>(
+    nodes: &[(T, f32)],
+    taken: &[T],
+    min_spacing: f32,
+    distance: impl Fn(T, T) -> f32,
+    seed: u64,
+) -> Vec<T> {
+    let mut order = nodes.to_vec();
+    let mut random = Random::new(seed);
+    for index in (1..order.len()).rev() {
+        let other = random.range(0, index as i32) as usize;
+        order.swap(index, other);
+    }
+
+    let mut placed: Vec<T> = Vec::new();
+    for (node, probability) in order {
+        if random.next_f32() >= probability {
+            continue;
+        }
+        let too_close = taken
+            .iter()
+            .chain(placed.iter())
+            .any(|other| distance(node, *other) < min_spacing);
+        if !too_close {
+            placed.push(node);
+        }
+    }
+    placed
+}
+
+/// Picks nodes that are at least `min_spacing` apart and cover the nodes evenly, without clumps
+/// (blue noise), with Bridson's algorithm. `ring` returns the candidates around a node, usually
+/// the nodes between `min_spacing` and twice that far away. Up to `attempts` random candidates are
+/// tried around every picked node, and when no more fit, the remaining nodes are checked in
+/// random order, so every node ends up closer than `min_spacing` to a picked one. Only the given
+/// nodes are picked. The result only depends on the seed and the order of `nodes`.
+pub fn poisson_disk<T: Eq + Hash + Copy>(
+    nodes: &[T],
+    min_spacing: f32,
+    distance: impl Fn(T, T) -> f32,
+    ring: impl Fn(T) -> Vec<T>,
+    attempts: u32,
+    seed: u64,
+) -> Vec<T> {
+    let allowed: HashSet<T> = nodes.iter().copied().collect();
+    let mut order = nodes.to_vec();
+    let mut random = Random::new(seed);
+    for index in (1..order.len()).rev() {
+        let other = random.range(0, index as i32) as usize;
+        order.swap(index, other);
+    }
+
+    let mut picked: Vec<T> = Vec::new();
+    let fits = |node: T, picked: &[T]| {
+        picked
+            .iter()
+            .all(|other| distance(node, *other) >= min_spacing)
+    };
+    for start in order {
+        if !fits(start, &picked) {
+            continue;
+        }
+        picked.push(start);
+        let mut active = vec![start];
+        while !active.is_empty() {
+            let index = random.range(0, active.len() as i32 - 1) as usize;
+            let candidates: Vec<T> = ring(active[index])
+                .into_iter()
+                .filter(|candidate| allowed.contains(candidate))
+                .collect();
+            let mut found = None;
+            for _ in 0..attempts {
+                if candidates.is_empty() {
+                    break;
+                }
+                let candidate = candidates[random.range(0, candidates.len() as i32 - 1) as usize];
+                if fits(candidate, &picked) {
+                    found = Some(candidate);
+                    break;
+                }
+            }
+            match found {
+                Some(node) => {
+                    picked.push(node);
+                    active.push(node);
+                }
+                None => {
+                    active.swap_remove(index);
+                }
+            }
+        }
+    }
+    picked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distance(first: i32, second: i32) -> f32 {
+        (first - second).abs() as f32
+    }
+
+    #[test]
+    fn scatter_respects_probabilities() {
+        let nodes: Vec<(i32, f32)> = (0..20)
+            .map(|node| (node, if node < 10 { 1.0 } else { 0.0 }))
+            .collect();
+
+        let mut placed = scatter(&nodes, &[], 0.0, distance, 5);
+        placed.sort_unstable();
+
+        assert_eq!((0..10).collect::<Vec<_>>(), placed);
+    }
+
+    #[test]
+    fn scatter_keeps_minimum_spacing() {
+        let nodes: Vec<(i32, f32)> = (0..100).map(|node| (node, 1.0)).collect();
+
+        let placed = scatter(&nodes, &[50], 3.0, distance, 5);
+
+        assert!(!placed.is_empty());
+        for (index, node) in placed.iter().enumerate() {
+            assert!(distance(*node, 50) >= 3.0);
+            for other in &placed[index + 1..] {
+                assert!(distance(*node, *other) >= 3.0);
+            }
+        }
+    }
+
+    #[test]
+    fn scatter_is_deterministic() {
+        let nodes: Vec<(i32, f32)> = (0..100).map(|node| (node, 0.3)).collect();
+
+        assert_eq!(
+            scatter(&nodes, &[], 2.0, distance, 11),
+            scatter(&nodes, &[], 2.0, distance, 11)
+        );
+    }
+
+    fn ring(node: i32) -> Vec<i32> {
+        (3..6)
+            .flat_map(|offset| vec![node - offset, node + offset])
+            .collect()
+    }
+
+    #[test]
+    fn poisson_disk_keeps_minimum_spacing_and_covers_nodes() {
+        let nodes: Vec<i32> = (0..100).filter(|node| node % 20 < 15).collect();
+
+        let picked = poisson_disk(&nodes, 3.0, distance, ring, 10, 7);
+
+        for (index, node) in picked.iter().enumerate() {
+            assert!(nodes.contains(node));
+            for other in &picked[index + 1..] {
+                assert!(distance(*node, *other) >= 3.0);
+            }
+        }
+        for node in &nodes {
+            assert!(picked.iter().any(|other| distance(*node, *other) < 3.0));
+        }
+    }
+
+    #[test]
+    fn poisson_disk_is_deterministic() {
+        let nodes: Vec<i32> = (0..100).collect();
+
+        assert_eq!(
+            poisson_disk(&nodes, 3.0, distance, ring, 10, 7),
+            poisson_disk(&nodes, 3.0, distance, ring, 10, 7)
+        );
+    }
+}
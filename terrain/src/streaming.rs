@@ -0,0 +1,209 @@
+use crate::gzip;
+use crate::save;
+use crate::save::Chunk;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Name of the index file in the directory of a store.
+pub const INDEX_FILE: &str = "index.hexmap";
+
+/// Position and CRC-32 of every stored map chunk, the only chunk of the index file.
+pub const INDEX: [u8; 4] = *b"INDX";
+
+/// Stores a large map in a directory with one file per map chunk plus an index, so map chunks
+/// can be loaded on demand and only changed ones are written again. Every file is a save of
+/// `save::write_chunks`, with whatever chunks the map chunk is stored as.
+pub struct ChunkStore {
+    directory: PathBuf,
+    compression_level: u32,
+    index: HashMap<(i32, i32), u32>,
+}
+
+impl ChunkStore {
+    /// Opens the store in a directory, which is created when the first map chunk is written.
+    /// Files are compressed with `compression_level`, see `save::write_chunks`. Returns an error
+    /// if there is an index that cannot be read.
+    pub fn open(directory: impl Into<PathBuf>, compression_level: u32) -> io::Result<ChunkStore> {
+        let directory = directory.into();
+        let index = match fs::read(directory.join(INDEX_FILE)) {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(error) => return Err(error),
+            Ok(bytes) => read_index(&bytes).ok_or_else(|| damaged("index"))?,
+        };
+        Ok(ChunkStore {
+            directory,
+            compression_level,
+            index,
+        })
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Returns whether a map chunk is stored.
+    pub fn contains(&self, position: (i32, i32)) -> bool {
+        self.index.contains_key(&position)
+    }
+
+    /// Returns the positions of all stored map chunks, ordered by position.
+    pub fn positions(&self) -> Vec<(i32, i32)> {
+        let mut positions: Vec<(i32, i32)> = self.index.keys().copied().collect();
+        positions.sort_unstable();
+        positions
+    }
+
+    /// Reads the chunks of a map chunk. Returns None if it is not stored, and an error if its
+    /// file cannot be read or is damaged.
+    pub fn load(&self, position: (i32, i32)) -> io::Result<Option<Vec<Chunk>>> {
+        if !self.contains(position) {
+            return Ok(None);
+        }
+        let bytes = fs::read(chunk_path(&self.directory, position))?;
+        match save::read_chunks(&bytes) {
+            None => Err(damaged("map chunk")),
+            Some((_, chunks)) => Ok(Some(chunks)),
+        }
+    }
+
+    /// Writes the map chunks whose chunks changed since they were stored, and then the index if
+    /// any were written. Returns the number of map chunks written.
+    pub fn store(
+        &mut self,
+        map_chunks: impl IntoIterator<Item = ((i32, i32), Vec<Chunk>)>,
+    ) -> io::Result<usize> {
+        let mut written = 0;
+        for (position, chunks) in map_chunks {
+            let checksum = gzip::crc32(&save::write_chunks(&chunks, 0));
+            if self.index.get(&position) == Some(&checksum) {
+                continue;
+            }
+            if written == 0 {
+                fs::create_dir_all(&self.directory)?;
+            }
+            let bytes = save::write_chunks(&chunks, self.compression_level);
+            write_file(&chunk_path(&self.directory, position), &bytes)?;
+            self.index.insert(position, checksum);
+            written += 1;
+        }
+        if written > 0 {
+            self.write_index()?;
+        }
+        Ok(written)
+    }
+
+    /// Deletes a map chunk. Returns whether it was stored.
+    pub fn remove(&mut self, position: (i32, i32)) -> io::Result<bool> {
+        if self.index.remove(&position).is_none() {
+            return Ok(false);
+        }
+        self.write_index()?;
+        match fs::remove_file(chunk_path(&self.directory, position)) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+            _ => Ok(true),
+        }
+    }
+
+    fn write_index(&self) -> io::Result<()> {
+        let mut data = Vec::new();
+        for position in self.positions() {
+            data.extend_from_slice(&position.0.to_le_bytes());
+            data.extend_from_slice(&position.1.to_le_bytes());
+            data.extend_from_slice(&self.index[&position].to_le_bytes());
+        }
+        let bytes = save::write_chunks(&[Chunk { id: INDEX, data }], 0);
+        write_file(&self.directory.join(INDEX_FILE), &bytes)
+    }
+}
+
+/// Returns the path of the file of a map chunk.
+pub fn chunk_path(directory: &Path, position: (i32, i32)) -> PathBuf {
+    directory.join(format!("chunk_{}_{}.hexmap", position.0, position.1))
+}
+
+/// Writes a file through a temporary file, so a crash while writing leaves the previous file
+/// intact.
+fn write_file(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, bytes)?;
+    fs::rename(&temporary, path)
+}
+
+fn read_index(bytes: &[u8]) -> Option<HashMap<(i32, i32), u32>> {
+    let (_, chunks) = save::read_chunks(bytes)?;
+    let entries = &chunks.iter().find(|chunk| chunk.id == INDEX)?.data;
+    if entries.len() % 12 != 0 {
+        return None;
+    }
+    let value = |entry: &[u8], offset: usize| {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&entry[offset..offset + 4]);
+        bytes
+    };
+    Some(
+        entries
+            .chunks(12)
+            .map(|entry| {
+                (
+                    (
+                        i32::from_le_bytes(value(entry, 0)),
+                        i32::from_le_bytes(value(entry, 4)),
+                    ),
+                    u32::from_le_bytes(value(entry, 8)),
+                )
+            })
+            .collect(),
+    )
+}
+
+fn damaged(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("damaged {}", what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunks(value: u8) -> Vec<Chunk> {
+        vec![Chunk {
+            id: *b"TEST",
+            data: vec![value; 10],
+        }]
+    }
+
+    #[test]
+    fn store_writes_changed_map_chunks_only() {
+        let directory = std::env::temp_dir().join("hex_terrain_streaming_test");
+        let _ = fs::remove_dir_all(&directory);
+        let mut store = ChunkStore::open(&directory, 6).unwrap();
+
+        assert_eq!(
+            2,
+            store
+                .store(vec![((0, 0), chunks(1)), ((-1, 2), chunks(2))])
+                .unwrap()
+        );
+        assert_eq!(
+            1,
+            store
+                .store(vec![((0, 0), chunks(1)), ((-1, 2), chunks(3))])
+                .unwrap()
+        );
+
+        let reopened = ChunkStore::open(&directory, 6).unwrap();
+        assert_eq!(vec![(-1, 2), (0, 0)], reopened.positions());
+        assert_eq!(Some(chunks(3)), reopened.load((-1, 2)).unwrap());
+        assert_eq!(None, reopened.load((5, 5)).unwrap());
+
+        assert!(store.remove((0, 0)).unwrap());
+        assert!(!store.remove((0, 0)).unwrap());
+        assert!(!chunk_path(&directory, (0, 0)).exists());
+        assert_eq!(
+            vec![(-1, 2)],
+            ChunkStore::open(&directory, 6).unwrap().positions()
+        );
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
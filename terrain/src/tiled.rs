@@ -0,0 +1,383 @@
+use crate::gzip;
+use crate::json;
+use crate::json::Json;
+
+/// Bits of global tile IDs that flip or rotate the tile.
+const FLIP_FLAGS: u32 = 0xf000_0000;
+
+/// A hexagonal map of the Tiled map editor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TiledMap {
+    pub width: u32,
+    pub height: u32,
+    /// Whether every other column is shifted ("staggeraxis" x), otherwise every other row.
+    pub stagger_columns: bool,
+    /// Whether the odd columns or rows are shifted ("staggerindex" odd), otherwise the even ones.
+    pub stagger_odd: bool,
+    /// First global tile ID of every tileset, ascending.
+    pub first_gids: Vec<u32>,
+    /// Name and global tile IDs of every tile layer, row by row.
+    pub layers: Vec<(String, Vec<u32>)>,
+}
+
+impl TiledMap {
+    /// Returns the global tile IDs of the first tile layer with the name.
+    pub fn layer(&self, name: &str) -> Option<&[u32]> {
+        self.layers
+            .iter()
+            .find(|(layer, _)| layer == name)
+            .map(|(_, tiles)| tiles.as_slice())
+    }
+
+    /// Returns the ID of a tile within its tileset, None for empty tiles. Flipping and rotation
+    /// are ignored.
+    pub fn local_id(&self, gid: u32) -> Option<u32> {
+        let gid = gid & !FLIP_FLAGS;
+        if gid == 0 {
+            return None;
+        }
+        let first_gid = self.first_gids.iter().rev().find(|first| **first <= gid)?;
+        Some(gid - first_gid)
+    }
+
+    /// Returns the column, row and local tile ID of every tile of a layer that is not empty.
+    /// Returns None if there is no such layer.
+    pub fn tiles(&self, layer: &str) -> Option<Vec<(u32, u32, u32)>> {
+        let tiles = self.layer(layer)?;
+        Some(
+            tiles
+                .iter()
+                .enumerate()
+                .filter_map(|(index, gid)| {
+                    let index = index as u32;
+                    Some((index % self.width, index / self.width, self.local_id(*gid)?))
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Reads a hexagonal map from a TMX file. Tile layers can be stored as CSV, as base64 with or
+/// without zlib or gzip compression, or as XML. Returns None if the map is not hexagonal, is
+/// infinite or cannot be read.
+pub fn parse_tmx(text: &str) -> Option<TiledMap> {
+    let tags = tags(text)?;
+    let map_tag = tags.iter().find(|tag| tag.name == "map" && !tag.closing)?;
+    if map_tag.attribute("orientation")? != "hexagonal" {
+        return None;
+    }
+    let mut map = TiledMap {
+        width: map_tag.attribute("width")?.parse().ok()?,
+        height: map_tag.attribute("height")?.parse().ok()?,
+        stagger_columns: map_tag.attribute("staggeraxis")? == "x",
+        stagger_odd: map_tag.attribute("staggerindex")? == "odd",
+        first_gids: Vec::new(),
+        layers: Vec::new(),
+    };
+
+    let mut layer = None;
+    let mut tiles = Vec::new();
+    for tag in &tags {
+        match (tag.name, tag.closing) {
+            ("tileset", false) => map
+                .first_gids
+                .push(tag.attribute("firstgid")?.parse().ok()?),
+            ("layer", false) => {
+                layer = Some(tag.attribute("name").unwrap_or("").to_owned());
+                tiles.clear();
+            }
+            ("data", false) if layer.is_some() => {
+                let content = &text[tag.end..];
+                let content = &content[..content.find('<').unwrap_or(content.len())];
+                match tag.attribute("encoding") {
+                    None => {}
+                    Some("csv") => tiles = csv_tiles(content)?,
+                    Some("base64") => {
+                        let compression = tag.attribute("compression").unwrap_or("");
+                        tiles = base64_tiles(content, compression)?;
+                    }
+                    Some(_) => return None,
+                }
+            }
+            ("tile", false) if layer.is_some() => {
+                tiles.push(tag.attribute("gid").unwrap_or("0").parse().ok()?)
+            }
+            ("layer", true) => map.layers.push((layer.take()?, std::mem::take(&mut tiles))),
+            _ => {}
+        }
+    }
+    finish(map)
+}
+
+/// Reads a hexagonal map from a Tiled JSON file. Tile layers in groups are read as well. Returns
+/// None if the map is not hexagonal, is infinite or cannot be read.
+pub fn parse_json(text: &str) -> Option<TiledMap> {
+    let map = json::parse(text)?;
+    if string(map.get("orientation")?)? != "hexagonal" {
+        return None;
+    }
+    let mut first_gids = Vec::new();
+    for tileset in map.get("tilesets").and_then(Json::as_array).unwrap_or(&[]) {
+        first_gids.push(tileset.get("firstgid")?.as_i32()? as u32);
+    }
+    let mut layers = Vec::new();
+    json_layers(map.get("layers")?.as_array()?, &mut layers)?;
+    finish(TiledMap {
+        width: map.get("width")?.as_i32()? as u32,
+        height: map.get("height")?.as_i32()? as u32,
+        stagger_columns: string(map.get("staggeraxis")?)? == "x",
+        stagger_odd: string(map.get("staggerindex")?)? == "odd",
+        first_gids,
+        layers,
+    })
+}
+
+fn json_layers(layers: &[Json], result: &mut Vec<(String, Vec<u32>)>) -> Option<()> {
+    for layer in layers {
+        match string(layer.get("type")?)? {
+            "group" => json_layers(layer.get("layers")?.as_array()?, result)?,
+            "tilelayer" => {
+                let tiles = match layer.get("data")? {
+                    Json::String(data) => {
+                        let compression = layer.get("compression").and_then(string);
+                        base64_tiles(data, compression.unwrap_or(""))?
+                    }
+                    Json::Array(gids) => gids
+                        .iter()
+                        .map(|gid| Some(gid.as_f64().filter(|gid| *gid >= 0.0)? as u32))
+                        .collect::<Option<Vec<u32>>>()?,
+                    _ => return None,
+                };
+                result.push((string(layer.get("name")?)?.to_owned(), tiles));
+            }
+            _ => {}
+        }
+    }
+    Some(())
+}
+
+/// Checks that every layer covers the whole map.
+fn finish(mut map: TiledMap) -> Option<TiledMap> {
+    let count = map.width.checked_mul(map.height)? as usize;
+    if map.width == 0 || map.layers.iter().any(|(_, tiles)| tiles.len() != count) {
+        return None;
+    }
+    map.first_gids.sort_unstable();
+    Some(map)
+}
+
+fn string(value: &Json) -> Option<&str> {
+    match value {
+        Json::String(text) => Some(text),
+        _ => None,
+    }
+}
+
+fn csv_tiles(text: &str) -> Option<Vec<u32>> {
+    text.split(',').map(|gid| gid.trim().parse().ok()).collect()
+}
+
+fn base64_tiles(text: &str, compression: &str) -> Option<Vec<u32>> {
+    let bytes = base64(text)?;
+    let bytes = match compression {
+        "" => bytes,
+        "zlib" => gzip::decompress_zlib(&bytes)?,
+        "gzip" => gzip::decompress(&bytes)?,
+        _ => return None,
+    };
+    if bytes.len() % 4 != 0 {
+        return None;
+    }
+    Some(
+        bytes
+            .chunks(4)
+            .map(|gid| u32::from_le_bytes([gid[0], gid[1], gid[2], gid[3]]))
+            .collect(),
+    )
+}
+
+fn base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut buffer = 0u32;
+    let mut count = 0;
+    for character in text
+        .bytes()
+        .filter(|character| !character.is_ascii_whitespace())
+    {
+        let value = match character {
+            b'A'..=b'Z' => character - b'A',
+            b'a'..=b'z' => character - b'a' + 26,
+            b'0'..=b'9' => character - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            _ => return None,
+        };
+        buffer = buffer << 6 | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((buffer >> count) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// An XML start or end tag.
+struct Tag<'a> {
+    name: &'a str,
+    attributes: Vec<(&'a str, String)>,
+    closing: bool,
+    /// Offset of the first byte after the tag.
+    end: usize,
+}
+
+impl Tag<'_> {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Returns the tags of an XML document in order, skipping comments and declarations. This is
+/// enough for the files Tiled writes, not for XML in general.
+fn tags(text: &str) -> Option<Vec<Tag<'_>>> {
+    let mut tags = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = text[offset..].find('<') {
+        let start = offset + start;
+        if text[start..].starts_with("<!--") {
+            offset = start + text[start..].find("-->")? + 3;
+            continue;
+        }
+        let end = start + text[start..].find('>')? + 1;
+        offset = end;
+        let inner = &text[start + 1..end - 1];
+        if inner.starts_with('?') || inner.starts_with('!') {
+            continue;
+        }
+
+        let closing = inner.starts_with('/');
+        let inner = inner.trim_start_matches('/').trim_end_matches('/');
+        let name_end = inner.find(char::is_whitespace).unwrap_or(inner.len());
+        let mut attributes = Vec::new();
+        let mut rest = inner[name_end..].trim_start();
+        while !rest.is_empty() {
+            let equals = rest.find('=')?;
+            let value = rest[equals + 1..].trim_start();
+            let quote = value
+                .chars()
+                .next()
+                .filter(|quote| *quote == '"' || *quote == '\'')?;
+            let value_end = value[1..].find(quote)? + 1;
+            attributes.push((rest[..equals].trim(), unescape(&value[1..value_end])));
+            rest = value[value_end + 1..].trim_start();
+        }
+        tags.push(Tag {
+            name: &inner[..name_end],
+            attributes,
+            closing,
+            end,
+        });
+    }
+    Some(tags)
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_tmx_reads_layers_in_every_encoding() {
+        let text = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="hexagonal" width="3" height="2" tilewidth="32"
+     tileheight="28" hexsidelength="14" staggeraxis="x" staggerindex="odd">
+ <tileset firstgid="1" source="ground.tsx"/>
+ <!-- <layer name="ignored"> -->
+ <layer id="1" name="Types &amp; more" width="3" height="2">
+  <data encoding="csv">
+1,2,0,
+3,2147483649,4
+</data>
+ </layer>
+ <layer id="2" name="Elevation" width="3" height="2">
+  <data encoding="base64" compression="zlib">
+   eJxjZGBgYGKAAGYgZmRgaGAB0gADFACM
+  </data>
+ </layer>
+ <layer id="3" name="Xml" width="3" height="2">
+  <data>
+   <tile gid="1"/><tile gid="2"/><tile/><tile gid="3"/><tile gid="2147483649"/><tile gid="4"/>
+  </data>
+ </layer>
+</map>"#;
+
+        let map = parse_tmx(text).unwrap();
+
+        assert_eq!(
+            (3, 2, true, true),
+            (map.width, map.height, map.stagger_columns, map.stagger_odd)
+        );
+        let gids = vec![1, 2, 0, 3, 0x8000_0001, 4];
+        assert_eq!(Some(gids.as_slice()), map.layer("Types & more"));
+        assert_eq!(Some(gids.as_slice()), map.layer("Elevation"));
+        assert_eq!(Some(gids.as_slice()), map.layer("Xml"));
+        assert_eq!(None, map.layer("ignored"));
+    }
+
+    #[test]
+    fn parse_json_reads_layers_in_groups() {
+        let text = r#"{"orientation": "hexagonal", "width": 2, "height": 3,
+            "staggeraxis": "y", "staggerindex": "even",
+            "tilesets": [{"firstgid": 1}, {"firstgid": 10}],
+            "layers": [
+                {"type": "objectgroup", "name": "Units", "objects": []},
+                {"type": "group", "name": "Ground", "layers": [
+                    {"type": "tilelayer", "name": "Types", "data": [1, 2, 0, 3, 2147483649, 4]}
+                ]},
+                {"type": "tilelayer", "name": "Elevation", "encoding": "base64",
+                 "data": "AQAAAAIAAAAAAAAAAwAAAAEAAIAEAAAA"}
+            ]}"#;
+
+        let map = parse_json(text).unwrap();
+
+        assert_eq!((false, false), (map.stagger_columns, map.stagger_odd));
+        assert_eq!(vec![1, 10], map.first_gids);
+        let gids = vec![1, 2, 0, 3, 0x8000_0001, 4];
+        assert_eq!(Some(gids.as_slice()), map.layer("Types"));
+        assert_eq!(Some(gids.as_slice()), map.layer("Elevation"));
+        assert_eq!(None, parse_json(&text.replace("hexagonal", "orthogonal")));
+        assert_eq!(
+            None,
+            parse_json(&text.replace("\"height\": 3", "\"height\": 4"))
+        );
+    }
+
+    #[test]
+    fn tiles_returns_local_ids_of_tiles() {
+        let map = TiledMap {
+            width: 2,
+            height: 2,
+            stagger_columns: true,
+            stagger_odd: true,
+            first_gids: vec![1, 10],
+            layers: vec![("Types".to_string(), vec![1, 0, 12, 0x4000_0002])],
+        };
+
+        assert_eq!(
+            Some(vec![(0, 0, 0), (0, 1, 2), (1, 1, 1)]),
+            map.tiles("Types")
+        );
+        assert_eq!(None, map.tiles("Elevation"));
+    }
+}
@@ -0,0 +1,290 @@
+/// Returns the heights of a ramp from `from_height` to `to_height`. Every node is given with its
+/// distance along the ramp, which is clamped to `0..=length`. If the ramp would be steeper than
+/// `max_slope` it only rises (or falls) as far as the slope allows.
+pub fn ramp_heights<T: Copy>(
+    nodes: &[(T, f32)],
+    length: f32,
+    from_height: i32,
+    to_height: i32,
+    max_slope: f32,
+) -> Vec<(T, i32)> {
+    let difference = to_height - from_height;
+    let slope = if length > 0.0 {
+        (difference.abs() as f32 / length).min(max_slope)
+    } else {
+        0.0
+    };
+
+    nodes
+        .iter()
+        .map(|(node, distance)| {
+            let distance = distance.max(0.0).min(length);
+            let change = ((slope * distance + 0.0001).floor() as i32).min(difference.abs());
+            (*node, from_height + change * difference.signum())
+        })
+        .collect()
+}
+
+/// Returns the heights snapped to the nearest multiple of `step`.
+pub fn terrace_heights<T: Copy>(heights: &[(T, i32)], step: i32) -> Vec<(T, i32)> {
+    let step = step.max(1);
+    heights
+        .iter()
+        .map(|(node, height)| (*node, (height + step / 2).div_euclid(step) * step))
+        .collect()
+}
+
+/// Returns how strong a brush is at the given distance from its center, from 1 at the center to 0
+/// at the radius.
+pub fn falloff(distance: f32, radius: f32) -> f32 {
+    if radius <= 0.0 {
+        return if distance <= 0.0 { 1.0 } else { 0.0 };
+    }
+    let t = (1.0 - distance / radius).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Returns the heights with random jitter added. Every node is given with its height, the strength
+/// of the brush at the node and a random value between -1 and 1.
+pub fn jitter_heights<T: Copy>(nodes: &[(T, i32, f32, f32)], amplitude: f32) -> Vec<(T, i32)> {
+    nodes
+        .iter()
+        .map(|(node, height, strength, random)| {
+            (
+                *node,
+                height + (random * amplitude * strength).round() as i32,
+            )
+        })
+        .collect()
+}
+
+/// Returns the heights raised along a ridge. Every node is given with its height and its distance
+/// from the ridge line. Nodes on the line are raised by `height`, the raise fades out towards
+/// `width`.
+pub fn ridge_heights<T: Copy>(nodes: &[(T, i32, f32)], height: i32, width: f32) -> Vec<(T, i32)> {
+    nodes
+        .iter()
+        .map(|(node, node_height, distance)| {
+            let raise = (height as f32 * falloff(*distance, width)).round() as i32;
+            (*node, node_height + raise)
+        })
+        .collect()
+}
+
+/// Returns the heights moved towards the average height of their neighbours. Every node is given
+/// with its height, the strength of the brush at the node and the average height of its neighbours.
+/// A `strength` of 1 moves a node at the brush center all the way to the average.
+pub fn smooth_heights<T: Copy>(nodes: &[(T, i32, f32, f32)], strength: f32) -> Vec<(T, i32)> {
+    let strength = strength.clamp(0.0, 1.0);
+    nodes
+        .iter()
+        .map(|(node, height, brush_strength, average)| {
+            let change = (average - *height as f32) * strength * brush_strength;
+            (*node, height + change.round() as i32)
+        })
+        .collect()
+}
+
+/// Returns the lowest level above the height, None if there is none.
+pub fn level_above(levels: &[i32], height: i32) -> Option<i32> {
+    levels.iter().copied().filter(|level| *level > height).min()
+}
+
+/// Returns the highest level below the height, None if there is none.
+pub fn level_below(levels: &[i32], height: i32) -> Option<i32> {
+    levels.iter().copied().filter(|level| *level < height).max()
+}
+
+/// Returns the level closest to the height, the lower one if two levels are equally close. None
+/// if there are no levels.
+pub fn nearest_level(levels: &[i32], height: i32) -> Option<i32> {
+    levels
+        .iter()
+        .copied()
+        .min_by_key(|level| ((level - height).abs(), *level))
+}
+
+/// How the heights of a stamp are combined with the heights of the terrain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    /// The stamp heights are used, relative to the height at the target.
+    Replace,
+    /// The stamp heights are added to the terrain heights.
+    Add,
+    /// The higher of the stamp and terrain height is used.
+    Max,
+    /// The lower of the stamp and terrain height is used.
+    Min,
+}
+
+/// Returns the heights of a stamp blended with the terrain. Every node is given with its current
+/// height and the stamp height relative to `base`.
+pub fn blend_heights<T: Copy>(
+    nodes: &[(T, i32, i32)],
+    base: i32,
+    mode: BlendMode,
+) -> Vec<(T, i32)> {
+    nodes
+        .iter()
+        .map(|(node, height, stamp_height)| {
+            let blended = match mode {
+                BlendMode::Replace => base + stamp_height,
+                BlendMode::Add => height + stamp_height,
+                BlendMode::Max => (*height).max(base + stamp_height),
+                BlendMode::Min => (*height).min(base + stamp_height),
+            };
+            (*node, blended)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::Terrain;
+
+    #[test]
+    fn ramp_heights_interpolates_between_heights() {
+        let nodes = [(0, 0.0), (1, 1.0), (2, 2.0), (3, 3.0), (4, 4.0)];
+
+        let heights = ramp_heights(&nodes, 4.0, 2, -2, 1.0);
+
+        assert_eq!(vec![(0, 2), (1, 1), (2, 0), (3, -1), (4, -2)], heights);
+    }
+
+    #[test]
+    fn ramp_heights_clamps_distance_to_ramp() {
+        let nodes = [(0, -1.0), (1, 5.0)];
+
+        let heights = ramp_heights(&nodes, 4.0, 0, 4, 1.0);
+
+        assert_eq!(vec![(0, 0), (1, 4)], heights);
+    }
+
+    #[test]
+    fn ramp_heights_limits_slope() {
+        let nodes = [(0, 0.0), (1, 1.0), (2, 2.0)];
+
+        let heights = ramp_heights(&nodes, 2.0, 0, 10, 0.5);
+
+        assert_eq!(vec![(0, 0), (1, 0), (2, 1)], heights);
+    }
+
+    #[test]
+    fn terrace_heights_snaps_to_nearest_multiple() {
+        let heights = [(0, 0), (1, 1), (2, 2), (3, 3), (4, -1), (5, -2), (6, -3)];
+
+        let terraced = terrace_heights(&heights, 3);
+
+        assert_eq!(
+            vec![(0, 0), (1, 0), (2, 3), (3, 3), (4, 0), (5, -3), (6, -3)],
+            terraced
+        );
+    }
+
+    #[test]
+    fn terrace_heights_on_slope_keep_connected_nodes_within_one_step() {
+        let mut terrain = Terrain::new(1);
+        for node in 0..7 {
+            terrain.add_connected_nodes(node, node + 1);
+        }
+        let slope: Vec<(i32, i32)> = (0..8).map(|node| (node, node)).collect();
+        terrain.set_heights(&slope);
+
+        terrain.set_generated_heights(&terrace_heights(&slope, 3));
+
+        let heights: Vec<i32> = (0..8)
+            .map(|node| terrain.get_height_of_node(node).unwrap())
+            .collect();
+        assert!(heights
+            .windows(2)
+            .all(|pair| (pair[1] - pair[0]).abs() <= 1));
+        assert_eq!(vec![6, 6, 6], heights[5..]);
+    }
+
+    #[test]
+    fn falloff_decreases_from_center_to_radius() {
+        assert_eq!(1.0, falloff(0.0, 2.0));
+        assert_eq!(0.5, falloff(1.0, 2.0));
+        assert_eq!(0.0, falloff(2.0, 2.0));
+        assert_eq!(0.0, falloff(3.0, 2.0));
+    }
+
+    #[test]
+    fn jitter_heights_scales_random_value_by_strength() {
+        let nodes = [(0, 1, 1.0, 1.0), (1, 1, 0.5, -1.0), (2, 1, 0.0, 1.0)];
+
+        let heights = jitter_heights(&nodes, 4.0);
+
+        assert_eq!(vec![(0, 5), (1, -1), (2, 1)], heights);
+    }
+
+    #[test]
+    fn ridge_heights_fade_out_towards_width() {
+        let heights = ridge_heights(&[(0, 1, 0.0), (1, 1, 1.0), (2, 1, 2.0)], 4, 2.0);
+
+        assert_eq!(vec![(0, 5), (1, 3), (2, 1)], heights);
+    }
+
+    #[test]
+    fn smooth_heights_moves_heights_towards_average() {
+        let nodes = [(0, 4, 1.0, 0.0), (1, 4, 0.5, 0.0), (2, -2, 1.0, 2.0)];
+
+        let heights = smooth_heights(&nodes, 1.0);
+
+        assert_eq!(vec![(0, 0), (1, 2), (2, 2)], heights);
+    }
+
+    #[test]
+    fn smooth_heights_without_strength_keeps_heights() {
+        let nodes = [(0, 4, 1.0, 0.0), (1, -3, 1.0, 3.0)];
+
+        let heights = smooth_heights(&nodes, 0.0);
+
+        assert_eq!(vec![(0, 4), (1, -3)], heights);
+    }
+
+    #[test]
+    fn level_above_and_below_return_neighbouring_levels() {
+        let levels = [4, -1, 0, 2];
+
+        assert_eq!(Some(2), level_above(&levels, 0));
+        assert_eq!(Some(2), level_above(&levels, 1));
+        assert_eq!(None, level_above(&levels, 4));
+        assert_eq!(Some(-1), level_below(&levels, 0));
+        assert_eq!(Some(2), level_below(&levels, 3));
+        assert_eq!(None, level_below(&levels, -1));
+    }
+
+    #[test]
+    fn nearest_level_prefers_lower_level_on_tie() {
+        let levels = [0, 2, 6];
+
+        assert_eq!(Some(0), nearest_level(&levels, 1));
+        assert_eq!(Some(6), nearest_level(&levels, 5));
+        assert_eq!(Some(0), nearest_level(&levels, -3));
+        assert_eq!(None, nearest_level(&[], 1));
+    }
+
+    #[test]
+    fn blend_heights_combines_stamp_and_terrain() {
+        let nodes = [(0, 1, 2), (1, 4, -1)];
+
+        assert_eq!(
+            vec![(0, 5), (1, 2)],
+            blend_heights(&nodes, 3, BlendMode::Replace)
+        );
+        assert_eq!(
+            vec![(0, 3), (1, 3)],
+            blend_heights(&nodes, 3, BlendMode::Add)
+        );
+        assert_eq!(
+            vec![(0, 5), (1, 4)],
+            blend_heights(&nodes, 3, BlendMode::Max)
+        );
+        assert_eq!(
+            vec![(0, 1), (1, 2)],
+            blend_heights(&nodes, 3, BlendMode::Min)
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use crate::random::Random;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// A module for wave function collapse.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Module {
+    /// How likely the module is chosen compared to the other possible modules.
+    pub weight: f32,
+    /// For every direction, the modules that may be next to this module in that direction.
+    pub allowed: Vec<Vec<usize>>,
+}
+
+/// Fills the cells with modules using wave function collapse. `neighbours` returns the
+/// neighbouring cells of a cell with their direction, an index into `Module::allowed`. The cell
+/// with the fewest possible modules is collapsed first, to a module chosen by weight, and the
+/// choice is propagated to the possible modules of the other cells. The rules should be symmetric:
+/// if a module allows another one in a direction, the other one should allow it in the opposite
+/// direction. Returns the module of every cell, or None if the rules lead to a cell without any
+/// possible module. The result only depends on the seed and the order of `cells`.
+pub fn collapse<T: Eq + Hash + Copy>(
+    cells: &[T],
+    neighbours: impl Fn(T) -> Vec<(usize, T)>,
+    modules: &[Module],
+    seed: u64,
+) -> Option<HashMap<T, usize>> {
+    let indices: HashMap<T, usize> = cells
+        .iter()
+        .enumerate()
+        .map(|(index, cell)| (*cell, index))
+        .collect();
+    let mut options: Vec<Vec<usize>> = vec![(0..modules.len()).collect(); cells.len()];
+    let mut collapsed = vec![false; cells.len()];
+    let mut random = Random::new(seed);
+
+    loop {
+        let next = (0..cells.len())
+            .filter(|index| !collapsed[*index])
+            .min_by_key(|index| options[*index].len());
+        let index = match next {
+            None => break,
+            Some(index) => index,
+        };
+        if options[index].is_empty() {
+            return None;
+        }
+
+        let total: f32 = options[index]
+            .iter()
+            .map(|module| modules[*module].weight.max(0.0))
+            .sum();
+        let mut choice = random.next_f32() * total;
+        let mut chosen = options[index][0];
+        for module in &options[index] {
+            chosen = *module;
+            choice -= modules[*module].weight.max(0.0);
+            if choice < 0.0 {
+                break;
+            }
+        }
+        options[index] = vec![chosen];
+        collapsed[index] = true;
+
+        let mut open = vec![index];
+        while let Some(index) = open.pop() {
+            for (direction, neighbour) in neighbours(cells[index]) {
+                let neighbour = match indices.get(&neighbour) {
+                    None => continue,
+                    Some(neighbour) => *neighbour,
+                };
+                let possible: Vec<usize> = options[neighbour]
+                    .iter()
+                    .copied()
+                    .filter(|candidate| {
+                        options[index].iter().any(|module| {
+                            matches!(modules[*module].allowed.get(direction),
+                                Some(allowed) if allowed.contains(candidate))
+                        })
+                    })
+                    .collect();
+                if possible.len() < options[neighbour].len() {
+                    if possible.is_empty() {
+                        return None;
+                    }
+                    options[neighbour] = possible;
+                    open.push(neighbour);
+                }
+            }
+        }
+    }
+
+    Some(
+        cells
+            .iter()
+            .zip(options)
+            .map(|(cell, options)| (*cell, options[0]))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cells on a line with direction 0 to the left and 1 to the right.
+    fn line_neighbours(cell: i32) -> Vec<(usize, i32)> {
+        vec![(0, cell - 1), (1, cell + 1)]
+    }
+
+    #[test]
+    fn collapse_respects_rules() {
+        // Module 0 and 1 alternate.
+        let modules = vec![
+            Module {
+                weight: 1.0,
+                allowed: vec![vec![1], vec![1]],
+            },
+            Module {
+                weight: 1.0,
+                allowed: vec![vec![0], vec![0]],
+            },
+        ];
+        let cells: Vec<i32> = (0..10).collect();
+
+        let result = collapse(&cells, line_neighbours, &modules, 2).unwrap();
+
+        for cell in 0..9 {
+            assert_ne!(result[&cell], result[&(cell + 1)]);
+        }
+    }
+
+    #[test]
+    fn collapse_is_deterministic() {
+        let modules = vec![
+            Module {
+                weight: 1.0,
+                allowed: vec![vec![0, 1], vec![0, 1]],
+            },
+            Module {
+                weight: 3.0,
+                allowed: vec![vec![0, 1], vec![0, 1]],
+            },
+        ];
+        let cells: Vec<i32> = (0..30).collect();
+
+        assert_eq!(
+            collapse(&cells, line_neighbours, &modules, 5),
+            collapse(&cells, line_neighbours, &modules, 5)
+        );
+    }
+
+    #[test]
+    fn collapse_returns_none_for_contradicting_rules() {
+        let modules = vec![Module {
+            weight: 1.0,
+            allowed: vec![Vec::new(), Vec::new()],
+        }];
+
+        assert_eq!(None, collapse(&[0, 1], line_neighbours, &modules, 1));
+    }
+}